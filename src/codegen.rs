@@ -1,31 +1,236 @@
 mod expr;
 mod stmt;
 
-use crate::parser::Function;
+use crate::parser::{arg_locs, ArgLoc, Data, Program, Reloc, VaArgs};
+use crate::ty::Ty;
 use stmt::gen_stmt;
 
-pub fn gen(ast: &[Function]) -> Result<String, String> {
+// Registers to pass arguments, in order.
+const ARG_REGS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+const ARG_REGS32: [&str; 6] = ["edi", "esi", "edx", "ecx", "r8d", "r9d"];
+const ARG_REGS16: [&str; 6] = ["di", "si", "dx", "cx", "r8w", "r9w"];
+const ARG_REGS8: [&str; 6] = ["dil", "sil", "dl", "cl", "r8b", "r9b"];
+
+pub fn gen(program: &Program) -> Result<String, String> {
     let mut label_index = LabelIndex::new();
 
-    let mut asm = "    .intel_syntax noprefix
-    .text
-    .globl main
-"
-    .to_string();
+    let mut asm = "    .intel_syntax noprefix\n".to_string();
 
-    for func in ast {
+    for data in &program.data {
+        asm.push_str(&gen_data(data));
+    }
+
+    asm.push_str("    .text\n");
+    for func in &program.funcs {
         let return_label = format!(".d.{}.return", func.name);
 
+        // Only externally visible symbols are global; others are local to
+        // the object file.
+        if !func.is_static {
+            asm.push_str(&format!("    .globl {}\n", func.name));
+        }
         asm.push_str(&format!("{}:\n", func.name));
+
+        // prologue
+        //
+        // A frame aligned to more than 16 bytes is realigned below the saved
+        // rbp, and the old rbp, which points to the saved rbp, is saved at the
+        // new rbp. Arguments in memory are then addressed off the old rbp,
+        // kept in r11 during the prologue.
+        asm.push_str("    push rbp\n");
+        let base = if func.align > 16 {
+            asm.push_str("    mov r11, rsp\n");
+            asm.push_str("    lea rbp, [rsp-8]\n");
+            asm.push_str(&format!("    and rbp, -{}\n", func.align));
+            asm.push_str("    mov [rbp], r11\n");
+            asm.push_str(&format!("    lea rsp, [rbp-{}]\n", func.stack_size));
+            "r11"
+        } else {
+            asm.push_str("    mov rbp, rsp\n");
+            asm.push_str(&format!("    sub rsp, {}\n", func.stack_size));
+            "rbp"
+        };
+
+        // Save the arguments to the parameters, from registers, or from memory
+        // above the return address and the saved rbp.
+        let tys: Vec<Ty> = func.params.iter().map(|param| param.ty.clone()).collect();
+        let (locs, mem_size) = arg_locs(&tys);
+        for (param, loc) in func.params.iter().zip(locs) {
+            let size = param.ty.size();
+            match loc {
+                ArgLoc::Reg(i) => {
+                    let regs = [ARG_REGS8[i], ARG_REGS16[i], ARG_REGS32[i], ARG_REGS[i]];
+                    asm.push_str(&store_reg(param.offset, size, &regs));
+                }
+                ArgLoc::FpReg(i) => {
+                    let mov = match size {
+                        4 => "movss dword ptr",
+                        _ => "movsd qword ptr",
+                    };
+                    asm.push_str(&format!("    {} [rbp-{}], xmm{}\n", mov, param.offset, i));
+                }
+                ArgLoc::Stack(offset) if size == 16 => asm.push_str(&format!(
+                    "    fld tbyte ptr [{}+{}]\n    fstp tbyte ptr [rbp-{}]\n",
+                    base,
+                    16 + offset,
+                    param.offset
+                )),
+                // An argument in memory is stored through rax.
+                ArgLoc::Stack(offset) => {
+                    asm.push_str(&format!("    mov rax, [{}+{}]\n", base, 16 + offset));
+                    asm.push_str(&store_reg(param.offset, size, &["al", "ax", "eax", "rax"]));
+                }
+            }
+        }
+
+        // A variadic function saves all the argument registers to the register
+        // save area, from which va_arg takes variable arguments. Only the lower
+        // 8 bytes of each 16-byte slot for an xmm register are used.
+        //
+        // It also saves the address of the variable arguments in memory, which
+        // follow the named ones.
+        if let Some(VaArgs { area, overflow, .. }) = func.va_args {
+            for (i, reg) in ARG_REGS.iter().enumerate() {
+                asm.push_str(&format!("    mov [rbp-{}], {}\n", area - i * 8, reg));
+            }
+            for i in 0..8 {
+                asm.push_str(&format!(
+                    "    movsd qword ptr [rbp-{}], xmm{}\n",
+                    area - 48 - i * 16,
+                    i
+                ));
+            }
+            asm.push_str(&format!("    lea rax, [{}+{}]\n", base, 16 + mem_size));
+            asm.push_str(&format!("    mov [rbp-{}], rax\n", overflow));
+        }
+
         for stmt in &func.body {
-            asm.push_str(&gen_stmt(&stmt, &return_label, &mut label_index)?);
+            asm.push_str(&gen_stmt(stmt, &return_label, &mut label_index)?);
         }
+
+        // epilogue
         asm.push_str(format!("{}:\n", return_label).as_str());
+        // A float or double value is returned in xmm0.
+        if matches!(func.ty, Ty::Float | Ty::Double) {
+            asm.push_str("    movq xmm0, rax\n");
+        }
+        if func.align > 16 {
+            asm.push_str("    mov rsp, [rbp]\n");
+        } else {
+            asm.push_str("    mov rsp, rbp\n");
+        }
+        asm.push_str("    pop rbp\n");
         asm.push_str("    ret\n");
     }
     Ok(asm)
 }
 
+// Returns code to store a register of `size` bytes to the local variable at
+// `offset`, where `regs` are the names of the register of 1, 2, 4 and 8 bytes.
+fn store_reg(offset: usize, size: usize, regs: &[&str; 4]) -> String {
+    match size {
+        1 => format!("    mov byte ptr [rbp-{}], {}\n", offset, regs[0]),
+        2 => format!("    mov word ptr [rbp-{}], {}\n", offset, regs[1]),
+        4 => format!("    mov dword ptr [rbp-{}], {}\n", offset, regs[2]),
+        8 => format!("    mov [rbp-{}], {}\n", offset, regs[3]),
+        size => unreachable!("parameter of {} bytes", size),
+    }
+}
+
+// Generates a variable with static storage duration, which is put in .data if
+// initialized, or in .bss otherwise.
+fn gen_data(data: &Data) -> String {
+    let Data {
+        name,
+        ty,
+        init,
+        relocs,
+        is_static,
+        align,
+    } = data;
+    let mut s = "".to_string();
+    if !is_static {
+        s.push_str(&format!("    .globl {}\n", name));
+    }
+    match init {
+        Some(bytes) => {
+            s.push_str("    .data\n");
+            s.push_str(&format!("    .align {}\n", align));
+            s.push_str(&format!("{}:\n", name));
+            s.push_str(&gen_init(ty, bytes, 0, relocs));
+        }
+        None => {
+            s.push_str("    .bss\n");
+            s.push_str(&format!("    .align {}\n", align));
+            s.push_str(&format!("{}:\n", name));
+            s.push_str(&format!("    .zero {}\n", ty.size()));
+        }
+    }
+    s
+}
+
+// Generates data directives for the initial bytes of an object of `ty` at
+// `offset` in the data, one for each scalar. A pointer with a relocation at
+// its offset is the address of the symbol plus the addend.
+//
+// A structure or union is generated byte by byte, since bit-fields and
+// padding do not fall on scalars, except for pointers with relocations.
+fn gen_init(ty: &Ty, bytes: &[u8], offset: usize, relocs: &[Reloc]) -> String {
+    if let Ty::Struct(_) = ty.unqual() {
+        let mut s = "".to_string();
+        let mut i = 0;
+        while i < bytes.len() {
+            if relocs.iter().any(|r| r.offset == offset + i) {
+                s.push_str(&gen_init(
+                    &Ty::Ptr(Box::new(Ty::Void)),
+                    &[],
+                    offset + i,
+                    relocs,
+                ));
+                i += 8;
+            } else {
+                s.push_str(&gen_init(&Ty::Char, &bytes[i..i + 1], offset + i, &[]));
+                i += 1;
+            }
+        }
+        return s;
+    }
+    if let Some(elem) = ty.pointee().filter(|_| ty.is_array()) {
+        return bytes
+            .chunks(elem.size())
+            .enumerate()
+            .map(|(i, chunk)| gen_init(elem, chunk, offset + i * elem.size(), relocs))
+            .collect();
+    }
+    if let Some(reloc) = relocs.iter().find(|r| r.offset == offset) {
+        return match reloc.addend {
+            0 => format!("    .quad {}\n", reloc.label),
+            addend => format!("    .quad {}{:+}\n", reloc.label, addend),
+        };
+    }
+    match bytes.len() {
+        1 => format!("    .byte {}\n", bytes[0] as i8),
+        2 => format!(
+            "    .short {}\n",
+            i16::from_le_bytes(bytes.try_into().unwrap())
+        ),
+        4 => format!(
+            "    .long {}\n",
+            i32::from_le_bytes(bytes.try_into().unwrap())
+        ),
+        8 => format!(
+            "    .quad {}\n",
+            i64::from_le_bytes(bytes.try_into().unwrap())
+        ),
+        // long double
+        16 => bytes
+            .chunks(8)
+            .map(|chunk| gen_init(&Ty::Double, chunk, offset, &[]))
+            .collect(),
+        size => unreachable!("scalar of {} bytes", size),
+    }
+}
+
 // This provides an index number to a label to make it globally unique.
 struct LabelIndex(u64);
 
@@ -44,7 +249,7 @@ impl LabelIndex {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parser::{Expr, Stmt};
+    use crate::parser::{Expr, Function, Stmt, Var};
     use crate::ty::Ty;
 
     #[test]
@@ -52,18 +257,32 @@ mod tests {
         let ast = vec![Function {
             ty: Ty::Int,
             name: "main".to_string(),
-            body: vec![Stmt::ExprStmt(Expr::Num(42))],
+            is_static: false,
+            params: vec![],
+            body: vec![Stmt::ExprStmt(Expr::Num(42, Ty::Int))],
+            stack_size: 0,
+            align: 16,
+            va_args: None,
         }];
         let expected = "    .intel_syntax noprefix
     .text
     .globl main
 main:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 0
     push 42
     pop rax
 .d.main.return:
+    mov rsp, rbp
+    pop rbp
     ret
 ";
-        let actual = gen(&ast).unwrap();
+        let actual = gen(&Program {
+            funcs: ast,
+            data: vec![],
+        })
+        .unwrap();
         assert_eq!(expected, actual);
     }
 
@@ -72,53 +291,419 @@ main:
         let ast = vec![Function {
             ty: Ty::Int,
             name: "main".to_string(),
-            body: vec![Stmt::ExprStmt(Expr::Num(3)), Stmt::ExprStmt(Expr::Num(42))],
+            is_static: false,
+            params: vec![],
+            body: vec![
+                Stmt::ExprStmt(Expr::Num(3, Ty::Int)),
+                Stmt::ExprStmt(Expr::Num(42, Ty::Int)),
+            ],
+            stack_size: 0,
+            align: 16,
+            va_args: None,
         }];
         let expected = "    .intel_syntax noprefix
     .text
     .globl main
 main:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 0
     push 3
     pop rax
     push 42
     pop rax
 .d.main.return:
+    mov rsp, rbp
+    pop rbp
     ret
 ";
-        let actual = gen(&ast).unwrap();
+        let actual = gen(&Program {
+            funcs: ast,
+            data: vec![],
+        })
+        .unwrap();
         assert_eq!(expected, actual);
     }
 
     #[test]
     fn gen_multiple_functions() {
-        // ret() { 42; } main() { 123; }
+        // static ret() { 42; } main() { 123; }
         let ast = vec![
             Function {
                 ty: Ty::Int,
                 name: "ret".to_string(),
-                body: vec![Stmt::ExprStmt(Expr::Num(42))],
+                is_static: true,
+                params: vec![],
+                body: vec![Stmt::ExprStmt(Expr::Num(42, Ty::Int))],
+                stack_size: 0,
+                align: 16,
+                va_args: None,
             },
             Function {
                 ty: Ty::Int,
                 name: "main".to_string(),
-                body: vec![Stmt::ExprStmt(Expr::Num(123))],
+                is_static: false,
+                params: vec![],
+                body: vec![Stmt::ExprStmt(Expr::Num(123, Ty::Int))],
+                stack_size: 16,
+                align: 16,
+                va_args: None,
             },
         ];
         let expected = "    .intel_syntax noprefix
     .text
-    .globl main
 ret:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 0
     push 42
     pop rax
 .d.ret.return:
+    mov rsp, rbp
+    pop rbp
     ret
+    .globl main
 main:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 16
     push 123
     pop rax
 .d.main.return:
+    mov rsp, rbp
+    pop rbp
+    ret
+";
+        let actual = gen(&Program {
+            funcs: ast,
+            data: vec![],
+        })
+        .unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_function_with_params() {
+        // int f(int a, int *b) { }
+        let ast = vec![Function {
+            ty: Ty::Int,
+            name: "f".to_string(),
+            is_static: false,
+            params: vec![
+                Var {
+                    name: "a".to_string(),
+                    ty: Ty::Int,
+                    offset: 4,
+                },
+                Var {
+                    name: "b".to_string(),
+                    ty: Ty::Ptr(Box::new(Ty::Int)),
+                    offset: 16,
+                },
+            ],
+            body: vec![],
+            stack_size: 16,
+            align: 16,
+            va_args: None,
+        }];
+        let expected = "    .intel_syntax noprefix
+    .text
+    .globl f
+f:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 16
+    mov dword ptr [rbp-4], edi
+    mov [rbp-16], rsi
+.d.f.return:
+    mov rsp, rbp
+    pop rbp
+    ret
+";
+        let actual = gen(&Program {
+            funcs: ast,
+            data: vec![],
+        })
+        .unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_realigned_frame() {
+        // int f(int a, int b, int c, int d, int e, int f, int g) {
+        //   _Alignas(32) int x;
+        // }
+        let param = |name: &str, offset| Var {
+            name: name.to_string(),
+            ty: Ty::Int,
+            offset,
+        };
+        let ast = vec![Function {
+            ty: Ty::Int,
+            name: "f".to_string(),
+            is_static: false,
+            params: ["a", "b", "c", "d", "e", "f", "g"]
+                .iter()
+                .zip(1..)
+                .map(|(name, i)| param(name, i * 4))
+                .collect(),
+            body: vec![],
+            stack_size: 32,
+            align: 32,
+            va_args: None,
+        }];
+        let expected = "    .intel_syntax noprefix
+    .text
+    .globl f
+f:
+    push rbp
+    mov r11, rsp
+    lea rbp, [rsp-8]
+    and rbp, -32
+    mov [rbp], r11
+    lea rsp, [rbp-32]
+    mov dword ptr [rbp-4], edi
+    mov dword ptr [rbp-8], esi
+    mov dword ptr [rbp-12], edx
+    mov dword ptr [rbp-16], ecx
+    mov dword ptr [rbp-20], r8d
+    mov dword ptr [rbp-24], r9d
+    mov rax, [r11+16]
+    mov dword ptr [rbp-28], eax
+.d.f.return:
+    mov rsp, [rbp]
+    pop rbp
     ret
 ";
-        let actual = gen(&ast).unwrap();
+        let actual = gen(&Program {
+            funcs: ast,
+            data: vec![],
+        })
+        .unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_global_variables() {
+        let data = vec![
+            Data {
+                name: "x".to_string(),
+                ty: Ty::Int,
+                init: Some(vec![3, 0, 0, 0]),
+                relocs: Vec::new(),
+                is_static: false,
+                align: 4,
+            },
+            Data {
+                name: "p.0".to_string(),
+                ty: Ty::Ptr(Box::new(Ty::Int)),
+                init: None,
+                relocs: Vec::new(),
+                is_static: true,
+                align: 8,
+            },
+        ];
+        let expected = "    .intel_syntax noprefix
+    .globl x
+    .data
+    .align 4
+x:
+    .long 3
+    .bss
+    .align 8
+p.0:
+    .zero 8
+    .text
+";
+        let actual = gen(&Program {
+            funcs: vec![],
+            data,
+        })
+        .unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_variadic_function_saving_registers() {
+        // int f(int a, ...) { }
+        let ast = vec![Function {
+            ty: Ty::Int,
+            name: "f".to_string(),
+            is_static: false,
+            params: vec![Var {
+                name: "a".to_string(),
+                ty: Ty::Int,
+                offset: 4,
+            }],
+            body: vec![],
+            stack_size: 192,
+            align: 16,
+            va_args: Some(VaArgs {
+                area: 184,
+                gp_offset: 8,
+                fp_offset: 48,
+                overflow: 192,
+            }),
+        }];
+        let expected = "    .intel_syntax noprefix
+    .text
+    .globl f
+f:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 192
+    mov dword ptr [rbp-4], edi
+    mov [rbp-184], rdi
+    mov [rbp-176], rsi
+    mov [rbp-168], rdx
+    mov [rbp-160], rcx
+    mov [rbp-152], r8
+    mov [rbp-144], r9
+    movsd qword ptr [rbp-136], xmm0
+    movsd qword ptr [rbp-120], xmm1
+    movsd qword ptr [rbp-104], xmm2
+    movsd qword ptr [rbp-88], xmm3
+    movsd qword ptr [rbp-72], xmm4
+    movsd qword ptr [rbp-56], xmm5
+    movsd qword ptr [rbp-40], xmm6
+    movsd qword ptr [rbp-24], xmm7
+    lea rax, [rbp+16]
+    mov [rbp-192], rax
+.d.f.return:
+    mov rsp, rbp
+    pop rbp
+    ret
+";
+        let actual = gen(&Program {
+            funcs: ast,
+            data: vec![],
+        })
+        .unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_initialized_arrays() {
+        let data = vec![
+            Data {
+                name: "s".to_string(),
+                ty: Ty::Array(Box::new(Ty::Char), 2),
+                init: Some(vec![b'a', 0xff]),
+                relocs: Vec::new(),
+                is_static: true,
+                align: 1,
+            },
+            Data {
+                name: "a".to_string(),
+                ty: Ty::Array(Box::new(Ty::Array(Box::new(Ty::Int), 1)), 2),
+                init: Some(vec![1, 0, 0, 0, 0xfe, 0xff, 0xff, 0xff]),
+                relocs: Vec::new(),
+                is_static: true,
+                align: 4,
+            },
+        ];
+        let expected = "    .intel_syntax noprefix
+    .data
+    .align 1
+s:
+    .byte 97
+    .byte -1
+    .data
+    .align 4
+a:
+    .long 1
+    .long -2
+    .text
+";
+        let actual = gen(&Program {
+            funcs: vec![],
+            data,
+        })
+        .unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_address_constants() {
+        // char *s = "abc"; int *p = &x; int (*fp)(int) = f;
+        // static int (*tbl[2])(int) = {f, f};
+        let reloc = |label: &str, offset, addend| Reloc {
+            offset,
+            label: label.to_string(),
+            addend,
+        };
+        let ptr = |name: &str, label: &str| Data {
+            name: name.to_string(),
+            ty: Ty::Ptr(Box::new(Ty::Char)),
+            init: Some(vec![0; 8]),
+            relocs: vec![reloc(label, 0, 0)],
+            is_static: true,
+            align: 8,
+        };
+        let data = vec![
+            ptr("s", ".str.0"),
+            ptr("p", "x"),
+            ptr("fp", "f"),
+            Data {
+                name: "tbl.0".to_string(),
+                ty: Ty::Array(Box::new(Ty::Ptr(Box::new(Ty::Int))), 2),
+                init: Some(vec![0; 16]),
+                relocs: vec![reloc("f", 0, 0), reloc("f", 8, 4)],
+                is_static: true,
+                align: 8,
+            },
+        ];
+        let expected = "    .intel_syntax noprefix
+    .data
+    .align 8
+s:
+    .quad .str.0
+    .data
+    .align 8
+p:
+    .quad x
+    .data
+    .align 8
+fp:
+    .quad f
+    .data
+    .align 8
+tbl.0:
+    .quad f
+    .quad f+4
+    .text
+";
+        let actual = gen(&Program {
+            funcs: vec![],
+            data,
+        })
+        .unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_over_aligned_data() {
+        let data = vec![Data {
+            name: "c".to_string(),
+            ty: Ty::Char,
+            init: None,
+            relocs: Vec::new(),
+            is_static: false,
+            align: 32,
+        }];
+        let expected = "    .intel_syntax noprefix
+    .globl c
+    .bss
+    .align 32
+c:
+    .zero 1
+    .text
+";
+        let actual = gen(&Program {
+            funcs: vec![],
+            data,
+        })
+        .unwrap();
         assert_eq!(expected, actual);
     }
 }
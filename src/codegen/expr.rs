@@ -1,79 +1,758 @@
-use crate::parser::{Binary, Expr};
+use super::ARG_REGS;
+use crate::parser::{arg_locs, ArgLoc, Binary, Call, Cast, CompoundLit, Expr, GVar, Var};
+use crate::ty::{align_to, BitField, Member, Ty};
 
-pub(super) fn gen_expr(expr: &Expr) -> Result<String, String> {
+// Generates code to push the value of an expression.
+//
+// `depth` is the number of 8-byte slots which the enclosing expressions have
+// pushed onto the stack, so that the stack can be aligned to 16 bytes on
+// function calls. A value takes one slot, except that a long double takes two.
+//
+// Every load and store is emitted where the expression accesses the object,
+// which accesses to volatile objects rely on.
+pub(super) fn gen_expr(expr: &Expr, depth: usize) -> Result<String, String> {
     match expr {
-        Expr::Num(n) => Ok(format!("    push {}\n", n)),
+        // A constant which does not fit in a sign-extended 32-bit immediate is
+        // moved through rax.
+        Expr::Num(n, _) if i32::try_from(*n).is_ok() => Ok(format!("    push {}\n", n)),
+        Expr::Num(n, _) => Ok(format!("    mov rax, {}\n    push rax\n", n)),
+        // A floating value is pushed as its bit pattern.
+        Expr::FNum(val, Ty::Float) => Ok(format!(
+            "    mov eax, {}\n    push rax\n",
+            (val.to_f64() as f32).to_bits()
+        )),
+        Expr::FNum(val, Ty::Double) => Ok(format!(
+            "    mov rax, {}\n    push rax\n",
+            val.to_f64().to_bits()
+        )),
+        Expr::FNum(val, _) => Ok(format!(
+            "    mov rax, {}\n    push rax\n    mov rax, {}\n    push rax\n",
+            val.exp | (val.sign as u16) << 15,
+            val.significand
+        )),
+        Expr::Var(_) | Expr::GVar(_) | Expr::CompoundLit(_) => {
+            let mut s = gen_addr(expr, depth)?;
+            s.push_str(&load(&expr.ty()));
+            Ok(s)
+        }
+        Expr::Deref(inner) => {
+            let mut s = gen_expr(inner, depth)?;
+            s.push_str(&load(&expr.ty()));
+            Ok(s)
+        }
+        Expr::Member(_, member) => {
+            let mut s = gen_addr(expr, depth)?;
+            match member.bit_field {
+                Some(bit_field) => s.push_str(&load_bit_field(&member.ty, bit_field)),
+                None => s.push_str(&load(&expr.ty())),
+            }
+            Ok(s)
+        }
+        Expr::Addr(inner) => gen_addr(inner, depth),
+        Expr::Assign(bin) => {
+            let Binary { lhs, rhs } = &**bin;
+            let mut s = gen_addr(lhs, depth)?;
+            s.push_str(&gen_expr(rhs, depth + 1)?);
+            match lhs {
+                Expr::Member(
+                    _,
+                    Member {
+                        ty,
+                        bit_field: Some(bit_field),
+                        ..
+                    },
+                ) => s.push_str(&store_bit_field(ty, *bit_field)),
+                _ => s.push_str(&store(&lhs.ty())),
+            }
+            Ok(s)
+        }
+        Expr::Cast(cast) => {
+            let Cast { expr, ty } = &**cast;
+            let mut s = gen_expr(expr, depth)?;
+            s.push_str(&cast_to(&expr.ty(), ty));
+            Ok(s)
+        }
+        // Long double operands are operated on in the x87 FPU, as the
+        // operands have been converted to the same type.
+        Expr::Add(bin) | Expr::Sub(bin) | Expr::Mul(bin) | Expr::Div(bin)
+            if expr.ty() == Ty::LongDouble =>
+        {
+            let Binary { lhs, rhs } = &**bin;
+            let op = match expr {
+                Expr::Add(_) => "fadd",
+                Expr::Sub(_) => "fsub",
+                Expr::Mul(_) => "fmul",
+                _ => "fdiv",
+            };
+            let mut s = gen_expr(lhs, depth)?;
+            s.push_str(&gen_expr(rhs, depth + 2)?);
+            s.push_str("    fld tbyte ptr [rsp]\n");
+            s.push_str("    fld tbyte ptr [rsp+16]\n");
+            s.push_str(&format!("    {} st(0), st(1)\n", op));
+            s.push_str("    add rsp, 16\n");
+            s.push_str("    fstp tbyte ptr [rsp]\n");
+            s.push_str("    fstp st(0)\n");
+            Ok(s)
+        }
+        // Other floating operands are operated on in xmm registers.
+        Expr::Add(bin) | Expr::Sub(bin) | Expr::Mul(bin) | Expr::Div(bin)
+            if expr.ty().is_flonum() =>
+        {
+            let Binary { lhs, rhs } = &**bin;
+            let op = match expr {
+                Expr::Add(_) => "add",
+                Expr::Sub(_) => "sub",
+                Expr::Mul(_) => "mul",
+                _ => "div",
+            };
+            let mut s = gen_expr(lhs, depth)?;
+            s.push_str(&gen_expr(rhs, depth + 1)?);
+            s.push_str("    pop rdi\n");
+            s.push_str("    pop rax\n");
+            s.push_str("    movq xmm0, rax\n");
+            s.push_str("    movq xmm1, rdi\n");
+            s.push_str(&format!(
+                "    {}{} xmm0, xmm1\n",
+                op,
+                sse_suffix(&expr.ty())
+            ));
+            s.push_str("    movq rax, xmm0\n");
+            s.push_str("    push rax\n");
+            Ok(s)
+        }
         Expr::Add(bin) => {
             let Binary { lhs, rhs } = &**bin;
-            let mut s = gen_expr(&lhs)?;
-            s.push_str(&gen_expr(&rhs)?);
+            let mut s = gen_expr(lhs, depth)?;
+            s.push_str(&gen_expr(rhs, depth + 1)?);
             s.push_str("    pop rdi\n");
             s.push_str("    pop rax\n");
             s.push_str("    add rax, rdi\n");
+            s.push_str(wrap(&expr.ty()));
             s.push_str("    push rax\n");
             Ok(s)
         }
         Expr::Sub(bin) => {
             let Binary { lhs, rhs } = &**bin;
-            let mut s = gen_expr(&lhs)?;
-            s.push_str(&gen_expr(&rhs)?);
+            let mut s = gen_expr(lhs, depth)?;
+            s.push_str(&gen_expr(rhs, depth + 1)?);
             s.push_str("    pop rdi\n");
             s.push_str("    pop rax\n");
             s.push_str("    sub rax, rdi\n");
+            s.push_str(wrap(&expr.ty()));
             s.push_str("    push rax\n");
             Ok(s)
         }
         Expr::Mul(bin) => {
             let Binary { lhs, rhs } = &**bin;
-            let mut s = gen_expr(&lhs)?;
-            s.push_str(&gen_expr(&rhs)?);
+            let mut s = gen_expr(lhs, depth)?;
+            s.push_str(&gen_expr(rhs, depth + 1)?);
             s.push_str("    pop rdi\n");
             s.push_str("    pop rax\n");
             s.push_str("    imul rax, rdi\n");
+            s.push_str(wrap(&expr.ty()));
             s.push_str("    push rax\n");
             Ok(s)
         }
         Expr::Div(bin) => {
             let Binary { lhs, rhs } = &**bin;
-            let mut s = gen_expr(&lhs)?;
-            s.push_str(&gen_expr(&rhs)?);
+            let mut s = gen_expr(lhs, depth)?;
+            s.push_str(&gen_expr(rhs, depth + 1)?);
             s.push_str("    pop rdi\n");
             s.push_str("    pop rax\n");
-            s.push_str("    xor rdx, rdx\n");
-            s.push_str("    idiv rdi\n");
+            // Operands are extended to 64 bits as their type is, so they are
+            // divided as 64-bit values, where the dividend in rdx:rax is
+            // zero-extended if unsigned and sign-extended otherwise.
+            if expr.ty().is_unsigned() {
+                s.push_str("    xor rdx, rdx\n");
+                s.push_str("    div rdi\n");
+            } else {
+                s.push_str("    cqo\n");
+                s.push_str("    idiv rdi\n");
+            }
             s.push_str("    push rax\n");
             Ok(s)
         }
-        Expr::FnName(fn_name) => Err(format!("cannot generate a function: {}", fn_name)),
-        Expr::FnCall(f) => {
-            let fn_name = match &**f {
-                Expr::FnName(fn_name) => fn_name,
-                _ => return Err("expected a function identifier".to_string()),
-            };
-            let mut s = format!("    call {}\n", fn_name);
+        // A function designator is converted to a pointer to the function.
+        Expr::FnName(..) => gen_addr(expr, depth),
+        Expr::FnCall(call) => gen_call(call, depth),
+        // A va_list holds the offsets of the next variable arguments within
+        // the register save area, the address of the next one in memory, and
+        // the address of the register save area.
+        Expr::VaStart(ap, va_args) => {
+            let mut s = gen_expr(ap, depth)?;
+            s.push_str("    pop rax\n");
+            s.push_str(&format!("    mov dword ptr [rax], {}\n", va_args.gp_offset));
+            s.push_str(&format!(
+                "    mov dword ptr [rax+4], {}\n",
+                va_args.fp_offset
+            ));
+            s.push_str(&format!("    mov rdx, [rbp-{}]\n", va_args.overflow));
+            s.push_str("    mov [rax+8], rdx\n");
+            s.push_str(&format!("    lea rdx, [rbp-{}]\n", va_args.area));
+            s.push_str("    mov [rax+16], rdx\n");
+            s.push_str("    push rax\n");
+            Ok(s)
+        }
+        Expr::VaArg(ap, ty) => {
+            let mut s = gen_expr(ap, depth)?;
+            s.push_str(&va_arg_addr(ty));
+            s.push_str(&load(ty));
+            Ok(s)
+        }
+        Expr::VaCopy(bin) => {
+            let Binary { lhs, rhs } = &**bin;
+            let mut s = gen_expr(lhs, depth)?;
+            s.push_str(&gen_expr(rhs, depth + 1)?);
+            s.push_str("    pop rsi\n");
+            s.push_str("    pop rdi\n");
+            for offset in [0, 8, 16] {
+                s.push_str(&format!("    mov rax, [rsi+{}]\n", offset));
+                s.push_str(&format!("    mov [rdi+{}], rax\n", offset));
+            }
+            s.push_str("    push rdi\n");
+            Ok(s)
+        }
+        // A variable length array is allocated below the stack pointer, which
+        // is kept aligned to 16 bytes.
+        Expr::VlaAlloc(var, size) => {
+            let mut s = gen_expr(size, depth)?;
+            s.push_str("    pop rax\n");
+            s.push_str("    sub rsp, rax\n");
+            s.push_str("    and rsp, -16\n");
+            s.push_str(&format!("    mov [rbp-{}], rsp\n", var.offset));
             s.push_str("    push rax\n");
             Ok(s)
         }
     }
 }
 
+// Pops the address of a va_list, and pushes the address of the next variable
+// argument of `ty`, advancing the va_list past it.
+//
+// A long double argument is always in memory, aligned to 16 bytes. Others are
+// in the register save area until the registers of their class run out, and
+// then in memory, which is selected without branches.
+fn va_arg_addr(ty: &Ty) -> String {
+    let mut s = "    pop rcx\n".to_string();
+    if *ty == Ty::LongDouble {
+        s.push_str("    mov rdx, [rcx+8]\n");
+        s.push_str("    add rdx, 15\n");
+        s.push_str("    and rdx, -16\n");
+        s.push_str("    lea rsi, [rdx+16]\n");
+        s.push_str("    mov [rcx+8], rsi\n");
+        s.push_str("    push rdx\n");
+        return s;
+    }
+
+    // the field of the offset, the end of the registers in the area, and the
+    // size of a register
+    let (field, end, step) = match ty {
+        Ty::Float | Ty::Double => ("[rcx+4]", 176, 16),
+        _ => ("[rcx]", 48, 8),
+    };
+    s.push_str(&format!("    mov eax, dword ptr {}\n", field));
+    s.push_str("    mov rdx, [rcx+8]\n");
+    s.push_str("    lea rsi, [rdx+8]\n");
+    s.push_str("    mov rdi, [rcx+16]\n");
+    s.push_str("    add rdi, rax\n");
+    s.push_str(&format!("    cmp eax, {}\n", end));
+    // If in registers, the address is in the area, and the address in memory
+    // is left as it is. Otherwise, the offset is left as it is.
+    s.push_str("    cmovb rdx, rdi\n");
+    s.push_str("    cmovb rsi, [rcx+8]\n");
+    s.push_str(&format!("    lea edi, [rax+{}]\n", step));
+    s.push_str("    cmovae edi, eax\n");
+    s.push_str(&format!("    mov dword ptr {}, edi\n", field));
+    s.push_str("    mov [rcx+8], rsi\n");
+    s.push_str("    push rdx\n");
+    s
+}
+
+// Generates code to call a function, and push the return value.
+//
+// A function designated by its name is called directly, and others are called
+// indirectly through the pointer value.
+//
+// Arguments are evaluated in order. Those passed in registers are pushed and
+// popped into the registers at last, while those passed in memory are stored
+// to the area reserved for them at the top of the stack, which is aligned to
+// 16 bytes at the call. A float or double value is returned in xmm0, and a
+// long double in st(0).
+fn gen_call(call: &Call, depth: usize) -> Result<String, String> {
+    let Call { func, args } = call;
+    let mut s = "".to_string();
+    let (locs, mem_size) = arg_locs(&args.iter().map(Expr::ty).collect::<Vec<_>>());
+
+    // The area is padded at the top so that the stack stays aligned.
+    let mut depth = depth;
+    let mut area = 0;
+    if mem_size > 0 {
+        area = align_to(mem_size, 16) + depth % 2 * 8;
+        s.push_str(&format!("    sub rsp, {}\n", area));
+        depth += area / 8;
+    }
+
+    let mut regs: Vec<String> = Vec::new();
+    for (arg, loc) in args.iter().zip(&locs) {
+        s.push_str(&gen_expr(arg, depth + regs.len())?);
+        // The area is below the values pushed for registers.
+        let offset = match loc {
+            ArgLoc::Reg(i) => {
+                regs.push(ARG_REGS[*i].to_string());
+                continue;
+            }
+            ArgLoc::FpReg(i) => {
+                regs.push(format!("xmm{}", i));
+                continue;
+            }
+            ArgLoc::Stack(offset) => offset + regs.len() * 8,
+        };
+        if arg.ty() == Ty::LongDouble {
+            s.push_str("    pop rax\n    pop rdx\n");
+            s.push_str(&format!("    mov [rsp+{}], rax\n", offset));
+            s.push_str(&format!("    mov [rsp+{}], rdx\n", offset + 8));
+        } else {
+            s.push_str(&format!("    pop rax\n    mov [rsp+{}], rax\n", offset));
+        }
+    }
+    let target = match func {
+        Expr::FnName(fn_name, _) => fn_name.clone(),
+        _ => {
+            s.push_str(&gen_expr(func, depth + regs.len())?);
+            s.push_str("    pop r10\n");
+            "r10".to_string()
+        }
+    };
+    let fp = regs.iter().filter(|reg| reg.starts_with("xmm")).count();
+    for reg in regs.iter().rev() {
+        if reg.starts_with("xmm") {
+            s.push_str(&format!("    pop rax\n    movq {}, rax\n", reg));
+        } else {
+            s.push_str(&format!("    pop {}\n", reg));
+        }
+    }
+    // A function which takes variable arguments, or may take them since it has
+    // no prototype, expects the number of xmm registers used in al.
+    let fty = call.func_ty();
+    if fty.params.is_none() || fty.is_variadic {
+        s.push_str(&format!("    mov eax, {}\n", fp));
+    }
+
+    // The stack pointer must be aligned to 16 bytes at a call.
+    if depth % 2 == 1 {
+        s.push_str("    sub rsp, 8\n");
+        s.push_str(&format!("    call {}\n", target));
+        s.push_str("    add rsp, 8\n");
+    } else {
+        s.push_str(&format!("    call {}\n", target));
+    }
+    if area > 0 {
+        s.push_str(&format!("    add rsp, {}\n", area));
+    }
+
+    // Only the lower bits of an integer return value are defined.
+    let ret = fty.ret;
+    if ret.is_integer() {
+        s.push_str(extend(&ret));
+    }
+    match ret {
+        Ty::Float | Ty::Double => s.push_str("    movq rax, xmm0\n"),
+        Ty::LongDouble => {
+            s.push_str("    sub rsp, 16\n    fstp tbyte ptr [rsp]\n");
+            return Ok(s);
+        }
+        _ => (),
+    }
+    s.push_str("    push rax\n");
+    Ok(s)
+}
+
+// Generates code to push the address of an lvalue or a function designator.
+fn gen_addr(expr: &Expr, depth: usize) -> Result<String, String> {
+    match expr {
+        // A variable length array is at the address held by its variable.
+        Expr::Var(Var {
+            offset,
+            ty: Ty::Vla(..),
+            ..
+        }) => Ok(format!("    mov rax, [rbp-{}]\n    push rax\n", offset)),
+        Expr::Var(Var { offset, .. }) => {
+            Ok(format!("    lea rax, [rbp-{}]\n    push rax\n", offset))
+        }
+        // The value of a pointer is the address it points to.
+        Expr::Deref(inner) => gen_expr(inner, depth),
+        // A structure is not loaded, so its value is its address, even if it
+        // is not an lvalue, e.g. `(a = b).x`.
+        Expr::Member(inner, member) => {
+            let mut s = gen_expr(inner, depth)?;
+            if member.offset > 0 {
+                s.push_str(&format!(
+                    "    pop rax\n    add rax, {}\n    push rax\n",
+                    member.offset
+                ));
+            }
+            Ok(s)
+        }
+        // The variable or the function may be defined in another shared
+        // object, so its address is taken from the GOT.
+        Expr::GVar(GVar { name, .. }) | Expr::FnName(name, _) => Ok(format!(
+            "    mov rax, [rip+{}@GOTPCREL]\n    push rax\n",
+            name
+        )),
+        // A compound literal is initialized before its address is taken.
+        Expr::CompoundLit(lit) => {
+            let CompoundLit { var, init } = &**lit;
+            let mut s = "".to_string();
+            for assign in init {
+                s.push_str(&gen_expr(assign, depth)?);
+                s.push_str(discard(&assign.ty()));
+            }
+            s.push_str(&format!(
+                "    lea rax, [rbp-{}]\n    push rax\n",
+                var.offset
+            ));
+            Ok(s)
+        }
+        _ => Err("expected an lvalue".to_string()),
+    }
+}
+
+// Pops an address, and pushes the value of `ty` at the address, which is
+// sign-extended or zero-extended to 64 bits as its type is.
+//
+// An array or a function is not loaded, since it is converted to a pointer to
+// itself, which is the address. Nor is a structure or union, whose value is
+// its address to be copied from.
+//
+// The address may not be aligned to the type, e.g. of a member of a packed
+// structure, so only instructions which allow it are used, as in `store`.
+fn load(ty: &Ty) -> String {
+    if ty.is_array() || matches!(ty.unqual(), Ty::Func(_) | Ty::Struct(_)) {
+        return "".to_string();
+    }
+    let mov = match ty.unqual() {
+        Ty::UChar => "movzx rax, byte ptr [rax]",
+        Ty::UShort => "movzx rax, word ptr [rax]",
+        Ty::UInt => "mov eax, dword ptr [rax]",
+        _ => match ty.size() {
+            1 => "movsx rax, byte ptr [rax]",
+            2 => "movsx rax, word ptr [rax]",
+            4 => "movsxd rax, dword ptr [rax]",
+            8 => "mov rax, [rax]",
+            16 => {
+                return "    pop rax\n    push qword ptr [rax+8]\n    push qword ptr [rax]\n"
+                    .to_string()
+            }
+            size => unreachable!("load of {} bytes", size),
+        },
+    };
+    format!("    pop rax\n    {}\n    push rax\n", mov)
+}
+
+// Pops a value and then an address, stores the value of `ty` to the address,
+// and pushes the value back.
+//
+// A structure or union is copied from the address which is its value, and the
+// address stored to is pushed as the value of the assignment.
+fn store(ty: &Ty) -> String {
+    if let Ty::Struct(_) = ty.unqual() {
+        let mut s = "    pop rsi\n    pop rdi\n".to_string();
+        let mut offset = 0;
+        for size in [8, 4, 2, 1] {
+            let reg = match size {
+                8 => "rax",
+                4 => "eax",
+                2 => "ax",
+                _ => "al",
+            };
+            while ty.size() - offset >= size {
+                s.push_str(&format!("    mov {}, [rsi+{}]\n", reg, offset));
+                s.push_str(&format!("    mov [rdi+{}], {}\n", offset, reg));
+                offset += size;
+            }
+        }
+        s.push_str("    push rdi\n");
+        return s;
+    }
+    if ty.size() == 16 {
+        return "    pop rdi\n    pop rsi\n    pop rax\n    mov [rax], rdi\n    mov [rax+8], rsi\n    push rsi\n    push rdi\n".to_string();
+    }
+    let mut s = "    pop rdi\n    pop rax\n".to_string();
+    match ty.size() {
+        1 => s.push_str("    mov byte ptr [rax], dil\n"),
+        2 => s.push_str("    mov word ptr [rax], di\n"),
+        4 => s.push_str("    mov dword ptr [rax], edi\n"),
+        8 => s.push_str("    mov [rax], rdi\n"),
+        size => unreachable!("store of {} bytes", size),
+    }
+    s.push_str("    push rdi\n");
+    s
+}
+
+// Returns code to load the `n` bytes at the address in `addr` into rax, which
+// are zero-extended. Bytes of other than the size of a register are gathered
+// one by one, from the most significant.
+fn load_bytes(addr: &str, n: usize) -> String {
+    match n {
+        1 => format!("    movzx eax, byte ptr [{}]\n", addr),
+        2 => format!("    movzx eax, word ptr [{}]\n", addr),
+        4 => format!("    mov eax, dword ptr [{}]\n", addr),
+        8 => format!("    mov rax, [{}]\n", addr),
+        _ => {
+            let mut s = "    xor eax, eax\n".to_string();
+            for i in (0..n).rev() {
+                s.push_str("    shl rax, 8\n");
+                s.push_str(&format!("    mov al, byte ptr [{}+{}]\n", addr, i));
+            }
+            s
+        }
+    }
+}
+
+// Returns code to store the lower `n` bytes of rax to the address in `addr`,
+// which clobbers rax.
+fn store_bytes(addr: &str, n: usize) -> String {
+    match n {
+        1 => format!("    mov byte ptr [{}], al\n", addr),
+        2 => format!("    mov word ptr [{}], ax\n", addr),
+        4 => format!("    mov dword ptr [{}], eax\n", addr),
+        8 => format!("    mov [{}], rax\n", addr),
+        _ => {
+            let mut s = "".to_string();
+            for i in 0..n {
+                s.push_str(&format!("    mov byte ptr [{}+{}], al\n", addr, i));
+                s.push_str("    shr rax, 8\n");
+            }
+            s
+        }
+    }
+}
+
+// Returns the instruction to shift a value of `ty` right, which fills the
+// upper bits with the sign bit unless the type is unsigned or _Bool.
+fn shift_right(ty: &Ty) -> &'static str {
+    if ty.is_unsigned() || *ty.unqual() == Ty::Bool {
+        "shr"
+    } else {
+        "sar"
+    }
+}
+
+// Pops the address of the bytes where a bit-field of `ty` is, and pushes its
+// value. Only the bytes which the bit-field occupies are accessed.
+fn load_bit_field(ty: &Ty, bit_field: BitField) -> String {
+    let BitField { bit_offset, width } = bit_field;
+    let mut s = "    pop rdi\n".to_string();
+    s.push_str(&load_bytes("rdi", (bit_offset + width).div_ceil(8)));
+    s.push_str(&format!("    shl rax, {}\n", 64 - bit_offset - width));
+    s.push_str(&format!("    {} rax, {}\n", shift_right(ty), 64 - width));
+    s.push_str("    push rax\n");
+    s
+}
+
+// Pops a value and then the address of the bytes where a bit-field of `ty` is,
+// stores the value to the bit-field, leaving the other bits as they are, and
+// pushes the value of the bit-field.
+fn store_bit_field(ty: &Ty, bit_field: BitField) -> String {
+    let BitField { bit_offset, width } = bit_field;
+    let n = (bit_offset + width).div_ceil(8);
+    let mask = (u64::MAX >> (64 - width)) << bit_offset;
+    let mut s = "    pop rdi\n    pop rsi\n".to_string();
+    s.push_str(&load_bytes("rsi", n));
+    s.push_str(&format!("    mov rdx, {}\n", !mask as i64));
+    s.push_str("    and rax, rdx\n");
+    s.push_str("    mov rdx, rdi\n");
+    s.push_str(&format!("    shl rdx, {}\n", 64 - width));
+    s.push_str(&format!("    shr rdx, {}\n", 64 - width - bit_offset));
+    s.push_str("    or rax, rdx\n");
+    s.push_str(&store_bytes("rsi", n));
+    s.push_str(&format!("    shl rdi, {}\n", 64 - width));
+    s.push_str(&format!("    {} rdi, {}\n", shift_right(ty), 64 - width));
+    s.push_str("    push rdi\n");
+    s
+}
+
+// Pops a value of `from`, and pushes it converted to `ty`.
+fn cast_to(from: &Ty, ty: &Ty) -> String {
+    // A value cast to void is only discarded, which takes one slot.
+    if *ty == Ty::Void {
+        return match from {
+            Ty::LongDouble => "    add rsp, 8\n".to_string(),
+            _ => "".to_string(),
+        };
+    }
+    if *from == Ty::LongDouble || *ty == Ty::LongDouble {
+        return convert_long_double(from, ty);
+    }
+    if from.is_flonum() || ty.is_flonum() {
+        return format!("    pop rax\n{}    push rax\n", convert_flonum(from, ty));
+    }
+    if *ty == Ty::Bool {
+        // Any nonzero value is converted to 1.
+        "    pop rax\n    cmp rax, 0\n    setne al\n    movzx rax, al\n    push rax\n".to_string()
+    } else if ty.is_integer() {
+        format!("    pop rax\n{}    push rax\n", extend(ty))
+    } else {
+        // A pointer has the same representation as the 64-bit value.
+        "".to_string()
+    }
+}
+
+// Returns code to convert the value in rax from `from` to `to`, either of
+// which is a floating type.
+//
+// The conversion instructions only take signed 64-bit integers, so an
+// unsigned long of 2^63 or greater is converted by halving it, keeping the
+// lowest bit for rounding, and doubling the result, and a floating value of
+// 2^63 or greater is converted after 2^63 is subtracted from it, which is
+// added back by flipping the highest bit. The branches jump to local labels,
+// which may be defined any number of times.
+fn convert_flonum(from: &Ty, to: &Ty) -> String {
+    if *from == Ty::ULong {
+        let sfx = sse_suffix(to);
+        return format!(
+            "    test rax, rax\n    js 1f\n    cvtsi2{sfx} xmm0, rax\n    jmp 2f\n1:\n    mov rdi, rax\n    shr rdi, 1\n    and eax, 1\n    or rdi, rax\n    cvtsi2{sfx} xmm0, rdi\n    add{sfx} xmm0, xmm0\n2:\n    movq rax, xmm0\n"
+        );
+    }
+    if !from.is_flonum() {
+        return format!(
+            "    cvtsi2{} xmm0, rax\n    movq rax, xmm0\n",
+            sse_suffix(to)
+        );
+    }
+    let from_sfx = sse_suffix(from);
+    match to {
+        // A NaN compares unordered, which is unequal to 0.
+        Ty::Bool => format!(
+            "    movq xmm0, rax\n    xorps xmm1, xmm1\n    ucomi{} xmm0, xmm1\n    setne al\n    setp dl\n    or al, dl\n    movzx rax, al\n",
+            from_sfx
+        ),
+        Ty::ULong => {
+            // 2^63 in the floating type
+            let bias = match from {
+                Ty::Float => 0x5f000000_u64,
+                _ => 0x43e0000000000000,
+            };
+            format!(
+                "    movq xmm0, rax\n    mov rax, {bias}\n    movq xmm1, rax\n    ucomi{from_sfx} xmm0, xmm1\n    jae 1f\n    cvtt{from_sfx}2si rax, xmm0\n    jmp 2f\n1:\n    sub{from_sfx} xmm0, xmm1\n    cvtt{from_sfx}2si rax, xmm0\n    btc rax, 63\n2:\n"
+            )
+        }
+        // The value is truncated toward zero.
+        _ if to.is_integer() => format!(
+            "    movq xmm0, rax\n    cvtt{}2si rax, xmm0\n{}",
+            from_sfx,
+            extend(to)
+        ),
+        _ if to == from => "".to_string(),
+        _ => format!(
+            "    movq xmm0, rax\n    cvt{}2{} xmm0, xmm0\n    movq rax, xmm0\n",
+            from_sfx,
+            sse_suffix(to)
+        ),
+    }
+}
+
+// Returns code to convert the value at the top of the stack from `from` to
+// `to`, either of which is long double, through the x87 FPU.
+//
+// As in `convert_flonum`, an unsigned long of 2^63 or greater is loaded as
+// signed, and 2^64 as a float is added to it, and a long double of 2^63 or
+// greater is stored after 2^63 is subtracted from it.
+fn convert_long_double(from: &Ty, to: &Ty) -> String {
+    let load = match from {
+        Ty::LongDouble => "    fld tbyte ptr [rsp]\n    add rsp, 16\n",
+        Ty::Float => "    fld dword ptr [rsp]\n    add rsp, 8\n",
+        Ty::Double => "    fld qword ptr [rsp]\n    add rsp, 8\n",
+        Ty::ULong => "    fild qword ptr [rsp]\n    cmp qword ptr [rsp], 0\n    jge 1f\n    mov dword ptr [rsp], 0x5f800000\n    fadd dword ptr [rsp]\n1:\n    add rsp, 8\n",
+        _ => "    fild qword ptr [rsp]\n    add rsp, 8\n",
+    };
+    let store = match to {
+        _ if to == from => return "".to_string(),
+        Ty::LongDouble => "    sub rsp, 16\n    fstp tbyte ptr [rsp]\n".to_string(),
+        Ty::Float => "    sub rsp, 8\n    fstp dword ptr [rsp]\n".to_string(),
+        Ty::Double => "    sub rsp, 8\n    fstp qword ptr [rsp]\n".to_string(),
+        // A NaN compares unordered, which is unequal to 0.
+        Ty::Bool => "    fldz\n    fucomip st, st(1)\n    fstp st(0)\n    setne al\n    setp dl\n    or al, dl\n    movzx rax, al\n    push rax\n".to_string(),
+        Ty::ULong => "    sub rsp, 8\n    mov dword ptr [rsp], 0x5f000000\n    fld dword ptr [rsp]\n    fcomip st, st(1)\n    jbe 1f\n    fisttp qword ptr [rsp]\n    jmp 2f\n1:\n    fsub dword ptr [rsp]\n    fisttp qword ptr [rsp]\n    btc qword ptr [rsp], 63\n2:\n".to_string(),
+        // The value is truncated toward zero.
+        _ => format!(
+            "    sub rsp, 8\n    fisttp qword ptr [rsp]\n    pop rax\n{}    push rax\n",
+            extend(to)
+        ),
+    };
+    format!("{}{}", load, store)
+}
+
+// Returns code to discard a value of `ty` pushed onto the stack.
+pub(super) fn discard(ty: &Ty) -> &'static str {
+    match ty {
+        Ty::LongDouble => "    add rsp, 16\n",
+        _ => "    pop rax\n",
+    }
+}
+
+// Returns the suffix of SSE instructions which operate on scalars of a
+// floating type.
+fn sse_suffix(ty: &Ty) -> &'static str {
+    match ty {
+        Ty::Float => "ss",
+        Ty::Double => "sd",
+        _ => unreachable!("not a floating type: {:?}", ty),
+    }
+}
+
+// Returns code to truncate rax to an integer type, and sign-extend or
+// zero-extend it back to 64 bits as the type is.
+fn extend(ty: &Ty) -> &'static str {
+    match ty.unqual() {
+        Ty::UChar => "    movzx rax, al\n",
+        Ty::UShort => "    movzx rax, ax\n",
+        Ty::UInt => "    mov eax, eax\n",
+        ty => match ty.size() {
+            1 => "    movsx rax, al\n",
+            2 => "    movsx rax, ax\n",
+            4 => "    movsxd rax, eax\n",
+            8 => "",
+            size => unreachable!("integer of {} bytes", size),
+        },
+    }
+}
+
+// Returns code to wrap the result of an integer operation in rax around to
+// `ty`, which only an unsigned int result needs as an int is not truncated.
+fn wrap(ty: &Ty) -> &'static str {
+    match ty {
+        Ty::UInt => extend(ty),
+        _ => "",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::f80::F80;
+    use crate::ty::{FuncTy, MemberDecl, StructRef};
 
     #[test]
     fn gen_num() {
-        let expr = Expr::Num(42);
+        let expr = Expr::Num(42, Ty::Int);
         let expected = "    push 42
 ";
-        let actual = gen_expr(&expr).unwrap();
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+
+        let expr = Expr::Num(1 << 32, Ty::Long);
+        let expected = "    mov rax, 4294967296
+    push rax
+";
+        let actual = gen_expr(&expr, 0).unwrap();
         assert_eq!(expected, actual);
     }
 
     #[test]
     fn gen_add_expr() {
-        let lhs = Expr::Num(12);
-        let rhs = Expr::Num(23);
+        let lhs = Expr::Num(12, Ty::Int);
+        let rhs = Expr::Num(23, Ty::Int);
         let expr = Expr::Add(Box::new(Binary { lhs, rhs }));
         let expected = "    push 12
     push 23
@@ -82,17 +761,17 @@ mod tests {
     add rax, rdi
     push rax
 ";
-        let actual = gen_expr(&expr).unwrap();
+        let actual = gen_expr(&expr, 0).unwrap();
         assert_eq!(expected, actual);
     }
 
     #[test]
     fn gen_nested_add_expr() {
         let lhs = Expr::Add(Box::new(Binary {
-            lhs: Expr::Num(12),
-            rhs: Expr::Num(23),
+            lhs: Expr::Num(12, Ty::Int),
+            rhs: Expr::Num(23, Ty::Int),
         }));
-        let rhs = Expr::Num(34);
+        let rhs = Expr::Num(34, Ty::Int);
         let expr = Expr::Add(Box::new(Binary { lhs, rhs }));
         let expected = "    push 12
     push 23
@@ -106,14 +785,14 @@ mod tests {
     add rax, rdi
     push rax
 ";
-        let actual = gen_expr(&expr).unwrap();
+        let actual = gen_expr(&expr, 0).unwrap();
         assert_eq!(expected, actual);
     }
 
     #[test]
     fn gen_sub_expr() {
-        let lhs = Expr::Num(23);
-        let rhs = Expr::Num(12);
+        let lhs = Expr::Num(23, Ty::Int);
+        let rhs = Expr::Num(12, Ty::Int);
         let expr = Expr::Sub(Box::new(Binary { lhs, rhs }));
         let expected = "    push 23
     push 12
@@ -122,14 +801,14 @@ mod tests {
     sub rax, rdi
     push rax
 ";
-        let actual = gen_expr(&expr).unwrap();
+        let actual = gen_expr(&expr, 0).unwrap();
         assert_eq!(expected, actual);
     }
 
     #[test]
     fn gen_mul_expr() {
-        let lhs = Expr::Num(2);
-        let rhs = Expr::Num(3);
+        let lhs = Expr::Num(2, Ty::Int);
+        let rhs = Expr::Num(3, Ty::Int);
         let expr = Expr::Mul(Box::new(Binary { lhs, rhs }));
         let expected = "    push 2
     push 3
@@ -138,40 +817,496 @@ mod tests {
     imul rax, rdi
     push rax
 ";
-        let actual = gen_expr(&expr).unwrap();
+        let actual = gen_expr(&expr, 0).unwrap();
         assert_eq!(expected, actual);
     }
 
     #[test]
     fn gen_div_expr() {
-        let lhs = Expr::Num(4);
-        let rhs = Expr::Num(2);
+        let lhs = Expr::Num(4, Ty::Int);
+        let rhs = Expr::Num(2, Ty::Int);
         let expr = Expr::Div(Box::new(Binary { lhs, rhs }));
         let expected = "    push 4
     push 2
     pop rdi
     pop rax
-    xor rdx, rdx
+    cqo
     idiv rdi
     push rax
 ";
-        let actual = gen_expr(&expr).unwrap();
+        let actual = gen_expr(&expr, 0).unwrap();
         assert_eq!(expected, actual);
     }
 
     #[test]
-    fn cannot_gen_function_name() {
-        let expr = Expr::FnName("some_func".to_string());
-        assert!(gen_expr(&expr).is_err());
+    fn gen_var() {
+        let expr = Expr::Var(Var {
+            name: "x".to_string(),
+            ty: Ty::Int,
+            offset: 4,
+        });
+        let expected = "    lea rax, [rbp-4]
+    push rax
+    pop rax
+    movsxd rax, dword ptr [rax]
+    push rax
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_assign_expr() {
+        let lhs = Expr::Var(Var {
+            name: "x".to_string(),
+            ty: Ty::Int,
+            offset: 8,
+        });
+        let rhs = Expr::Num(3, Ty::Int);
+        let expr = Expr::Assign(Box::new(Binary { lhs, rhs }));
+        let expected = "    lea rax, [rbp-8]
+    push rax
+    push 3
+    pop rdi
+    pop rax
+    mov dword ptr [rax], edi
+    push rdi
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_deref_of_pointer_variable() {
+        let expr = Expr::Deref(Box::new(Expr::Var(Var {
+            name: "p".to_string(),
+            ty: Ty::Ptr(Box::new(Ty::Int)),
+            offset: 8,
+        })));
+        let expected = "    lea rax, [rbp-8]
+    push rax
+    pop rax
+    mov rax, [rax]
+    push rax
+    pop rax
+    movsxd rax, dword ptr [rax]
+    push rax
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_addr_of_array_variable() {
+        // An array is not loaded.
+        let expr = Expr::Var(Var {
+            name: "a".to_string(),
+            ty: Ty::Array(Box::new(Ty::Int), 4),
+            offset: 16,
+        });
+        let expected = "    lea rax, [rbp-16]
+    push rax
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_cast_to_int() {
+        let expr = Expr::Cast(Box::new(Cast {
+            expr: Expr::Num(42, Ty::Int),
+            ty: Ty::Int,
+        }));
+        let expected = "    push 42
+    pop rax
+    movsxd rax, eax
+    push rax
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_cast_to_bool() {
+        let expr = Expr::Cast(Box::new(Cast {
+            expr: Expr::Num(42, Ty::Int),
+            ty: Ty::Bool,
+        }));
+        let expected = "    push 42
+    pop rax
+    cmp rax, 0
+    setne al
+    movzx rax, al
+    push rax
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_bit_field_access() {
+        // s.b = 3 for struct { char a; unsigned b : 3; } s
+        let ty = StructRef::new(None, false);
+        ty.complete(
+            vec![
+                MemberDecl {
+                    name: Some("a".to_string()),
+                    ty: Ty::Char,
+                    width: None,
+                    align: 0,
+                    is_packed: false,
+                },
+                MemberDecl {
+                    name: Some("b".to_string()),
+                    ty: Ty::UInt,
+                    width: Some(3),
+                    align: 0,
+                    is_packed: false,
+                },
+            ],
+            false,
+            0,
+        );
+        let member = ty.find_member("b").unwrap();
+        let s = Expr::Var(Var {
+            name: "s".to_string(),
+            ty: Ty::Struct(ty),
+            offset: 4,
+        });
+        let lhs = Expr::Member(Box::new(s), member);
+        let expr = Expr::Assign(Box::new(Binary {
+            lhs: lhs.clone(),
+            rhs: Expr::Num(3, Ty::Int),
+        }));
+        let expected = "    lea rax, [rbp-4]
+    push rax
+    pop rax
+    add rax, 1
+    push rax
+    push 3
+    pop rdi
+    pop rsi
+    movzx eax, byte ptr [rsi]
+    mov rdx, -8
+    and rax, rdx
+    mov rdx, rdi
+    shl rdx, 61
+    shr rdx, 61
+    or rax, rdx
+    mov byte ptr [rsi], al
+    shl rdi, 61
+    shr rdi, 61
+    push rdi
+";
+        assert_eq!(expected, gen_expr(&expr, 0).unwrap());
+
+        let expected = "    lea rax, [rbp-4]
+    push rax
+    pop rax
+    add rax, 1
+    push rax
+    pop rdi
+    movzx eax, byte ptr [rdi]
+    shl rax, 61
+    shr rax, 61
+    push rax
+";
+        assert_eq!(expected, gen_expr(&lhs, 0).unwrap());
+    }
+
+    #[test]
+    fn cannot_assign_to_non_lvalue() {
+        let lhs = Expr::Num(1, Ty::Int);
+        let rhs = Expr::Num(2, Ty::Int);
+        let expr = Expr::Assign(Box::new(Binary { lhs, rhs }));
+        assert!(gen_expr(&expr, 0).is_err());
+    }
+
+    fn int_func() -> Box<FuncTy> {
+        Box::new(FuncTy {
+            ret: Ty::Int,
+            params: None,
+            is_variadic: false,
+        })
+    }
+
+    #[test]
+    fn gen_global_variable() {
+        let expr = Expr::GVar(GVar {
+            name: "x".to_string(),
+            ty: Ty::Int,
+        });
+        let expected = "    mov rax, [rip+x@GOTPCREL]
+    push rax
+    pop rax
+    movsxd rax, dword ptr [rax]
+    push rax
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_function_name() {
+        let expr = Expr::FnName("some_func".to_string(), int_func());
+        let expected = "    mov rax, [rip+some_func@GOTPCREL]
+    push rax
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
     }
 
     #[test]
     fn gen_function_call() {
-        let expr = Expr::FnCall(Box::new(Expr::FnName("some_func".to_string())));
-        let expected = "    call some_func
+        let expr = Expr::FnCall(Box::new(Call {
+            func: Expr::FnName("some_func".to_string(), int_func()),
+            args: vec![],
+        }));
+        let expected = "    mov eax, 0
+    call some_func
+    movsxd rax, eax
+    push rax
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_function_call_with_args() {
+        let expr = Expr::FnCall(Box::new(Call {
+            func: Expr::FnName("some_func".to_string(), int_func()),
+            args: vec![Expr::Num(1, Ty::Int), Expr::Num(2, Ty::Int)],
+        }));
+        let expected = "    push 1
+    push 2
+    pop rsi
+    pop rdi
+    mov eax, 0
+    call some_func
+    movsxd rax, eax
+    push rax
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_function_call_with_unaligned_stack() {
+        let expr = Expr::FnCall(Box::new(Call {
+            func: Expr::FnName("some_func".to_string(), int_func()),
+            args: vec![],
+        }));
+        let expected = "    mov eax, 0
+    sub rsp, 8
+    call some_func
+    add rsp, 8
+    movsxd rax, eax
     push rax
 ";
-        let actual = gen_expr(&expr).unwrap();
+        let actual = gen_expr(&expr, 1).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_indirect_function_call() {
+        let ty = Ty::Ptr(Box::new(Ty::Func(Box::new(FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Int]),
+            is_variadic: false,
+        }))));
+        let fp = Expr::Var(Var {
+            name: "fp".to_string(),
+            ty,
+            offset: 8,
+        });
+        let expr = Expr::FnCall(Box::new(Call {
+            func: fp,
+            args: vec![Expr::Num(3, Ty::Int)],
+        }));
+        let expected = "    push 3
+    lea rax, [rbp-8]
+    push rax
+    pop rax
+    mov rax, [rax]
+    push rax
+    pop r10
+    pop rdi
+    sub rsp, 8
+    call r10
+    add rsp, 8
+    movsxd rax, eax
+    push rax
+";
+        let actual = gen_expr(&expr, 1).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_double_add_expr() {
+        let lhs = Expr::FNum(F80::from_f64(1.5), Ty::Double);
+        let rhs = Expr::FNum(F80::from_f64(0.25), Ty::Double);
+        let expr = Expr::Add(Box::new(Binary { lhs, rhs }));
+        let expected = "    mov rax, 4609434218613702656
+    push rax
+    mov rax, 4598175219545276416
+    push rax
+    pop rdi
+    pop rax
+    movq xmm0, rax
+    movq xmm1, rdi
+    addsd xmm0, xmm1
+    movq rax, xmm0
+    push rax
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_casts_between_int_and_float() {
+        let to_float = Expr::Cast(Box::new(Cast {
+            expr: Expr::Num(3, Ty::Int),
+            ty: Ty::Float,
+        }));
+        let expr = Expr::Cast(Box::new(Cast {
+            expr: to_float,
+            ty: Ty::Char,
+        }));
+        let expected = "    push 3
+    pop rax
+    cvtsi2ss xmm0, rax
+    movq rax, xmm0
+    push rax
+    pop rax
+    movq xmm0, rax
+    cvttss2si rax, xmm0
+    movsx rax, al
+    push rax
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_cast_from_unsigned_long_to_double() {
+        let expr = Expr::Cast(Box::new(Cast {
+            expr: Expr::Num(-1, Ty::ULong),
+            ty: Ty::Double,
+        }));
+        let expected = "    push -1
+    pop rax
+    test rax, rax
+    js 1f
+    cvtsi2sd xmm0, rax
+    jmp 2f
+1:
+    mov rdi, rax
+    shr rdi, 1
+    and eax, 1
+    or rdi, rax
+    cvtsi2sd xmm0, rdi
+    addsd xmm0, xmm0
+2:
+    movq rax, xmm0
+    push rax
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_function_call_with_floating_args() {
+        // f(1, 0.5f, 2) for f(int, float, int) returning double
+        let fty = Box::new(FuncTy {
+            ret: Ty::Double,
+            params: Some(vec![Ty::Int, Ty::Float, Ty::Int]),
+            is_variadic: false,
+        });
+        let expr = Expr::FnCall(Box::new(Call {
+            func: Expr::FnName("f".to_string(), fty),
+            args: vec![
+                Expr::Num(1, Ty::Int),
+                Expr::FNum(F80::from_f64(0.5), Ty::Float),
+                Expr::Num(2, Ty::Int),
+            ],
+        }));
+        let expected = "    push 1
+    mov eax, 1056964608
+    push rax
+    push 2
+    pop rsi
+    pop rax
+    movq xmm0, rax
+    pop rdi
+    call f
+    movq rax, xmm0
+    push rax
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_long_double_mul_expr() {
+        let lhs = Expr::FNum(F80::from_f64(1.0), Ty::LongDouble);
+        let rhs = Expr::FNum(F80::from_f64(-2.0), Ty::LongDouble);
+        let expr = Expr::Mul(Box::new(Binary { lhs, rhs }));
+        let expected = "    mov rax, 16383
+    push rax
+    mov rax, 9223372036854775808
+    push rax
+    mov rax, 49152
+    push rax
+    mov rax, 9223372036854775808
+    push rax
+    fld tbyte ptr [rsp]
+    fld tbyte ptr [rsp+16]
+    fmul st(0), st(1)
+    add rsp, 16
+    fstp tbyte ptr [rsp]
+    fstp st(0)
+";
+        let actual = gen_expr(&expr, 0).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn gen_function_call_with_long_double_args() {
+        // f(1, x, 2) for long double f(int, long double, int)
+        let fty = Box::new(FuncTy {
+            ret: Ty::LongDouble,
+            params: Some(vec![Ty::Int, Ty::LongDouble, Ty::Int]),
+            is_variadic: false,
+        });
+        let x = Expr::Var(Var {
+            name: "x".to_string(),
+            ty: Ty::LongDouble,
+            offset: 16,
+        });
+        let expr = Expr::FnCall(Box::new(Call {
+            func: Expr::FnName("f".to_string(), fty),
+            args: vec![Expr::Num(1, Ty::Int), x, Expr::Num(2, Ty::Int)],
+        }));
+        let expected = "    sub rsp, 24
+    push 1
+    lea rax, [rbp-16]
+    push rax
+    pop rax
+    push qword ptr [rax+8]
+    push qword ptr [rax]
+    pop rax
+    pop rdx
+    mov [rsp+8], rax
+    mov [rsp+16], rdx
+    push 2
+    pop rsi
+    pop rdi
+    call f
+    add rsp, 24
+    sub rsp, 16
+    fstp tbyte ptr [rsp]
+";
+        let actual = gen_expr(&expr, 1).unwrap();
         assert_eq!(expected, actual);
     }
 }
@@ -1,5 +1,9 @@
-use super::{expr::gen_expr, LabelIndex};
-use crate::parser::{IfStruct, Stmt};
+use super::{
+    expr::{discard, gen_expr},
+    LabelIndex,
+};
+use crate::parser::{IfStruct, Stmt, VlaBlock};
+use crate::ty::Ty;
 
 pub(super) fn gen_stmt(
     stmt: &Stmt,
@@ -8,20 +12,29 @@ pub(super) fn gen_stmt(
 ) -> Result<String, String> {
     match stmt {
         Stmt::ExprStmt(expr) => {
-            let mut asm = gen_expr(expr)?;
-            asm.push_str("    pop rax\n");
+            let mut asm = gen_expr(expr, 0)?;
+            asm.push_str(discard(&expr.ty()));
             Ok(asm)
         }
         Stmt::ReturnStmt(expr) => {
-            let mut asm = gen_expr(expr)?;
-            asm.push_str("    pop rax\n");
+            let mut asm = "".to_string();
+            // A long double value is returned in st(0), and others in rax,
+            // which is moved to xmm0 for float and double in the epilogue.
+            if let Some(expr) = expr {
+                asm.push_str(gen_expr(expr, 0)?.as_str());
+                if expr.ty() == Ty::LongDouble {
+                    asm.push_str("    fld tbyte ptr [rsp]\n    add rsp, 16\n");
+                } else {
+                    asm.push_str("    pop rax\n");
+                }
+            }
             asm.push_str(format!("    jmp {}\n", return_label).as_str());
             Ok(asm)
         }
         Stmt::IfStmt(if_struct) => {
             let IfStruct { cond, then } = &**if_struct;
             let else_label = format!(".d.if.else.{}", label_index.get());
-            let mut asm = gen_expr(cond)?;
+            let mut asm = gen_expr(cond, 0)?;
             asm.push_str("    pop rax\n");
             asm.push_str("    cmp rax, 0\n");
             asm.push_str(format!("    je {}\n", else_label).as_str());
@@ -36,6 +49,15 @@ pub(super) fn gen_stmt(
             }
             Ok(asm)
         }
+        Stmt::VlaBlock(block) => {
+            let VlaBlock { sp, body } = &**block;
+            let mut asm = format!("    mov [rbp-{}], rsp\n", sp);
+            for stmt in body {
+                asm.push_str(gen_stmt(stmt, return_label, label_index)?.as_str());
+            }
+            asm.push_str(format!("    mov rsp, [rbp-{}]\n", sp).as_str());
+            Ok(asm)
+        }
         Stmt::NullStmt => Ok("".to_string()),
     }
 }
@@ -47,7 +69,7 @@ mod tests {
 
     #[test]
     fn gen_expr_stmt() {
-        let ast = Stmt::ExprStmt(Expr::Num(42));
+        let ast = Stmt::ExprStmt(Expr::Num(42, Ty::Int));
         let expected = "    push 42
     pop rax
 ";
@@ -57,7 +79,7 @@ mod tests {
 
     #[test]
     fn gen_return_stmt() {
-        let ast = Stmt::ReturnStmt(Expr::Num(42));
+        let ast = Stmt::ReturnStmt(Some(Expr::Num(42, Ty::Int)));
         let expected = "    push 42
     pop rax
     jmp some_label
@@ -69,8 +91,8 @@ mod tests {
     #[test]
     fn gen_if_stmt() {
         let ast = Stmt::IfStmt(Box::new(IfStruct {
-            cond: Expr::Num(1),
-            then: Stmt::ExprStmt(Expr::Num(2)),
+            cond: Expr::Num(1, Ty::Int),
+            then: Stmt::ExprStmt(Expr::Num(2, Ty::Int)),
         }));
         let expected = "    push 1
     pop rax
@@ -87,8 +109,8 @@ mod tests {
     #[test]
     fn gen_compound_stmt() {
         let ast = Stmt::CompStmt(vec![
-            Stmt::ExprStmt(Expr::Num(2)),
-            Stmt::ExprStmt(Expr::Num(3)),
+            Stmt::ExprStmt(Expr::Num(2, Ty::Int)),
+            Stmt::ExprStmt(Expr::Num(3, Ty::Int)),
         ]);
         let expected = "    push 2
     pop rax
@@ -99,6 +121,21 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn gen_vla_block() {
+        let ast = Stmt::VlaBlock(Box::new(VlaBlock {
+            sp: 8,
+            body: vec![Stmt::ExprStmt(Expr::Num(2, Ty::Int))],
+        }));
+        let expected = "    mov [rbp-8], rsp
+    push 2
+    pop rax
+    mov rsp, [rbp-8]
+";
+        let actual = gen_stmt(&ast, ".d.main.return", &mut LabelIndex::new()).unwrap();
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn gen_null_stmt() {
         let ast = Stmt::NullStmt;
@@ -0,0 +1,351 @@
+// Floating value in the x87 80-bit extended precision format, which is the
+// representation of long double. It represents any value of float and double
+// exactly, so it holds floating constants of all the floating types.
+//
+// It has a sign bit, a 15-bit exponent biased by 16383, and a 64-bit
+// significand whose most significant bit is the explicit integer bit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct F80 {
+    pub sign: bool,
+    pub exp: u16,
+    pub significand: u64,
+}
+
+const EXP_BIAS: i32 = 16383;
+const EXP_MAX: u16 = 0x7fff; // exponent of infinities and NaNs
+
+impl F80 {
+    // Returns the value of `val` exactly.
+    pub fn from_f64(val: f64) -> F80 {
+        let bits = val.to_bits();
+        let sign = bits >> 63 == 1;
+        let exp = ((bits >> 52) & 0x7ff) as i32;
+        let frac = bits & ((1 << 52) - 1);
+        let (exp, significand) = match exp {
+            0x7ff => (EXP_MAX, 1 << 63 | frac << 11),
+            0 if frac == 0 => (0, 0),
+            // A subnormal double is normal in the extended format.
+            0 => {
+                let lz = frac.leading_zeros() as i32;
+                ((EXP_BIAS - 1011 - lz) as u16, frac << lz)
+            }
+            _ => ((exp - 1023 + EXP_BIAS) as u16, 1 << 63 | frac << 11),
+        };
+        F80 {
+            sign,
+            exp,
+            significand,
+        }
+    }
+
+    // Returns the value of `n` exactly.
+    pub fn from_i64(n: i64) -> F80 {
+        let val = F80::from_u64(n.unsigned_abs());
+        F80 { sign: n < 0, ..val }
+    }
+
+    // Returns the value of `n` exactly.
+    pub fn from_u64(n: u64) -> F80 {
+        F80::from_ratio(&[n as u32, (n >> 32) as u32], &[1], 0)
+    }
+
+    // Returns the value of decimal `digits` multiplied by 10 to the power of
+    // `exp`, rounded to nearest.
+    //
+    // e.g. F80::from_decimal("15", -1) is 1.5
+    pub fn from_decimal(digits: &str, exp: i32) -> F80 {
+        let digits = digits.trim_start_matches('0');
+        if digits.is_empty() {
+            return F80::from_f64(0.0);
+        }
+        // Values beyond the range of the format are infinity or zero.
+        let magnitude = exp.saturating_add(digits.len() as i32);
+        if magnitude > 4933 {
+            return F80::from_f64(f64::INFINITY);
+        }
+        if magnitude < -4952 {
+            return F80::from_f64(0.0);
+        }
+
+        let mut num = big_from_digits(digits, 10);
+        let mut den = vec![1];
+        for _ in 0..exp.unsigned_abs() {
+            if exp > 0 {
+                big_mul_small(&mut num, 10, 0);
+            } else {
+                big_mul_small(&mut den, 10, 0);
+            }
+        }
+        F80::from_ratio(&num, &den, 0)
+    }
+
+    // Returns the value of hexadecimal `digits` multiplied by 2 to the power
+    // of `exp`, rounded to nearest.
+    //
+    // e.g. F80::from_hex("18", -3) is 3.0
+    pub fn from_hex(digits: &str, exp: i32) -> F80 {
+        let digits = digits.trim_start_matches('0');
+        if digits.is_empty() {
+            return F80::from_f64(0.0);
+        }
+        let magnitude = exp.saturating_add(digits.len() as i32 * 4);
+        if magnitude > 16385 {
+            return F80::from_f64(f64::INFINITY);
+        }
+        if magnitude < -16446 {
+            return F80::from_f64(0.0);
+        }
+        F80::from_ratio(&big_from_digits(digits, 16), &[1], exp)
+    }
+
+    // Returns `num` / `den` multiplied by 2 to the power of `exp`, rounded to
+    // nearest, ties to even.
+    fn from_ratio(num: &[u32], den: &[u32], exp: i32) -> F80 {
+        if big_bit_len(num) == 0 {
+            return F80::from_f64(0.0);
+        }
+
+        // Scale the numerator or the denominator so that the quotient has
+        // 66 or 67 bits, enough to round it to 64 bits.
+        let mut num = num.to_vec();
+        let mut den = den.to_vec();
+        let shift = big_bit_len(&den) as i32 + 66 - big_bit_len(&num) as i32;
+        if shift > 0 {
+            big_shl(&mut num, shift as usize);
+        } else {
+            big_shl(&mut den, (-shift) as usize);
+        }
+        let (quot, is_exact) = big_div(&num, &den);
+
+        // value = quot * 2^(exp - shift), whose most significant bit becomes
+        // the integer bit with the biased exponent. A subnormal value has the
+        // minimum exponent without the integer bit, so more bits are rounded
+        // off.
+        let bits = 128 - quot.leading_zeros() as i32;
+        let mut biased = exp - shift + bits - 1 + EXP_BIAS;
+        let drop = bits - 64 + (1 - biased).max(0);
+        let mut significand = round_shr(quot, drop, is_exact);
+        if biased <= 0 {
+            // A subnormal value rounded up to the integer bit is normal.
+            return F80 {
+                sign: false,
+                exp: (significand >> 63) as u16,
+                significand: significand as u64,
+            };
+        }
+        if significand >> 64 == 1 {
+            significand >>= 1;
+            biased += 1;
+        }
+        if biased >= EXP_MAX as i32 {
+            return F80::from_f64(f64::INFINITY);
+        }
+        F80 {
+            sign: false,
+            exp: biased as u16,
+            significand: significand as u64,
+        }
+    }
+
+    // Returns the value rounded to double.
+    pub fn to_f64(self) -> f64 {
+        let sign = if self.sign { -1.0 } else { 1.0 };
+        if self.exp == EXP_MAX {
+            return match self.significand << 1 {
+                0 => sign * f64::INFINITY,
+                _ => f64::NAN,
+            };
+        }
+        if self.significand == 0 {
+            return sign * 0.0;
+        }
+
+        // Round the normalized significand to 53 bits.
+        let lz = self.significand.leading_zeros();
+        let significand = self.significand << lz;
+        let mut exp = self.exp.max(1) as i32 - EXP_BIAS - lz as i32;
+        let rem = significand & 0x7ff;
+        let mut top = significand >> 11;
+        if rem > 0x400 || (rem == 0x400 && top & 1 == 1) {
+            top += 1;
+            if top >> 53 == 1 {
+                top >>= 1;
+                exp += 1;
+            }
+        }
+        if exp > 1023 {
+            return sign * f64::INFINITY;
+        }
+        if exp < -1022 {
+            // The result is subnormal, whose precision is less than 53 bits.
+            return sign * top as f64 * 2f64.powi(exp + 600 - 52) * 2f64.powi(-600);
+        }
+        let bits = ((exp + 1023) as u64) << 52 | (top & ((1 << 52) - 1));
+        sign * f64::from_bits(bits)
+    }
+
+    // Returns the 10 bytes of the value in memory, in little endian.
+    pub fn to_le_bytes(self) -> [u8; 10] {
+        let mut bytes = [0; 10];
+        bytes[..8].copy_from_slice(&self.significand.to_le_bytes());
+        let exp = self.exp | (self.sign as u16) << 15;
+        bytes[8..].copy_from_slice(&exp.to_le_bytes());
+        bytes
+    }
+}
+
+// Returns `n` shifted right by `drop` bits, rounded to nearest, ties to even.
+// `is_exact` is false if `n` has been truncated, which breaks a tie upwards.
+fn round_shr(n: u128, drop: i32, is_exact: bool) -> u128 {
+    if drop >= 128 {
+        return 0;
+    }
+    let rem = n & ((1 << drop) - 1);
+    let half = 1 << (drop - 1);
+    let q = n >> drop;
+    if rem > half || (rem == half && (!is_exact || q & 1 == 1)) {
+        q + 1
+    } else {
+        q
+    }
+}
+
+// Arbitrary precision unsigned integers, as little-endian 32-bit words, which
+// are only needed for exact conversion of floating constants.
+
+fn big_from_digits(digits: &str, radix: u32) -> Vec<u32> {
+    let mut n = vec![0];
+    for c in digits.chars() {
+        big_mul_small(&mut n, radix, c.to_digit(radix).unwrap());
+    }
+    n
+}
+
+// n = n * m + add
+fn big_mul_small(n: &mut Vec<u32>, m: u32, add: u32) {
+    let mut carry = add as u64;
+    for word in n.iter_mut() {
+        let v = *word as u64 * m as u64 + carry;
+        *word = v as u32;
+        carry = v >> 32;
+    }
+    if carry > 0 {
+        n.push(carry as u32);
+    }
+}
+
+fn big_bit_len(n: &[u32]) -> usize {
+    match n.iter().rposition(|&w| w != 0) {
+        Some(i) => i * 32 + 32 - n[i].leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+fn big_bit(n: &[u32], i: usize) -> bool {
+    n.get(i / 32).is_some_and(|w| w >> (i % 32) & 1 == 1)
+}
+
+fn big_shl(n: &mut Vec<u32>, bits: usize) {
+    let mut shifted = vec![0; bits / 32];
+    let mut carry = 0;
+    for &word in n.iter() {
+        let v = (word as u64) << (bits % 32) | carry;
+        shifted.push(v as u32);
+        carry = v >> 32;
+    }
+    shifted.push(carry as u32);
+    *n = shifted;
+}
+
+fn big_ge(a: &[u32], b: &[u32]) -> bool {
+    let len = a.len().max(b.len());
+    for i in (0..len).rev() {
+        let (x, y) = (a.get(i).unwrap_or(&0), b.get(i).unwrap_or(&0));
+        if x != y {
+            return x > y;
+        }
+    }
+    true
+}
+
+// a = a - b, where a >= b
+fn big_sub(a: &mut [u32], b: &[u32]) {
+    let mut borrow = 0;
+    for (i, word) in a.iter_mut().enumerate() {
+        let v = *word as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        *word = v as u32;
+        borrow = (v < 0) as i64;
+    }
+}
+
+// Returns the quotient of `num` / `den`, which must fit in u128, and whether
+// the division is exact.
+fn big_div(num: &[u32], den: &[u32]) -> (u128, bool) {
+    let mut quot: u128 = 0;
+    let mut rem = vec![0; den.len() + 1];
+    for i in (0..big_bit_len(num)).rev() {
+        big_mul_small(&mut rem, 2, big_bit(num, i) as u32);
+        quot <<= 1;
+        if big_ge(&rem, den) {
+            big_sub(&mut rem, den);
+            quot |= 1;
+        }
+    }
+    (quot, big_bit_len(&rem) == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_from_and_to_double() {
+        for val in [0.0, 1.0, -2.5, 0.1, 1e300, 5e-324, f64::MAX, f64::INFINITY] {
+            assert_eq!(val, F80::from_f64(val).to_f64(), "{}", val);
+        }
+        let one = F80 {
+            sign: false,
+            exp: 16383,
+            significand: 1 << 63,
+        };
+        assert_eq!(one, F80::from_f64(1.0));
+        assert_eq!(one, F80::from_i64(1));
+    }
+
+    #[test]
+    fn converts_decimal_with_extended_precision() {
+        // 0.1 rounded to 64 bits of significand, as gcc emits for 0.1L
+        let tenth = F80::from_decimal("1", -1);
+        assert_eq!(
+            [0xcd, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xfb, 0x3f],
+            tenth.to_le_bytes()
+        );
+        assert_eq!(0.1, tenth.to_f64());
+        assert_eq!(F80::from_f64(1500.0), F80::from_decimal("0015", 2));
+        assert_eq!(F80::from_f64(f64::INFINITY), F80::from_decimal("1", 5000));
+    }
+
+    #[test]
+    fn converts_hexadecimal() {
+        assert_eq!(F80::from_f64(3.0), F80::from_hex("18", -3));
+        let max = F80::from_hex("ffffffffffffffff", 0);
+        assert_eq!(u64::MAX, max.significand);
+        assert_eq!(16383 + 63, max.exp);
+    }
+
+    #[test]
+    fn rounds_subnormal_to_nearest() {
+        // 0x1.fffffffffffffffep-16383L rounds up to the minimum normal value.
+        let min = F80::from_hex("1fffffffffffffffe", -16383 - 64);
+        assert_eq!(1, min.exp);
+        assert_eq!(1 << 63, min.significand);
+        // Multiples of the minimum subnormal value 0x1p-16445L: 2.5 is
+        // rounded to even, 2.5000001 up, and 0.75 up.
+        let tie = F80::from_hex("28", -16445 - 4);
+        assert_eq!((0, 2), (tie.exp, tie.significand));
+        let above = F80::from_hex("28000001", -16445 - 28);
+        assert_eq!((0, 3), (above.exp, above.significand));
+        let below = F80::from_hex("c", -16445 - 4);
+        assert_eq!((0, 1), (below.exp, below.significand));
+    }
+}
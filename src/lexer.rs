@@ -1,50 +1,219 @@
+use crate::f80::F80;
+
+use std::rc::Rc;
+
 #[derive(Debug, PartialEq)]
 pub enum Token {
-    Num(u64),
+    // integer constant, whose type is given by its suffix and value
+    Num(u64, IntKind),
+    // floating constant, whose value is rounded to its type
+    FNum(F80, FloatKind),
     // puctuator
     Punct(String),
     // identifier
     Ident(String),
+    // string literal, without the terminating null character
+    Str(Vec<u8>),
     // keyword
     Kw(KwKind),
 }
 
+// type of a floating constant, given by its suffix
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FloatKind {
+    Float,      // with `f` or `F`
+    Double,     // without a suffix
+    LongDouble, // with `l` or `L`
+}
+
+// type of an integer constant, which is the first of the types allowed by its
+// suffix that can represent the value
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum IntKind {
+    Int,
+    UInt,
+    Long,
+    ULong,
+}
+
+// preprocessing token, which keeps its spelling for the preprocessor, and is
+// converted to a token after preprocessing
+#[derive(Debug, Clone, PartialEq)]
+pub struct PpToken {
+    pub kind: PpKind,
+    pub text: String,
+    pub file: Rc<str>,   // name of the file it appears in
+    pub line: usize,     // line number, starting from 1
+    pub at_bol: bool,    // true if it is the first token of a line
+    pub has_space: bool, // true if whitespace precedes it
+    // names of the macros whose expansion produced it, which are not expanded
+    // again in it
+    pub hideset: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PpKind {
+    Ident,
+    Num, // preprocessing number, e.g. `1.5e+3f`, which may be invalid as a number
+    Str,
+    Char, // character constant, e.g. `'a'`
+    Punct,
+    Other, // any other character, e.g. `@`, which is invalid after preprocessing
+}
+
 // keyword kind
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum KwKind {
-    Return, // return
-    If,     // if
-    Int,    // int
+    Auto,         // auto
+    Break,        // break
+    Case,         // case
+    Char,         // char
+    Const,        // const
+    Continue,     // continue
+    Default,      // default
+    Do,           // do
+    Double,       // double
+    Else,         // else
+    Enum,         // enum
+    Extern,       // extern
+    Float,        // float
+    For,          // for
+    Goto,         // goto
+    If,           // if
+    Inline,       // inline
+    Int,          // int
+    Long,         // long
+    Register,     // register
+    Restrict,     // restrict
+    Return,       // return
+    Short,        // short
+    Signed,       // signed
+    Sizeof,       // sizeof
+    Static,       // static
+    Struct,       // struct
+    Switch,       // switch
+    Typedef,      // typedef
+    Union,        // union
+    Unsigned,     // unsigned
+    Void,         // void
+    Volatile,     // volatile
+    While,        // while
+    Alignas,      // _Alignas
+    Alignof,      // _Alignof
+    Atomic,       // _Atomic
+    Bool,         // _Bool
+    Complex,      // _Complex
+    Generic,      // _Generic
+    Imaginary,    // _Imaginary
+    Noreturn,     // _Noreturn
+    StaticAssert, // _Static_assert
+    ThreadLocal,  // _Thread_local
+    Attribute,    // __attribute__
 }
 
+// keywords of C11, and __attribute__ of GNU C
+const KEYWORDS: [(&str, KwKind); 45] = [
+    ("auto", KwKind::Auto),
+    ("break", KwKind::Break),
+    ("case", KwKind::Case),
+    ("char", KwKind::Char),
+    ("const", KwKind::Const),
+    ("continue", KwKind::Continue),
+    ("default", KwKind::Default),
+    ("do", KwKind::Do),
+    ("double", KwKind::Double),
+    ("else", KwKind::Else),
+    ("enum", KwKind::Enum),
+    ("extern", KwKind::Extern),
+    ("float", KwKind::Float),
+    ("for", KwKind::For),
+    ("goto", KwKind::Goto),
+    ("if", KwKind::If),
+    ("inline", KwKind::Inline),
+    ("int", KwKind::Int),
+    ("long", KwKind::Long),
+    ("register", KwKind::Register),
+    ("restrict", KwKind::Restrict),
+    ("return", KwKind::Return),
+    ("short", KwKind::Short),
+    ("signed", KwKind::Signed),
+    ("sizeof", KwKind::Sizeof),
+    ("static", KwKind::Static),
+    ("struct", KwKind::Struct),
+    ("switch", KwKind::Switch),
+    ("typedef", KwKind::Typedef),
+    ("union", KwKind::Union),
+    ("unsigned", KwKind::Unsigned),
+    ("void", KwKind::Void),
+    ("volatile", KwKind::Volatile),
+    ("while", KwKind::While),
+    ("_Alignas", KwKind::Alignas),
+    ("_Alignof", KwKind::Alignof),
+    ("_Atomic", KwKind::Atomic),
+    ("_Bool", KwKind::Bool),
+    ("_Complex", KwKind::Complex),
+    ("_Generic", KwKind::Generic),
+    ("_Imaginary", KwKind::Imaginary),
+    ("_Noreturn", KwKind::Noreturn),
+    ("_Static_assert", KwKind::StaticAssert),
+    ("_Thread_local", KwKind::ThreadLocal),
+    ("__attribute__", KwKind::Attribute),
+];
+
 pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
     let mut tokens: Vec<Token> = Vec::new();
 
-    let mut rest = input;
-    loop {
-        let c = match rest.chars().next() {
-            Some(c) => c,
-            None => break,
-        };
-
-        // skip whitespace
-        if c == ' ' {
+    let input = splice_lines(input);
+    let mut rest = input.as_str();
+    while let Some(c) = rest.chars().next() {
+        // skip whitespace and comments
+        if c == '\n' {
             rest = &rest[1..];
             continue;
         }
+        if let Some(len) = space_len(rest)? {
+            rest = &rest[len..];
+            continue;
+        }
 
-        // number
-        if c.is_ascii_digit() {
+        // number, which may start with a period, e.g. `.5`
+        if c.is_ascii_digit() || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit())) {
             let num;
-            (num, rest) = take_number_from_start(&rest).expect("failed to take number");
-            tokens.push(Token::Num(num));
+            (num, rest) = take_number_from_start(rest)?.expect("failed to take number");
+            tokens.push(num);
             continue;
         }
 
-        // operator
-        if c.is_ascii_punctuation() {
+        // string literal
+        if c == '"' {
+            let s;
+            (s, rest) = take_string_from_start(rest)?;
+            tokens.push(Token::Str(s));
+            continue;
+        }
+
+        // keyword or identifier
+        if let Some((ident, r)) = take_ident_from_start(rest) {
+            let tok = match KEYWORDS.iter().find(|(kw, _)| *kw == ident) {
+                Some((_, kind)) => Token::Kw(*kind),
+                None => Token::Ident(ident),
+            };
+            tokens.push(tok);
+            rest = r;
+            continue;
+        }
+
+        // operator, where an ellipsis and an arrow are the only ones of
+        // multiple characters
+        if let Some(punct) = ["...", "->"].iter().find(|p| rest.starts_with(*p)) {
+            tokens.push(Token::Punct(punct.to_string()));
+            rest = &rest[punct.len()..];
+            continue;
+        }
+        if c.is_ascii_punctuation() && c != '_' {
             match c {
-                '+' | '-' | '*' | '/' | '(' | ')' | '{' | '}' | ';' => {
+                '+' | '-' | '*' | '/' | '(' | ')' | '{' | '}' | '[' | ']' | ';' | '=' | ','
+                | '&' | '.' | ':' => {
                     tokens.push(Token::Punct(c.to_string()));
                     rest = &rest[1..];
                     continue;
@@ -53,76 +222,586 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
             }
         }
 
-        // keyword or identifier
-        if c.is_ascii_alphabetic() {
-            let ident;
-            (ident, rest) = match take_ident_from_start(rest) {
-                Some((ident, rest)) => (ident, rest),
-                None => return Err("identifier not found".to_string()),
-            };
-            let tok = match ident {
-                "return" => Token::Kw(KwKind::Return),
-                "if" => Token::Kw(KwKind::If),
-                "int" => Token::Kw(KwKind::Int),
-                _ => Token::Ident(ident.to_string()),
-            };
-            tokens.push(tok);
+        return Err(format!("unexpected input: {}", rest));
+    }
+
+    Ok(tokens)
+}
+
+// punctuators of C, longest first
+pub const PUNCTS: [&str; 48] = [
+    "...", "<<=", ">>=", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "*=",
+    "/=", "%=", "+=", "-=", "&=", "^=", "|=", "##", "[", "]", "(", ")", "{", "}", ".", "&", "*",
+    "+", "-", "~", "!", "/", "%", "<", ">", "^", "|", "?", ":", ";", "=", ",", "#",
+];
+
+// Splits the input of the file into preprocessing tokens, recording the lines
+// they are on.
+pub fn tokenize_pp(file: &str, input: &str) -> Result<Vec<PpToken>, String> {
+    let file: Rc<str> = Rc::from(file);
+    let mut tokens: Vec<PpToken> = Vec::new();
+    let mut line = 1;
+    let mut at_bol = true;
+    let mut has_space = false;
+
+    let input = splice_lines(input);
+    let mut rest = input.as_str();
+    while let Some(c) = rest.chars().next() {
+        if c == '\n' {
+            line += 1;
+            at_bol = true;
+            has_space = false;
+            rest = &rest[1..];
+            continue;
+        }
+        // A comment is a space, even if it spans lines.
+        if let Some(len) = space_len(rest).map_err(|err| format!("{}:{}: {}", file, line, err))? {
+            line += rest[..len].matches('\n').count();
+            has_space = true;
+            rest = &rest[len..];
             continue;
         }
 
-        return Err(format!("unexpected input: {}", rest));
+        let mut ident = None;
+        let (kind, len) = if c.is_ascii_digit()
+            || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            (PpKind::Num, pp_number_len(rest))
+        } else if c == '"' || c == '\'' {
+            // An unclosed quote is left to be an error after preprocessing,
+            // since it may be in a skipped group, e.g. `it's`.
+            match (quoted_len(rest), c) {
+                (Some(len), '"') => (PpKind::Str, len),
+                (Some(len), _) => (PpKind::Char, len),
+                (None, _) => (PpKind::Other, 1),
+            }
+        } else if let Some((name, r)) = take_ident_from_start(rest) {
+            // An identifier is spelled with universal character names
+            // decoded, which name the same identifier as the characters.
+            ident = Some(name);
+            (PpKind::Ident, rest.len() - r.len())
+        } else if let Some(punct) = PUNCTS.iter().find(|p| rest.starts_with(*p)) {
+            (PpKind::Punct, punct.len())
+        } else {
+            (PpKind::Other, c.len_utf8())
+        };
+        tokens.push(PpToken {
+            kind,
+            text: ident.unwrap_or_else(|| rest[..len].to_string()),
+            file: file.clone(),
+            line,
+            at_bol,
+            has_space,
+            hideset: Vec::new(),
+        });
+        at_bol = false;
+        has_space = false;
+        rest = &rest[len..];
     }
 
     Ok(tokens)
 }
 
-// Takes a number from the start of `s`, and returns the rest of the str.
+// Removes backslash-newlines, which join the lines around them. As many
+// newlines are added after the joined line, so that the lines following it
+// keep their numbers.
+fn splice_lines(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut removed = 0;
+    let mut rest = input;
+    while let Some(c) = rest.chars().next() {
+        if let Some(r) = rest
+            .strip_prefix("\\\n")
+            .or_else(|| rest.strip_prefix("\\\r\n"))
+        {
+            removed += 1;
+            rest = r;
+            continue;
+        }
+        if c == '\n' {
+            output.push_str(&"\n".repeat(removed));
+            removed = 0;
+        }
+        output.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    output.push_str(&"\n".repeat(removed));
+    output
+}
+
+// Returns the length of whitespace other than a newline, or of a comment, at
+// the start of `s`, or None if it starts with neither. A line comment ends
+// before the newline, and a block comment may contain newlines.
 //
 // e.g.
-//   take_number_from_start("123hello") => Some(123, "hello")
-//   take_number_from_start("hello123") => None
-fn take_number_from_start<'a>(s: &'a str) -> Option<(u64, &'a str)> {
-    let mut len = 0;
-    for c in s.chars() {
-        if c.is_ascii_digit() {
-            len += 1;
-        } else {
+//   space_len("\t x") => Ok(Some(1))
+//   space_len("// a\nx") => Ok(Some(4))
+//   space_len("/* a\n */x") => Ok(Some(9))
+//   space_len("/* a") => Err("unterminated comment")
+fn space_len(s: &str) -> Result<Option<usize>, String> {
+    if s.starts_with("//") {
+        return Ok(Some(s.find('\n').unwrap_or(s.len())));
+    }
+    if let Some(comment) = s.strip_prefix("/*") {
+        return match comment.find("*/") {
+            Some(len) => Ok(Some(len + 4)),
+            None => Err("unterminated comment".to_string()),
+        };
+    }
+    match s.chars().next() {
+        Some(' ' | '\t' | '\r' | '\x0b' | '\x0c') => Ok(Some(1)),
+        _ => Ok(None),
+    }
+}
+
+// Returns the length of a preprocessing number at the start of `s`, which
+// consists of digits, identifier characters, periods, and signs following an
+// exponent mark, e.g. `0x1.8p+1`.
+fn pp_number_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let mut len = 1;
+    while len < bytes.len() {
+        let c = bytes[len];
+        let is_sign =
+            matches!(c, b'+' | b'-') && matches!(bytes[len - 1], b'e' | b'E' | b'p' | b'P');
+        if !(is_sign || c.is_ascii_alphanumeric() || c == b'_' || c == b'.') {
             break;
         }
+        len += 1;
     }
+    len
+}
 
-    match len {
+// Returns the length of a string literal or a character constant at the start
+// of `s`, including the quotes, or None if it is not closed in the line.
+fn quoted_len(s: &str) -> Option<usize> {
+    let quote = s.chars().next()?;
+    let mut escaped = false;
+    for (i, c) in s.char_indices().skip(1) {
+        match c {
+            '\n' => return None,
+            c if c == quote && !escaped => return Some(i + 1),
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+impl PpToken {
+    // Returns the error message at the location of the token.
+    pub fn error(&self, msg: &str) -> String {
+        format!("{}:{}: {}", self.file, self.line, msg)
+    }
+}
+
+// Converts preprocessing tokens into tokens, each of which must make exactly
+// one token.
+pub fn convert_pp_tokens(tokens: &[PpToken]) -> Result<Vec<Token>, String> {
+    tokens
+        .iter()
+        .map(|tok| {
+            let mut converted = tokenize(&tok.text).map_err(|err| tok.error(&err))?;
+            match converted.len() {
+                1 => Ok(converted.pop().unwrap()),
+                _ => Err(tok.error(&format!("invalid token: {}", tok.text))),
+            }
+        })
+        .collect()
+}
+
+// Takes a number from the start of `s`, and returns the rest of the str.
+//
+// A number is an integer constant, decimal or hexadecimal, or a floating
+// constant. An integer constant may be followed by a suffix of `u` and `l` or
+// `ll` in either order. A floating constant has a fraction or an exponent,
+// which is introduced by `e` for a decimal one and by `p` (a power of 2) for
+// a hexadecimal one, and may be followed by a suffix `f` to make it a float
+// or `l` to make it a long double.
+//
+// Returns an error if an integer constant does not fit in 64 bits.
+//
+// e.g.
+//   take_number_from_start("123hello") => Ok(Some((Num(123, Int), "hello")))
+//   take_number_from_start("0x1fu;") => Ok(Some((Num(31, UInt), ";")))
+//   take_number_from_start("1.5e1f;") => Ok(Some((FNum(15.0, Float), ";")))
+//   take_number_from_start("0x1.8p1;") => Ok(Some((FNum(3.0, Double), ";")))
+//   take_number_from_start("hello123") => Ok(None)
+fn take_number_from_start(s: &str) -> Result<Option<(Token, &str)>, String> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        if let Some(num) = take_hex_number_from_start(hex)? {
+            return Ok(Some(num));
+        }
+    }
+
+    let int_len = count_digits(s, 10);
+    let mut len = int_len;
+    let mut frac_len = 0;
+    if s[len..].starts_with('.') {
+        frac_len = count_digits(&s[len + 1..], 10);
+        if int_len == 0 && frac_len == 0 {
+            return Ok(None);
+        }
+        len += 1 + frac_len;
+    } else if int_len == 0 {
+        return Ok(None);
+    }
+    let mantissa_len = len;
+    let exp_len = exponent_len(&s[len..], 'e');
+    len += exp_len.unwrap_or(0);
+
+    if frac_len == 0 && mantissa_len == int_len && exp_len.is_none() {
+        let num = s[..len]
+            .parse()
+            .map_err(|_| "integer constant is too large".to_string())?;
+        let (kind, rest) = take_int_suffix(&s[len..], num, true);
+        return Ok(Some((Token::Num(num, kind), rest)));
+    }
+    let (kind, rest) = take_float_suffix(&s[len..]);
+    // A value is rounded directly from the decimal to its type, not through
+    // another floating type.
+    let val = match kind {
+        FloatKind::Float => match s[..len].parse::<f32>() {
+            Ok(val) => F80::from_f64(val as f64),
+            Err(_) => return Ok(None),
+        },
+        FloatKind::Double => match s[..len].parse::<f64>() {
+            Ok(val) => F80::from_f64(val),
+            Err(_) => return Ok(None),
+        },
+        FloatKind::LongDouble => {
+            let digits = s[..mantissa_len].replace('.', "");
+            let exp = parse_exponent(&s[mantissa_len..len]).saturating_sub(frac_len as i32);
+            F80::from_decimal(&digits, exp)
+        }
+    };
+    Ok(Some((Token::FNum(val, kind), rest)))
+}
+
+// Takes a hexadecimal number after `0x` from the start of `s`, and returns the
+// rest of the str.
+fn take_hex_number_from_start(s: &str) -> Result<Option<(Token, &str)>, String> {
+    let int_len = count_digits(s, 16);
+    let mut len = int_len;
+    if s[len..].starts_with('.') {
+        len += 1 + count_digits(&s[len + 1..], 16);
+    }
+    if len == 0 || (len == 1 && int_len == 0) {
+        return Ok(None);
+    }
+
+    // hexadecimal integer
+    let exp_len = match exponent_len(&s[len..], 'p') {
+        Some(exp_len) => exp_len,
+        None if len == int_len => {
+            let num = u64::from_str_radix(&s[..len], 16)
+                .map_err(|_| "integer constant is too large".to_string())?;
+            let (kind, rest) = take_int_suffix(&s[len..], num, false);
+            return Ok(Some((Token::Num(num, kind), rest)));
+        }
+        // The exponent is required in a hexadecimal floating constant.
+        None => return Ok(None),
+    };
+
+    // hexadecimal floating constant, whose value is the significand
+    // multiplied by 2 to the power of the exponent
+    let digits = s[..len].replace('.', "");
+    let frac_len = digits.len() - int_len;
+    let exp = parse_exponent(&s[len..len + exp_len]).saturating_sub(frac_len as i32 * 4);
+    let val = F80::from_hex(&digits, exp);
+    let (kind, rest) = take_float_suffix(&s[len + exp_len..]);
+    let val = match kind {
+        FloatKind::Float => F80::from_f64(val.to_f64() as f32 as f64),
+        FloatKind::Double => F80::from_f64(val.to_f64()),
+        FloatKind::LongDouble => val,
+    };
+    Ok(Some((Token::FNum(val, kind), rest)))
+}
+
+// Returns the number of digits in the radix at the start of `s`.
+fn count_digits(s: &str, radix: u32) -> usize {
+    s.chars().take_while(|c| c.is_digit(radix)).count()
+}
+
+// Returns the length of an exponent at the start of `s`, which consists of
+// `mark`, an optional sign and decimal digits, e.g. `e+10`.
+fn exponent_len(s: &str, mark: char) -> Option<usize> {
+    let rest = s.strip_prefix([mark, mark.to_ascii_uppercase()])?;
+    let sign_len = if rest.starts_with(['+', '-']) { 1 } else { 0 };
+    match count_digits(&rest[sign_len..], 10) {
         0 => None,
-        _ => Some((
-            (&s[..len])
-                .parse()
-                .expect(format!(r#"failed to parse "{}" into number"#, &s[..len]).as_str()),
-            &s[len..],
-        )),
+        n => Some(1 + sign_len + n),
     }
 }
 
-// Takes an ident from the start of `s`, and returns the rest of the str.
+// Returns the value of an exponent such as `e+10`, or 0 for an empty str. A
+// value too large for i32 saturates, which is out of range of any floating
+// type anyway.
+fn parse_exponent(s: &str) -> i32 {
+    if s.is_empty() {
+        return 0;
+    }
+    let digits = s[1..].trim_start_matches('+');
+    digits.parse().unwrap_or(if digits.starts_with('-') {
+        i32::MIN
+    } else {
+        i32::MAX
+    })
+}
+
+// Takes the suffix of an integer constant from the start of `s`, and returns
+// the type of the constant of the value and the rest of the str.
+//
+// The type is the first of int, unsigned int, long and unsigned long that can
+// represent the value, where `u` allows only the unsigned ones, `l` or `ll`
+// only the long ones, and a decimal constant only the signed ones unless
+// `u` is given. `long long` is the same as long. A decimal constant too large
+// for long is unsigned long, as GCC makes it.
+fn take_int_suffix(s: &str, num: u64, is_decimal: bool) -> (IntKind, &str) {
+    let suffix = ["ull", "llu", "ul", "lu", "ll", "u", "l"]
+        .into_iter()
+        .find(|suffix| {
+            s.get(..suffix.len())
+                .is_some_and(|p| p.eq_ignore_ascii_case(suffix))
+        })
+        .unwrap_or("");
+    let is_unsigned = suffix.contains('u');
+    let is_long = suffix.contains('l');
+
+    let kind = if !is_long && !is_unsigned && num <= i32::MAX as u64 {
+        IntKind::Int
+    } else if !is_long && (is_unsigned || !is_decimal) && num <= u32::MAX as u64 {
+        IntKind::UInt
+    } else if !is_unsigned && num <= i64::MAX as u64 {
+        IntKind::Long
+    } else {
+        IntKind::ULong
+    };
+    (kind, &s[suffix.len()..])
+}
+
+// Takes the suffix of a floating constant from the start of `s`, and returns
+// the type given by it and the rest of the str.
+fn take_float_suffix(s: &str) -> (FloatKind, &str) {
+    if let Some(rest) = s.strip_prefix(['f', 'F']) {
+        return (FloatKind::Float, rest);
+    }
+    match s.strip_prefix(['l', 'L']) {
+        Some(rest) => (FloatKind::LongDouble, rest),
+        None => (FloatKind::Double, s),
+    }
+}
+
+// Takes an identifier from the start of `s`, and returns its name and the
+// rest of the str.
+//
+// An identifier consists of letters, digits, underscores, `$` as an extension
+// of GNU, and the characters of C11 Annex D, which may be spelled by universal
+// character names. It does not start with a digit or a combining character.
 //
 // e.g.
-//   take_ident_from_start("hello123") => Some(("hello", "123"))
+//   take_ident_from_start("hello123 x") => Some(("hello123", " x"))
+//   take_ident_from_start("_Bool x") => Some(("_Bool", " x"))
+//   take_ident_from_start(r"caf\u00e9 x") => Some(("café", " x"))
 //   take_ident_from_start("123hello") => None
-fn take_ident_from_start<'a>(s: &'a str) -> Option<(&'a str, &'a str)> {
-    let mut len = 0;
-    for c in s.chars() {
-        if c.is_ascii_alphabetic() {
-            len += 1;
-        } else {
+fn take_ident_from_start(s: &str) -> Option<(String, &str)> {
+    let mut name = String::new();
+    let mut rest = s;
+    while let Some((c, len)) = take_char_from_start(rest) {
+        let is_allowed = match c {
+            'a'..='z' | 'A'..='Z' | '_' | '$' => true,
+            '0'..='9' => !name.is_empty(),
+            _ => {
+                in_ranges(c, &IDENT_CHAR_RANGES)
+                    && !(name.is_empty() && in_ranges(c, &COMBINING_CHAR_RANGES))
+            }
+        };
+        if !is_allowed {
             break;
         }
+        name.push(c);
+        rest = &rest[len..];
     }
 
-    match len {
+    match name.len() {
         0 => None,
-        _ => Some((&s[..len], &s[len..])),
+        _ => Some((name, rest)),
+    }
+}
+
+// Returns the character at the start of `s` and its length, where a universal
+// character name `\uXXXX` or `\UXXXXXXXX` spells a character. A universal
+// character name may not spell a basic character below U+00A0.
+fn take_char_from_start(s: &str) -> Option<(char, usize)> {
+    let ucn = match (s.strip_prefix("\\u"), s.strip_prefix("\\U")) {
+        (Some(hex), _) => hex.get(..4),
+        (_, Some(hex)) => hex.get(..8),
+        _ => None,
+    };
+    let c = ucn
+        .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+        .and_then(|hex| char::from_u32(u32::from_str_radix(hex, 16).ok()?))
+        .filter(|&c| c >= '\u{a0}');
+    match (c, ucn) {
+        (Some(c), Some(hex)) => Some((c, hex.len() + 2)),
+        _ => s.chars().next().map(|c| (c, c.len_utf8())),
+    }
+}
+
+// Returns true if the character is in one of the ranges.
+fn in_ranges(c: char, ranges: &[(u32, u32)]) -> bool {
+    ranges
+        .iter()
+        .any(|&(start, end)| (start..=end).contains(&(c as u32)))
+}
+
+// ranges of characters allowed in identifiers, by C11 Annex D.1
+const IDENT_CHAR_RANGES: [(u32, u32); 45] = [
+    (0xA8, 0xA8),
+    (0xAA, 0xAA),
+    (0xAD, 0xAD),
+    (0xAF, 0xAF),
+    (0xB2, 0xB5),
+    (0xB7, 0xBA),
+    (0xBC, 0xBE),
+    (0xC0, 0xD6),
+    (0xD8, 0xF6),
+    (0xF8, 0xFF),
+    (0x100, 0x167F),
+    (0x1681, 0x180D),
+    (0x180F, 0x1FFF),
+    (0x200B, 0x200D),
+    (0x202A, 0x202E),
+    (0x203F, 0x2040),
+    (0x2054, 0x2054),
+    (0x2060, 0x206F),
+    (0x2070, 0x218F),
+    (0x2460, 0x24FF),
+    (0x2776, 0x2793),
+    (0x2C00, 0x2DFF),
+    (0x2E80, 0x2FFF),
+    (0x3004, 0x3007),
+    (0x3021, 0x302F),
+    (0x3031, 0x303F),
+    (0x3040, 0xD7FF),
+    (0xF900, 0xFD3D),
+    (0xFD40, 0xFDCF),
+    (0xFDF0, 0xFE44),
+    (0xFE47, 0xFFFD),
+    (0x10000, 0x1FFFD),
+    (0x20000, 0x2FFFD),
+    (0x30000, 0x3FFFD),
+    (0x40000, 0x4FFFD),
+    (0x50000, 0x5FFFD),
+    (0x60000, 0x6FFFD),
+    (0x70000, 0x7FFFD),
+    (0x80000, 0x8FFFD),
+    (0x90000, 0x9FFFD),
+    (0xA0000, 0xAFFFD),
+    (0xB0000, 0xBFFFD),
+    (0xC0000, 0xCFFFD),
+    (0xD0000, 0xDFFFD),
+    (0xE0000, 0xEFFFD),
+];
+
+// ranges of combining characters, which may not start identifiers, by C11
+// Annex D.2
+const COMBINING_CHAR_RANGES: [(u32, u32); 4] = [
+    (0x300, 0x36F),
+    (0x1DC0, 0x1DFF),
+    (0x20D0, 0x20FF),
+    (0xFE20, 0xFE2F),
+];
+
+// Takes a string literal from the start of `s`, which starts with a double
+// quote, and returns its bytes and the rest of the str.
+//
+// e.g.
+//   take_string_from_start(r#""a\n"b"#) => Ok((b"a\n", "b"))
+fn take_string_from_start(s: &str) -> Result<(Vec<u8>, &str), String> {
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut rest = &s[1..];
+    loop {
+        let c = match rest.chars().next() {
+            Some('"') => return Ok((bytes, &rest[1..])),
+            Some(c) => c,
+            None => return Err("unclosed string literal".to_string()),
+        };
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        // escape sequence
+        let b;
+        (b, rest) = take_escape_from_start(&rest[1..])?;
+        bytes.push(b);
     }
 }
 
+// Returns the value of a character constant, e.g. `'a'`, which is an int of
+// the value of its char. Only a single character is supported.
+pub fn char_value(text: &str) -> Result<i64, String> {
+    let body = text
+        .strip_prefix('\'')
+        .and_then(|s| s.strip_suffix('\''))
+        .ok_or_else(|| format!("invalid character constant: {}", text))?;
+    let (b, rest) = match body.strip_prefix('\\') {
+        Some(escape) => take_escape_from_start(escape)?,
+        None => match body.chars().next() {
+            // A non-ASCII character is multiple chars in UTF-8.
+            Some(c) if c.is_ascii() => (c as u8, &body[1..]),
+            Some(_) => (0, body),
+            None => return Err("empty character constant".to_string()),
+        },
+    };
+    if !rest.is_empty() {
+        return Err(format!("multi-character character constant: {}", text));
+    }
+    // char is signed.
+    Ok(b as i8 as i64)
+}
+
+// Takes the character of an escape sequence after a backslash from the start
+// of `s`, and returns its value and the rest of the str.
+fn take_escape_from_start(s: &str) -> Result<(u8, &str), String> {
+    let c = s.chars().next().ok_or("unclosed string literal")?;
+
+    // octal escape sequence of up to 3 digits
+    if c.is_digit(8) {
+        let len = s.chars().take(3).take_while(|c| c.is_digit(8)).count();
+        let val = u32::from_str_radix(&s[..len], 8).unwrap();
+        return Ok((val as u8, &s[len..]));
+    }
+
+    // hexadecimal escape sequence
+    if c == 'x' {
+        let len = s[1..].chars().take_while(|c| c.is_ascii_hexdigit()).count();
+        if len == 0 {
+            return Err("\\x used with no following hex digits".to_string());
+        }
+        let val = u64::from_str_radix(&s[1..1 + len], 16)
+            .map_err(|_| "hex escape sequence out of range".to_string())?;
+        return Ok((val as u8, &s[1 + len..]));
+    }
+
+    let val = match c {
+        'a' => 0x07,
+        'b' => 0x08,
+        't' => b'\t',
+        'n' => b'\n',
+        'v' => 0x0b,
+        'f' => 0x0c,
+        'r' => b'\r',
+        'e' => 0x1b, // GNU extension
+        '\\' | '\'' | '"' | '?' => c as u8,
+        _ => return Err(format!("unknown escape sequence: \\{}", c)),
+    };
+    Ok((val, &s[c.len_utf8()..]))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,7 +809,7 @@ mod tests {
     #[test]
     fn tokenizes_single_digit_number() {
         let input = "2;";
-        let expected = vec![Token::Num(2), Token::Punct(";".to_string())];
+        let expected = vec![Token::Num(2, IntKind::Int), Token::Punct(";".to_string())];
         let actual = tokenize(input).unwrap();
         assert_eq!(expected, actual);
     }
@@ -138,7 +817,7 @@ mod tests {
     #[test]
     fn tokenizes_multi_digit_number() {
         let input = "123;";
-        let expected = vec![Token::Num(123), Token::Punct(";".to_string())];
+        let expected = vec![Token::Num(123, IntKind::Int), Token::Punct(";".to_string())];
         let actual = tokenize(input).unwrap();
         assert_eq!(expected, actual);
     }
@@ -146,7 +825,41 @@ mod tests {
     #[test]
     fn tokenizes_with_spaces() {
         let input = "  42 ;";
-        let expected = vec![Token::Num(42), Token::Punct(";".to_string())];
+        let expected = vec![Token::Num(42, IntKind::Int), Token::Punct(";".to_string())];
+        let actual = tokenize(input).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn tokenizes_with_whitespace_and_comments() {
+        let input = "\t42\r\n// a \\\n b\n+/* c\n */\x0b\x0c1; /**/";
+        let expected = vec![
+            Token::Num(42, IntKind::Int),
+            Token::Punct("+".to_string()),
+            Token::Num(1, IntKind::Int),
+            Token::Punct(";".to_string()),
+        ];
+        let actual = tokenize(input).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn cannot_tokenize_unterminated_comment() {
+        assert_eq!(
+            Err("unterminated comment".to_string()),
+            tokenize("1 /* a */ /* b")
+        );
+    }
+
+    #[test]
+    fn tokenizes_keywords() {
+        let input = "while _Static_assert whilex __attribute__";
+        let expected = vec![
+            Token::Kw(KwKind::While),
+            Token::Kw(KwKind::StaticAssert),
+            Token::Ident("whilex".to_string()),
+            Token::Kw(KwKind::Attribute),
+        ];
         let actual = tokenize(input).unwrap();
         assert_eq!(expected, actual);
     }
@@ -155,22 +868,49 @@ mod tests {
     fn tokenizes_add_expr() {
         let input = "12+23;";
         let expected = vec![
-            Token::Num(12),
+            Token::Num(12, IntKind::Int),
             Token::Punct("+".to_string()),
-            Token::Num(23),
+            Token::Num(23, IntKind::Int),
             Token::Punct(";".to_string()),
         ];
         let actual = tokenize(input).unwrap();
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn tokenizes_ellipsis() {
+        let input = "(a,...)";
+        let expected = vec![
+            Token::Punct("(".to_string()),
+            Token::Ident("a".to_string()),
+            Token::Punct(",".to_string()),
+            Token::Punct("...".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        let actual = tokenize(input).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn tokenizes_member_access() {
+        let input = "p->x:";
+        let expected = vec![
+            Token::Ident("p".to_string()),
+            Token::Punct("->".to_string()),
+            Token::Ident("x".to_string()),
+            Token::Punct(":".to_string()),
+        ];
+        let actual = tokenize(input).unwrap();
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn tokenizes_sub_expr() {
         let input = "23-12;";
         let expected = vec![
-            Token::Num(23),
+            Token::Num(23, IntKind::Int),
             Token::Punct("-".to_string()),
-            Token::Num(12),
+            Token::Num(12, IntKind::Int),
             Token::Punct(";".to_string()),
         ];
         let actual = tokenize(input).unwrap();
@@ -181,9 +921,9 @@ mod tests {
     fn tokenizes_mul_expr() {
         let input = "2*3;";
         let expected = vec![
-            Token::Num(2),
+            Token::Num(2, IntKind::Int),
             Token::Punct("*".to_string()),
-            Token::Num(3),
+            Token::Num(3, IntKind::Int),
             Token::Punct(";".to_string()),
         ];
         let actual = tokenize(input).unwrap();
@@ -194,9 +934,9 @@ mod tests {
     fn tokenizes_div_expr() {
         let input = "9/3;";
         let expected = vec![
-            Token::Num(9),
+            Token::Num(9, IntKind::Int),
             Token::Punct("/".to_string()),
-            Token::Num(3),
+            Token::Num(3, IntKind::Int),
             Token::Punct(";".to_string()),
         ];
         let actual = tokenize(input).unwrap();
@@ -208,12 +948,12 @@ mod tests {
         let input = "(1+2)*3;";
         let expected = vec![
             Token::Punct("(".to_string()),
-            Token::Num(1),
+            Token::Num(1, IntKind::Int),
             Token::Punct("+".to_string()),
-            Token::Num(2),
+            Token::Num(2, IntKind::Int),
             Token::Punct(")".to_string()),
             Token::Punct("*".to_string()),
-            Token::Num(3),
+            Token::Num(3, IntKind::Int),
             Token::Punct(";".to_string()),
         ];
         let actual = tokenize(input).unwrap();
@@ -230,7 +970,7 @@ mod tests {
             Token::Punct(")".to_string()),
             Token::Punct("{".to_string()),
             Token::Kw(KwKind::Return),
-            Token::Num(42),
+            Token::Num(42, IntKind::Int),
             Token::Punct(";".to_string()),
             Token::Punct("}".to_string()),
         ];
@@ -244,28 +984,284 @@ mod tests {
         let expected = vec![
             Token::Kw(KwKind::If),
             Token::Punct("(".to_string()),
-            Token::Num(0),
+            Token::Num(0, IntKind::Int),
             Token::Punct(")".to_string()),
-            Token::Num(123),
+            Token::Num(123, IntKind::Int),
             Token::Punct(";".to_string()),
         ];
         let actual = tokenize(input).unwrap();
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn tokenizes_enum_declaration() {
+        let input = "enum E { A, B = 2 };";
+        let expected = vec![
+            Token::Kw(KwKind::Enum),
+            Token::Ident("E".to_string()),
+            Token::Punct("{".to_string()),
+            Token::Ident("A".to_string()),
+            Token::Punct(",".to_string()),
+            Token::Ident("B".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Num(2, IntKind::Int),
+            Token::Punct("}".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        let actual = tokenize(input).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn tokenizes_string_literal() {
+        let input = r#"char s[] = "a\tb\\\"\101\x42";"#;
+        let expected = vec![
+            Token::Kw(KwKind::Char),
+            Token::Ident("s".to_string()),
+            Token::Punct("[".to_string()),
+            Token::Punct("]".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Str(b"a\tb\\\"AB".to_vec()),
+            Token::Punct(";".to_string()),
+        ];
+        let actual = tokenize(input).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn cannot_tokenize_unclosed_string_literal() {
+        assert!(tokenize(r#""abc"#).is_err());
+    }
+
+    // Returns the texts of the preprocessing tokens.
+    fn texts(tokens: &[PpToken]) -> Vec<&str> {
+        tokens.iter().map(|tok| tok.text.as_str()).collect()
+    }
+
+    #[test]
+    fn tokenizes_into_preprocessing_tokens() {
+        let input = "#include <a.h>\nx+=1.5e+3f ##\"a\\\"b\" @";
+        let tokens = tokenize_pp("f.c", input).unwrap();
+        assert_eq!(
+            vec![
+                "#",
+                "include",
+                "<",
+                "a",
+                ".",
+                "h",
+                ">",
+                "x",
+                "+=",
+                "1.5e+3f",
+                "##",
+                "\"a\\\"b\"",
+                "@"
+            ],
+            texts(&tokens)
+        );
+        assert_eq!(PpKind::Num, tokens[9].kind);
+        assert_eq!(PpKind::Str, tokens[11].kind);
+        assert_eq!(PpKind::Other, tokens[12].kind);
+
+        // `#` and `x` start lines, and `##` follows a space.
+        let at_bol: Vec<usize> = (0..tokens.len()).filter(|&i| tokens[i].at_bol).collect();
+        assert_eq!(vec![0, 7], at_bol);
+        assert!(tokens[10].has_space && !tokens[9].has_space);
+        assert_eq!((1, 2), (tokens[6].line, tokens[7].line));
+    }
+
+    #[test]
+    fn skips_comments_between_preprocessing_tokens() {
+        let input = "a/* x\n */b // y\n\tc/**/d /*\n*/ #";
+        let tokens = tokenize_pp("f.c", input).unwrap();
+        assert_eq!(vec!["a", "b", "c", "d", "#"], texts(&tokens));
+        let lines: Vec<usize> = tokens.iter().map(|tok| tok.line).collect();
+        assert_eq!(vec![1, 2, 3, 3, 4], lines);
+        // A comment is a space, and does not start a line.
+        assert!(tokens[1].has_space && tokens[3].has_space);
+        assert!(tokens[2].at_bol && !tokens[4].at_bol);
+
+        assert_eq!(
+            Err("f.c:2: unterminated comment".to_string()),
+            tokenize_pp("f.c", "a\nb /* c\n")
+        );
+    }
+
+    #[test]
+    fn splices_lines() {
+        let input = "#define A 1 \\\n+ 2\nab\\\r\nc \"d\\\ne\"\nf";
+        let tokens = tokenize_pp("f.c", input).unwrap();
+        assert_eq!(
+            vec!["#", "define", "A", "1", "+", "2", "abc", "\"de\"", "f"],
+            texts(&tokens)
+        );
+        // The lines following spliced ones keep their numbers.
+        assert_eq!((3, 6), (tokens[6].line, tokens[8].line));
+    }
+
+    #[test]
+    fn spells_identifiers_with_universal_character_names_decoded() {
+        let tokens = tokenize_pp("f.c", r"caf\u00e9+x_1").unwrap();
+        assert_eq!(vec!["café", "+", "x_1"], texts(&tokens));
+        assert_eq!(PpKind::Ident, tokens[0].kind);
+    }
+
+    #[test]
+    fn cannot_convert_string_literal_across_lines() {
+        let tokens = tokenize_pp("f.c", "x\n\"a\nb\"").unwrap();
+        assert_eq!(vec!["x", "\"", "a", "b", "\""], texts(&tokens));
+        assert_eq!(
+            Err("f.c:2: unclosed string literal".to_string()),
+            convert_pp_tokens(&tokens)
+        );
+    }
+
+    #[test]
+    fn converts_preprocessing_tokens() {
+        let tokens = tokenize_pp("f.c", "return 0x10;").unwrap();
+        let expected = vec![
+            Token::Kw(KwKind::Return),
+            Token::Num(16, IntKind::Int),
+            Token::Punct(";".to_string()),
+        ];
+        assert_eq!(Ok(expected), convert_pp_tokens(&tokens));
+
+        let tokens = tokenize_pp("f.c", "\n1 @").unwrap();
+        assert_eq!(
+            Err("f.c:2: unknown punctuator: @".to_string()),
+            convert_pp_tokens(&tokens)
+        );
+    }
+
     mod tests_take_number_from_start {
-        use super::take_number_from_start;
+        use super::{take_number_from_start, FloatKind, IntKind, Token, F80};
 
         #[test]
         fn takes_number_from_the_start() {
             let s = "123hello";
-            assert_eq!(take_number_from_start(s), Some((123, "hello")));
+            assert_eq!(
+                take_number_from_start(s),
+                Ok(Some((Token::Num(123, IntKind::Int), "hello")))
+            );
+        }
+
+        #[test]
+        fn takes_hexadecimal_number() {
+            let s = "0x1Fg";
+            assert_eq!(
+                take_number_from_start(s),
+                Ok(Some((Token::Num(31, IntKind::Int), "g")))
+            );
+        }
+
+        #[test]
+        fn takes_decimal_floating_constants() {
+            for (s, val, kind, rest) in [
+                ("1.5;", 1.5, FloatKind::Double, ";"),
+                (".25f", 0.25, FloatKind::Float, ""),
+                ("3.", 3.0, FloatKind::Double, ""),
+                ("1e3x", 1000.0, FloatKind::Double, "x"),
+                ("2.5E-1F", 0.25, FloatKind::Float, ""),
+                ("0.1f", 0.1f32 as f64, FloatKind::Float, ""),
+            ] {
+                assert_eq!(
+                    take_number_from_start(s),
+                    Ok(Some((Token::FNum(F80::from_f64(val), kind), rest))),
+                    "{}",
+                    s
+                );
+            }
+        }
+
+        #[test]
+        fn takes_hexadecimal_floating_constants() {
+            for (s, val, kind) in [
+                ("0x1p3", 8.0, FloatKind::Double),
+                ("0x1.8p1", 3.0, FloatKind::Double),
+                ("0X.8P-1f", 0.25, FloatKind::Float),
+                ("0xa.p0", 10.0, FloatKind::Double),
+            ] {
+                assert_eq!(
+                    take_number_from_start(s),
+                    Ok(Some((Token::FNum(F80::from_f64(val), kind), ""))),
+                    "{}",
+                    s
+                );
+            }
+        }
+
+        #[test]
+        fn takes_long_double_constants() {
+            let s = "0.1L;";
+            let expected = Token::FNum(F80::from_decimal("1", -1), FloatKind::LongDouble);
+            assert_eq!(take_number_from_start(s), Ok(Some((expected, ";"))));
+            let s = "2.5e-1l";
+            let expected = Token::FNum(F80::from_f64(0.25), FloatKind::LongDouble);
+            assert_eq!(take_number_from_start(s), Ok(Some((expected, ""))));
+            let s = "0x1.fffffffffffffffep0L";
+            let expected = Token::FNum(
+                F80::from_hex("1fffffffffffffffe", -64),
+                FloatKind::LongDouble,
+            );
+            assert_eq!(take_number_from_start(s), Ok(Some((expected, ""))));
+        }
+
+        #[test]
+        fn takes_integer_followed_by_incomplete_exponent() {
+            let s = "1e";
+            assert_eq!(
+                take_number_from_start(s),
+                Ok(Some((Token::Num(1, IntKind::Int), "e")))
+            );
+        }
+
+        #[test]
+        fn reports_too_large_integer_constant() {
+            for s in ["99999999999999999999", "0x1ffffffffffffffff"] {
+                assert_eq!(
+                    take_number_from_start(s),
+                    Err("integer constant is too large".to_string()),
+                    "{}",
+                    s
+                );
+            }
+            let s = "18446744073709551615";
+            assert_eq!(
+                take_number_from_start(s),
+                Ok(Some((Token::Num(u64::MAX, IntKind::ULong), "")))
+            );
+        }
+
+        #[test]
+        fn types_integer_constants_by_suffix_and_value() {
+            for (s, kind, rest) in [
+                ("2147483647", IntKind::Int, ""),
+                ("2147483648", IntKind::Long, ""),
+                ("0x80000000", IntKind::UInt, ""),
+                ("0x100000000", IntKind::Long, ""),
+                ("0xffffffffffffffff", IntKind::ULong, ""),
+                ("1u;", IntKind::UInt, ";"),
+                ("4294967296U", IntKind::ULong, ""),
+                ("1l", IntKind::Long, ""),
+                ("1LL", IntKind::Long, ""),
+                ("0x8000000000000000L", IntKind::ULong, ""),
+                ("1uLL", IntKind::ULong, ""),
+                ("1llu", IntKind::ULong, ""),
+                ("1Lu", IntKind::ULong, ""),
+                ("1lul", IntKind::ULong, "l"),
+            ] {
+                let Ok(Some((Token::Num(_, actual), r))) = take_number_from_start(s) else {
+                    panic!("{}", s);
+                };
+                assert_eq!((kind, rest), (actual, r), "{}", s);
+            }
         }
 
         #[test]
         fn returns_none_for_not_starting_with_number() {
             let s = "hello123";
-            assert_eq!(take_number_from_start(s), None);
+            assert_eq!(take_number_from_start(s), Ok(None));
         }
     }
 
@@ -274,8 +1270,49 @@ mod tests {
 
         #[test]
         fn takes_identifier_from_the_start() {
-            let s = "hello123";
-            assert_eq!(take_ident_from_start(s), Some(("hello", "123")));
+            let s = "hello123 x";
+            assert_eq!(
+                take_ident_from_start(s),
+                Some(("hello123".to_string(), " x"))
+            );
+        }
+
+        #[test]
+        fn takes_identifier_with_underscore() {
+            let s = "_Bool b";
+            assert_eq!(take_ident_from_start(s), Some(("_Bool".to_string(), " b")));
+        }
+
+        #[test]
+        fn takes_identifier_with_dollar_sign() {
+            let s = "$a$1+";
+            assert_eq!(take_ident_from_start(s), Some(("$a$1".to_string(), "+")));
+        }
+
+        #[test]
+        fn takes_identifier_with_universal_character_names() {
+            let s = r"caf\u00e9\U0001F600\u0041";
+            assert_eq!(
+                take_ident_from_start(s),
+                Some(("café\u{1f600}".to_string(), r"\u0041"))
+            );
+        }
+
+        #[test]
+        fn takes_utf8_identifier() {
+            let s = "変数_1 = 1";
+            assert_eq!(
+                take_ident_from_start(s),
+                Some(("変数_1".to_string(), " = 1"))
+            );
+            // A combining character may follow a letter, but not start.
+            let s = "e\u{301}x";
+            assert_eq!(
+                take_ident_from_start(s),
+                Some(("e\u{301}x".to_string(), ""))
+            );
+            assert_eq!(take_ident_from_start("\u{301}x"), None);
+            assert_eq!(take_ident_from_start("\u{a0}"), None);
         }
 
         #[test]
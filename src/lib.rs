@@ -1,11 +1,36 @@
 mod codegen;
+mod f80;
 mod lexer;
 mod parser;
+mod preprocess;
 mod ty;
 
-pub fn compile(input: &str) -> Result<String, String> {
-    let tokens = lexer::tokenize(input)?;
+// options given on the command line
+#[derive(Debug, Default)]
+pub struct Options {
+    // directories searched for headers, given by -I
+    pub include_paths: Vec<String>,
+    // macros defined by -D and undefined by -U, in the order given
+    pub macros: Vec<MacroOpt>,
+}
+
+#[derive(Debug)]
+pub enum MacroOpt {
+    Define(String), // -D<name> or -D<name>=<body>
+    Undef(String),  // -U<name>
+}
+
+// Compiles the input of the file into assembly.
+pub fn compile(file: &str, input: &str, opts: &Options) -> Result<String, String> {
+    let tokens = preprocess::preprocess(file, input, opts)?;
+    let tokens = lexer::convert_pp_tokens(&tokens)?;
     let ast = parser::parse(&tokens)?;
     let asm = codegen::gen(&ast)?;
     Ok(asm)
 }
+
+// Preprocesses the input of the file, and returns the resulting text.
+pub fn preprocess(file: &str, input: &str, opts: &Options) -> Result<String, String> {
+    let tokens = preprocess::preprocess(file, input, opts)?;
+    Ok(preprocess::print_tokens(&tokens))
+}
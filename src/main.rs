@@ -1,21 +1,95 @@
-use rocc::compile;
+use rocc::{compile, preprocess, MacroOpt, Options};
 
 use std::env;
+use std::fs;
+use std::io::{self, Read};
 use std::process;
 
+// rocc [-E] [-I <dir> | -D <name>[=<body>] | -U <name>]... <file>
+//
+// Compiles the C source file, or stdin if it is "-", and prints the assembly.
+// With -E, only preprocesses it and prints the result.
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Required only one argument");
-        process::exit(1)
-    }
+    let (file, opts, is_preprocess_only) = match parse_args(&args[1..]) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
 
-    let asm = match compile(&args[1]) {
-        Ok(asm) => asm,
+    let (file, input) = match read_input(&file) {
+        Ok(read) => read,
+        Err(err) => {
+            eprintln!("cannot read {}: {}", file, err);
+            process::exit(1);
+        }
+    };
+    let output = if is_preprocess_only {
+        preprocess(&file, &input, &opts)
+    } else {
+        compile(&file, &input, &opts)
+    };
+    match output {
+        Ok(output) => print!("{}", output),
         Err(err) => {
             eprintln!("compile failed: {}", err);
             process::exit(1);
         }
+    }
+}
+
+// Returns the input file, the options, and true if only preprocessing is
+// requested by -E.
+fn parse_args(args: &[String]) -> Result<(String, Options, bool), String> {
+    let mut opts = Options::default();
+    let mut file: Option<String> = None;
+    let mut is_preprocess_only = false;
+    let mut rest = args;
+    while let Some(arg) = rest.first() {
+        rest = &rest[1..];
+        if arg == "-E" {
+            is_preprocess_only = true;
+        } else if let Some(dir) = option_value(arg, "-I", &mut rest)? {
+            opts.include_paths.push(dir);
+        } else if let Some(def) = option_value(arg, "-D", &mut rest)? {
+            opts.macros.push(MacroOpt::Define(def));
+        } else if let Some(name) = option_value(arg, "-U", &mut rest)? {
+            opts.macros.push(MacroOpt::Undef(name));
+        } else if arg.starts_with('-') && arg != "-" {
+            return Err(format!("unknown option: {}", arg));
+        } else if file.replace(arg.clone()).is_some() {
+            return Err("Required only one input file".to_string());
+        }
+    }
+    let file = file.ok_or("Required an input file")?;
+    Ok((file, opts, is_preprocess_only))
+}
+
+// Returns the value of the option `name` if `arg` is the option, which is
+// either attached to it, e.g. `-Idir`, or the next argument, e.g. `-I dir`.
+fn option_value(arg: &str, name: &str, rest: &mut &[String]) -> Result<Option<String>, String> {
+    let value = match arg.strip_prefix(name) {
+        Some(value) => value,
+        None => return Ok(None),
     };
-    print!("{}", asm);
+    if !value.is_empty() {
+        return Ok(Some(value.to_string()));
+    }
+    let (next, r) = rest
+        .split_first()
+        .ok_or_else(|| format!("missing argument after {}", name))?;
+    *rest = r;
+    Ok(Some(next.clone()))
+}
+
+// Returns the name of the input file and its contents, where "-" is stdin.
+fn read_input(file: &str) -> io::Result<(String, String)> {
+    if file == "-" {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        return Ok(("<stdin>".to_string(), input));
+    }
+    Ok((file.to_string(), fs::read_to_string(file)?))
 }
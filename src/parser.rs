@@ -1,30 +1,171 @@
+mod decl;
 mod expr;
 mod func;
+mod init;
+mod scope;
 mod stmt;
 
 use crate::lexer::Token;
+use crate::ty::Ty;
+use decl::{
+    check_alignas, check_complete, check_object, check_static_storage, parse_declarator,
+    parse_declarator_attrs, parse_declspec, parse_static_init, parse_typedef_names, DeclSpec,
+    Declarator, StaticInit,
+};
 use func::parse_func;
+use scope::{Global, Scope};
 
-pub use expr::{Binary, Expr};
-pub use func::Function;
-pub use stmt::{IfStruct, Stmt};
+pub use decl::{Data, Reloc};
+pub use expr::{Binary, Call, Cast, CompoundLit, Expr, GVar, VaArgs, Var};
+pub use func::{arg_locs, ArgLoc, Function};
+pub use stmt::{IfStruct, Stmt, VlaBlock};
 
-// <program> ::= <function>*
-pub fn parse(tokens: &[Token]) -> Result<Vec<Function>, String> {
+#[derive(Debug, PartialEq)]
+pub struct Program {
+    pub funcs: Vec<Function>,
+    pub data: Vec<Data>, // global and static local variables
+}
+
+// <program> ::= (<declaration> | <function>)*
+// <declaration> ::= <declspec> ";"
+//                 | <declspec> <typedef-names>
+//                 | <declspec> <init-declarator> ("," <init-declarator>)* ";"
+// <init-declarator> ::= <declarator> <attribute>* <static-init>
+pub fn parse(tokens: &[Token]) -> Result<Program, String> {
+    let mut scope = Scope::new();
     let mut funcs: Vec<Function> = Vec::new();
+    let mut data: Vec<Data> = Vec::new();
     let mut rest = tokens;
     while !rest.is_empty() {
-        let f;
-        (f, rest) = parse_func(rest)?;
-        funcs.push(f);
+        let spec;
+        (spec, rest) = parse_declspec(rest, &mut scope)?;
+        if spec.is_auto || spec.is_register {
+            return Err("auto or register at file scope".to_string());
+        }
+
+        // typedef
+        if spec.is_typedef {
+            (_, rest) = parse_typedef_names(&spec, rest, &mut scope)?;
+            continue;
+        }
+
+        // declaration
+        if let Ok(r) = consume_punct(rest, ";") {
+            rest = r;
+            continue;
+        }
+
+        let (decl, align);
+        (decl, rest) = parse_declarator(spec.ty.clone(), rest, &mut scope)?;
+        (align, rest) = parse_declarator_attrs(&spec, rest, &mut scope)?;
+
+        // function
+        if consume_punct(rest, "{").is_ok() {
+            if align > 0 {
+                return Err("alignment of a function".to_string());
+            }
+            let f;
+            (f, rest) = parse_func(decl, spec.is_static, rest, &mut scope)?;
+            funcs.push(f);
+            continue;
+        }
+
+        // declarations of functions and variables
+        rest = declare_global(&spec, decl, align, rest, &mut scope, &mut data)?;
+        loop {
+            if let Ok(r) = consume_punct(rest, ";") {
+                rest = r;
+                break;
+            }
+            rest = consume_punct(rest, ",")?;
+            let (decl, align);
+            (decl, rest) = parse_declarator(spec.ty.clone(), rest, &mut scope)?;
+            (align, rest) = parse_declarator_attrs(&spec, rest, &mut scope)?;
+            rest = declare_global(&spec, decl, align, rest, &mut scope, &mut data)?;
+        }
     }
-    Ok(funcs)
+    // An array of unknown length which is only tentatively defined has one
+    // element.
+    for d in &mut data {
+        if let Ty::IncompleteArray(elem) = &d.ty {
+            d.ty = Ty::Array(elem.clone(), 1);
+        }
+    }
+    data.append(&mut scope.take_statics());
+    Ok(Program { funcs, data })
+}
+
+// Declares a function or a variable at file scope, parsing the initializer
+// of the variable if any. `align` is the alignment requested for it, or 0 if
+// not requested.
+//
+// A variable is defined unless it is declared `extern` without an
+// initializer. A definition without an initializer is tentative, so it may
+// appear more than once, and is zero-initialized unless another definition
+// initializes the variable.
+fn declare_global<'a>(
+    spec: &DeclSpec,
+    decl: Declarator,
+    align: usize,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+    data: &mut Vec<Data>,
+) -> Result<&'a [Token], String> {
+    let name = decl.name.ok_or("expected an identifier")?;
+    if let Ty::Func(_) = decl.ty {
+        if align > 0 {
+            return Err(format!("alignment of a function: {}", name));
+        }
+        scope.declare_global(&name, decl.ty, spec.is_static, false)?;
+        return Ok(tokens);
+    }
+    if spec.is_noreturn {
+        return Err(format!("_Noreturn of a variable: {}", name));
+    }
+
+    check_object(&name, &decl.ty)?;
+    check_alignas(&name, &decl.ty, spec, align)?;
+    check_static_storage(&name, &decl.ty)?;
+    let (StaticInit { ty, init, relocs }, rest) = parse_static_init(decl.ty, tokens, scope)?;
+    let Global { ty, is_static, .. } =
+        scope.declare_global(&name, ty, spec.is_static, init.is_some())?;
+    // The type of a variable defined before may be completed by a later
+    // declaration.
+    if let Some(d) = data.iter_mut().find(|d| d.name == name) {
+        d.ty = ty.clone();
+    }
+    if spec.is_extern && init.is_none() {
+        return Ok(rest);
+    }
+    // A tentative definition may leave the length of an array unknown.
+    if init.is_some() || !matches!(ty, Ty::IncompleteArray(_)) {
+        check_complete(&name, &ty)?;
+    }
+    let align = align.max(ty.align());
+    match data.iter_mut().find(|d| d.name == name) {
+        Some(d) => {
+            if d.init.is_none() {
+                d.init = init;
+                d.relocs = relocs;
+            }
+            d.align = d.align.max(align);
+        }
+        None => data.push(Data {
+            name,
+            ty,
+            init,
+            relocs,
+            is_static,
+            align,
+        }),
+    }
+    Ok(rest)
 }
 
 // Consumes a punct token from the start of tokens,
 // then returns rest of the tokens.
 fn consume_punct<'a>(tokens: &'a [Token], punct: &str) -> Result<&'a [Token], String> {
-    match tokens.get(0) {
+    match tokens.first() {
         Some(Token::Punct(p)) if p == punct => Ok(&tokens[1..]),
         _ => Err(format!(r#"expected "{}""#, punct)),
     }
@@ -33,8 +174,7 @@ fn consume_punct<'a>(tokens: &'a [Token], punct: &str) -> Result<&'a [Token], St
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::lexer::KwKind;
-    use crate::ty::Ty;
+    use crate::lexer::{IntKind, KwKind};
 
     #[test]
     fn parses_multiple_functions() {
@@ -46,7 +186,7 @@ mod tests {
             Token::Punct(")".to_string()),
             Token::Punct("{".to_string()),
             Token::Kw(KwKind::Return),
-            Token::Num(42),
+            Token::Num(42, IntKind::Int),
             Token::Punct(";".to_string()),
             Token::Punct("}".to_string()),
             Token::Kw(KwKind::Int),
@@ -55,7 +195,7 @@ mod tests {
             Token::Punct(")".to_string()),
             Token::Punct("{".to_string()),
             Token::Kw(KwKind::Return),
-            Token::Num(123),
+            Token::Num(123, IntKind::Int),
             Token::Punct(";".to_string()),
             Token::Punct("}".to_string()),
         ];
@@ -63,15 +203,178 @@ mod tests {
             Function {
                 ty: Ty::Int,
                 name: "ret".to_string(),
-                body: vec![Stmt::ReturnStmt(Expr::Num(42))],
+                is_static: false,
+                params: vec![],
+                body: vec![Stmt::ReturnStmt(Some(Expr::Num(42, Ty::Int)))],
+                stack_size: 0,
+                align: 16,
+                va_args: None,
             },
             Function {
                 ty: Ty::Int,
                 name: "main".to_string(),
-                body: vec![Stmt::ReturnStmt(Expr::Num(123))],
+                is_static: false,
+                params: vec![],
+                body: vec![Stmt::ReturnStmt(Some(Expr::Num(123, Ty::Int)))],
+                stack_size: 0,
+                align: 16,
+                va_args: None,
+            },
+        ];
+        let actual = parse(&tokens).unwrap().funcs;
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parses_enum_declaration_and_function() {
+        // enum { A = 7 }; enum E { B } f() { return A; }
+        let tokens = vec![
+            Token::Kw(KwKind::Enum),
+            Token::Punct("{".to_string()),
+            Token::Ident("A".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Num(7, IntKind::Int),
+            Token::Punct("}".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Kw(KwKind::Enum),
+            Token::Ident("E".to_string()),
+            Token::Punct("{".to_string()),
+            Token::Ident("B".to_string()),
+            Token::Punct("}".to_string()),
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct("{".to_string()),
+            Token::Kw(KwKind::Return),
+            Token::Ident("A".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Punct("}".to_string()),
+        ];
+        let expected = vec![Function {
+            ty: Ty::Enum,
+            name: "f".to_string(),
+            is_static: false,
+            params: vec![],
+            body: vec![Stmt::ReturnStmt(Some(Expr::Num(7, Ty::Int)))],
+            stack_size: 0,
+            align: 16,
+            va_args: None,
+        }];
+        let actual = parse(&tokens).unwrap().funcs;
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parses_function_declarations() {
+        // int f(int), g(); int f(int x) { return g(); }
+        let tokens = vec![
+            Token::Kw(KwKind::Int),
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct(")".to_string()),
+            Token::Punct(",".to_string()),
+            Token::Ident("g".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("x".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct("{".to_string()),
+            Token::Kw(KwKind::Return),
+            Token::Ident("g".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Punct("}".to_string()),
+        ];
+        let funcs = parse(&tokens).unwrap().funcs;
+        assert_eq!(1, funcs.len());
+        assert_eq!("f", funcs[0].name);
+    }
+
+    #[test]
+    fn cannot_redeclare_function_with_conflicting_type() {
+        // int f(int); int f(int *);
+        let tokens = vec![
+            Token::Kw(KwKind::Int),
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct(")".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct("*".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        assert!(parse(&tokens).is_err());
+    }
+
+    #[test]
+    fn parses_global_variables() {
+        // extern int x; int y; int x = 3; static int y;
+        let tokens = vec![
+            Token::Kw(KwKind::Extern),
+            Token::Kw(KwKind::Int),
+            Token::Ident("x".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("y".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("x".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Num(3, IntKind::Int),
+            Token::Punct(";".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("y".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        let expected = vec![
+            Data {
+                name: "y".to_string(),
+                ty: Ty::Int,
+                init: None,
+                relocs: Vec::new(),
+                is_static: false,
+                align: 4,
+            },
+            Data {
+                name: "x".to_string(),
+                ty: Ty::Int,
+                init: Some(vec![3, 0, 0, 0]),
+                relocs: Vec::new(),
+                is_static: false,
+                align: 4,
             },
         ];
-        let actual = parse(&tokens).unwrap();
+        let actual = parse(&tokens).unwrap().data;
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn cannot_initialize_global_variable_twice() {
+        // int x = 1; int x = 2;
+        let tokens = vec![
+            Token::Kw(KwKind::Int),
+            Token::Ident("x".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Num(1, IntKind::Int),
+            Token::Punct(";".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("x".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Num(2, IntKind::Int),
+            Token::Punct(";".to_string()),
+        ];
+        assert!(parse(&tokens).is_err());
+    }
 }
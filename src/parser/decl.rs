@@ -0,0 +1,2317 @@
+use super::{
+    consume_punct,
+    expr::{eval, parse_assign, parse_const_expr, sizeof_expr, Binary, Expr},
+    init::{eval_init, init_local, parse_initializer},
+    scope::{Scope, Symbol},
+    stmt::Stmt,
+};
+use crate::lexer::{KwKind, Token};
+use crate::ty::{FuncTy, MemberDecl, Quals, StructRef, Ty};
+
+// Declaration specifiers.
+#[derive(Debug, PartialEq)]
+pub(super) struct DeclSpec {
+    pub ty: Ty,
+    pub is_typedef: bool,
+    pub is_static: bool,
+    pub is_extern: bool,
+    pub is_auto: bool,     // `auto`, which only declares a local variable
+    pub is_register: bool, // `register`, which is a hint ignored here
+    pub is_noreturn: bool, // `_Noreturn`, which only specifies a function
+    // alignment of the declared variables by _Alignas or the aligned
+    // attribute, or 0 if not given
+    pub align: usize,
+    pub has_alignas: bool, // true if _Alignas is given
+}
+
+impl DeclSpec {
+    // Returns true if a storage-class specifier is given.
+    pub fn has_storage_class(&self) -> bool {
+        self.is_typedef || self.is_static || self.is_extern || self.is_auto || self.is_register
+    }
+}
+
+// Variable with static storage duration, which is emitted as data.
+#[derive(Debug, PartialEq)]
+pub struct Data {
+    pub name: String, // symbol name
+    pub ty: Ty,
+    pub init: Option<Vec<u8>>, // initial bytes, or None if zero-initialized
+    pub relocs: Vec<Reloc>,    // addresses in the initial bytes
+    pub is_static: bool,       // true if it has internal linkage
+    pub align: usize,          // at least the alignment of the type
+}
+
+// Address of a symbol plus an addend, which is written in the initial bytes
+// of data at the offset when linked, e.g. `&x` of `int *p = &x;`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reloc {
+    pub offset: usize,
+    pub label: String,
+    pub addend: i64,
+}
+
+// Returns true if tokens start with declaration specifiers.
+//
+// An identifier starts declaration specifiers only if it is a typedef name
+// visible in the current scope, e.g. `T * x;` is a declaration if `T` is a
+// typedef name, and is an expression otherwise.
+pub(super) fn is_declspec(tokens: &[Token], scope: &Scope) -> bool {
+    match tokens.first() {
+        Some(Token::Kw(
+            KwKind::Void
+            | KwKind::Bool
+            | KwKind::Char
+            | KwKind::Short
+            | KwKind::Int
+            | KwKind::Long
+            | KwKind::Signed
+            | KwKind::Unsigned
+            | KwKind::Float
+            | KwKind::Double
+            | KwKind::Enum
+            | KwKind::Typedef
+            | KwKind::Static
+            | KwKind::Extern
+            | KwKind::Auto
+            | KwKind::Register
+            | KwKind::Const
+            | KwKind::Volatile
+            | KwKind::Restrict
+            | KwKind::Struct
+            | KwKind::Union
+            | KwKind::Alignas
+            | KwKind::Noreturn
+            | KwKind::Attribute,
+        )) => true,
+        Some(Token::Ident(name)) => matches!(scope.find(name), Some(Symbol::Typedef(_))),
+        _ => false,
+    }
+}
+
+// <declspec>       ::= (<storage-class> | <type-specifier> | <type-qualifier>
+//                       | "_Noreturn" | <alignment-specifier> | <attribute>)+
+// <storage-class>  ::= "typedef" | "static" | "extern" | "auto" | "register"
+// <type-qualifier> ::= "const" | "volatile" | "restrict"
+// <type-specifier> ::= "void"
+//                    | "_Bool"
+//                    | "char"
+//                    | "short"
+//                    | "int"
+//                    | "long"
+//                    | "signed"
+//                    | "unsigned"
+//                    | "float"
+//                    | "double"
+//                    | <struct-specifier>
+//                    | <enum-specifier>
+//                    | typedef-name
+//
+// Type specifiers are combined as in C, e.g. `unsigned short int`, where
+// `int` may be omitted, and at most one storage-class specifier is allowed.
+// `long long` is the same type as `long`, which is 64 bits.
+pub(super) fn parse_declspec<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(DeclSpec, &'a [Token]), String> {
+    let mut ty: Option<Ty> = None;
+    let mut is_typedef = false;
+    let mut is_static = false;
+    let mut is_extern = false;
+    let mut is_auto = false;
+    let mut is_register = false;
+    let mut is_noreturn = false;
+    let mut is_signed = false;
+    let mut is_unsigned = false;
+    let mut is_short = false;
+    let mut longs = 0;
+    let mut quals = Quals::default();
+    let mut align = 0;
+    let mut has_alignas = false;
+    let mut rest = tokens;
+    loop {
+        if let Some(q) = type_qualifier(rest.first()) {
+            quals = quals.merge(q);
+            rest = &rest[1..];
+            continue;
+        }
+        match rest.first() {
+            Some(Token::Kw(
+                kw @ (KwKind::Typedef
+                | KwKind::Static
+                | KwKind::Extern
+                | KwKind::Auto
+                | KwKind::Register),
+            )) => {
+                if is_typedef || is_static || is_extern || is_auto || is_register {
+                    return Err("multiple storage classes in declaration".to_string());
+                }
+                match kw {
+                    KwKind::Typedef => is_typedef = true,
+                    KwKind::Static => is_static = true,
+                    KwKind::Extern => is_extern = true,
+                    KwKind::Auto => is_auto = true,
+                    _ => is_register = true,
+                }
+                rest = &rest[1..];
+                continue;
+            }
+            // A function specifier may be repeated.
+            Some(Token::Kw(KwKind::Noreturn)) => {
+                is_noreturn = true;
+                rest = &rest[1..];
+                continue;
+            }
+            Some(Token::Kw(KwKind::Alignas)) => {
+                let a;
+                (a, rest) = parse_alignas(&rest[1..], scope)?;
+                align = align.max(a);
+                has_alignas = true;
+                continue;
+            }
+            Some(Token::Kw(KwKind::Attribute)) => {
+                let attrs;
+                (attrs, rest) = parse_attribute(&rest[1..], scope)?;
+                if attrs.is_packed {
+                    return Err("packed attribute requires a struct type".to_string());
+                }
+                align = align.max(attrs.align);
+                continue;
+            }
+            Some(Token::Kw(KwKind::Void)) if ty.is_none() => {
+                ty = Some(Ty::Void);
+                rest = &rest[1..];
+                continue;
+            }
+            Some(Token::Kw(KwKind::Bool)) if ty.is_none() => {
+                ty = Some(Ty::Bool);
+                rest = &rest[1..];
+                continue;
+            }
+            Some(Token::Kw(KwKind::Char)) if ty.is_none() => {
+                ty = Some(Ty::Char);
+                rest = &rest[1..];
+                continue;
+            }
+            Some(Token::Kw(KwKind::Int)) if ty.is_none() => {
+                ty = Some(Ty::Int);
+                rest = &rest[1..];
+                continue;
+            }
+            Some(Token::Kw(KwKind::Short)) if !is_short => {
+                is_short = true;
+                rest = &rest[1..];
+                continue;
+            }
+            Some(Token::Kw(KwKind::Long)) if longs < 2 => {
+                longs += 1;
+                rest = &rest[1..];
+                continue;
+            }
+            Some(Token::Kw(KwKind::Signed)) if !is_signed && !is_unsigned => {
+                is_signed = true;
+                rest = &rest[1..];
+                continue;
+            }
+            Some(Token::Kw(KwKind::Unsigned)) if !is_signed && !is_unsigned => {
+                is_unsigned = true;
+                rest = &rest[1..];
+                continue;
+            }
+            Some(Token::Kw(KwKind::Float)) if ty.is_none() => {
+                ty = Some(Ty::Float);
+                rest = &rest[1..];
+                continue;
+            }
+            Some(Token::Kw(KwKind::Double)) if ty.is_none() => {
+                ty = Some(Ty::Double);
+                rest = &rest[1..];
+                continue;
+            }
+            Some(Token::Kw(kw @ (KwKind::Struct | KwKind::Union))) if ty.is_none() => {
+                let t;
+                (t, rest) = parse_struct_specifier(*kw == KwKind::Union, &rest[1..], scope)?;
+                ty = Some(t);
+                continue;
+            }
+            Some(Token::Kw(KwKind::Enum)) if ty.is_none() => {
+                let t;
+                (t, rest) = parse_enum_specifier(&rest[1..], scope)?;
+                ty = Some(t);
+                continue;
+            }
+            // A typedef name is a type specifier only if no other type specifier
+            // has been seen, e.g. `T` is a declarator in `int T;`.
+            Some(Token::Ident(name))
+                if ty.is_none() && !is_signed && !is_unsigned && !is_short && longs == 0 =>
+            {
+                if let Some(Symbol::Typedef(t)) = scope.find(name) {
+                    ty = Some(t.clone());
+                    rest = &rest[1..];
+                    continue;
+                }
+            }
+            Some(Token::Kw(
+                KwKind::Void
+                | KwKind::Bool
+                | KwKind::Char
+                | KwKind::Short
+                | KwKind::Int
+                | KwKind::Long
+                | KwKind::Signed
+                | KwKind::Unsigned
+                | KwKind::Float
+                | KwKind::Double
+                | KwKind::Struct
+                | KwKind::Union
+                | KwKind::Enum,
+            )) => return Err("multiple type specifiers in declaration".to_string()),
+            _ => (),
+        }
+        break;
+    }
+
+    let ty = match (ty, is_short, longs > 0) {
+        (Some(Ty::Double), false, true) if !is_signed && !is_unsigned && longs == 1 => {
+            Some(Ty::LongDouble)
+        }
+        (None | Some(Ty::Int), false, true) if is_unsigned => Some(Ty::ULong),
+        (None | Some(Ty::Int), false, true) => Some(Ty::Long),
+        (_, _, true) => return Err("invalid combination of type specifiers".to_string()),
+        (Some(Ty::Char), false, _) if is_unsigned => Some(Ty::UChar),
+        (Some(Ty::Char), false, _) => Some(Ty::Char),
+        (None | Some(Ty::Int), true, _) if is_unsigned => Some(Ty::UShort),
+        (None | Some(Ty::Int), true, _) => Some(Ty::Short),
+        (None | Some(Ty::Int), false, _) if is_unsigned => Some(Ty::UInt),
+        (None, false, _) if is_signed => Some(Ty::Int),
+        (ty, false, _) if !is_signed && !is_unsigned => ty,
+        (Some(Ty::Int), false, _) => Some(Ty::Int),
+        _ => return Err("invalid combination of type specifiers".to_string()),
+    };
+    let ty = ty.ok_or("expected a type")?.qualify(quals);
+    check_restrict(&ty)?;
+    Ok((
+        DeclSpec {
+            ty,
+            is_typedef,
+            is_static,
+            is_extern,
+            is_auto,
+            is_register,
+            is_noreturn,
+            align,
+            has_alignas,
+        },
+        rest,
+    ))
+}
+
+// <alignment-specifier> ::= "_Alignas" "(" (<type-name> | <const-expr>) ")"
+//
+// Parses an alignment specifier following "_Alignas", and returns the
+// alignment, which is 0 for `_Alignas(0)` to specify none.
+fn parse_alignas<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(usize, &'a [Token]), String> {
+    let rest = consume_punct(tokens, "(")?;
+    let (align, rest) = if is_declspec(rest, scope) {
+        let (ty, rest) = parse_typename(rest, scope)?;
+        if matches!(ty, Ty::Func(_)) || ty.is_incomplete() {
+            return Err("alignment of an incomplete type".to_string());
+        }
+        (ty.align() as i64, rest)
+    } else {
+        parse_const_expr(rest, scope)?
+    };
+    check_align(align)?;
+    Ok((align as usize, consume_punct(rest, ")")?))
+}
+
+// Attributes given by `__attribute__`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Attrs {
+    align: usize, // alignment by `aligned`, or 0 if not given
+    is_packed: bool,
+}
+
+impl Attrs {
+    // Returns the attributes of both.
+    fn merge(self, other: Attrs) -> Attrs {
+        Attrs {
+            align: self.align.max(other.align),
+            is_packed: self.is_packed || other.is_packed,
+        }
+    }
+}
+
+// <attribute> ::= "__attribute__" "(" "(" (<attr> ("," <attr>)*)? ")" ")"
+// <attr>      ::= "aligned" ("(" <const-expr> ")")?
+//               | "packed"
+//
+// Parses an attribute following "__attribute__". `aligned` without an
+// argument gives the largest alignment of any type.
+//
+// `packed` is only meaningful for a structure or union type, or a member,
+// which the caller checks.
+fn parse_attribute<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Attrs, &'a [Token]), String> {
+    let mut rest = consume_punct(tokens, "(")?;
+    rest = consume_punct(rest, "(")?;
+    let mut attrs = Attrs::default();
+    let mut first = true;
+    loop {
+        if let Ok(r) = consume_punct(rest, ")") {
+            rest = consume_punct(r, ")")?;
+            return Ok((attrs, rest));
+        }
+        if !first {
+            rest = consume_punct(rest, ",")?;
+        }
+        first = false;
+
+        match rest.first() {
+            Some(Token::Ident(name)) if name == "aligned" || name == "__aligned__" => {
+                rest = &rest[1..];
+                let a = match consume_punct(rest, "(") {
+                    Ok(r) => {
+                        let a;
+                        (a, rest) = parse_const_expr(r, scope)?;
+                        rest = consume_punct(rest, ")")?;
+                        a
+                    }
+                    Err(_) => Ty::LongDouble.align() as i64,
+                };
+                check_align(a)?;
+                attrs.align = attrs.align.max(a as usize);
+            }
+            Some(Token::Ident(name)) if name == "packed" || name == "__packed__" => {
+                rest = &rest[1..];
+                attrs.is_packed = true;
+            }
+            Some(Token::Ident(name)) => return Err(format!("unsupported attribute: {}", name)),
+            _ => return Err("expected an attribute".to_string()),
+        }
+    }
+}
+
+// <attribute>*
+//
+// Parses attributes, if any, e.g. `__attribute__((packed))` following the
+// members of a structure.
+fn parse_attributes<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Attrs, &'a [Token]), String> {
+    let mut attrs = Attrs::default();
+    let mut rest = tokens;
+    while let Some(Token::Kw(KwKind::Attribute)) = rest.first() {
+        let a;
+        (a, rest) = parse_attribute(&rest[1..], scope)?;
+        attrs = attrs.merge(a);
+    }
+    Ok((attrs, rest))
+}
+
+// <attribute>*
+//
+// Parses attributes following the declarator of a variable or a function, e.g.
+// `__attribute__((aligned(16)))` in `int x __attribute__((aligned(16)));`, and
+// returns the alignment requested by them or by the declaration specifiers
+// `spec`, or 0 if not requested.
+pub(super) fn parse_declarator_attrs<'a>(
+    spec: &DeclSpec,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(usize, &'a [Token]), String> {
+    let (attrs, rest) = parse_attributes(tokens, scope)?;
+    if attrs.is_packed {
+        return Err("packed attribute requires a struct type".to_string());
+    }
+    Ok((spec.align.max(attrs.align), rest))
+}
+
+// Returns an error if _Alignas is given in `spec`, but the alignment requested
+// for a variable or a member is less strict than that of its type. The aligned
+// attribute cannot lower it either, but is just ignored then.
+pub(super) fn check_alignas(
+    name: &str,
+    ty: &Ty,
+    spec: &DeclSpec,
+    align: usize,
+) -> Result<(), String> {
+    if spec.has_alignas && align > 0 && align < ty.align() {
+        return Err(format!(
+            "requested alignment is less than the alignment of {}: {}",
+            ty, name
+        ));
+    }
+    Ok(())
+}
+
+// Returns an error unless the alignment is 0 or a power of 2.
+fn check_align(align: i64) -> Result<(), String> {
+    if align < 0 || align & (align - 1).max(0) != 0 {
+        return Err(format!(
+            "requested alignment is not a power of 2: {}",
+            align
+        ));
+    }
+    Ok(())
+}
+
+// Returns the qualifier if the token is a type qualifier.
+fn type_qualifier(token: Option<&Token>) -> Option<Quals> {
+    let mut quals = Quals::default();
+    match token {
+        Some(Token::Kw(KwKind::Const)) => quals.is_const = true,
+        Some(Token::Kw(KwKind::Volatile)) => quals.is_volatile = true,
+        Some(Token::Kw(KwKind::Restrict)) => quals.is_restrict = true,
+        _ => return None,
+    }
+    Some(quals)
+}
+
+// Only a pointer type can be restrict-qualified.
+fn check_restrict(ty: &Ty) -> Result<(), String> {
+    if ty.quals().is_restrict && !matches!(ty.unqual(), Ty::Ptr(_)) {
+        return Err("restrict requires a pointer type".to_string());
+    }
+    Ok(())
+}
+
+// <struct-specifier> ::= ("struct" | "union") <attribute>* ident?
+//                          "{" <struct-declaration>* "}" <attribute>*
+//                      | ("struct" | "union") ident
+//
+// Parses a structure or union specifier following "struct" or "union".
+//
+// A tag without members refers to the type of the tag visible, or declares
+// an incomplete type in the current scope if there is none, or if the
+// specifier alone is declared, e.g. `struct node;`. The members complete the
+// type of the tag declared in the current scope, or declare a new type, which
+// the members can refer to by the tag, e.g. `struct node *next;`.
+//
+// Attributes before the tag or after the members apply to the type, e.g.
+// `struct __attribute__((packed)) header { ... }`.
+fn parse_struct_specifier<'a>(
+    is_union: bool,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Ty, &'a [Token]), String> {
+    let (mut attrs, rest) = parse_attributes(tokens, scope)?;
+    let (tag, rest) = match rest.first() {
+        Some(Token::Ident(tag)) => (Some(tag), &rest[1..]),
+        _ => (None, rest),
+    };
+
+    let mut rest = match consume_punct(rest, "{") {
+        Ok(r) => r,
+        Err(_) => {
+            let tag = tag.ok_or("expected a struct tag or member list")?;
+            if attrs != Attrs::default() {
+                return Err(format!("attribute of {} without its members", tag));
+            }
+            let ty = if consume_punct(rest, ";").is_ok() {
+                scope.find_innermost_tag(tag)
+            } else {
+                scope.find_tag(tag)
+            };
+            return match ty.cloned() {
+                Some(Ty::Struct(s)) if s.is_union() == is_union => Ok((Ty::Struct(s), rest)),
+                Some(_) => Err(format!("{} defined as wrong kind of tag", tag)),
+                None => {
+                    let ty = Ty::Struct(StructRef::new(Some(tag.clone()), is_union));
+                    scope.declare_tag(tag, ty.clone())?;
+                    Ok((ty, rest))
+                }
+            };
+        }
+    };
+
+    let s = match tag {
+        Some(tag) => match scope.find_innermost_tag(tag).cloned() {
+            Some(Ty::Struct(s)) if s.is_union() != is_union => {
+                return Err(format!("{} defined as wrong kind of tag", tag))
+            }
+            Some(Ty::Struct(s)) if s.is_complete() => {
+                return Err(format!("redefinition of {:?}", s))
+            }
+            Some(Ty::Struct(s)) => s,
+            Some(_) => return Err(format!("{} defined as wrong kind of tag", tag)),
+            None => {
+                let s = StructRef::new(Some(tag.clone()), is_union);
+                scope.declare_tag(tag, Ty::Struct(s.clone()))?;
+                s
+            }
+        },
+        None => StructRef::new(None, is_union),
+    };
+
+    let mut decls: Vec<MemberDecl> = Vec::new();
+    loop {
+        if let Ok(r) = consume_punct(rest, "}") {
+            rest = r;
+            break;
+        }
+        rest = parse_struct_declaration(&mut decls, rest, scope)?;
+    }
+    check_flexible_array(&decls, is_union)?;
+    let after;
+    (after, rest) = parse_attributes(rest, scope)?;
+    attrs = attrs.merge(after);
+    s.complete(decls, attrs.is_packed, attrs.align);
+
+    let mut names = s.member_names();
+    names.sort();
+    if let Some(w) = names.windows(2).find(|w| w[0] == w[1]) {
+        return Err(format!("duplicate member {}", w[0]));
+    }
+    Ok((Ty::Struct(s), rest))
+}
+
+// <struct-declaration> ::= <declspec> (<member-declarator> ("," <member-declarator>)*)? ";"
+// <member-declarator>  ::= <declarator> (":" <const-expr>)? <attribute>*
+//                        | ":" <const-expr> <attribute>*
+//
+// Parses a declaration of members, which appends them to `decls`. A member
+// with a width is a bit-field, which is unnamed if it has no declarator.
+// Attributes following a member declarator apply to the member, e.g.
+// `int x __attribute__((packed));`.
+//
+// A structure or union type without a tag nor a declarator is an anonymous
+// member, whose members are accessed as if they were of the enclosing one.
+fn parse_struct_declaration<'a>(
+    decls: &mut Vec<MemberDecl>,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<&'a [Token], String> {
+    let (spec, mut rest) = parse_declspec(tokens, scope)?;
+    if spec.has_storage_class() {
+        return Err("storage class in a member declaration".to_string());
+    }
+    if spec.is_noreturn {
+        return Err("_Noreturn in a member declaration".to_string());
+    }
+    if let Ok(r) = consume_punct(rest, ";") {
+        if let Ty::Struct(s) = spec.ty.unqual() {
+            if s.is_anonymous() {
+                decls.push(MemberDecl {
+                    name: None,
+                    ty: spec.ty,
+                    width: None,
+                    align: spec.align,
+                    is_packed: false,
+                });
+            }
+        }
+        return Ok(r);
+    }
+
+    let mut first = true;
+    loop {
+        if let Ok(r) = consume_punct(rest, ";") {
+            return Ok(r);
+        }
+        if !first {
+            rest = consume_punct(rest, ",")?;
+        }
+        first = false;
+
+        let (name, ty) = if consume_punct(rest, ":").is_ok() {
+            (None, spec.ty.clone())
+        } else {
+            let (name, ty);
+            (name, ty, rest) = parse_named_declarator(spec.ty.clone(), rest, scope)?;
+            (Some(name), ty)
+        };
+        let name_str = name.as_deref().unwrap_or("(unnamed bit-field)");
+        check_member(name_str, &ty)?;
+        let width = match consume_punct(rest, ":") {
+            Ok(r) => {
+                let width;
+                (width, rest) = parse_const_expr(r, scope)?;
+                Some(check_bit_field(name_str, &ty, width, name.is_some())?)
+            }
+            Err(_) => None,
+        };
+        let attrs;
+        (attrs, rest) = parse_attributes(rest, scope)?;
+        let align = spec.align.max(attrs.align);
+        if width.is_some() && align > 0 {
+            return Err(format!("alignment of a bit-field: {}", name_str));
+        }
+        check_alignas(name_str, &ty, &spec, align)?;
+        decls.push(MemberDecl {
+            name,
+            ty,
+            width,
+            align,
+            is_packed: attrs.is_packed,
+        });
+    }
+}
+
+// Returns an error if the type cannot be the type of a member.
+fn check_member(name: &str, ty: &Ty) -> Result<(), String> {
+    if let Ty::Func(_) = ty {
+        return Err(format!("member declared as a function: {}", name));
+    }
+    if ty.is_variably_modified() {
+        return Err(format!("member has a variably modified type: {}", name));
+    }
+    // An array of unknown length may be a flexible array member.
+    if ty.is_incomplete() && !matches!(ty, Ty::IncompleteArray(_)) {
+        return Err(format!("member has incomplete type: {}", name));
+    }
+    Ok(())
+}
+
+// A member of an array type of unknown length is a flexible array member,
+// which must be the last member of a structure with another named member.
+fn check_flexible_array(decls: &[MemberDecl], is_union: bool) -> Result<(), String> {
+    let i = match decls
+        .iter()
+        .position(|d| matches!(d.ty, Ty::IncompleteArray(_)))
+    {
+        Some(i) => i,
+        None => return Ok(()),
+    };
+    let name = decls[i].name.as_deref().unwrap_or_default();
+    if is_union {
+        return Err(format!("flexible array member in union: {}", name));
+    }
+    if i + 1 < decls.len() {
+        return Err(format!(
+            "flexible array member not at end of struct: {}",
+            name
+        ));
+    }
+    if !decls[..i].iter().any(|d| d.name.is_some()) {
+        return Err(format!(
+            "flexible array member in a struct with no named members: {}",
+            name
+        ));
+    }
+    Ok(())
+}
+
+// Returns the width of a bit-field, which must be of an integer type and no
+// wider than it. Only an unnamed bit-field can have zero width.
+fn check_bit_field(name: &str, ty: &Ty, width: i64, is_named: bool) -> Result<usize, String> {
+    if !ty.is_integer() {
+        return Err(format!("bit-field has non-integer type {}: {}", ty, name));
+    }
+    let max = match ty.unqual() {
+        Ty::Bool => 1,
+        ty => ty.size() as i64 * 8,
+    };
+    if width < 0 {
+        return Err(format!("negative width of bit-field: {}", name));
+    }
+    if width > max {
+        return Err(format!("width of bit-field exceeds its type: {}", name));
+    }
+    if width == 0 && is_named {
+        return Err(format!("named bit-field has zero width: {}", name));
+    }
+    Ok(width as usize)
+}
+
+// <enum-specifier> ::= "enum" ident? "{" <enumerator> ("," <enumerator>)* ","? "}"
+//                    | "enum" ident
+// <enumerator>     ::= ident ("=" <const-expr>)?
+//
+// Enumerators are declared in the current scope, alongside other ordinary
+// identifiers.
+fn parse_enum_specifier<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Ty, &'a [Token]), String> {
+    let (tag, rest) = match tokens.first() {
+        Some(Token::Ident(tag)) => (Some(tag), &tokens[1..]),
+        _ => (None, tokens),
+    };
+
+    let mut rest = match consume_punct(rest, "{") {
+        Ok(r) => r,
+        Err(_) => {
+            // reference to a previously declared tag
+            let tag = tag.ok_or("expected an enum tag or enumerator list")?;
+            return match scope.find_tag(tag) {
+                Some(Ty::Enum) => Ok((Ty::Enum, rest)),
+                _ => Err(format!("unknown enum tag: {}", tag)),
+            };
+        }
+    };
+
+    let mut val = 0;
+    loop {
+        let name = match rest.first() {
+            Some(Token::Ident(name)) => name,
+            _ => return Err("expected an enumerator".to_string()),
+        };
+        rest = &rest[1..];
+        if let Ok(r) = consume_punct(rest, "=") {
+            (val, rest) = parse_const_expr(r, scope)?;
+        }
+        scope.declare(name, Symbol::EnumConst(val))?;
+        val += 1;
+
+        if let Ok(r) = consume_punct(rest, "}") {
+            rest = r;
+            break;
+        }
+        rest = consume_punct(rest, ",")?;
+        if let Ok(r) = consume_punct(rest, "}") {
+            rest = r;
+            break;
+        }
+    }
+
+    if let Some(tag) = tag {
+        scope.declare_tag(tag, Ty::Enum)?;
+    }
+    Ok((Ty::Enum, rest))
+}
+
+// Declarator, which gives a name and a type derived from a base type.
+//
+// The name is None if the declarator is abstract, e.g. `*[4]` in
+// `sizeof(int *[4])`.
+#[derive(Debug, PartialEq)]
+pub(super) struct Declarator {
+    pub name: Option<String>,
+    pub ty: Ty,
+    // names of the parameters if the declarator declares a function
+    pub param_names: Vec<Option<String>>,
+}
+
+// Type derivation by a part of a declarator.
+#[derive(Debug, PartialEq)]
+enum Derivation {
+    Ptr(Quals),           // "*" <type-qualifier>*
+    Array(Option<usize>), // "[" <const-expr>? "]"
+    Vla(Expr),            // "[" <assign> "]" of a non-constant length
+    Func(Params),         // "(" <params> ")"
+}
+
+// Parameters of a function declarator.
+#[derive(Debug, PartialEq)]
+struct Params {
+    tys: Option<Vec<Ty>>, // None if unspecified
+    names: Vec<Option<String>>,
+    is_variadic: bool, // true if the parameters end with "..."
+}
+
+// Derivations of a declarator, which are to be applied in order to the base
+// type.
+struct Derivations {
+    name: Option<String>,
+    derivs: Vec<Derivation>,
+}
+
+// <declarator> ::= ("*" <type-qualifier>*)* <direct-declarator>
+//
+// Parses a declarator, deriving its type from `base`.
+pub(super) fn parse_declarator<'a>(
+    base: Ty,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Declarator, &'a [Token]), String> {
+    let (Derivations { name, derivs }, rest) = parse_derivations(tokens, scope)?;
+    let mut ty = base;
+    let mut param_names = Vec::new();
+    for deriv in derivs {
+        param_names = Vec::new();
+        ty = match deriv {
+            Derivation::Ptr(quals) => Ty::Ptr(Box::new(ty)).qualify(quals),
+            Derivation::Array(len) => {
+                check_elem(&ty)?;
+                match len {
+                    Some(len) => Ty::Array(Box::new(ty), len),
+                    None => Ty::IncompleteArray(Box::new(ty)),
+                }
+            }
+            Derivation::Vla(len) => {
+                check_elem(&ty)?;
+                Ty::Vla(Box::new(ty), Box::new(len))
+            }
+            Derivation::Func(Params {
+                tys,
+                names,
+                is_variadic,
+            }) => {
+                if matches!(ty, Ty::Func(_)) || ty.is_array() {
+                    return Err("function returning a function or an array".to_string());
+                }
+                param_names = names;
+                // Qualifiers of a return value have no meaning, since it is
+                // not an lvalue.
+                Ty::Func(Box::new(FuncTy {
+                    ret: ty.unqual().clone(),
+                    params: tys,
+                    is_variadic,
+                }))
+            }
+        };
+    }
+    Ok((
+        Declarator {
+            name,
+            ty,
+            param_names,
+        },
+        rest,
+    ))
+}
+
+// Returns an error if the type cannot be the element type of an array.
+fn check_elem(ty: &Ty) -> Result<(), String> {
+    if let Ty::Func(_) = ty {
+        return Err("array of functions".to_string());
+    }
+    if ty.is_incomplete() {
+        return Err("array has incomplete element type".to_string());
+    }
+    Ok(())
+}
+
+// <declarator>        ::= ("*" <type-qualifier>*)* <direct-declarator>
+// <direct-declarator> ::= (ident | "(" <declarator> ")")? <type-suffix>*
+// <type-suffix>       ::= "[" <assign>? "]"
+//                       | "(" <params>? ")"
+// <params>            ::= "void" | <param> ("," <param>)* ("," "...")?
+// <param>             ::= <declspec> <declarator>
+//
+// e.g. for `*(*x)[4]`, the derivations are [Ptr, Array(4), Ptr] so that `x` is
+// a pointer to an array of pointers.
+fn parse_derivations<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Derivations, &'a [Token]), String> {
+    let mut derivs: Vec<Derivation> = Vec::new();
+    let mut rest = tokens;
+    while let Ok(r) = consume_punct(rest, "*") {
+        let mut quals = Quals::default();
+        rest = r;
+        while let Some(q) = type_qualifier(rest.first()) {
+            quals = quals.merge(q);
+            rest = &rest[1..];
+        }
+        derivs.push(Derivation::Ptr(quals));
+    }
+
+    // The innermost part of a declarator is applied last.
+    let (name, inner) = if is_nested_declarator(rest, scope) {
+        let (Derivations { name, derivs }, r) = parse_derivations(&rest[1..], scope)?;
+        rest = consume_punct(r, ")")?;
+        (name, derivs)
+    } else if let Some(Token::Ident(name)) = rest.first() {
+        rest = &rest[1..];
+        (Some(name.clone()), Vec::new())
+    } else {
+        (None, Vec::new())
+    };
+
+    // Type suffixes are applied from right to left, e.g. `x[2][3]` is an array
+    // of 2 arrays of 3 elements.
+    let mut suffixes: Vec<Derivation> = Vec::new();
+    loop {
+        if let Ok(r) = consume_punct(rest, "[") {
+            if let Ok(r) = consume_punct(r, "]") {
+                rest = r;
+                suffixes.push(Derivation::Array(None));
+                continue;
+            }
+            let array;
+            (array, rest) = parse_array_len(r, scope)?;
+            rest = consume_punct(rest, "]")?;
+            suffixes.push(array);
+        } else if let Ok(r) = consume_punct(rest, "(") {
+            let params;
+            (params, rest) = parse_params(r, scope)?;
+            suffixes.push(Derivation::Func(params));
+        } else {
+            break;
+        }
+    }
+
+    derivs.extend(suffixes.into_iter().rev());
+    derivs.extend(inner);
+    Ok((Derivations { name, derivs }, rest))
+}
+
+// Parses the length of an array, which is variable unless it is an integer
+// constant expression.
+fn parse_array_len<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Derivation, &'a [Token]), String> {
+    let (len, rest) = parse_assign(tokens, scope)?;
+    if !len.ty().is_integer() {
+        return Err("array length has non-integer type".to_string());
+    }
+    match eval(&len) {
+        Ok(n) if n < 0 => Err("negative array length".to_string()),
+        Ok(n) => Ok((Derivation::Array(Some(n as usize)), rest)),
+        Err(_) => Ok((Derivation::Vla(len), rest)),
+    }
+}
+
+// Returns true if tokens start with a parenthesized declarator rather than a
+// parameter list, e.g. `(*x)` rather than `(int)`.
+fn is_nested_declarator(tokens: &[Token], scope: &Scope) -> bool {
+    if consume_punct(tokens, "(").is_err() {
+        return false;
+    }
+    match tokens.get(1) {
+        Some(Token::Punct(p)) => p == "*" || p == "(",
+        Some(Token::Ident(_)) => !is_declspec(&tokens[1..], scope),
+        _ => false,
+    }
+}
+
+// Parses parameters following "(".
+//
+// As in C, an empty parameter list leaves the parameters unspecified, while
+// `(void)` specifies that there are none. A parameter of an array type or a
+// function type is adjusted to a pointer.
+//
+// "..." after at least one parameter makes the function variadic.
+//
+// Parameters are declared in their own scope, so that a parameter can give
+// the length of a variable length array in the following ones.
+fn parse_params<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Params, &'a [Token]), String> {
+    let stack_size = scope.enter_params();
+    let params = parse_param_list(tokens, scope);
+    scope.leave_params(stack_size);
+    params
+}
+
+fn parse_param_list<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Params, &'a [Token]), String> {
+    if let Ok(rest) = consume_punct(tokens, ")") {
+        let params = Params {
+            tys: None,
+            names: Vec::new(),
+            is_variadic: false,
+        };
+        return Ok((params, rest));
+    }
+
+    let mut tys: Vec<Ty> = Vec::new();
+    let mut names: Vec<Option<String>> = Vec::new();
+    let mut rest = tokens;
+    loop {
+        if let Ok(r) = consume_punct(rest, ")") {
+            let params = Params {
+                tys: Some(tys),
+                names,
+                is_variadic: false,
+            };
+            return Ok((params, r));
+        }
+        if !tys.is_empty() {
+            rest = consume_punct(rest, ",")?;
+            if let Ok(r) = consume_punct(rest, "...") {
+                let params = Params {
+                    tys: Some(tys),
+                    names,
+                    is_variadic: true,
+                };
+                return Ok((params, consume_punct(r, ")")?));
+            }
+        }
+
+        let spec;
+        (spec, rest) = parse_declspec(rest, scope)?;
+        // `register` is the only storage class allowed for a parameter.
+        if spec.has_storage_class() && !spec.is_register {
+            return Err("storage class in a parameter".to_string());
+        }
+        if spec.align > 0 {
+            return Err("alignment of a parameter".to_string());
+        }
+        if spec.is_noreturn {
+            return Err("_Noreturn in a parameter".to_string());
+        }
+        let decl;
+        (decl, rest) = parse_declarator(spec.ty, rest, scope)?;
+        if *decl.ty.unqual() == Ty::Void {
+            if tys.is_empty() && decl.name.is_none() && consume_punct(rest, ")").is_ok() {
+                continue;
+            }
+            return Err("parameter has void type".to_string());
+        }
+        let ty = match decl.ty {
+            Ty::Array(elem, _) | Ty::IncompleteArray(elem) | Ty::Vla(elem, _) => Ty::Ptr(elem),
+            ty @ Ty::Func(_) => Ty::Ptr(Box::new(ty)),
+            ty => ty,
+        };
+        // A parameter may have an incomplete structure type until the function
+        // is defined, which has no size to be allocated.
+        match &decl.name {
+            _ if ty.is_incomplete() => (),
+            Some(name) => {
+                scope.declare_local(name, ty.clone())?;
+            }
+            None => {
+                scope.alloc_local("", ty.clone());
+            }
+        }
+        tys.push(ty);
+        names.push(decl.name);
+    }
+}
+
+// Parses a declarator which must have a name.
+fn parse_named_declarator<'a>(
+    base: Ty,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(String, Ty, &'a [Token]), String> {
+    match parse_declarator(base, tokens, scope)? {
+        (
+            Declarator {
+                name: Some(name),
+                ty,
+                ..
+            },
+            rest,
+        ) => Ok((name, ty, rest)),
+        _ => Err("expected an identifier in declarator".to_string()),
+    }
+}
+
+// <type-name> ::= <declspec> <declarator>
+//
+// The declarator must be abstract, e.g. `int *[4]`.
+pub(super) fn parse_typename<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Ty, &'a [Token]), String> {
+    let (spec, rest) = parse_declspec(tokens, scope)?;
+    if spec.has_storage_class() {
+        return Err("storage class in a type name".to_string());
+    }
+    if spec.align > 0 {
+        return Err("alignment in a type name".to_string());
+    }
+    if spec.is_noreturn {
+        return Err("_Noreturn in a type name".to_string());
+    }
+    match parse_declarator(spec.ty, rest, scope)? {
+        (Declarator { name: None, ty, .. }, rest) => Ok((ty, rest)),
+        (
+            Declarator {
+                name: Some(name), ..
+            },
+            _,
+        ) => Err(format!("unexpected identifier in type name: {}", name)),
+    }
+}
+
+// <typedef-names> ::= (<declarator> ("," <declarator>)*)? ";"
+//
+// Declares typedef names of the type in `spec`, which has already been parsed
+// by the caller. Results in a compound statement which evaluates the lengths
+// of variable length arrays in the types, which is only allowed in a block.
+pub(super) fn parse_typedef_names<'a>(
+    spec: &DeclSpec,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Stmt, &'a [Token]), String> {
+    if spec.align > 0 {
+        return Err("alignment of a typedef".to_string());
+    }
+    if spec.is_noreturn {
+        return Err("_Noreturn in a typedef".to_string());
+    }
+    let mut lens: Vec<Stmt> = Vec::new();
+    let mut rest = tokens;
+    let mut first = true;
+    loop {
+        if let Ok(r) = consume_punct(rest, ";") {
+            return Ok((Stmt::CompStmt(lens), r));
+        }
+        if !first {
+            rest = consume_punct(rest, ",")?;
+        }
+        first = false;
+
+        let (name, ty);
+        (name, ty, rest) = parse_named_declarator(spec.ty.clone(), rest, scope)?;
+        if scope.is_file_scope() && ty.is_variably_modified() {
+            return Err(format!("variably modified type at file scope: {}", name));
+        }
+        let ty = save_vla_lengths(ty, scope, &mut lens);
+        scope.declare(&name, Symbol::Typedef(ty))?;
+    }
+}
+
+// Evaluates the lengths of variable length arrays in a type when it is
+// declared, so that the size of the type does not change with the variables
+// of the lengths. Each length is saved to an unnamed local variable, which the
+// returned type refers to instead, and `lens` receives the assignments to
+// them.
+fn save_vla_lengths(ty: Ty, scope: &mut Scope, lens: &mut Vec<Stmt>) -> Ty {
+    match ty {
+        Ty::Vla(elem, len) => {
+            let elem = save_vla_lengths(*elem, scope, lens);
+            let var = scope.alloc_local("(vla length)", Ty::Int);
+            lens.push(Stmt::ExprStmt(Expr::Assign(Box::new(Binary {
+                lhs: Expr::Var(var.clone()),
+                rhs: *len,
+            }))));
+            Ty::Vla(Box::new(elem), Box::new(Expr::Var(var)))
+        }
+        Ty::Ptr(ty) => Ty::Ptr(Box::new(save_vla_lengths(*ty, scope, lens))),
+        Ty::Array(elem, len) => Ty::Array(Box::new(save_vla_lengths(*elem, scope, lens)), len),
+        Ty::Qual(ty, quals) => Ty::Qual(Box::new(save_vla_lengths(*ty, scope, lens)), quals),
+        ty => ty,
+    }
+}
+
+// <declaration>     ::= <declspec> (<init-declarator> ("," <init-declarator>)*)? ";"
+// <init-declarator> ::= <declarator> <attribute>* ("=" <initializer>)?
+//
+// Declares local variables and functions, and results in a compound statement
+// which assigns the initial values of automatic variables. Static local
+// variables are initialized with constants instead, and `extern` declares a
+// variable with linkage.
+pub(super) fn parse_declaration<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Stmt, &'a [Token]), String> {
+    let (spec, rest) = parse_declspec(tokens, scope)?;
+    if spec.is_typedef {
+        return parse_typedef_names(&spec, rest, scope);
+    }
+
+    let mut inits: Vec<Stmt> = Vec::new();
+    let mut rest = rest;
+    let mut first = true;
+    loop {
+        if let Ok(r) = consume_punct(rest, ";") {
+            return Ok((Stmt::CompStmt(inits), r));
+        }
+        if !first {
+            rest = consume_punct(rest, ",")?;
+        }
+        first = false;
+
+        let (name, mut ty, req_align);
+        (name, ty, rest) = parse_named_declarator(spec.ty.clone(), rest, scope)?;
+        (req_align, rest) = parse_declarator_attrs(&spec, rest, scope)?;
+        if let Ty::Func(_) = ty {
+            if spec.is_static {
+                return Err(format!("static function in a block: {}", name));
+            }
+            if spec.is_auto || spec.is_register {
+                return Err(format!("auto or register function: {}", name));
+            }
+            if req_align > 0 {
+                return Err(format!("alignment of a function: {}", name));
+            }
+            scope.declare_global(&name, ty, false, false)?;
+            continue;
+        }
+        if spec.is_noreturn {
+            return Err(format!("_Noreturn of a variable: {}", name));
+        }
+        check_object(&name, &ty)?;
+        check_alignas(&name, &ty, &spec, req_align)?;
+        if spec.is_extern {
+            check_static_storage(&name, &ty)?;
+            scope.declare_global(&name, ty, false, false)?;
+            continue;
+        }
+        if spec.is_static {
+            check_static_storage(&name, &ty)?;
+            let (init, relocs);
+            (StaticInit { ty, init, relocs }, rest) = parse_static_init(ty, rest, scope)?;
+            check_complete(&name, &ty)?;
+            let align = req_align.max(ty.align());
+            scope.declare_static_local(&name, ty, init, relocs, align)?;
+            continue;
+        }
+
+        // Only an array of unknown length may be completed by the initializer.
+        if !matches!(ty, Ty::IncompleteArray(_)) {
+            check_complete(&name, &ty)?;
+        }
+
+        let align = req_align.max(ty.align());
+
+        // A variable length array is allocated when the declaration is reached,
+        // after its lengths are evaluated.
+        ty = save_vla_lengths(ty, scope, &mut inits);
+        if let Ty::Vla(..) = ty {
+            if consume_punct(rest, "=").is_ok() {
+                return Err(format!(
+                    "variable length array may not be initialized: {}",
+                    name
+                ));
+            }
+            let size = sizeof_expr(&ty);
+            let var = scope.declare_local(&name, ty)?;
+            scope.mark_vla();
+            inits.push(Stmt::ExprStmt(Expr::VlaAlloc(var, Box::new(size))));
+            continue;
+        }
+
+        let r = match consume_punct(rest, "=") {
+            Ok(r) => r,
+            Err(_) => {
+                check_complete(&name, &ty)?;
+                scope.declare_aligned_local(&name, ty, align)?;
+                continue;
+            }
+        };
+        // The initializer of an array of unknown length is parsed before the
+        // variable is declared, since it gives the length.
+        let (var, init);
+        if let Ty::IncompleteArray(_) = ty {
+            (init, ty, rest) = parse_initializer(&ty, r, scope)?;
+            var = scope.declare_aligned_local(&name, ty, align)?;
+        } else {
+            var = scope.declare_aligned_local(&name, ty, align)?;
+            (init, _, rest) = parse_initializer(&var.ty, r, scope)?;
+        }
+        inits.extend(init_local(&var, init).into_iter().map(Stmt::ExprStmt));
+    }
+}
+
+// Returns an error if the type cannot be the type of a variable.
+pub(super) fn check_object(name: &str, ty: &Ty) -> Result<(), String> {
+    match ty.unqual() {
+        Ty::Void => Err(format!("variable declared void: {}", name)),
+        _ => Ok(()),
+    }
+}
+
+// Returns an error if the variable with static storage duration cannot have
+// the type, since its size is only known at runtime.
+pub(super) fn check_static_storage(name: &str, ty: &Ty) -> Result<(), String> {
+    if ty.is_variably_modified() {
+        return Err(format!(
+            "variably modified type with static storage duration: {}",
+            name
+        ));
+    }
+    Ok(())
+}
+
+// Returns an error if the variable of the type cannot be defined since it has
+// no size.
+pub(super) fn check_complete(name: &str, ty: &Ty) -> Result<(), String> {
+    match ty {
+        Ty::IncompleteArray(_) => Err(format!("array size missing in {}", name)),
+        _ if ty.is_incomplete() => Err(format!("storage size of {} isn't known", name)),
+        _ => Ok(()),
+    }
+}
+
+// Variable with static storage duration, whose type may be completed by the
+// initializer.
+pub(super) struct StaticInit {
+    pub ty: Ty,
+    pub init: Option<Vec<u8>>, // initial bytes
+    pub relocs: Vec<Reloc>,    // addresses in the initial bytes
+}
+
+// <static-init> ::= ("=" <initializer>)?
+//
+// Parses the initializer of a variable with static storage duration, which
+// must be constant since it is emitted as data.
+pub(super) fn parse_static_init<'a>(
+    ty: Ty,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(StaticInit, &'a [Token]), String> {
+    let rest = match consume_punct(tokens, "=") {
+        Ok(r) => r,
+        Err(_) => {
+            let init = StaticInit {
+                ty,
+                init: None,
+                relocs: Vec::new(),
+            };
+            return Ok((init, tokens));
+        }
+    };
+    let (init, ty, rest) = parse_initializer(&ty, rest, scope)?;
+    let (bytes, relocs) = eval_init(&ty, &init)?;
+    let init = StaticInit {
+        ty,
+        init: Some(bytes),
+        relocs,
+    };
+    Ok((init, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::IntKind;
+    use crate::parser::scope::Global;
+    use crate::parser::{Binary, Cast, Expr, GVar, Var};
+    use crate::ty::BitField;
+
+    #[test]
+    fn parses_enum_specifier() {
+        // enum E { A, B = 5, C, } ;
+        let tokens = vec![
+            Token::Kw(KwKind::Enum),
+            Token::Ident("E".to_string()),
+            Token::Punct("{".to_string()),
+            Token::Ident("A".to_string()),
+            Token::Punct(",".to_string()),
+            Token::Ident("B".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Num(5, IntKind::Int),
+            Token::Punct(",".to_string()),
+            Token::Ident("C".to_string()),
+            Token::Punct(",".to_string()),
+            Token::Punct("}".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        let mut scope = Scope::new();
+        let (spec, rest) = parse_declspec(&tokens, &mut scope).unwrap();
+        assert_eq!(Ty::Enum, spec.ty);
+        assert_eq!(vec![Token::Punct(";".to_string())], rest);
+        assert_eq!(Some(&Symbol::EnumConst(0)), scope.find("A"));
+        assert_eq!(Some(&Symbol::EnumConst(5)), scope.find("B"));
+        assert_eq!(Some(&Symbol::EnumConst(6)), scope.find("C"));
+        assert_eq!(Some(&Ty::Enum), scope.find_tag("E"));
+    }
+
+    #[test]
+    fn parses_declared_enum_tag() {
+        let tokens = vec![Token::Kw(KwKind::Enum), Token::Ident("E".to_string())];
+        let mut scope = Scope::new();
+        assert!(parse_declspec(&tokens, &mut scope).is_err());
+        scope.declare_tag("E", Ty::Enum).unwrap();
+        let (spec, rest) = parse_declspec(&tokens, &mut scope).unwrap();
+        assert_eq!(Ty::Enum, spec.ty);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn parses_typedef_name_as_type() {
+        // T x;
+        let tokens = vec![
+            Token::Ident("T".to_string()),
+            Token::Ident("x".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        let mut scope = Scope::new();
+        assert!(!is_declspec(&tokens, &scope));
+        scope.declare("T", Symbol::Typedef(Ty::Int)).unwrap();
+        assert!(is_declspec(&tokens, &scope));
+        let (spec, rest) = parse_declspec(&tokens, &mut scope).unwrap();
+        assert_eq!(
+            DeclSpec {
+                ty: Ty::Int,
+                is_typedef: false,
+                is_static: false,
+                is_extern: false,
+                is_auto: false,
+                is_register: false,
+                is_noreturn: false,
+                align: 0,
+                has_alignas: false,
+            },
+            spec
+        );
+        assert_eq!(&tokens[1..], rest);
+    }
+
+    #[test]
+    fn typedef_name_after_type_specifier_is_declarator() {
+        // int T;
+        let tokens = vec![
+            Token::Kw(KwKind::Int),
+            Token::Ident("T".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        let mut scope = Scope::new();
+        scope.declare("T", Symbol::Typedef(Ty::Int)).unwrap();
+        let (_, rest) = parse_declspec(&tokens, &mut scope).unwrap();
+        assert_eq!(&tokens[1..], rest);
+    }
+
+    #[test]
+    fn parses_typedef_declaration() {
+        // typedef int T, U;
+        let tokens = vec![
+            Token::Kw(KwKind::Typedef),
+            Token::Kw(KwKind::Int),
+            Token::Ident("T".to_string()),
+            Token::Punct(",".to_string()),
+            Token::Ident("U".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        let mut scope = Scope::new();
+        let (stmt, rest) = parse_declaration(&tokens, &mut scope).unwrap();
+        assert_eq!(Stmt::CompStmt(Vec::new()), stmt);
+        assert_eq!(Vec::<Token>::new(), rest);
+        assert_eq!(Some(&Symbol::Typedef(Ty::Int)), scope.find("T"));
+        assert_eq!(Some(&Symbol::Typedef(Ty::Int)), scope.find("U"));
+    }
+
+    #[test]
+    fn parses_function_declaration_in_block() {
+        // int f(int);
+        let tokens = vec![
+            Token::Kw(KwKind::Int),
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct(")".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let (stmt, rest) = parse_declaration(&tokens, &mut scope).unwrap();
+        assert_eq!(Stmt::CompStmt(Vec::new()), stmt);
+        assert_eq!(Vec::<Token>::new(), rest);
+        let expected = Symbol::Global(Global {
+            ty: Ty::Func(Box::new(FuncTy {
+                ret: Ty::Int,
+                params: Some(vec![Ty::Int]),
+                is_variadic: false,
+            })),
+            is_defined: false,
+            is_static: false,
+        });
+        assert_eq!(Some(&expected), scope.find("f"));
+        assert_eq!((0, 16), scope.leave_func());
+    }
+
+    #[test]
+    fn parses_static_local_declaration() {
+        // static int x = 3;
+        let tokens = vec![
+            Token::Kw(KwKind::Static),
+            Token::Kw(KwKind::Int),
+            Token::Ident("x".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Num(3, IntKind::Int),
+            Token::Punct(";".to_string()),
+        ];
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let (stmt, _) = parse_declaration(&tokens, &mut scope).unwrap();
+        assert_eq!(Stmt::CompStmt(Vec::new()), stmt);
+        let var = GVar {
+            name: "x.0".to_string(),
+            ty: Ty::Int,
+        };
+        assert_eq!(Some(&Symbol::GVar(var)), scope.find("x"));
+        assert_eq!((0, 16), scope.leave_func());
+        let data = Data {
+            name: "x.0".to_string(),
+            ty: Ty::Int,
+            init: Some(vec![3, 0, 0, 0]),
+            relocs: Vec::new(),
+            is_static: true,
+            align: 4,
+        };
+        assert_eq!(vec![data], scope.take_statics());
+    }
+
+    #[test]
+    fn parses_attribute_following_declarator() {
+        // char c; int x __attribute__((aligned(16)));
+        let tokens = vec![
+            Token::Kw(KwKind::Char),
+            Token::Ident("c".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("x".to_string()),
+            Token::Kw(KwKind::Attribute),
+            Token::Punct("(".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Ident("aligned".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Num(16, IntKind::Int),
+            Token::Punct(")".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let (_, rest) = parse_declaration(&tokens, &mut scope).unwrap();
+        let (_, rest) = parse_declaration(rest, &mut scope).unwrap();
+        assert!(rest.is_empty());
+        let var = Var {
+            name: "x".to_string(),
+            ty: Ty::Int,
+            offset: 16,
+        };
+        assert_eq!(Some(&Symbol::Var(var)), scope.find("x"));
+    }
+
+    #[test]
+    fn cannot_weaken_alignment_by_alignas() {
+        // _Alignas(2) int x;
+        let tokens = |align| {
+            vec![
+                Token::Kw(KwKind::Alignas),
+                Token::Punct("(".to_string()),
+                Token::Num(align, IntKind::Int),
+                Token::Punct(")".to_string()),
+                Token::Kw(KwKind::Int),
+                Token::Ident("x".to_string()),
+                Token::Punct(";".to_string()),
+            ]
+        };
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        assert_eq!(
+            Err("requested alignment is less than the alignment of int: x".to_string()),
+            parse_declaration(&tokens(2), &mut scope)
+        );
+        assert!(parse_declaration(&tokens(4), &mut scope).is_ok());
+    }
+
+    #[test]
+    fn cannot_declare_multiple_storage_classes() {
+        let tokens = vec![
+            Token::Kw(KwKind::Static),
+            Token::Kw(KwKind::Extern),
+            Token::Kw(KwKind::Int),
+        ];
+        assert!(parse_declspec(&tokens, &mut Scope::new()).is_err());
+    }
+
+    #[test]
+    fn parses_variable_declaration() {
+        // int x, y = 3;
+        let tokens = vec![
+            Token::Kw(KwKind::Int),
+            Token::Ident("x".to_string()),
+            Token::Punct(",".to_string()),
+            Token::Ident("y".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Num(3, IntKind::Int),
+            Token::Punct(";".to_string()),
+        ];
+        let y = Var {
+            name: "y".to_string(),
+            ty: Ty::Int,
+            offset: 8,
+        };
+        let expected = Stmt::CompStmt(vec![Stmt::ExprStmt(Expr::Assign(Box::new(Binary {
+            lhs: Expr::Var(y.clone()),
+            rhs: Expr::Num(3, Ty::Int),
+        })))]);
+        let mut scope = Scope::new();
+        let (stmt, rest) = parse_declaration(&tokens, &mut scope).unwrap();
+        assert_eq!(expected, stmt);
+        assert_eq!(Vec::<Token>::new(), rest);
+        assert_eq!(Some(&Symbol::Var(y)), scope.find("y"));
+    }
+
+    #[test]
+    fn parses_array_of_pointers_declarator() {
+        // *x[4]
+        let tokens = vec![
+            Token::Punct("*".to_string()),
+            Token::Ident("x".to_string()),
+            Token::Punct("[".to_string()),
+            Token::Num(4, IntKind::Int),
+            Token::Punct("]".to_string()),
+        ];
+        let expected = Ty::Array(Box::new(Ty::Ptr(Box::new(Ty::Int))), 4);
+        let (decl, rest) = parse_declarator(Ty::Int, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(Some("x".to_string()), decl.name);
+        assert_eq!(expected, decl.ty);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn parses_pointer_to_array_declarator() {
+        // (*x)[4]
+        let tokens = vec![
+            Token::Punct("(".to_string()),
+            Token::Punct("*".to_string()),
+            Token::Ident("x".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct("[".to_string()),
+            Token::Num(4, IntKind::Int),
+            Token::Punct("]".to_string()),
+        ];
+        let expected = Ty::Ptr(Box::new(Ty::Array(Box::new(Ty::Int), 4)));
+        let (decl, rest) = parse_declarator(Ty::Int, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(Some("x".to_string()), decl.name);
+        assert_eq!(expected, decl.ty);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn parses_function_pointer_declarator() {
+        // (*fp)(int, int *)
+        let tokens = vec![
+            Token::Punct("(".to_string()),
+            Token::Punct("*".to_string()),
+            Token::Ident("fp".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct(",".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct("*".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        let expected = Ty::Ptr(Box::new(Ty::Func(Box::new(FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Int, Ty::Ptr(Box::new(Ty::Int))]),
+            is_variadic: false,
+        }))));
+        let (decl, rest) = parse_declarator(Ty::Int, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(Some("fp".to_string()), decl.name);
+        assert_eq!(expected, decl.ty);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn parses_function_returning_function_pointer() {
+        // (*f(int))(int)
+        let tokens = vec![
+            Token::Punct("(".to_string()),
+            Token::Punct("*".to_string()),
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct(")".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct(")".to_string()),
+        ];
+        let fp = Ty::Ptr(Box::new(Ty::Func(Box::new(FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Int]),
+            is_variadic: false,
+        }))));
+        let expected = Ty::Func(Box::new(FuncTy {
+            ret: fp,
+            params: Some(vec![Ty::Int]),
+            is_variadic: false,
+        }));
+        let (decl, rest) = parse_declarator(Ty::Int, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(Some("f".to_string()), decl.name);
+        assert_eq!(expected, decl.ty);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn adjusts_array_and_function_parameters_to_pointers() {
+        // f(int a[3], int g())
+        let tokens = vec![
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("a".to_string()),
+            Token::Punct("[".to_string()),
+            Token::Num(3, IntKind::Int),
+            Token::Punct("]".to_string()),
+            Token::Punct(",".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("g".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        let g = Ty::Func(Box::new(FuncTy {
+            ret: Ty::Int,
+            params: None,
+            is_variadic: false,
+        }));
+        let expected = Ty::Func(Box::new(FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Ptr(Box::new(Ty::Int)), Ty::Ptr(Box::new(g))]),
+            is_variadic: false,
+        }));
+        let (decl, _) = parse_declarator(Ty::Int, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(expected, decl.ty);
+        assert_eq!(
+            vec![Some("a".to_string()), Some("g".to_string())],
+            decl.param_names
+        );
+    }
+
+    #[test]
+    fn parses_abstract_declarator_in_typename() {
+        // int (*)[4]
+        let tokens = vec![
+            Token::Kw(KwKind::Int),
+            Token::Punct("(".to_string()),
+            Token::Punct("*".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct("[".to_string()),
+            Token::Num(4, IntKind::Int),
+            Token::Punct("]".to_string()),
+        ];
+        let expected = Ty::Ptr(Box::new(Ty::Array(Box::new(Ty::Int), 4)));
+        let (ty, rest) = parse_typename(&tokens, &mut Scope::new()).unwrap();
+        assert_eq!(expected, ty);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn parses_typedef_name_in_parameter_list_as_type() {
+        // int (T) is a function type if T is a typedef name.
+        let tokens = vec![
+            Token::Kw(KwKind::Int),
+            Token::Punct("(".to_string()),
+            Token::Ident("T".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        let mut scope = Scope::new();
+        scope.declare("T", Symbol::Typedef(Ty::Int)).unwrap();
+        let expected = Ty::Func(Box::new(FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Int]),
+            is_variadic: false,
+        }));
+        let (ty, _) = parse_typename(&tokens, &mut scope).unwrap();
+        assert_eq!(expected, ty);
+    }
+
+    #[test]
+    fn cannot_declare_array_of_functions() {
+        // x[2]()
+        let tokens = vec![
+            Token::Ident("x".to_string()),
+            Token::Punct("[".to_string()),
+            Token::Num(2, IntKind::Int),
+            Token::Punct("]".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        assert!(parse_declarator(Ty::Int, &tokens, &mut Scope::new()).is_err());
+    }
+
+    #[test]
+    fn parses_void_parameter_list() {
+        // f(void)
+        let tokens = vec![
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Void),
+            Token::Punct(")".to_string()),
+        ];
+        let expected = Ty::Func(Box::new(FuncTy {
+            ret: Ty::Void,
+            params: Some(vec![]),
+            is_variadic: false,
+        }));
+        let (decl, rest) = parse_declarator(Ty::Void, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(expected, decl.ty);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn parses_variadic_parameter_list() {
+        // f(int, ...)
+        let tokens = vec![
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct(",".to_string()),
+            Token::Punct("...".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        let expected = Ty::Func(Box::new(FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Int]),
+            is_variadic: true,
+        }));
+        let (decl, rest) = parse_declarator(Ty::Int, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(expected, decl.ty);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn parses_variable_length_array_declaration() {
+        // int a[n];
+        let tokens = vec![
+            Token::Kw(KwKind::Int),
+            Token::Ident("a".to_string()),
+            Token::Punct("[".to_string()),
+            Token::Ident("n".to_string()),
+            Token::Punct("]".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let n = scope.declare_local("n", Ty::Int).unwrap();
+        let (stmt, _) = parse_declaration(&tokens, &mut scope).unwrap();
+
+        // The length is saved before the array is allocated.
+        let len = Var {
+            name: "(vla length)".to_string(),
+            ty: Ty::Int,
+            offset: 8,
+        };
+        let a = Var {
+            name: "a".to_string(),
+            ty: Ty::Vla(Box::new(Ty::Int), Box::new(Expr::Var(len.clone()))),
+            offset: 16,
+        };
+        let size = Expr::Mul(Box::new(Binary {
+            lhs: Expr::Cast(Box::new(Cast {
+                expr: Expr::Var(len.clone()),
+                ty: Ty::ULong,
+            })),
+            rhs: Expr::Num(4, Ty::ULong),
+        }));
+        let expected = Stmt::CompStmt(vec![
+            Stmt::ExprStmt(Expr::Assign(Box::new(Binary {
+                lhs: Expr::Var(len),
+                rhs: Expr::Var(n),
+            }))),
+            Stmt::ExprStmt(Expr::VlaAlloc(a, Box::new(size))),
+        ]);
+        assert_eq!(expected, stmt);
+        assert!(scope.has_vla());
+    }
+
+    #[test]
+    fn parameter_gives_length_of_following_one() {
+        // f(int n, int a[][n])
+        let tokens = vec![
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("n".to_string()),
+            Token::Punct(",".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("a".to_string()),
+            Token::Punct("[".to_string()),
+            Token::Punct("]".to_string()),
+            Token::Punct("[".to_string()),
+            Token::Ident("n".to_string()),
+            Token::Punct("]".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        // `n` is at the same offset as when the function is defined.
+        let n = Var {
+            name: "n".to_string(),
+            ty: Ty::Int,
+            offset: 4,
+        };
+        let vla = Ty::Vla(Box::new(Ty::Int), Box::new(Expr::Var(n)));
+        let expected = Ty::Func(Box::new(FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Int, Ty::Ptr(Box::new(vla))]),
+            is_variadic: false,
+        }));
+        let mut scope = Scope::new();
+        let (decl, rest) = parse_declarator(Ty::Int, &tokens, &mut scope).unwrap();
+        assert_eq!(expected, decl.ty);
+        assert_eq!(Vec::<Token>::new(), rest);
+        assert_eq!(None, scope.find("n"));
+    }
+
+    #[test]
+    fn cannot_declare_static_variable_length_array() {
+        // static int a[n];
+        let tokens = vec![
+            Token::Kw(KwKind::Static),
+            Token::Kw(KwKind::Int),
+            Token::Ident("a".to_string()),
+            Token::Punct("[".to_string()),
+            Token::Ident("n".to_string()),
+            Token::Punct("]".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        scope.declare_local("n", Ty::Int).unwrap();
+        assert!(parse_declaration(&tokens, &mut scope).is_err());
+    }
+
+    #[test]
+    fn declares_auto_and_register_variables() {
+        // auto int x; register int y;
+        let tokens = |kw| {
+            vec![
+                Token::Kw(kw),
+                Token::Kw(KwKind::Int),
+                Token::Ident("x".to_string()),
+                Token::Punct(";".to_string()),
+            ]
+        };
+        for kw in [KwKind::Auto, KwKind::Register] {
+            let mut scope = Scope::new();
+            scope.enter_func(Ty::Int);
+            let tokens = tokens(kw);
+            let (_, rest) = parse_declaration(&tokens, &mut scope).unwrap();
+            assert_eq!(Vec::<Token>::new(), rest);
+            assert!(matches!(scope.find("x"), Some(Symbol::Var(_))));
+        }
+    }
+
+    #[test]
+    fn cannot_declare_void_parameter_with_name() {
+        // f(void x)
+        let tokens = vec![
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Void),
+            Token::Ident("x".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        assert!(parse_declarator(Ty::Int, &tokens, &mut Scope::new()).is_err());
+    }
+
+    #[test]
+    fn cannot_declare_void_variable() {
+        // void x;
+        let tokens = vec![
+            Token::Kw(KwKind::Void),
+            Token::Ident("x".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        assert!(parse_declaration(&tokens, &mut scope).is_err());
+    }
+
+    #[test]
+    fn parses_qualified_pointers() {
+        // int const *const volatile p
+        let tokens = vec![
+            Token::Kw(KwKind::Int),
+            Token::Kw(KwKind::Const),
+            Token::Punct("*".to_string()),
+            Token::Kw(KwKind::Const),
+            Token::Kw(KwKind::Volatile),
+            Token::Ident("p".to_string()),
+        ];
+        let c = Quals {
+            is_const: true,
+            ..Quals::default()
+        };
+        let cv = Quals {
+            is_volatile: true,
+            ..c
+        };
+        let expected = Ty::Qual(
+            Box::new(Ty::Ptr(Box::new(Ty::Qual(Box::new(Ty::Int), c)))),
+            cv,
+        );
+        let mut scope = Scope::new();
+        let (spec, rest) = parse_declspec(&tokens, &mut scope).unwrap();
+        let (decl, rest) = parse_declarator(spec.ty, rest, &mut scope).unwrap();
+        assert_eq!(expected, decl.ty);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn parses_struct_specifier_with_bit_fields() {
+        // struct S { unsigned short a : 3, : 0; struct S *next; } ;
+        let tokens = vec![
+            Token::Kw(KwKind::Struct),
+            Token::Ident("S".to_string()),
+            Token::Punct("{".to_string()),
+            Token::Kw(KwKind::Unsigned),
+            Token::Kw(KwKind::Short),
+            Token::Ident("a".to_string()),
+            Token::Punct(":".to_string()),
+            Token::Num(3, IntKind::Int),
+            Token::Punct(",".to_string()),
+            Token::Punct(":".to_string()),
+            Token::Num(0, IntKind::Int),
+            Token::Punct(";".to_string()),
+            Token::Kw(KwKind::Struct),
+            Token::Ident("S".to_string()),
+            Token::Punct("*".to_string()),
+            Token::Ident("next".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Punct("}".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        let mut scope = Scope::new();
+        assert!(is_declspec(&tokens, &scope));
+        let (spec, rest) = parse_declspec(&tokens, &mut scope).unwrap();
+        assert_eq!(vec![Token::Punct(";".to_string())], rest);
+        let s = match &spec.ty {
+            Ty::Struct(s) => s.clone(),
+            ty => panic!("expected a structure type, but got {}", ty),
+        };
+        assert_eq!(Some(&spec.ty), scope.find_tag("S"));
+        assert_eq!((16, 8), (spec.ty.size(), spec.ty.align()));
+        let a = s.find_member("a").unwrap();
+        assert_eq!(Ty::UShort, a.ty);
+        assert_eq!(
+            Some(BitField {
+                bit_offset: 0,
+                width: 3
+            }),
+            a.bit_field
+        );
+        // The member points to the structure type itself.
+        let next = s.find_member("next").unwrap();
+        assert_eq!(
+            (8, Ty::Ptr(Box::new(spec.ty.clone()))),
+            (next.offset, next.ty)
+        );
+    }
+
+    #[test]
+    fn completes_declared_struct_tag() {
+        // struct S ;
+        let decl = vec![
+            Token::Kw(KwKind::Struct),
+            Token::Ident("S".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        let mut scope = Scope::new();
+        let (spec, _) = parse_declspec(&decl, &mut scope).unwrap();
+        assert!(spec.ty.is_incomplete());
+        assert_eq!(
+            Err("storage size of s isn't known".to_string()),
+            check_complete("s", &spec.ty)
+        );
+
+        // union S { int x; }
+        let def = vec![
+            Token::Kw(KwKind::Union),
+            Token::Ident("S".to_string()),
+            Token::Punct("{".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("x".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Punct("}".to_string()),
+        ];
+        assert_eq!(
+            Err("S defined as wrong kind of tag".to_string()),
+            parse_declspec(&def, &mut scope).map(|_| ())
+        );
+        // struct S { int x; }
+        let mut def = def;
+        def[0] = Token::Kw(KwKind::Struct);
+        parse_declspec(&def, &mut scope).unwrap();
+        assert!(!spec.ty.is_incomplete());
+        assert_eq!(4, spec.ty.size());
+        assert_eq!(
+            Err("redefinition of struct S".to_string()),
+            parse_declspec(&def, &mut scope).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn parses_packed_struct_with_flexible_array_member() {
+        // struct __attribute__((packed)) { char a; int b __attribute__((aligned(2))); double d[]; }
+        let attr = |name: &str, arg: Option<u64>| {
+            let mut tokens = vec![
+                Token::Kw(KwKind::Attribute),
+                Token::Punct("(".to_string()),
+                Token::Punct("(".to_string()),
+                Token::Ident(name.to_string()),
+            ];
+            if let Some(n) = arg {
+                tokens.push(Token::Punct("(".to_string()));
+                tokens.push(Token::Num(n, IntKind::Int));
+                tokens.push(Token::Punct(")".to_string()));
+            }
+            tokens.push(Token::Punct(")".to_string()));
+            tokens.push(Token::Punct(")".to_string()));
+            tokens
+        };
+        let mut tokens = vec![Token::Kw(KwKind::Struct)];
+        tokens.extend(attr("packed", None));
+        tokens.extend([
+            Token::Punct("{".to_string()),
+            Token::Kw(KwKind::Char),
+            Token::Ident("a".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("b".to_string()),
+        ]);
+        tokens.extend(attr("aligned", Some(2)));
+        tokens.extend([
+            Token::Punct(";".to_string()),
+            Token::Kw(KwKind::Double),
+            Token::Ident("d".to_string()),
+            Token::Punct("[".to_string()),
+            Token::Punct("]".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Punct("}".to_string()),
+        ]);
+        let (spec, rest) = parse_declspec(&tokens, &mut Scope::new()).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!((6, 2), (spec.ty.size(), spec.ty.align()));
+        let s = match &spec.ty {
+            Ty::Struct(s) => s.clone(),
+            ty => panic!("expected a structure type, but got {}", ty),
+        };
+        assert_eq!(2, s.find_member("b").unwrap().offset);
+        assert_eq!(6, s.find_member("d").unwrap().offset);
+
+        // packed is only given to a structure or a member.
+        let mut tokens = attr("packed", None);
+        tokens.push(Token::Kw(KwKind::Int));
+        assert!(parse_declspec(&tokens, &mut Scope::new()).is_err());
+    }
+
+    #[test]
+    fn checks_flexible_array_members() {
+        let decl = |name: &str, ty| MemberDecl {
+            name: Some(name.to_string()),
+            ty,
+            width: None,
+            align: 0,
+            is_packed: false,
+        };
+        let fam = || Ty::IncompleteArray(Box::new(Ty::Int));
+        let n = decl("n", Ty::Int);
+        assert_eq!(Ok(()), check_flexible_array(&[n, decl("a", fam())], false));
+        let n = decl("n", Ty::Int);
+        assert!(check_flexible_array(&[n, decl("a", fam())], true).is_err());
+        let n = decl("n", Ty::Int);
+        assert!(check_flexible_array(&[decl("a", fam()), n], false).is_err());
+        assert!(check_flexible_array(&[decl("a", fam())], false).is_err());
+    }
+
+    #[test]
+    fn checks_bit_fields() {
+        assert_eq!(Ok(32), check_bit_field("x", &Ty::UInt, 32, true));
+        assert!(check_bit_field("x", &Ty::Int, 33, true).is_err());
+        assert!(check_bit_field("x", &Ty::Bool, 2, true).is_err());
+        assert!(check_bit_field("x", &Ty::Double, 1, true).is_err());
+        assert!(check_bit_field("x", &Ty::Char, -1, true).is_err());
+        assert!(check_bit_field("x", &Ty::Char, 0, true).is_err());
+        assert_eq!(Ok(0), check_bit_field("x", &Ty::Char, 0, false));
+    }
+
+    #[test]
+    fn resolves_integer_type_specifiers() {
+        let resolve = |kws: &[KwKind]| {
+            let tokens: Vec<Token> = kws.iter().map(|kw| Token::Kw(*kw)).collect();
+            parse_declspec(&tokens, &mut Scope::new()).map(|(spec, _)| spec.ty)
+        };
+        assert_eq!(Ok(Ty::UChar), resolve(&[KwKind::Unsigned, KwKind::Char]));
+        assert_eq!(Ok(Ty::Char), resolve(&[KwKind::Signed, KwKind::Char]));
+        assert_eq!(Ok(Ty::Short), resolve(&[KwKind::Short, KwKind::Int]));
+        assert_eq!(Ok(Ty::UShort), resolve(&[KwKind::Short, KwKind::Unsigned]));
+        assert_eq!(Ok(Ty::UInt), resolve(&[KwKind::Unsigned]));
+        assert_eq!(Ok(Ty::Int), resolve(&[KwKind::Signed]));
+        assert_eq!(Ok(Ty::Long), resolve(&[KwKind::Long, KwKind::Int]));
+        assert_eq!(
+            Ok(Ty::ULong),
+            resolve(&[KwKind::Unsigned, KwKind::Long, KwKind::Long])
+        );
+        assert_eq!(Ok(Ty::Long), resolve(&[KwKind::Signed, KwKind::Long]));
+        assert!(resolve(&[KwKind::Long, KwKind::Long, KwKind::Long]).is_err());
+        assert!(resolve(&[KwKind::Short, KwKind::Long]).is_err());
+        assert!(resolve(&[KwKind::Unsigned, KwKind::Double]).is_err());
+        assert!(resolve(&[KwKind::Short, KwKind::Char]).is_err());
+    }
+
+    #[test]
+    fn parses_alignment_specifiers() {
+        // _Alignas(double) char __attribute__((aligned(4)))
+        let tokens = vec![
+            Token::Kw(KwKind::Alignas),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Double),
+            Token::Punct(")".to_string()),
+            Token::Kw(KwKind::Char),
+            Token::Kw(KwKind::Attribute),
+            Token::Punct("(".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Ident("aligned".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Num(4, IntKind::Int),
+            Token::Punct(")".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        let (spec, rest) = parse_declspec(&tokens, &mut Scope::new()).unwrap();
+        assert_eq!(Ty::Char, spec.ty);
+        assert_eq!(8, spec.align);
+        assert!(rest.is_empty());
+
+        // _Alignas(3) int
+        let tokens = vec![
+            Token::Kw(KwKind::Alignas),
+            Token::Punct("(".to_string()),
+            Token::Num(3, IntKind::Int),
+            Token::Punct(")".to_string()),
+            Token::Kw(KwKind::Int),
+        ];
+        assert!(parse_declspec(&tokens, &mut Scope::new()).is_err());
+    }
+
+    #[test]
+    fn parses_noreturn() {
+        // static _Noreturn void
+        let tokens = vec![
+            Token::Kw(KwKind::Static),
+            Token::Kw(KwKind::Noreturn),
+            Token::Kw(KwKind::Void),
+        ];
+        let (spec, _) = parse_declspec(&tokens, &mut Scope::new()).unwrap();
+        assert!(spec.is_static && spec.is_noreturn);
+
+        // _Noreturn int
+        let tokens = vec![Token::Kw(KwKind::Noreturn), Token::Kw(KwKind::Int)];
+        assert!(is_declspec(&tokens, &Scope::new()));
+        assert_eq!(
+            Err("_Noreturn in a type name".to_string()),
+            parse_typename(&tokens, &mut Scope::new())
+        );
+    }
+
+    #[test]
+    fn cannot_restrict_non_pointer() {
+        // restrict int
+        let tokens = vec![Token::Kw(KwKind::Restrict), Token::Kw(KwKind::Int)];
+        assert!(parse_declspec(&tokens, &mut Scope::new()).is_err());
+
+        // int *restrict
+        let tokens = vec![
+            Token::Kw(KwKind::Int),
+            Token::Punct("*".to_string()),
+            Token::Kw(KwKind::Restrict),
+        ];
+        assert!(parse_typename(&tokens, &mut Scope::new()).is_ok());
+    }
+
+    #[test]
+    fn parses_long_double() {
+        // double long
+        let tokens = vec![Token::Kw(KwKind::Double), Token::Kw(KwKind::Long)];
+        let (spec, _) = parse_declspec(&tokens, &mut Scope::new()).unwrap();
+        assert_eq!(Ty::LongDouble, spec.ty);
+
+        // long long double, unsigned long double
+        let tokens = vec![
+            Token::Kw(KwKind::Long),
+            Token::Kw(KwKind::Long),
+            Token::Kw(KwKind::Double),
+        ];
+        assert!(parse_declspec(&tokens, &mut Scope::new()).is_err());
+        let tokens = vec![
+            Token::Kw(KwKind::Unsigned),
+            Token::Kw(KwKind::Long),
+            Token::Kw(KwKind::Double),
+        ];
+        assert!(parse_declspec(&tokens, &mut Scope::new()).is_err());
+    }
+}
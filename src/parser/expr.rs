@@ -1,40 +1,472 @@
-use super::consume_punct;
-use crate::lexer::Token;
+use super::{
+    consume_punct,
+    decl::{is_declspec, parse_typename},
+    func::check_by_value,
+    init::{eval_init, init_local, parse_initializer},
+    scope::{Global, Scope, Symbol},
+};
+use crate::f80::F80;
+use crate::lexer::{FloatKind, IntKind, KwKind, Token};
+use crate::ty::{FuncTy, Member, Ty};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
-    Num(u64),
-    Add(Box<Binary>),  // +
-    Sub(Box<Binary>),  // -
-    Mul(Box<Binary>),  // *
-    Div(Box<Binary>),  // *
-    FnName(String),    // Function identifier
-    FnCall(Box<Expr>), // function call
+    Num(i64, Ty),                  // integer constant
+    FNum(F80, Ty),                 // floating constant
+    Add(Box<Binary>),              // +
+    Sub(Box<Binary>),              // -
+    Mul(Box<Binary>),              // *
+    Div(Box<Binary>),              // *
+    Assign(Box<Binary>),           // =
+    Addr(Box<Expr>),               // unary &
+    Deref(Box<Expr>),              // unary *
+    Cast(Box<Cast>),               // (type) expr
+    Var(Var),                      // local variable
+    GVar(GVar),                    // variable with static storage duration
+    FnName(String, Box<FuncTy>),   // function designator
+    FnCall(Box<Call>),             // function call
+    CompoundLit(Box<CompoundLit>), // (type) { init-list } in a function
+    VaStart(Box<Expr>, VaArgs),    // va_start(ap, last)
+    VaArg(Box<Expr>, Ty),          // va_arg(ap, type)
+    VaCopy(Box<Binary>),           // va_copy(dst, src)
+    VlaAlloc(Var, Box<Expr>),      // allocation of a variable length array of the size
+    Member(Box<Expr>, Member),     // member of a structure or union
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Binary {
     pub lhs: Expr,
     pub rhs: Expr,
 }
 
-// <expr> ::= <add>
-pub(super) fn parse_expr(tokens: &[Token]) -> Result<(Expr, &[Token]), String> {
-    parse_add(tokens)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub func: Expr, // function designator or pointer to function
+    pub args: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cast {
+    pub expr: Expr,
+    pub ty: Ty, // type converted to
+}
+
+// Compound literal with automatic storage duration, which is an unnamed
+// local variable initialized each time the expression is evaluated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompoundLit {
+    pub var: Var,
+    pub init: Vec<Expr>, // assignments to initialize the variable
+}
+
+// Where the variable arguments of a variadic function are, which va_start
+// initializes a va_list with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VaArgs {
+    pub area: usize, // offset from rbp of the register save area
+    // offsets within the register save area of the first variable arguments
+    // passed in general-purpose registers and in vector registers
+    pub gp_offset: usize,
+    pub fp_offset: usize,
+    // offset from rbp of the slot holding the address of the first one passed
+    // in memory
+    pub overflow: usize,
+}
+
+// Local variable
+#[derive(Debug, Clone, PartialEq)]
+pub struct Var {
+    pub name: String,
+    pub ty: Ty,
+    pub offset: usize, // offset from rbp
+}
+
+// Variable with static storage duration, which is addressed by its symbol.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GVar {
+    pub name: String, // symbol name
+    pub ty: Ty,
+}
+
+impl Expr {
+    // Returns the type of the value of the expression, which has no
+    // qualifiers.
+    pub fn ty(&self) -> Ty {
+        match self {
+            Expr::Num(_, ty) | Expr::FNum(_, ty) => ty.clone(),
+            // The operands have been converted to their common type, and an
+            // integer operation results in it if it is of int or greater rank,
+            // or in int otherwise.
+            Expr::Add(bin) | Expr::Sub(bin) | Expr::Mul(bin) | Expr::Div(bin) => {
+                match bin.lhs.ty() {
+                    ty if ty.is_flonum() => ty,
+                    ty @ (Ty::UInt | Ty::Long | Ty::ULong) => ty,
+                    _ => Ty::Int,
+                }
+            }
+            Expr::Assign(bin) => bin.lhs.ty(),
+            Expr::Addr(expr) => Ty::Ptr(Box::new(expr.qual_ty())),
+            Expr::Cast(cast) => cast.ty.clone(),
+            Expr::Var(_)
+            | Expr::GVar(_)
+            | Expr::Deref(_)
+            | Expr::CompoundLit(_)
+            | Expr::Member(..) => self.qual_ty().unqual().clone(),
+            Expr::FnName(_, fty) => Ty::Func(fty.clone()),
+            Expr::FnCall(call) => call.func_ty().ret.clone(),
+            Expr::VaStart(..) | Expr::VaCopy(_) | Expr::VlaAlloc(..) => Ty::Void,
+            Expr::VaArg(_, ty) => ty.clone(),
+        }
+    }
+
+    // Returns the type of the expression with qualifiers, which only an lvalue
+    // has as the type of the object it designates.
+    fn qual_ty(&self) -> Ty {
+        match self {
+            Expr::Var(var) => var.ty.clone(),
+            Expr::GVar(var) => var.ty.clone(),
+            Expr::CompoundLit(lit) => lit.var.ty.clone(),
+            // Dereferencing a function designator results in itself.
+            Expr::Deref(expr) => match expr.ty() {
+                ty @ Ty::Func(_) => ty,
+                ty => ty.pointee().expect("dereference of a non-pointer").clone(),
+            },
+            // A member has the qualifiers of the structure in addition to its
+            // own.
+            Expr::Member(expr, member) => member.ty.clone().qualify(expr.qual_ty().quals()),
+            _ => self.ty(),
+        }
+    }
+
+    // Returns true if the expression designates an object, which can be
+    // assigned to or taken the address of.
+    fn is_lvalue(&self) -> bool {
+        match self {
+            Expr::Var(_) | Expr::GVar(_) | Expr::Deref(_) | Expr::CompoundLit(_) => true,
+            // A member of a structure which is not an lvalue, e.g. the result
+            // of an assignment, is not either.
+            Expr::Member(expr, _) => expr.is_lvalue(),
+            _ => false,
+        }
+    }
+
+    // Returns true if the expression designates a bit-field.
+    pub fn is_bit_field(&self) -> bool {
+        matches!(self, Expr::Member(_, member) if member.bit_field.is_some())
+    }
+}
+
+impl Call {
+    // Returns the type of the function called.
+    pub fn func_ty(&self) -> Box<FuncTy> {
+        match self.func.ty() {
+            Ty::Func(fty) => fty,
+            Ty::Ptr(ty) => match *ty {
+                Ty::Func(fty) => fty,
+                _ => unreachable!("call of a non-function"),
+            },
+            _ => unreachable!("call of a non-function"),
+        }
+    }
+}
+
+// <expr> ::= <assign>
+pub(super) fn parse_expr<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Expr, &'a [Token]), String> {
+    parse_assign(tokens, scope)
+}
+
+// <assign> ::= <add> ("=" <assign>)?
+pub(super) fn parse_assign<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Expr, &'a [Token]), String> {
+    let (lhs, rest) = parse_add(tokens, scope)?;
+
+    match consume_punct(rest, "=") {
+        Ok(r) => {
+            if !lhs.is_lvalue() {
+                return Err("expected an lvalue on the left of assignment".to_string());
+            }
+            if lhs.ty().is_array() {
+                return Err("cannot assign to an array".to_string());
+            }
+            if lhs.qual_ty().quals().is_const {
+                return Err("cannot assign to a const-qualified lvalue".to_string());
+            }
+            if lhs.ty().is_incomplete() {
+                return Err(format!("cannot assign to an incomplete type: {}", lhs.ty()));
+            }
+            if matches!(lhs.ty(), Ty::Struct(s) if s.has_const_member()) {
+                return Err("cannot assign to a struct with a const-qualified member".to_string());
+            }
+            let (rhs, rest) = parse_assign(r, scope)?;
+            check_value(&rhs)?;
+            let rhs = convert(rhs, &lhs.ty())?;
+            Ok((Expr::Assign(Box::new(Binary { lhs, rhs })), rest))
+        }
+        Err(_) => Ok((lhs, rest)),
+    }
+}
+
+// Returns an error if the expression has no value to be used, i.e. it has
+// type void.
+pub(super) fn check_value(expr: &Expr) -> Result<(), String> {
+    match expr.ty() {
+        Ty::Void => Err("void value not ignored as it ought to be".to_string()),
+        _ => Ok(()),
+    }
+}
+
+// Converts the value of the expression to the type as if by assignment.
+//
+// Only conversions to _Bool, which turns any nonzero value into 1, from or to
+// a floating type, and between integer types need code. An integer is
+// converted unless the type converted to can represent all the values of the
+// type, since a value is held in 64 bits as its type extends it.
+//
+// The value must be assignable to the type as an argument must be to its
+// parameter, and a pointer cannot be converted to one which discards
+// qualifiers of the type pointed to, e.g. `const int *` to `int *`.
+pub(super) fn convert(expr: Expr, ty: &Ty) -> Result<Expr, String> {
+    let ty = ty.unqual();
+    let from = expr.ty().decay();
+    if !is_assignable(ty, &expr) {
+        return Err(format!("cannot convert {} to {}", from, ty));
+    }
+    if let (Ty::Ptr(to), Ty::Ptr(from)) = (ty, &from) {
+        if !to.quals().contains(from.quals()) {
+            return Err("conversion discards qualifiers of pointer target type".to_string());
+        }
+    }
+    if *ty == Ty::Bool
+        || ty.is_flonum()
+        || from.is_flonum()
+        || (ty.is_integer() && from.is_integer() && !represents(ty, &from))
+    {
+        Ok(cast(expr, ty))
+    } else {
+        Ok(expr)
+    }
+}
+
+// Returns true if the integer type `ty` can represent all the values of the
+// integer type `from`.
+fn represents(ty: &Ty, from: &Ty) -> bool {
+    let range = |ty: &Ty| match ty.unqual() {
+        Ty::Bool => (0, 1),
+        Ty::Char => (i8::MIN as i128, i8::MAX as i128),
+        Ty::UChar => (0, u8::MAX as i128),
+        Ty::Short => (i16::MIN as i128, i16::MAX as i128),
+        Ty::UShort => (0, u16::MAX as i128),
+        Ty::Int | Ty::Enum => (i32::MIN as i128, i32::MAX as i128),
+        Ty::UInt => (0, u32::MAX as i128),
+        Ty::Long => (i64::MIN as i128, i64::MAX as i128),
+        Ty::ULong => (0, u64::MAX as i128),
+        ty => unreachable!("not an integer type: {}", ty),
+    };
+    let ((min, max), (from_min, from_max)) = (range(ty), range(from));
+    min <= from_min && from_max <= max
+}
+
+// Casts the expression to the type, unless it already has the type.
+fn cast(expr: Expr, ty: &Ty) -> Expr {
+    if expr.ty() == *ty {
+        return expr;
+    }
+    Expr::Cast(Box::new(Cast {
+        expr,
+        ty: ty.clone(),
+    }))
+}
+
+// <const-expr> ::= <add>
+//
+// Parses an integer constant expression, and evaluates it at compile time.
+pub(super) fn parse_const_expr<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(i64, &'a [Token]), String> {
+    let (expr, rest) = parse_add(tokens, scope)?;
+    Ok((eval(&expr)?, rest))
+}
+
+// Evaluates an integer constant expression. An unsigned int result wraps
+// around as it does at runtime.
+pub(super) fn eval(expr: &Expr) -> Result<i64, String> {
+    let eval_bin =
+        |bin: &Binary| -> Result<(i64, i64), String> { Ok((eval(&bin.lhs)?, eval(&bin.rhs)?)) };
+
+    let val = match expr {
+        Expr::Num(n, _) => *n,
+        Expr::Add(bin) => {
+            let (l, r) = eval_bin(bin)?;
+            l.wrapping_add(r)
+        }
+        Expr::Sub(bin) => {
+            let (l, r) = eval_bin(bin)?;
+            l.wrapping_sub(r)
+        }
+        Expr::Mul(bin) => {
+            let (l, r) = eval_bin(bin)?;
+            l.wrapping_mul(r)
+        }
+        Expr::Div(bin) => {
+            let (l, r) = eval_bin(bin)?;
+            if r == 0 {
+                return Err("division by zero in constant expression".to_string());
+            }
+            if expr.ty() == Ty::ULong {
+                (l as u64 / r as u64) as i64
+            } else {
+                l.wrapping_div(r)
+            }
+        }
+        // A floating constant may only appear as the operand of a cast to an
+        // integer type.
+        Expr::Cast(cast) if cast.ty.is_integer() => {
+            let val = if cast.expr.ty().is_flonum() {
+                let val = eval_float(&cast.expr)?;
+                match cast.ty {
+                    Ty::Bool => return Ok((val != 0.0) as i64),
+                    Ty::ULong => val as u64 as i64,
+                    _ => val as i64,
+                }
+            } else {
+                eval(&cast.expr)?
+            };
+            match cast.ty {
+                Ty::Bool => (val != 0) as i64,
+                Ty::Char => val as i8 as i64,
+                Ty::UChar => val as u8 as i64,
+                Ty::Short => val as i16 as i64,
+                Ty::UShort => val as u16 as i64,
+                Ty::UInt => val as u32 as i64,
+                // The 64 bits of an unsigned long are held as they are.
+                Ty::Long | Ty::ULong => val,
+                _ => val as i32 as i64,
+            }
+        }
+        Expr::FNum(..) => return Err("not an integer constant expression".to_string()),
+        Expr::Assign(_)
+        | Expr::Addr(_)
+        | Expr::Deref(_)
+        | Expr::Cast(_)
+        | Expr::Var(_)
+        | Expr::GVar(_)
+        | Expr::FnName(..)
+        | Expr::FnCall(_)
+        | Expr::CompoundLit(_)
+        | Expr::VaStart(..)
+        | Expr::VaArg(..)
+        | Expr::VaCopy(_)
+        | Expr::VlaAlloc(..)
+        | Expr::Member(..) => return Err("not a constant expression".to_string()),
+    };
+    match expr.ty() {
+        Ty::UInt => Ok(val as u32 as i64),
+        _ => Ok(val),
+    }
+}
+
+// Evaluates an address constant, which is the address of an object with
+// static storage duration or a function, e.g. `&x`, `"abc"` or `f`, or an
+// integer constant cast to a pointer. Returns the symbol of the address, or
+// None for an integer, and the offset from it.
+pub(super) fn eval_addr(expr: &Expr) -> Result<(Option<String>, i64), String> {
+    match expr {
+        // An array or a function is converted to its address.
+        Expr::GVar(GVar { name, ty }) if ty.is_array() => Ok((Some(name.clone()), 0)),
+        Expr::FnName(name, _) => Ok((Some(name.clone()), 0)),
+        Expr::Addr(expr) => match &**expr {
+            Expr::GVar(GVar { name, .. }) | Expr::FnName(name, _) => Ok((Some(name.clone()), 0)),
+            Expr::Deref(expr) => eval_addr(expr),
+            Expr::Member(expr, member) => {
+                let (name, offset) = eval_addr(&Expr::Addr(expr.clone()))?;
+                Ok((name, offset + member.offset as i64))
+            }
+            _ => Err("not a constant expression".to_string()),
+        },
+        // An array member is converted to its address.
+        Expr::Member(_, member) if member.ty.is_array() => {
+            eval_addr(&Expr::Addr(Box::new(expr.clone())))
+        }
+        Expr::Cast(cast) if matches!(cast.ty, Ty::Ptr(_)) => eval_addr(&cast.expr),
+        _ => Ok((None, eval(expr)?)),
+    }
+}
+
+// Evaluates an arithmetic constant expression as a floating value, which is
+// rounded to float if the expression has type float. A long double expression
+// is evaluated in double precision.
+pub(super) fn eval_float(expr: &Expr) -> Result<f64, String> {
+    let eval_bin = |bin: &Binary| -> Result<(f64, f64), String> {
+        Ok((eval_float(&bin.lhs)?, eval_float(&bin.rhs)?))
+    };
+
+    let val = match expr {
+        _ if expr.ty() == Ty::ULong => eval(expr)? as u64 as f64,
+        _ if expr.ty().is_integer() => eval(expr)? as f64,
+        Expr::FNum(val, _) => val.to_f64(),
+        Expr::Add(bin) => {
+            let (l, r) = eval_bin(bin)?;
+            l + r
+        }
+        Expr::Sub(bin) => {
+            let (l, r) = eval_bin(bin)?;
+            l - r
+        }
+        Expr::Mul(bin) => {
+            let (l, r) = eval_bin(bin)?;
+            l * r
+        }
+        Expr::Div(bin) => {
+            let (l, r) = eval_bin(bin)?;
+            l / r
+        }
+        Expr::Cast(cast) if expr.ty().is_flonum() => eval_float(&cast.expr)?,
+        _ => return Err("not a constant expression".to_string()),
+    };
+    if expr.ty() == Ty::Float {
+        Ok(val as f32 as f64)
+    } else {
+        Ok(val)
+    }
+}
+
+// Evaluates an arithmetic constant expression of type long double. A constant
+// and a value converted to long double are exact, while arithmetic is
+// evaluated in double precision.
+pub(super) fn eval_long_double(expr: &Expr) -> Result<F80, String> {
+    match expr {
+        Expr::FNum(val, _) => Ok(*val),
+        Expr::Cast(cast) if cast.expr.ty() == Ty::ULong => {
+            Ok(F80::from_u64(eval(&cast.expr)? as u64))
+        }
+        Expr::Cast(cast) if cast.expr.ty().is_integer() => Ok(F80::from_i64(eval(&cast.expr)?)),
+        Expr::Cast(cast) if cast.expr.ty() != Ty::LongDouble => {
+            Ok(F80::from_f64(eval_float(&cast.expr)?))
+        }
+        _ => Ok(F80::from_f64(eval_float(expr)?)),
+    }
 }
 
 // <add> ::= <mul> (("+" | "-") <mul>)*
-fn parse_add(tokens: &[Token]) -> Result<(Expr, &[Token]), String> {
-    let (mut node, mut rest) = parse_mul(tokens)?;
+fn parse_add<'a>(tokens: &'a [Token], scope: &mut Scope) -> Result<(Expr, &'a [Token]), String> {
+    let (mut node, mut rest) = parse_mul(tokens, scope)?;
 
-    while let Some(Token::Punct(punct)) = rest.get(0) {
+    while let Some(Token::Punct(punct)) = rest.first() {
         if punct != "+" && punct != "-" {
             break;
         }
 
         let lhs = node;
         let rhs;
-        (rhs, rest) = parse_mul(&rest[1..])?;
+        (rhs, rest) = parse_mul(&rest[1..], scope)?;
+        check_arith_operands(&lhs, &rhs)?;
+        let (lhs, rhs) = arith_conv(lhs, rhs);
 
         node = if punct == "+" {
             Expr::Add(Box::new(Binary { lhs, rhs }))
@@ -48,18 +480,20 @@ fn parse_add(tokens: &[Token]) -> Result<(Expr, &[Token]), String> {
     Ok((node, rest))
 }
 
-// <mul> ::= <postfix> ("*" <postfix>)*
-fn parse_mul(tokens: &[Token]) -> Result<(Expr, &[Token]), String> {
-    let (mut node, mut rest) = parse_postfix(tokens)?;
+// <mul> ::= <cast> (("*" | "/") <cast>)*
+fn parse_mul<'a>(tokens: &'a [Token], scope: &mut Scope) -> Result<(Expr, &'a [Token]), String> {
+    let (mut node, mut rest) = parse_cast(tokens, scope)?;
 
-    while let Some(Token::Punct(punct)) = rest.get(0) {
+    while let Some(Token::Punct(punct)) = rest.first() {
         if punct != "*" && punct != "/" {
             break;
         }
 
         let lhs = node;
         let rhs;
-        (rhs, rest) = parse_postfix(&rest[1..])?;
+        (rhs, rest) = parse_cast(&rest[1..], scope)?;
+        check_arith_operands(&lhs, &rhs)?;
+        let (lhs, rhs) = arith_conv(lhs, rhs);
 
         node = if punct == "*" {
             Expr::Mul(Box::new(Binary { lhs, rhs }))
@@ -73,67 +507,621 @@ fn parse_mul(tokens: &[Token]) -> Result<(Expr, &[Token]), String> {
     Ok((node, rest))
 }
 
-// <postfix> ::= <primary> ("(" ")")?
-fn parse_postfix(tokens: &[Token]) -> Result<(Expr, &[Token]), String> {
-    let (mut node, mut rest) = parse_primary(tokens)?;
+// Pointer arithmetic is not supported, so both operands must be arithmetic.
+fn check_arith_operands(lhs: &Expr, rhs: &Expr) -> Result<(), String> {
+    if lhs.ty().is_arith() && rhs.ty().is_arith() {
+        Ok(())
+    } else {
+        Err("invalid operands to arithmetic operator".to_string())
+    }
+}
 
-    match consume_punct(rest, "(") {
-        Ok(r) => {
-            node = Expr::FnCall(Box::new(node));
-            rest = consume_punct(r, ")")?;
+// Converts the operands of an arithmetic operator to their common type by the
+// usual arithmetic conversions. If either operand is floating, both are
+// converted to the floating type of the greater rank. Otherwise, both are
+// converted to unsigned long, long or unsigned int, whichever either is first,
+// as long can represent all the values of unsigned int. Other integers are
+// promoted to int, which they are operated on as they are.
+fn arith_conv(lhs: Expr, rhs: Expr) -> (Expr, Expr) {
+    let ty = match (lhs.ty(), rhs.ty()) {
+        (Ty::LongDouble, _) | (_, Ty::LongDouble) => Ty::LongDouble,
+        (Ty::Double, _) | (_, Ty::Double) => Ty::Double,
+        (Ty::Float, _) | (_, Ty::Float) => Ty::Float,
+        (Ty::ULong, _) | (_, Ty::ULong) => Ty::ULong,
+        (Ty::Long, _) | (_, Ty::Long) => Ty::Long,
+        (Ty::UInt, _) | (_, Ty::UInt) => Ty::UInt,
+        _ => return (lhs, rhs),
+    };
+    (cast(lhs, &ty), cast(rhs, &ty))
+}
+
+// <cast> ::= "(" <type-name> ")" <cast>
+//          | <unary>
+//
+// A compound literal also begins with a parenthesized type name, so it is
+// told from a cast by the following "{".
+fn parse_cast<'a>(tokens: &'a [Token], scope: &mut Scope) -> Result<(Expr, &'a [Token]), String> {
+    let rest = match consume_punct(tokens, "(") {
+        Ok(r) if is_declspec(r, scope) => r,
+        _ => return parse_unary(tokens, scope),
+    };
+
+    let (ty, rest) = parse_typename(rest, scope)?;
+    let rest = consume_punct(rest, ")")?;
+    if consume_punct(rest, "{").is_ok() {
+        let (lit, rest) = parse_compound_literal(ty, rest, scope)?;
+        return parse_postfix_ops(lit, rest, scope);
+    }
+    // The result of a cast is not an lvalue, so it has no qualifiers.
+    let ty = ty.unqual().clone();
+    let (expr, rest) = parse_cast(rest, scope)?;
+    // Any value can be cast to void, which discards it.
+    if ty == Ty::Void {
+        return Ok((Expr::Cast(Box::new(Cast { expr, ty })), rest));
+    }
+    if !ty.is_arith() && !matches!(ty, Ty::Ptr(_)) {
+        return Err(format!("cannot cast to a non-scalar type: {}", ty));
+    }
+    if !expr.ty().is_arith() && !matches!(expr.ty(), Ty::Ptr(_)) && !expr.ty().is_array() {
+        return Err(format!("cannot cast a non-scalar type: {}", expr.ty()));
+    }
+    if (ty.is_flonum() && !expr.ty().is_arith()) || (expr.ty().is_flonum() && !ty.is_arith()) {
+        return Err("cannot cast between a pointer and a floating type".to_string());
+    }
+    Ok((Expr::Cast(Box::new(Cast { expr, ty })), rest))
+}
+
+// <unary> ::= "&" <cast>
+//           | "*" <cast>
+//           | "sizeof" "(" <type-name> ")"
+//           | "sizeof" <unary>
+//           | "_Alignof" "(" <type-name> ")"
+//           | <postfix>
+fn parse_unary<'a>(tokens: &'a [Token], scope: &mut Scope) -> Result<(Expr, &'a [Token]), String> {
+    match tokens.first() {
+        Some(Token::Punct(punct)) if punct == "&" => {
+            let (expr, rest) = parse_cast(&tokens[1..], scope)?;
+            if !expr.is_lvalue() && !matches!(expr.ty(), Ty::Func(_)) {
+                return Err("cannot take the address of an rvalue".to_string());
+            }
+            if let Expr::Member(_, member) = &expr {
+                if member.bit_field.is_some() {
+                    let name = member.name.as_deref().unwrap_or_default();
+                    return Err(format!("cannot take the address of bit-field {}", name));
+                }
+            }
+            Ok((Expr::Addr(Box::new(expr)), rest))
+        }
+        Some(Token::Punct(punct)) if punct == "*" => {
+            let (expr, rest) = parse_cast(&tokens[1..], scope)?;
+            if expr.ty().pointee().is_none() && !matches!(expr.ty(), Ty::Func(_)) {
+                return Err("cannot dereference a non-pointer".to_string());
+            }
+            if expr.ty().pointee().map(Ty::unqual) == Some(&Ty::Void) {
+                return Err("cannot dereference a void pointer".to_string());
+            }
+            Ok((Expr::Deref(Box::new(expr)), rest))
+        }
+        Some(Token::Kw(KwKind::Sizeof)) => {
+            let (ty, rest) = match consume_punct(&tokens[1..], "(") {
+                Ok(r) if is_declspec(r, scope) => {
+                    let (ty, r) = parse_typename(r, scope)?;
+                    let r = consume_punct(r, ")")?;
+                    // The type of a compound literal may be completed by its
+                    // initializer, e.g. `sizeof (int[]){1, 2}`.
+                    if consume_punct(r, "{").is_ok() {
+                        let (lit, r) = parse_compound_literal(ty, r, scope)?;
+                        let (expr, r) = parse_postfix_ops(lit, r, scope)?;
+                        (expr.ty(), r)
+                    } else {
+                        (ty, r)
+                    }
+                }
+                _ => {
+                    let (expr, r) = parse_unary(&tokens[1..], scope)?;
+                    if expr.is_bit_field() {
+                        return Err("sizeof of a bit-field".to_string());
+                    }
+                    (expr.ty(), r)
+                }
+            };
+            if let Ty::Func(_) = ty {
+                return Err("sizeof of a function type".to_string());
+            }
+            if ty.is_incomplete() {
+                return Err("sizeof of an incomplete type".to_string());
+            }
+            Ok((sizeof_expr(&ty), rest))
         }
-        Err(_) => (),
+        Some(Token::Kw(KwKind::Alignof)) => {
+            let rest = consume_punct(&tokens[1..], "(")?;
+            let (ty, rest) = parse_typename(rest, scope)?;
+            if let Ty::Func(_) = ty {
+                return Err("_Alignof of a function type".to_string());
+            }
+            // An array of unknown length is aligned as its elements are.
+            if ty.is_incomplete() && !ty.is_array() {
+                return Err("_Alignof of an incomplete type".to_string());
+            }
+            Ok((
+                Expr::Num(ty.align() as i64, Ty::ULong),
+                consume_punct(rest, ")")?,
+            ))
+        }
+        _ => parse_postfix(tokens, scope),
+    }
+}
+
+// Returns the size of the type as a size_t, i.e. an unsigned long, which is
+// evaluated at runtime if the type is variably modified, e.g. `n * 4` for
+// `int[n]`.
+pub(super) fn sizeof_expr(ty: &Ty) -> Expr {
+    match ty {
+        Ty::Vla(elem, len) => Expr::Mul(Box::new(Binary {
+            lhs: cast((**len).clone(), &Ty::ULong),
+            rhs: sizeof_expr(elem),
+        })),
+        Ty::Array(elem, len) if elem.is_variably_modified() => Expr::Mul(Box::new(Binary {
+            lhs: Expr::Num(*len as i64, Ty::ULong),
+            rhs: sizeof_expr(elem),
+        })),
+        Ty::Qual(ty, _) => sizeof_expr(ty),
+        _ => Expr::Num(ty.size() as i64, Ty::ULong),
+    }
+}
+
+// <postfix> ::= (<primary> | <compound-literal>) <postfix-op>*
+// <postfix-op> ::= "(" <args>? ")"
+//                | "." ident
+//                | "->" ident
+// <args>    ::= <assign> ("," <assign>)*
+fn parse_postfix<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Expr, &'a [Token]), String> {
+    let (node, rest) = parse_primary(tokens, scope)?;
+    parse_postfix_ops(node, rest, scope)
+}
+
+// Parses postfix operators applied to `node`.
+fn parse_postfix_ops<'a>(
+    mut node: Expr,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Expr, &'a [Token]), String> {
+    let mut rest = tokens;
+    loop {
+        if let Ok(r) = consume_punct(rest, ".") {
+            (node, rest) = parse_member(node, r)?;
+            continue;
+        }
+        if let Ok(r) = consume_punct(rest, "->") {
+            match node.ty().decay().pointee() {
+                Some(ty) if matches!(ty.unqual(), Ty::Struct(_)) => (),
+                _ => return Err("expected a pointer to a structure or union before ->".to_string()),
+            }
+            (node, rest) = parse_member(Expr::Deref(Box::new(node)), r)?;
+            continue;
+        }
+        let r = match consume_punct(rest, "(") {
+            Ok(r) => r,
+            Err(_) => break,
+        };
+        let fty = match node.ty() {
+            Ty::Func(fty) => fty,
+            Ty::Ptr(ty) => match *ty {
+                Ty::Func(fty) => fty,
+                _ => return Err("called object is not a function".to_string()),
+            },
+            _ => return Err("called object is not a function".to_string()),
+        };
+
+        let mut args: Vec<Expr> = Vec::new();
+        rest = r;
+        loop {
+            if let Ok(r) = consume_punct(rest, ")") {
+                rest = r;
+                break;
+            }
+            if !args.is_empty() {
+                rest = consume_punct(rest, ",")?;
+            }
+            let arg;
+            (arg, rest) = parse_assign(rest, scope)?;
+            check_value(&arg)?;
+            args.push(arg);
+        }
+        check_args(&fty, &args)?;
+        args = args
+            .into_iter()
+            .enumerate()
+            .map(
+                |(i, arg)| match fty.params.as_ref().and_then(|p| p.get(i)) {
+                    Some(param) => convert(arg, param),
+                    // An argument without a parameter type, i.e. one to a function
+                    // without a prototype or a variable argument, is promoted from
+                    // float to double.
+                    None => match arg.ty() {
+                        Ty::Float => Ok(cast(arg, &Ty::Double)),
+                        _ => Ok(arg),
+                    },
+                },
+            )
+            .collect::<Result<_, _>>()?;
+        check_by_value(&fty.ret, &args.iter().map(Expr::ty).collect::<Vec<_>>())?;
+
+        node = Expr::FnCall(Box::new(Call { func: node, args }));
     }
 
     Ok((node, rest))
 }
 
+// Parses the name of a member of `node` following "." or "->", which must be
+// a complete structure or union.
+fn parse_member(node: Expr, tokens: &[Token]) -> Result<(Expr, &[Token]), String> {
+    let s = match node.ty() {
+        Ty::Struct(s) if s.is_complete() => s,
+        Ty::Struct(s) => return Err(format!("member access of an incomplete type: {:?}", s)),
+        _ => return Err("member access of a non-structure".to_string()),
+    };
+    let name = match tokens.first() {
+        Some(Token::Ident(name)) => name,
+        _ => return Err("expected a member name".to_string()),
+    };
+    let member = s
+        .find_member(name)
+        .ok_or_else(|| format!("{:?} has no member named {}", s, name))?;
+    Ok((Expr::Member(Box::new(node), member), &tokens[1..]))
+}
+
+// <compound-literal> ::= "(" <type-name> ")" "{" <init-list> "}"
+//
+// Parses a compound literal from the "{", given the type name parsed by the
+// caller.
+//
+// It is an unnamed object, which has static storage duration at file scope,
+// or automatic storage duration in a function.
+fn parse_compound_literal<'a>(
+    ty: Ty,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Expr, &'a [Token]), String> {
+    if let Ty::Void | Ty::Func(_) = ty.unqual() {
+        return Err("compound literal of void or a function type".to_string());
+    }
+    if let Ty::Vla(..) = ty {
+        return Err("compound literal of a variable length array type".to_string());
+    }
+    let (init, ty, rest) = parse_initializer(&ty, tokens, scope)?;
+    if scope.is_file_scope() {
+        let (init, relocs) = eval_init(&ty, &init)?;
+        let var = scope.declare_compound_literal(ty, init, relocs);
+        return Ok((Expr::GVar(var), rest));
+    }
+    let var = scope.alloc_local("(compound literal)", ty);
+    let init = init_local(&var, init);
+    Ok((Expr::CompoundLit(Box::new(CompoundLit { var, init })), rest))
+}
+
+// Checks the arguments against the parameter types of the function, unless
+// they are unspecified. A variadic function takes any more arguments.
+fn check_args(fty: &FuncTy, args: &[Expr]) -> Result<(), String> {
+    let params = match &fty.params {
+        Some(params) => params,
+        None => return Ok(()),
+    };
+    if args.len() < params.len() {
+        return Err("too few arguments to function".to_string());
+    }
+    if args.len() > params.len() && !fty.is_variadic {
+        return Err("too many arguments to function".to_string());
+    }
+    for (i, (param, arg)) in params.iter().zip(args).enumerate() {
+        if !is_assignable(param, arg) {
+            return Err(format!(
+                "incompatible type for argument {}: expected {}, but got {}",
+                i + 1,
+                param,
+                arg.ty().decay()
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Returns true if the expression can be assigned to an object of the type,
+// as when passed as an argument or used as an initializer.
+fn is_assignable(ty: &Ty, expr: &Expr) -> bool {
+    match (ty.unqual(), expr.ty().decay()) {
+        // Any scalar can be converted to _Bool.
+        (Ty::Bool, from) => from.is_arith() || matches!(from, Ty::Ptr(_)),
+        (to, from) if to.is_arith() => from.is_arith(),
+        // A null pointer constant can be assigned to any pointer.
+        (Ty::Ptr(_), _) if matches!(expr, Expr::Num(0, _)) => true,
+        // A pointer to void can be converted from and to any object pointer.
+        //
+        // Qualifiers of the types pointed to are checked on conversion.
+        (Ty::Ptr(to), Ty::Ptr(from)) if *to.unqual() == Ty::Void => !matches!(*from, Ty::Func(_)),
+        (Ty::Ptr(to), Ty::Ptr(from)) if *from.unqual() == Ty::Void => !matches!(**to, Ty::Func(_)),
+        (Ty::Ptr(to), Ty::Ptr(from)) => to.unqual().is_compatible(from.unqual()),
+        (Ty::Struct(to), Ty::Struct(from)) => *to == from,
+        _ => false,
+    }
+}
+
 // <primary> ::= "(" <expr> ")"
+//             | enumeration-constant
+//             | variable
+//             | "__func__"
 //             | func-name
 //             | number
-fn parse_primary(tokens: &[Token]) -> Result<(Expr, &[Token]), String> {
-    match tokens.get(0) {
+//             | floating-constant
+//             | string-literal+
+//             | <va-builtin>
+fn parse_primary<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Expr, &'a [Token]), String> {
+    match tokens.first() {
         // "(" <expr> ")"
         Some(Token::Punct(punct)) if punct == "(" => {
-            let (node, rest) = parse_expr(&tokens[1..])?;
+            let (node, rest) = parse_expr(&tokens[1..], scope)?;
             Ok((node, consume_punct(rest, ")")?))
         }
-        // function name
-        Some(Token::Ident(ident)) => {
-            // TODO: Check if the function exists.
-            Ok((Expr::FnName(ident.clone()), &tokens[1..]))
+        Some(Token::Ident(ident)) if is_va_builtin(ident) => parse_va_builtin(tokens, scope),
+        Some(Token::Ident(ident)) if ident == "__builtin_offsetof" => {
+            parse_offsetof(&tokens[1..], scope)
+        }
+        // the name of the function, unless it is declared otherwise
+        Some(Token::Ident(ident)) if ident == "__func__" && scope.find(ident).is_none() => {
+            let var = scope.func_name().ok_or("__func__ outside a function")?;
+            Ok((Expr::GVar(var), &tokens[1..]))
         }
+        Some(Token::Ident(ident)) => match scope.find(ident) {
+            // enumeration constant
+            Some(Symbol::EnumConst(val)) => Ok((Expr::Num(*val, Ty::Int), &tokens[1..])),
+            // variable
+            Some(Symbol::Var(var)) => Ok((Expr::Var(var.clone()), &tokens[1..])),
+            Some(Symbol::GVar(var)) => Ok((Expr::GVar(var.clone()), &tokens[1..])),
+            // function name
+            Some(Symbol::Global(Global {
+                ty: Ty::Func(fty), ..
+            })) => Ok((Expr::FnName(ident.clone(), fty.clone()), &tokens[1..])),
+            Some(Symbol::Global(Global { ty, .. })) => {
+                let var = GVar {
+                    name: ident.clone(),
+                    ty: ty.clone(),
+                };
+                Ok((Expr::GVar(var), &tokens[1..]))
+            }
+            Some(Symbol::Typedef(_)) => Err(format!("unexpected typedef name: {}", ident)),
+            None => Err(format!("undeclared identifier: {}", ident)),
+        },
         // number
-        Some(Token::Num(num)) => Ok((Expr::Num(*num), &tokens[1..])),
+        Some(Token::Num(num, kind)) => {
+            let ty = match kind {
+                IntKind::Int => Ty::Int,
+                IntKind::UInt => Ty::UInt,
+                IntKind::Long => Ty::Long,
+                IntKind::ULong => Ty::ULong,
+            };
+            Ok((Expr::Num(*num as i64, ty), &tokens[1..]))
+        }
+        Some(Token::FNum(val, kind)) => {
+            let ty = match kind {
+                FloatKind::Float => Ty::Float,
+                FloatKind::Double => Ty::Double,
+                FloatKind::LongDouble => Ty::LongDouble,
+            };
+            Ok((Expr::FNum(*val, ty), &tokens[1..]))
+        }
+        // A string literal is an anonymous array of characters.
+        Some(Token::Str(_)) => {
+            let (s, rest) = parse_string(tokens).unwrap();
+            Ok((Expr::GVar(scope.declare_string(s)), rest))
+        }
         _ => Err("failed to parse primary expression".to_string()),
     }
 }
 
+// Returns true if the identifier is one of the builtins for accessing
+// variable arguments, which the macros of <stdarg.h> expand to.
+fn is_va_builtin(ident: &str) -> bool {
+    matches!(
+        ident,
+        "__builtin_va_start" | "__builtin_va_arg" | "__builtin_va_end" | "__builtin_va_copy"
+    )
+}
+
+// <va-builtin> ::= "__builtin_va_start" "(" <assign> "," ident ")"
+//                | "__builtin_va_arg" "(" <assign> "," <type-name> ")"
+//                | "__builtin_va_end" "(" <assign> ")"
+//                | "__builtin_va_copy" "(" <assign> "," <assign> ")"
+//
+// Each takes a va_list first, which decays to a pointer to operate on.
+fn parse_va_builtin<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Expr, &'a [Token]), String> {
+    let name = match tokens.first() {
+        Some(Token::Ident(name)) => name,
+        _ => unreachable!("expected a builtin name"),
+    };
+    let rest = consume_punct(&tokens[1..], "(")?;
+    let (ap, mut rest) = parse_va_list(rest, scope)?;
+    let expr = match name.as_str() {
+        "__builtin_va_start" => {
+            let (va_args, last) = scope
+                .va_args()
+                .ok_or("va_start used in a function with fixed arguments")?;
+            // The last parameter is only named, since the variable arguments
+            // are known to follow the parameters.
+            rest = consume_punct(rest, ",")?;
+            match rest.first() {
+                Some(Token::Ident(name)) if name == last => rest = &rest[1..],
+                Some(Token::Ident(name)) => {
+                    return Err(format!(
+                        "second argument of va_start is not the last parameter: {}",
+                        name
+                    ))
+                }
+                _ => return Err("expected the last parameter of va_start".to_string()),
+            }
+            Expr::VaStart(Box::new(ap), va_args.clone())
+        }
+        "__builtin_va_arg" => {
+            rest = consume_punct(rest, ",")?;
+            let ty;
+            (ty, rest) = parse_typename(rest, scope)?;
+            let ty = ty.unqual().clone();
+            if !ty.is_arith() && !matches!(ty, Ty::Ptr(_)) {
+                return Err(format!("va_arg of a non-scalar type: {}", ty));
+            }
+            Expr::VaArg(Box::new(ap), ty)
+        }
+        // Nothing is to be cleaned up, so only the argument is evaluated.
+        "__builtin_va_end" => Expr::Cast(Box::new(Cast {
+            expr: ap,
+            ty: Ty::Void,
+        })),
+        "__builtin_va_copy" => {
+            rest = consume_punct(rest, ",")?;
+            let src;
+            (src, rest) = parse_va_list(rest, scope)?;
+            Expr::VaCopy(Box::new(Binary { lhs: ap, rhs: src }))
+        }
+        _ => unreachable!("unknown builtin: {}", name),
+    };
+    Ok((expr, consume_punct(rest, ")")?))
+}
+
+// <offsetof> ::= "__builtin_offsetof" "(" <type-name> "," ident <designator>* ")"
+// <designator> ::= "." ident | "[" <const-expr> "]"
+//
+// Parses the builtin which `offsetof` of <stddef.h> expands to, following
+// the name, and results in the offset in bytes of the designated member from
+// the beginning of the structure or union, e.g. `offsetof(struct S, a[1].b)`.
+fn parse_offsetof<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Expr, &'a [Token]), String> {
+    let rest = consume_punct(tokens, "(")?;
+    let (ty, rest) = parse_typename(rest, scope)?;
+    let rest = consume_punct(rest, ",")?;
+    let (mut member, mut rest) = offsetof_member(&ty, rest)?;
+    let mut offset = member.offset as i64;
+    loop {
+        if let Ok(r) = consume_punct(rest, ".") {
+            let m;
+            (m, rest) = offsetof_member(&member.ty, r)?;
+            offset += m.offset as i64;
+            member = m;
+        } else if let Ok(r) = consume_punct(rest, "[") {
+            let elem = match member.ty.unqual() {
+                Ty::Array(elem, _) | Ty::IncompleteArray(elem) => (**elem).clone(),
+                ty => return Err(format!("subscript of a non-array in offsetof: {}", ty)),
+            };
+            let index;
+            (index, rest) = parse_const_expr(r, scope)?;
+            rest = consume_punct(rest, "]")?;
+            offset += index * elem.size() as i64;
+            member.ty = elem;
+        } else {
+            return Ok((Expr::Num(offset, Ty::ULong), consume_punct(rest, ")")?));
+        }
+    }
+}
+
+// Finds the member of the structure or union type named by the first token,
+// which must not be a bit-field for its offset to be taken.
+fn offsetof_member<'a>(ty: &Ty, tokens: &'a [Token]) -> Result<(Member, &'a [Token]), String> {
+    let s = match ty.unqual() {
+        Ty::Struct(s) if s.is_complete() => s,
+        _ => return Err(format!("offsetof of a non-structure: {}", ty)),
+    };
+    let name = match tokens.first() {
+        Some(Token::Ident(name)) => name,
+        _ => return Err("expected a member name".to_string()),
+    };
+    let member = s
+        .find_member(name)
+        .ok_or_else(|| format!("{} has no member named {}", ty, name))?;
+    if member.bit_field.is_some() {
+        return Err(format!("offsetof of bit-field {}", name));
+    }
+    Ok((member, &tokens[1..]))
+}
+
+// Parses an expression of va_list, which may be a parameter adjusted to a
+// pointer.
+fn parse_va_list<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Expr, &'a [Token]), String> {
+    let (expr, rest) = parse_assign(tokens, scope)?;
+    if expr.ty().decay() != Ty::va_list().decay() {
+        return Err(format!("expected a va_list, but got {}", expr.ty()));
+    }
+    Ok((expr, rest))
+}
+
+// Parses adjacent string literals, which are concatenated into one.
+pub(super) fn parse_string(tokens: &[Token]) -> Option<(Vec<u8>, &[Token])> {
+    let mut s: Vec<u8> = Vec::new();
+    let mut rest = tokens;
+    while let Some(Token::Str(bytes)) = rest.first() {
+        s.extend(bytes);
+        rest = &rest[1..];
+    }
+    if rest.len() == tokens.len() {
+        return None;
+    }
+    Some((s, rest))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ty::{MemberDecl, Quals, StructRef};
 
     #[test]
     fn parses_single_num_token() {
-        let tokens = vec![Token::Num(42)];
-        let expected = Expr::Num(42);
-        let (actual, rest) = parse_expr(&tokens).unwrap();
+        let tokens = vec![Token::Num(42, IntKind::Int)];
+        let expected = Expr::Num(42, Ty::Int);
+        let (actual, rest) = parse_expr(&tokens, &mut Scope::new()).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
     }
 
+    #[test]
+    fn types_integer_constants_by_their_kinds() {
+        // 4294967295u + 1
+        let tokens = vec![
+            Token::Num(u32::MAX as u64, IntKind::UInt),
+            Token::Punct("+".to_string()),
+            Token::Num(1, IntKind::Int),
+        ];
+        let (expr, _) = parse_expr(&tokens, &mut Scope::new()).unwrap();
+        assert_eq!(Ty::UInt, expr.ty());
+        assert_eq!(Ok(0), eval(&expr));
+        // 18446744073709551615
+        let tokens = vec![Token::Num(u64::MAX, IntKind::ULong)];
+        let (expr, _) = parse_expr(&tokens, &mut Scope::new()).unwrap();
+        assert_eq!(Expr::Num(-1, Ty::ULong), expr);
+    }
+
     #[test]
     fn parses_add_expr() {
         let tokens = vec![
-            Token::Num(12),
+            Token::Num(12, IntKind::Int),
             Token::Punct("+".to_string()),
-            Token::Num(23),
+            Token::Num(23, IntKind::Int),
         ];
         let expected = Expr::Add(Box::new(Binary {
-            lhs: Expr::Num(12),
-            rhs: Expr::Num(23),
+            lhs: Expr::Num(12, Ty::Int),
+            rhs: Expr::Num(23, Ty::Int),
         }));
-        let (actual, rest) = parse_expr(&tokens).unwrap();
+        let (actual, rest) = parse_expr(&tokens, &mut Scope::new()).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
     }
@@ -145,29 +1133,449 @@ mod tests {
             Token::Punct("(".to_string()),
             Token::Punct(")".to_string()),
         ];
-        let expected = Expr::FnCall(Box::new(Expr::FnName("somefunc".to_string())));
-        let (expr, rest) = parse_expr(&tokens).unwrap();
+        let fty = Box::new(FuncTy {
+            ret: Ty::Int,
+            params: None,
+            is_variadic: false,
+        });
+        let mut scope = Scope::new();
+        scope
+            .declare_global("somefunc", Ty::Func(fty.clone()), false, false)
+            .unwrap();
+        let expected = Expr::FnCall(Box::new(Call {
+            func: Expr::FnName("somefunc".to_string(), fty),
+            args: vec![],
+        }));
+        let (expr, rest) = parse_expr(&tokens, &mut scope).unwrap();
+        assert_eq!(expected, expr);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn parses_function_call_with_args() {
+        // f(1, 2+3)
+        let tokens = vec![
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Num(1, IntKind::Int),
+            Token::Punct(",".to_string()),
+            Token::Num(2, IntKind::Int),
+            Token::Punct("+".to_string()),
+            Token::Num(3, IntKind::Int),
+            Token::Punct(")".to_string()),
+        ];
+        let fty = Box::new(FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Int, Ty::Int]),
+            is_variadic: false,
+        });
+        let mut scope = Scope::new();
+        scope
+            .declare_global("f", Ty::Func(fty.clone()), false, false)
+            .unwrap();
+        let expected = Expr::FnCall(Box::new(Call {
+            func: Expr::FnName("f".to_string(), fty),
+            args: vec![
+                Expr::Num(1, Ty::Int),
+                Expr::Add(Box::new(Binary {
+                    lhs: Expr::Num(2, Ty::Int),
+                    rhs: Expr::Num(3, Ty::Int),
+                })),
+            ],
+        }));
+        let (expr, rest) = parse_expr(&tokens, &mut scope).unwrap();
+        assert_eq!(expected, expr);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn cannot_call_with_wrong_number_of_args() {
+        // f(1)
+        let tokens = vec![
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Num(1, IntKind::Int),
+            Token::Punct(")".to_string()),
+        ];
+        let fty = Box::new(FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Int, Ty::Int]),
+            is_variadic: false,
+        });
+        let mut scope = Scope::new();
+        scope
+            .declare_global("f", Ty::Func(fty), false, false)
+            .unwrap();
+        assert!(parse_expr(&tokens, &mut scope).is_err());
+    }
+
+    #[test]
+    fn checks_pointer_args() {
+        // f(0), f(x), f(&x)
+        let fty = Box::new(FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Ptr(Box::new(Ty::Int))]),
+            is_variadic: false,
+        });
+        let mut scope = Scope::new();
+        scope
+            .declare_global("f", Ty::Func(fty), false, false)
+            .unwrap();
+        scope.enter_func(Ty::Int);
+        scope.declare_local("x", Ty::Int).unwrap();
+        let call = |arg: Vec<Token>| {
+            let mut tokens = vec![Token::Ident("f".to_string()), Token::Punct("(".to_string())];
+            tokens.extend(arg);
+            tokens.push(Token::Punct(")".to_string()));
+            tokens
+        };
+        let null = call(vec![Token::Num(0, IntKind::Int)]);
+        assert!(parse_expr(&null, &mut scope).is_ok());
+        let int = call(vec![Token::Ident("x".to_string())]);
+        assert!(parse_expr(&int, &mut scope).is_err());
+        let ptr = call(vec![
+            Token::Punct("&".to_string()),
+            Token::Ident("x".to_string()),
+        ]);
+        assert!(parse_expr(&ptr, &mut scope).is_ok());
+    }
+
+    #[test]
+    fn converts_void_pointer_args() {
+        // f(vp), g(ip)
+        let fty = |param| {
+            Ty::Func(Box::new(FuncTy {
+                ret: Ty::Void,
+                params: Some(vec![Ty::Ptr(Box::new(param))]),
+                is_variadic: false,
+            }))
+        };
+        let mut scope = Scope::new();
+        scope
+            .declare_global("f", fty(Ty::Int), false, false)
+            .unwrap();
+        scope
+            .declare_global("g", fty(Ty::Void), false, false)
+            .unwrap();
+        scope.enter_func(Ty::Int);
+        scope
+            .declare_local("vp", Ty::Ptr(Box::new(Ty::Void)))
+            .unwrap();
+        scope
+            .declare_local("ip", Ty::Ptr(Box::new(Ty::Int)))
+            .unwrap();
+        let call = |f: &str, arg: &str| {
+            vec![
+                Token::Ident(f.to_string()),
+                Token::Punct("(".to_string()),
+                Token::Ident(arg.to_string()),
+                Token::Punct(")".to_string()),
+            ]
+        };
+        assert!(parse_expr(&call("f", "vp"), &mut scope).is_ok());
+        assert!(parse_expr(&call("g", "ip"), &mut scope).is_ok());
+    }
+
+    #[test]
+    fn cannot_use_void_value() {
+        // x = f()
+        let fty = Box::new(FuncTy {
+            ret: Ty::Void,
+            params: Some(vec![]),
+            is_variadic: false,
+        });
+        let mut scope = Scope::new();
+        scope
+            .declare_global("f", Ty::Func(fty), false, false)
+            .unwrap();
+        scope.enter_func(Ty::Int);
+        scope.declare_local("x", Ty::Int).unwrap();
+        let tokens = vec![
+            Token::Ident("x".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        assert!(parse_expr(&tokens, &mut scope).is_err());
+        assert!(parse_expr(&tokens[2..], &mut scope).is_ok());
+    }
+
+    #[test]
+    fn cannot_use_undeclared_identifier() {
+        let tokens = vec![Token::Ident("f".to_string())];
+        assert!(parse_expr(&tokens, &mut Scope::new()).is_err());
+    }
+
+    #[test]
+    fn parses_call_through_function_pointer() {
+        // (*fp)(1)
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let fty = FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Int]),
+            is_variadic: false,
+        };
+        let ty = Ty::Ptr(Box::new(Ty::Func(Box::new(fty))));
+        let fp = scope.declare_local("fp", ty).unwrap();
+        let tokens = vec![
+            Token::Punct("(".to_string()),
+            Token::Punct("*".to_string()),
+            Token::Ident("fp".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Num(1, IntKind::Int),
+            Token::Punct(")".to_string()),
+        ];
+        let expected = Expr::FnCall(Box::new(Call {
+            func: Expr::Deref(Box::new(Expr::Var(fp))),
+            args: vec![Expr::Num(1, Ty::Int)],
+        }));
+        let (expr, rest) = parse_expr(&tokens, &mut scope).unwrap();
         assert_eq!(expected, expr);
         assert_eq!(Vec::<Token>::new(), rest);
     }
 
+    #[test]
+    fn cannot_call_non_function() {
+        // 1()
+        let tokens = vec![
+            Token::Num(1, IntKind::Int),
+            Token::Punct("(".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        assert!(parse_expr(&tokens, &mut Scope::new()).is_err());
+    }
+
+    #[test]
+    fn parses_enumeration_constant() {
+        let mut scope = Scope::new();
+        scope.declare("A", Symbol::EnumConst(3)).unwrap();
+        let tokens = vec![Token::Ident("A".to_string())];
+        let (expr, rest) = parse_expr(&tokens, &mut scope).unwrap();
+        assert_eq!(Expr::Num(3, Ty::Int), expr);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn parses_assign_expr() {
+        // x = y = 3
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let x = scope.declare_local("x", Ty::Int).unwrap();
+        let y = scope.declare_local("y", Ty::Int).unwrap();
+        let tokens = vec![
+            Token::Ident("x".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Ident("y".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Num(3, IntKind::Int),
+        ];
+        let expected = Expr::Assign(Box::new(Binary {
+            lhs: Expr::Var(x),
+            rhs: Expr::Assign(Box::new(Binary {
+                lhs: Expr::Var(y),
+                rhs: Expr::Num(3, Ty::Int),
+            })),
+        }));
+        let (actual, rest) = parse_expr(&tokens, &mut scope).unwrap();
+        assert_eq!(expected, actual);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn converts_value_assigned_to_bool() {
+        // b = 2
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let b = scope.declare_local("b", Ty::Bool).unwrap();
+        let tokens = vec![
+            Token::Ident("b".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Num(2, IntKind::Int),
+        ];
+        let expected = Expr::Assign(Box::new(Binary {
+            lhs: Expr::Var(b),
+            rhs: Expr::Cast(Box::new(Cast {
+                expr: Expr::Num(2, Ty::Int),
+                ty: Ty::Bool,
+            })),
+        }));
+        let (actual, _) = parse_expr(&tokens, &mut scope).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn cannot_assign_to_non_lvalue() {
+        // 1 = 2
+        let tokens = vec![
+            Token::Num(1, IntKind::Int),
+            Token::Punct("=".to_string()),
+            Token::Num(2, IntKind::Int),
+        ];
+        assert!(parse_expr(&tokens, &mut Scope::new()).is_err());
+    }
+
+    #[test]
+    fn parses_sizeof_typename() {
+        // sizeof(int *[4])
+        let tokens = vec![
+            Token::Kw(KwKind::Sizeof),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct("*".to_string()),
+            Token::Punct("[".to_string()),
+            Token::Num(4, IntKind::Int),
+            Token::Punct("]".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        let (actual, rest) = parse_expr(&tokens, &mut Scope::new()).unwrap();
+        assert_eq!(Expr::Num(32, Ty::ULong), actual);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn parses_alignof() {
+        // _Alignof(long double[])
+        let tokens = vec![
+            Token::Kw(KwKind::Alignof),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Long),
+            Token::Kw(KwKind::Double),
+            Token::Punct("[".to_string()),
+            Token::Punct("]".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        let (actual, rest) = parse_expr(&tokens, &mut Scope::new()).unwrap();
+        assert_eq!(Expr::Num(16, Ty::ULong), actual);
+        assert_eq!(Vec::<Token>::new(), rest);
+        // _Alignof(void)
+        let tokens = vec![
+            Token::Kw(KwKind::Alignof),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Void),
+            Token::Punct(")".to_string()),
+        ];
+        assert!(parse_expr(&tokens, &mut Scope::new()).is_err());
+    }
+
+    #[test]
+    fn parses_sizeof_expr() {
+        // sizeof *p
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let ty = Ty::Ptr(Box::new(Ty::Array(Box::new(Ty::Int), 4)));
+        scope.declare_local("p", ty).unwrap();
+        let tokens = vec![
+            Token::Kw(KwKind::Sizeof),
+            Token::Punct("*".to_string()),
+            Token::Ident("p".to_string()),
+        ];
+        let (actual, rest) = parse_expr(&tokens, &mut scope).unwrap();
+        assert_eq!(Expr::Num(16, Ty::ULong), actual);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn parses_sizeof_compound_literal() {
+        // sizeof (int[]){1, 2, 3}
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let tokens = vec![
+            Token::Kw(KwKind::Sizeof),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct("[".to_string()),
+            Token::Punct("]".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct("{".to_string()),
+            Token::Num(1, IntKind::Int),
+            Token::Punct(",".to_string()),
+            Token::Num(2, IntKind::Int),
+            Token::Punct(",".to_string()),
+            Token::Num(3, IntKind::Int),
+            Token::Punct("}".to_string()),
+        ];
+        let (actual, rest) = parse_expr(&tokens, &mut scope).unwrap();
+        assert_eq!(Expr::Num(12, Ty::ULong), actual);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn parses_cast_expr() {
+        // (int *)0
+        let tokens = vec![
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct("*".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Num(0, IntKind::Int),
+        ];
+        let expected = Expr::Cast(Box::new(Cast {
+            expr: Expr::Num(0, Ty::Int),
+            ty: Ty::Ptr(Box::new(Ty::Int)),
+        }));
+        let (actual, rest) = parse_expr(&tokens, &mut Scope::new()).unwrap();
+        assert_eq!(expected, actual);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn cannot_dereference_non_pointer() {
+        // *1
+        let tokens = vec![Token::Punct("*".to_string()), Token::Num(1, IntKind::Int)];
+        assert!(parse_expr(&tokens, &mut Scope::new()).is_err());
+    }
+
+    #[test]
+    fn evaluates_const_expr() {
+        // (1+2)*3-8/2
+        let tokens = vec![
+            Token::Punct("(".to_string()),
+            Token::Num(1, IntKind::Int),
+            Token::Punct("+".to_string()),
+            Token::Num(2, IntKind::Int),
+            Token::Punct(")".to_string()),
+            Token::Punct("*".to_string()),
+            Token::Num(3, IntKind::Int),
+            Token::Punct("-".to_string()),
+            Token::Num(8, IntKind::Int),
+            Token::Punct("/".to_string()),
+            Token::Num(2, IntKind::Int),
+        ];
+        let (val, rest) = parse_const_expr(&tokens, &mut Scope::new()).unwrap();
+        assert_eq!(5, val);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn function_call_is_not_const_expr() {
+        let tokens = vec![
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        assert!(parse_const_expr(&tokens, &mut Scope::new()).is_err());
+    }
+
     #[test]
     fn parses_nested_add_expr() {
         let tokens = vec![
-            Token::Num(12),
+            Token::Num(12, IntKind::Int),
             Token::Punct("+".to_string()),
-            Token::Num(23),
+            Token::Num(23, IntKind::Int),
             Token::Punct("+".to_string()),
-            Token::Num(34),
+            Token::Num(34, IntKind::Int),
         ];
         let expected = Expr::Add(Box::new(Binary {
             lhs: Expr::Add(Box::new(Binary {
-                lhs: Expr::Num(12),
-                rhs: Expr::Num(23),
+                lhs: Expr::Num(12, Ty::Int),
+                rhs: Expr::Num(23, Ty::Int),
             })),
-            rhs: Expr::Num(34),
+            rhs: Expr::Num(34, Ty::Int),
         }));
-        let (actual, rest) = parse_expr(&tokens).unwrap();
+        let (actual, rest) = parse_expr(&tokens, &mut Scope::new()).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
     }
@@ -175,27 +1583,31 @@ mod tests {
     #[test]
     fn parses_sub_expr() {
         let tokens = vec![
-            Token::Num(23),
+            Token::Num(23, IntKind::Int),
             Token::Punct("-".to_string()),
-            Token::Num(12),
+            Token::Num(12, IntKind::Int),
         ];
         let expected = Expr::Sub(Box::new(Binary {
-            lhs: Expr::Num(23),
-            rhs: Expr::Num(12),
+            lhs: Expr::Num(23, Ty::Int),
+            rhs: Expr::Num(12, Ty::Int),
         }));
-        let (actual, rest) = parse_expr(&tokens).unwrap();
+        let (actual, rest) = parse_expr(&tokens, &mut Scope::new()).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
     }
 
     #[test]
     fn parses_mul_expr() {
-        let tokens = vec![Token::Num(2), Token::Punct("*".to_string()), Token::Num(3)];
+        let tokens = vec![
+            Token::Num(2, IntKind::Int),
+            Token::Punct("*".to_string()),
+            Token::Num(3, IntKind::Int),
+        ];
         let expected = Expr::Mul(Box::new(Binary {
-            lhs: Expr::Num(2),
-            rhs: Expr::Num(3),
+            lhs: Expr::Num(2, Ty::Int),
+            rhs: Expr::Num(3, Ty::Int),
         }));
-        let (actual, rest) = parse_expr(&tokens).unwrap();
+        let (actual, rest) = parse_expr(&tokens, &mut Scope::new()).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
     }
@@ -204,25 +1616,25 @@ mod tests {
     fn parses_mul_expr_with_add() {
         // 1+2*3-4
         let tokens = vec![
-            Token::Num(1),
+            Token::Num(1, IntKind::Int),
             Token::Punct("+".to_string()),
-            Token::Num(2),
+            Token::Num(2, IntKind::Int),
             Token::Punct("*".to_string()),
-            Token::Num(3),
+            Token::Num(3, IntKind::Int),
             Token::Punct("-".to_string()),
-            Token::Num(4),
+            Token::Num(4, IntKind::Int),
         ];
         let expected = Expr::Sub(Box::new(Binary {
             lhs: Expr::Add(Box::new(Binary {
-                lhs: Expr::Num(1),
+                lhs: Expr::Num(1, Ty::Int),
                 rhs: Expr::Mul(Box::new(Binary {
-                    lhs: Expr::Num(2),
-                    rhs: Expr::Num(3),
+                    lhs: Expr::Num(2, Ty::Int),
+                    rhs: Expr::Num(3, Ty::Int),
                 })),
             })),
-            rhs: Expr::Num(4),
+            rhs: Expr::Num(4, Ty::Int),
         }));
-        let (actual, rest) = parse_expr(&tokens).unwrap();
+        let (actual, rest) = parse_expr(&tokens, &mut Scope::new()).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
     }
@@ -231,25 +1643,25 @@ mod tests {
     fn parses_div_expr_with_add() {
         // 1+3/2-4
         let tokens = vec![
-            Token::Num(1),
+            Token::Num(1, IntKind::Int),
             Token::Punct("+".to_string()),
-            Token::Num(3),
+            Token::Num(3, IntKind::Int),
             Token::Punct("/".to_string()),
-            Token::Num(2),
+            Token::Num(2, IntKind::Int),
             Token::Punct("-".to_string()),
-            Token::Num(4),
+            Token::Num(4, IntKind::Int),
         ];
         let expected = Expr::Sub(Box::new(Binary {
             lhs: Expr::Add(Box::new(Binary {
-                lhs: Expr::Num(1),
+                lhs: Expr::Num(1, Ty::Int),
                 rhs: Expr::Div(Box::new(Binary {
-                    lhs: Expr::Num(3),
-                    rhs: Expr::Num(2),
+                    lhs: Expr::Num(3, Ty::Int),
+                    rhs: Expr::Num(2, Ty::Int),
                 })),
             })),
-            rhs: Expr::Num(4),
+            rhs: Expr::Num(4, Ty::Int),
         }));
-        let (actual, rest) = parse_expr(&tokens).unwrap();
+        let (actual, rest) = parse_expr(&tokens, &mut Scope::new()).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
     }
@@ -258,20 +1670,20 @@ mod tests {
     fn parses_expr_without_parenthesis() {
         // 1+2*3
         let tokens = vec![
-            Token::Num(1),
+            Token::Num(1, IntKind::Int),
             Token::Punct("+".to_string()),
-            Token::Num(2),
+            Token::Num(2, IntKind::Int),
             Token::Punct("*".to_string()),
-            Token::Num(3),
+            Token::Num(3, IntKind::Int),
         ];
         let expected = Expr::Add(Box::new(Binary {
-            lhs: Expr::Num(1),
+            lhs: Expr::Num(1, Ty::Int),
             rhs: Expr::Mul(Box::new(Binary {
-                lhs: Expr::Num(2),
-                rhs: Expr::Num(3),
+                lhs: Expr::Num(2, Ty::Int),
+                rhs: Expr::Num(3, Ty::Int),
             })),
         }));
-        let (actual, rest) = parse_expr(&tokens).unwrap();
+        let (actual, rest) = parse_expr(&tokens, &mut Scope::new()).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
     }
@@ -281,22 +1693,631 @@ mod tests {
         // (1+2)*3
         let tokens = vec![
             Token::Punct("(".to_string()),
-            Token::Num(1),
+            Token::Num(1, IntKind::Int),
             Token::Punct("+".to_string()),
-            Token::Num(2),
+            Token::Num(2, IntKind::Int),
             Token::Punct(")".to_string()),
             Token::Punct("*".to_string()),
-            Token::Num(3),
+            Token::Num(3, IntKind::Int),
         ];
         let expected = Expr::Mul(Box::new(Binary {
             lhs: Expr::Add(Box::new(Binary {
-                lhs: Expr::Num(1),
-                rhs: Expr::Num(2),
+                lhs: Expr::Num(1, Ty::Int),
+                rhs: Expr::Num(2, Ty::Int),
             })),
-            rhs: Expr::Num(3),
+            rhs: Expr::Num(3, Ty::Int),
+        }));
+        let (actual, rest) = parse_expr(&tokens, &mut Scope::new()).unwrap();
+        assert_eq!(expected, actual);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn parses_string_literals() {
+        // "ab" "c"
+        let tokens = vec![Token::Str(b"ab".to_vec()), Token::Str(b"c".to_vec())];
+        let mut scope = Scope::new();
+        let (actual, rest) = parse_expr(&tokens, &mut scope).unwrap();
+        let expected = Expr::GVar(GVar {
+            name: ".str.0".to_string(),
+            ty: Ty::Array(Box::new(Ty::Char), 4),
+        });
+        assert_eq!(expected, actual);
+        assert_eq!(Vec::<Token>::new(), rest);
+        assert_eq!(Some(b"abc\0".to_vec()), scope.take_statics()[0].init);
+    }
+
+    #[test]
+    fn parses_compound_literal() {
+        // (int[]){1, 2}
+        let tokens = vec![
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct("[".to_string()),
+            Token::Punct("]".to_string()),
+            Token::Punct(")".to_string()),
+            Token::Punct("{".to_string()),
+            Token::Num(1, IntKind::Int),
+            Token::Punct(",".to_string()),
+            Token::Num(2, IntKind::Int),
+            Token::Punct("}".to_string()),
+        ];
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let (actual, rest) = parse_expr(&tokens, &mut scope).unwrap();
+        let var = Var {
+            name: "(compound literal)".to_string(),
+            ty: Ty::Array(Box::new(Ty::Int), 2),
+            offset: 8,
+        };
+        let elem = |offset, val| {
+            let lhs = Expr::Var(Var {
+                name: var.name.clone(),
+                ty: Ty::Int,
+                offset,
+            });
+            Expr::Assign(Box::new(Binary {
+                lhs,
+                rhs: Expr::Num(val, Ty::Int),
+            }))
+        };
+        let expected = Expr::CompoundLit(Box::new(CompoundLit {
+            var: var.clone(),
+            init: vec![elem(8, 1), elem(4, 2)],
         }));
-        let (actual, rest) = parse_expr(&tokens).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
+        assert_eq!((16, 16), scope.leave_func());
+    }
+
+    #[test]
+    fn compound_literal_at_file_scope_is_static() {
+        // (int){3}
+        let tokens = vec![
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct(")".to_string()),
+            Token::Punct("{".to_string()),
+            Token::Num(3, IntKind::Int),
+            Token::Punct("}".to_string()),
+        ];
+        let mut scope = Scope::new();
+        let (actual, _) = parse_expr(&tokens, &mut scope).unwrap();
+        let expected = Expr::GVar(GVar {
+            name: ".compoundlit.0".to_string(),
+            ty: Ty::Int,
+        });
+        assert_eq!(expected, actual);
+        assert_eq!(Some(vec![3, 0, 0, 0]), scope.take_statics()[0].init);
+    }
+
+    // struct { int a; int b : 3; }
+    fn bit_field_struct() -> Ty {
+        let s = StructRef::new(None, false);
+        s.complete(
+            vec![
+                MemberDecl {
+                    name: Some("a".to_string()),
+                    ty: Ty::Int,
+                    width: None,
+                    align: 0,
+                    is_packed: false,
+                },
+                MemberDecl {
+                    name: Some("b".to_string()),
+                    ty: Ty::Int,
+                    width: Some(3),
+                    align: 0,
+                    is_packed: false,
+                },
+            ],
+            false,
+            0,
+        );
+        Ty::Struct(s)
+    }
+
+    #[test]
+    fn parses_member_access() {
+        // p->b = s.a
+        let tokens = vec![
+            Token::Ident("p".to_string()),
+            Token::Punct("->".to_string()),
+            Token::Ident("b".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Ident("s".to_string()),
+            Token::Punct(".".to_string()),
+            Token::Ident("a".to_string()),
+        ];
+        let ty = bit_field_struct();
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let s = scope
+            .declare_local(
+                "s",
+                ty.clone().qualify(Quals {
+                    is_volatile: true,
+                    ..Quals::default()
+                }),
+            )
+            .unwrap();
+        let p = scope
+            .declare_local("p", Ty::Ptr(Box::new(ty.clone())))
+            .unwrap();
+        let (actual, _) = parse_expr(&tokens, &mut scope).unwrap();
+        let member = |name: &str| match &ty {
+            Ty::Struct(s) => s.find_member(name).unwrap(),
+            _ => unreachable!(),
+        };
+        let lhs = Expr::Member(Box::new(Expr::Deref(Box::new(Expr::Var(p)))), member("b"));
+        let rhs = Expr::Member(Box::new(Expr::Var(s)), member("a"));
+        // A member of a volatile structure is volatile.
+        assert!(rhs.qual_ty().quals().is_volatile);
+        assert!(lhs.is_bit_field() && !rhs.is_bit_field());
+        assert_eq!(Expr::Assign(Box::new(Binary { lhs, rhs })), actual);
+    }
+
+    #[test]
+    fn cannot_take_address_of_bit_field() {
+        // &s.b
+        let tokens = vec![
+            Token::Punct("&".to_string()),
+            Token::Ident("s".to_string()),
+            Token::Punct(".".to_string()),
+            Token::Ident("b".to_string()),
+        ];
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        scope.declare_local("s", bit_field_struct()).unwrap();
+        assert_eq!(
+            Err("cannot take the address of bit-field b".to_string()),
+            parse_expr(&tokens, &mut scope).map(|_| ())
+        );
+        // s.c
+        let tokens = vec![
+            Token::Ident("s".to_string()),
+            Token::Punct(".".to_string()),
+            Token::Ident("c".to_string()),
+        ];
+        assert_eq!(
+            Err("struct <anonymous> has no member named c".to_string()),
+            parse_expr(&tokens, &mut scope).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn parses_offsetof() {
+        // __builtin_offsetof(T, c[2].a), where T is
+        // `struct { long a; struct { int a; int b : 3; } c[3]; }`
+        let offsetof = |member: &str| {
+            vec![
+                Token::Ident("__builtin_offsetof".to_string()),
+                Token::Punct("(".to_string()),
+                Token::Ident("T".to_string()),
+                Token::Punct(",".to_string()),
+                Token::Ident("c".to_string()),
+                Token::Punct("[".to_string()),
+                Token::Num(2, IntKind::Int),
+                Token::Punct("]".to_string()),
+                Token::Punct(".".to_string()),
+                Token::Ident(member.to_string()),
+                Token::Punct(")".to_string()),
+            ]
+        };
+        let s = StructRef::new(None, false);
+        s.complete(
+            vec![
+                MemberDecl {
+                    name: Some("a".to_string()),
+                    ty: Ty::Long,
+                    width: None,
+                    align: 0,
+                    is_packed: false,
+                },
+                MemberDecl {
+                    name: Some("c".to_string()),
+                    ty: Ty::Array(Box::new(bit_field_struct()), 3),
+                    width: None,
+                    align: 0,
+                    is_packed: false,
+                },
+            ],
+            false,
+            0,
+        );
+        let mut scope = Scope::new();
+        scope.declare("T", Symbol::Typedef(Ty::Struct(s))).unwrap();
+        let tokens = offsetof("a");
+        let (actual, rest) = parse_expr(&tokens, &mut scope).unwrap();
+        assert_eq!(Expr::Num(24, Ty::ULong), actual);
+        assert!(rest.is_empty());
+        assert_eq!(
+            Err("offsetof of bit-field b".to_string()),
+            parse_expr(&offsetof("b"), &mut scope).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn converts_operands_to_unsigned_int() {
+        // (unsigned)0 - 1
+        let tokens = vec![
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Unsigned),
+            Token::Punct(")".to_string()),
+            Token::Num(0, IntKind::Int),
+            Token::Punct("-".to_string()),
+            Token::Num(1, IntKind::Int),
+        ];
+        let (expr, _) = parse_expr(&tokens, &mut Scope::new()).unwrap();
+        assert_eq!(Ty::UInt, expr.ty());
+        assert_eq!(Ok(u32::MAX as i64), eval(&expr));
+        // An int value is converted to unsigned char, which cannot represent
+        // all of them, but not from unsigned char to int.
+        assert_eq!(
+            Ty::UChar,
+            convert(Expr::Num(300, Ty::Int), &Ty::UChar).unwrap().ty()
+        );
+        let uc = Expr::Cast(Box::new(Cast {
+            expr: Expr::Num(3, Ty::Int),
+            ty: Ty::UChar,
+        }));
+        assert_eq!(uc.clone(), convert(uc, &Ty::Int).unwrap());
+    }
+
+    #[test]
+    fn converts_operands_to_long() {
+        // (unsigned)1 - (long)2
+        let tokens = vec![
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Unsigned),
+            Token::Punct(")".to_string()),
+            Token::Num(1, IntKind::Int),
+            Token::Punct("-".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Long),
+            Token::Punct(")".to_string()),
+            Token::Num(2, IntKind::Int),
+        ];
+        let (expr, _) = parse_expr(&tokens, &mut Scope::new()).unwrap();
+        assert_eq!(Ty::Long, expr.ty());
+        assert_eq!(Ok(-1), eval(&expr));
+        // An unsigned long is divided as it is unsigned.
+        let ul = |n| {
+            Expr::Cast(Box::new(Cast {
+                expr: Expr::Num(n, Ty::Int),
+                ty: Ty::ULong,
+            }))
+        };
+        let div = Expr::Div(Box::new(Binary {
+            lhs: ul(-2),
+            rhs: ul(2),
+        }));
+        assert_eq!(Ok(i64::MAX), eval(&div));
+        // Neither long nor unsigned int can represent all the values of the
+        // other, but long can represent those of int.
+        assert_eq!(Ty::UInt, convert(ul(1), &Ty::UInt).unwrap().ty());
+        assert_eq!(
+            Expr::Num(1, Ty::Int),
+            convert(Expr::Num(1, Ty::Int), &Ty::Long).unwrap()
+        );
+    }
+
+    fn const_int() -> Ty {
+        Ty::Int.qualify(Quals {
+            is_const: true,
+            ..Quals::default()
+        })
+    }
+
+    #[test]
+    fn cannot_assign_to_const() {
+        // x = 1, *p = 1, q = 0
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        scope.declare_local("x", const_int()).unwrap();
+        let p = Ty::Ptr(Box::new(const_int()));
+        scope.declare_local("p", p.clone()).unwrap();
+        scope.declare_local("q", p).unwrap();
+        let x = vec![
+            Token::Ident("x".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Num(1, IntKind::Int),
+        ];
+        assert!(parse_expr(&x, &mut scope).is_err());
+        let p = vec![
+            Token::Punct("*".to_string()),
+            Token::Ident("p".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Num(1, IntKind::Int),
+        ];
+        assert!(parse_expr(&p, &mut scope).is_err());
+        let q = vec![
+            Token::Ident("q".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Num(0, IntKind::Int),
+        ];
+        assert!(parse_expr(&q, &mut scope).is_ok());
+    }
+
+    #[test]
+    fn cannot_discard_qualifiers_of_pointer_target() {
+        // p = &x, x = *p, q = p
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        scope.declare_local("x", Ty::Int).unwrap();
+        scope
+            .declare_local("p", Ty::Ptr(Box::new(const_int())))
+            .unwrap();
+        scope
+            .declare_local("q", Ty::Ptr(Box::new(Ty::Int)))
+            .unwrap();
+        let assign = |lhs: Vec<Token>, rhs: Vec<Token>| {
+            let mut tokens = lhs;
+            tokens.push(Token::Punct("=".to_string()));
+            tokens.extend(rhs);
+            tokens
+        };
+        let ident = |name: &str| Token::Ident(name.to_string());
+        let adding = assign(
+            vec![ident("p")],
+            vec![Token::Punct("&".to_string()), ident("x")],
+        );
+        assert!(parse_expr(&adding, &mut scope).is_ok());
+        let reading = assign(
+            vec![ident("x")],
+            vec![Token::Punct("*".to_string()), ident("p")],
+        );
+        assert!(parse_expr(&reading, &mut scope).is_ok());
+        let discarding = assign(vec![ident("q")], vec![ident("p")]);
+        assert!(parse_expr(&discarding, &mut scope).is_err());
+    }
+
+    #[test]
+    fn assigns_only_assignable_values() {
+        // fp = f, p = 1, c = p, p = 0
+        let mut scope = Scope::new();
+        let func = |param: Ty| {
+            Ty::Func(Box::new(FuncTy {
+                ret: Ty::Int,
+                params: Some(vec![param]),
+                is_variadic: false,
+            }))
+        };
+        scope
+            .declare_global("f", func(Ty::Int), false, false)
+            .unwrap();
+        scope.enter_func(Ty::Int);
+        scope
+            .declare_local("fp", Ty::Ptr(Box::new(func(Ty::Double))))
+            .unwrap();
+        scope
+            .declare_local("p", Ty::Ptr(Box::new(Ty::Int)))
+            .unwrap();
+        scope.declare_local("c", Ty::Char).unwrap();
+        let assign = |lhs: &str, rhs: Token| {
+            vec![
+                Token::Ident(lhs.to_string()),
+                Token::Punct("=".to_string()),
+                rhs,
+            ]
+        };
+        let ident = |name: &str| Token::Ident(name.to_string());
+        assert!(parse_expr(&assign("fp", ident("f")), &mut scope).is_err());
+        assert!(parse_expr(&assign("p", Token::Num(1, IntKind::Int)), &mut scope).is_err());
+        assert!(parse_expr(&assign("c", ident("p")), &mut scope).is_err());
+        assert!(parse_expr(&assign("p", Token::Num(0, IntKind::Int)), &mut scope).is_ok());
+    }
+
+    #[test]
+    fn converts_operands_to_common_floating_type() {
+        // 1 + 2.5f * 2.0
+        let tokens = vec![
+            Token::Num(1, IntKind::Int),
+            Token::Punct("+".to_string()),
+            Token::FNum(F80::from_f64(2.5), FloatKind::Float),
+            Token::Punct("*".to_string()),
+            Token::FNum(F80::from_f64(2.0), FloatKind::Double),
+        ];
+        let to_double = |expr| {
+            Expr::Cast(Box::new(Cast {
+                expr,
+                ty: Ty::Double,
+            }))
+        };
+        let expected = Expr::Add(Box::new(Binary {
+            lhs: to_double(Expr::Num(1, Ty::Int)),
+            rhs: Expr::Mul(Box::new(Binary {
+                lhs: to_double(Expr::FNum(F80::from_f64(2.5), Ty::Float)),
+                rhs: Expr::FNum(F80::from_f64(2.0), Ty::Double),
+            })),
+        }));
+        let (actual, _) = parse_expr(&tokens, &mut Scope::new()).unwrap();
+        assert_eq!(expected, actual);
+        assert_eq!(Ty::Double, actual.ty());
+        assert_eq!(6.0, eval_float(&actual).unwrap());
+    }
+
+    #[test]
+    fn promotes_float_args_without_prototype() {
+        // f(1.5f)
+        let tokens = vec![
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::FNum(F80::from_f64(1.5), FloatKind::Float),
+            Token::Punct(")".to_string()),
+        ];
+        let fty = Box::new(FuncTy {
+            ret: Ty::Int,
+            params: None,
+            is_variadic: false,
+        });
+        let mut scope = Scope::new();
+        scope
+            .declare_global("f", Ty::Func(fty.clone()), false, false)
+            .unwrap();
+        let expected = Expr::FnCall(Box::new(Call {
+            func: Expr::FnName("f".to_string(), fty),
+            args: vec![Expr::Cast(Box::new(Cast {
+                expr: Expr::FNum(F80::from_f64(1.5), Ty::Float),
+                ty: Ty::Double,
+            }))],
+        }));
+        let (actual, _) = parse_expr(&tokens, &mut scope).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn promotes_variable_args() {
+        // f(1, 1.5f)
+        let tokens = vec![
+            Token::Ident("f".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Num(1, IntKind::Int),
+            Token::Punct(",".to_string()),
+            Token::FNum(F80::from_f64(1.5), FloatKind::Float),
+            Token::Punct(")".to_string()),
+        ];
+        let fty = Box::new(FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Double]),
+            is_variadic: true,
+        });
+        let mut scope = Scope::new();
+        scope
+            .declare_global("f", Ty::Func(fty.clone()), false, false)
+            .unwrap();
+        let expected = Expr::FnCall(Box::new(Call {
+            func: Expr::FnName("f".to_string(), fty),
+            args: vec![
+                Expr::Cast(Box::new(Cast {
+                    expr: Expr::Num(1, Ty::Int),
+                    ty: Ty::Double,
+                })),
+                Expr::Cast(Box::new(Cast {
+                    expr: Expr::FNum(F80::from_f64(1.5), Ty::Float),
+                    ty: Ty::Double,
+                })),
+            ],
+        }));
+        let (actual, _) = parse_expr(&tokens, &mut scope).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parses_va_arg() {
+        // __builtin_va_arg(ap, int)
+        let tokens = vec![
+            Token::Ident("__builtin_va_arg".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Ident("ap".to_string()),
+            Token::Punct(",".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct(")".to_string()),
+        ];
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let ap = scope.declare_local("ap", Ty::va_list()).unwrap();
+        let expected = Expr::VaArg(Box::new(Expr::Var(ap)), Ty::Int);
+        let (actual, _) = parse_expr(&tokens, &mut scope).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn cannot_use_va_start_without_variable_args() {
+        // __builtin_va_start(ap, x) in a function with fixed parameters
+        let tokens = vec![
+            Token::Ident("__builtin_va_start".to_string()),
+            Token::Punct("(".to_string()),
+            Token::Ident("ap".to_string()),
+            Token::Punct(",".to_string()),
+            Token::Ident("x".to_string()),
+            Token::Punct(")".to_string()),
+        ];
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        scope.declare_local("x", Ty::Int).unwrap();
+        scope.declare_local("ap", Ty::va_list()).unwrap();
+        assert!(parse_expr(&tokens, &mut scope).is_err());
+    }
+
+    #[test]
+    fn checks_arguments_of_va_start() {
+        // __builtin_va_start(ap, x)
+        let va_start = |ap: &str| {
+            vec![
+                Token::Ident("__builtin_va_start".to_string()),
+                Token::Punct("(".to_string()),
+                Token::Ident(ap.to_string()),
+                Token::Punct(",".to_string()),
+                Token::Ident("x".to_string()),
+                Token::Punct(")".to_string()),
+            ]
+        };
+        let va_args = VaArgs {
+            area: 184,
+            gp_offset: 16,
+            fp_offset: 48,
+            overflow: 192,
+        };
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        scope.declare_local("x", Ty::Int).unwrap();
+        scope.declare_local("ap", Ty::va_list()).unwrap();
+        scope
+            .declare_local("p", Ty::Ptr(Box::new(Ty::va_list().decay())))
+            .unwrap();
+        scope
+            .declare_local("q", Ty::Array(Box::new(Ty::Ptr(Box::new(Ty::Void))), 3))
+            .unwrap();
+        scope.set_va_args(va_args.clone(), "x");
+        let (expr, _) = parse_expr(&va_start("ap"), &mut scope).unwrap();
+        assert!(matches!(expr, Expr::VaStart(_, v) if v == va_args));
+        // Only a va_list, which may be adjusted to a pointer, is accepted.
+        assert!(parse_expr(&va_start("p"), &mut scope).is_err());
+        assert!(parse_expr(&va_start("q"), &mut scope).is_err());
+        // `x` must be the last parameter.
+        scope.set_va_args(va_args, "y");
+        assert!(parse_expr(&va_start("ap"), &mut scope).is_err());
+    }
+
+    #[test]
+    fn cannot_convert_between_pointer_and_floating_type() {
+        // (int *)1.0, d = &x
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        scope.declare_local("x", Ty::Int).unwrap();
+        scope.declare_local("d", Ty::Double).unwrap();
+        let cast = vec![
+            Token::Punct("(".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct("*".to_string()),
+            Token::Punct(")".to_string()),
+            Token::FNum(F80::from_f64(1.0), FloatKind::Double),
+        ];
+        assert!(parse_expr(&cast, &mut scope).is_err());
+        let assign = vec![
+            Token::Ident("d".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Punct("&".to_string()),
+            Token::Ident("x".to_string()),
+        ];
+        assert!(parse_expr(&assign, &mut scope).is_err());
+    }
+
+    #[test]
+    fn evaluates_floating_constant_cast_to_integer() {
+        // (char)(7 / 2.0)
+        let expr = Expr::Cast(Box::new(Cast {
+            expr: Expr::Div(Box::new(Binary {
+                lhs: Expr::Cast(Box::new(Cast {
+                    expr: Expr::Num(7, Ty::Int),
+                    ty: Ty::Double,
+                })),
+                rhs: Expr::FNum(F80::from_f64(2.0), Ty::Double),
+            })),
+            ty: Ty::Char,
+        }));
+        assert_eq!(3, eval(&expr).unwrap());
+        assert!(eval(&Expr::FNum(F80::from_f64(1.0), Ty::Double)).is_err());
     }
 }
@@ -1,32 +1,77 @@
 use super::{
     consume_punct,
-    stmt::{parse_stmt, Stmt},
+    decl::Declarator,
+    expr::{VaArgs, Var},
+    scope::Scope,
+    stmt::{parse_block_item, Stmt},
 };
-use crate::lexer::{KwKind, Token};
-use crate::ty::Ty;
+use crate::lexer::Token;
+use crate::ty::{align_to, FuncTy, Ty};
+
+// Maximum numbers of parameters passed in general-purpose registers and in
+// vector registers. Parameters which do not fit in them are passed in memory,
+// as are long double ones.
+pub const MAX_PARAMS: usize = 6;
+pub const MAX_FP_PARAMS: usize = 8;
+
+// Size of the register save area of a variadic function, where all the
+// argument registers are saved: 6 general-purpose registers of 8 bytes, then
+// 8 vector registers of 16 bytes.
+pub const REG_SAVE_AREA_SIZE: usize = MAX_PARAMS * 8 + MAX_FP_PARAMS * 16;
 
 #[derive(Debug, PartialEq)]
 pub struct Function {
     pub ty: Ty,
     pub name: String,
+    pub is_static: bool, // true if it has internal linkage
+    pub params: Vec<Var>,
     pub body: Vec<Stmt>,
+    pub stack_size: usize, // size of the stack frame for local variables
+    pub align: usize,      // alignment of the stack frame
+    // where the variable arguments are, if the function is variadic
+    pub va_args: Option<VaArgs>,
 }
 
-// <function> ::= <type> ident "(" ")" "{" <stmt>* "}"
-pub(super) fn parse_func(tokens: &[Token]) -> Result<(Function, &[Token]), String> {
-    // type
-    let (ty, rest) = parse_type(tokens)?;
-
-    // name
-    let name = match rest.get(0) {
-        Some(Token::Ident(name)) => name.clone(),
-        _ => return Err(format!("expected a function name: {:?}", rest)),
+// <function> ::= <declspec> <declarator> "{" <block-item>* "}"
+//
+// The declaration specifiers and the declarator have already been parsed by
+// the caller, and are given as `decl` and `is_static`.
+pub(super) fn parse_func<'a>(
+    decl: Declarator,
+    is_static: bool,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Function, &'a [Token]), String> {
+    let name = decl.name.ok_or("expected a function name")?;
+    let fty = match decl.ty {
+        Ty::Func(fty) => fty,
+        _ => return Err(format!("expected a function: {}", name)),
     };
-    let rest = &rest[1..];
+    let param_tys = fty.params.clone().unwrap_or_default();
+    check_by_value(&fty.ret, &param_tys)?;
+    if let Some(ty) = param_tys.iter().find(|ty| ty.is_incomplete()) {
+        return Err(format!("parameter has incomplete type {}: {}", ty, name));
+    }
+    let rest = consume_punct(tokens, "{")?;
 
-    let rest = consume_punct(rest, "(")?;
-    let rest = consume_punct(rest, ")")?;
-    let rest = consume_punct(rest, "{")?;
+    // The function is declared before its body, so that it can call itself.
+    let is_static = scope
+        .declare_global(&name, Ty::Func(fty.clone()), is_static, true)?
+        .is_static;
+
+    // Parameters are in the outermost block scope of the function.
+    scope.enter_func(fty.ret.clone());
+    scope.set_func_name(&name);
+    let mut params: Vec<Var> = Vec::new();
+    for (ty, param_name) in param_tys.into_iter().zip(decl.param_names) {
+        let param_name = param_name.ok_or(format!("parameter name omitted: {}", name))?;
+        params.push(scope.declare_local(&param_name, ty)?);
+    }
+    // "..." follows at least one parameter.
+    let va_args = match params.last() {
+        Some(last) if fty.is_variadic => Some(alloc_reg_save_area(&fty, &last.name, scope)),
+        _ => None,
+    };
 
     // body
     let mut body: Vec<Stmt> = Vec::new();
@@ -38,54 +83,254 @@ pub(super) fn parse_func(tokens: &[Token]) -> Result<(Function, &[Token]), Strin
                 break;
             }
             Err(_) => {
-                let (stmt, r) = parse_stmt(rest)?;
+                let (stmt, r) = parse_block_item(rest, scope)?;
                 body.push(stmt);
                 rest = r;
             }
         }
     }
+    let (stack_size, align) = scope.leave_func();
 
-    Ok((Function { ty, name, body }, rest))
+    Ok((
+        Function {
+            ty: fty.ret,
+            name,
+            is_static,
+            params,
+            body,
+            stack_size,
+            align,
+            va_args,
+        },
+        rest,
+    ))
 }
 
-// <type> ::= "int"
-fn parse_type(tokens: &[Token]) -> Result<(Ty, &[Token]), String> {
-    if let Some(Token::Kw(KwKind::Int)) = tokens.get(0) {
-        return Ok((Ty::Int, &tokens[1..]));
+// Allocates the register save area of a variadic function, and tells the scope
+// where its variable arguments follow the named parameters, the last of which
+// is `last`, and returns where they are.
+//
+// The address of the first variable argument in memory is saved in a slot by
+// the prologue, since rbp may not point to the arguments in a realigned frame.
+fn alloc_reg_save_area(fty: &FuncTy, last: &str, scope: &mut Scope) -> VaArgs {
+    let params = fty.params.as_deref().unwrap_or_default();
+    let (locs, _) = arg_locs(params);
+    let gp = locs
+        .iter()
+        .filter(|loc| matches!(loc, ArgLoc::Reg(_)))
+        .count();
+    let fp = locs
+        .iter()
+        .filter(|loc| matches!(loc, ArgLoc::FpReg(_)))
+        .count();
+
+    let area = Ty::Array(
+        Box::new(Ty::Ptr(Box::new(Ty::Void))),
+        REG_SAVE_AREA_SIZE / 8,
+    );
+    let area = scope.alloc_local("(register save area)", area).offset;
+    let overflow = Ty::Ptr(Box::new(Ty::Void));
+    let overflow = scope.alloc_local("(overflow area)", overflow).offset;
+    let va_args = VaArgs {
+        area,
+        gp_offset: gp * 8,
+        fp_offset: MAX_PARAMS * 8 + fp * 16,
+        overflow,
+    };
+    scope.set_va_args(va_args.clone(), last);
+    va_args
+}
+
+// Returns an error if a structure or union is passed or returned by value,
+// which the classification of its members by the ABI is not implemented for.
+pub(super) fn check_by_value(ret: &Ty, params: &[Ty]) -> Result<(), String> {
+    if std::iter::once(ret)
+        .chain(params)
+        .any(|ty| matches!(ty.unqual(), Ty::Struct(_)))
+    {
+        return Err("passing or returning a struct by value is not supported".to_string());
     }
+    Ok(())
+}
+
+// Location of an argument, where a caller puts it and the function called
+// takes it from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgLoc {
+    Reg(usize),   // i-th general-purpose register
+    FpReg(usize), // i-th vector register, i.e. xmm<i>
+    // offset in memory from the stack pointer at the call, which is 16 bytes
+    // below it in the function called, past the return address and rbp
+    Stack(usize),
+}
 
-    Err("expected a type".to_string())
+// Returns the locations of arguments of the types, and the size of those
+// passed in memory.
+//
+// As the System V ABI specifies, float and double arguments are passed in
+// vector registers, and other scalars in general-purpose registers, each in
+// order while registers of the class remain. The others and long double
+// arguments are passed in memory in order, each in an 8-byte slot, except that
+// a long double takes a 16-byte one aligned to 16 bytes.
+pub fn arg_locs(tys: &[Ty]) -> (Vec<ArgLoc>, usize) {
+    let (mut gp, mut fp, mut mem) = (0, 0, 0);
+    let mut stack = |size: usize| {
+        mem = align_to(mem, size) + size;
+        ArgLoc::Stack(mem - size)
+    };
+    let locs = tys
+        .iter()
+        .map(|ty| match ty.unqual() {
+            Ty::LongDouble => stack(16),
+            Ty::Float | Ty::Double if fp < MAX_FP_PARAMS => {
+                fp += 1;
+                ArgLoc::FpReg(fp - 1)
+            }
+            Ty::Float | Ty::Double => stack(8),
+            _ if gp < MAX_PARAMS => {
+                gp += 1;
+                ArgLoc::Reg(gp - 1)
+            }
+            _ => stack(8),
+        })
+        .collect();
+    (locs, mem)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::lexer::KwKind;
+    use crate::lexer::{IntKind, KwKind};
     use crate::parser::*;
 
     #[test]
     fn parses_function_with_multiple_stmt() {
-        // hello() { 2; return 3; }
+        // (int hello()) { 2; return 3; }
         let tokens = vec![
-            Token::Kw(KwKind::Int),
-            Token::Ident("hello".to_string()),
-            Token::Punct("(".to_string()),
-            Token::Punct(")".to_string()),
             Token::Punct("{".to_string()),
-            Token::Num(2),
+            Token::Num(2, IntKind::Int),
             Token::Punct(";".to_string()),
             Token::Kw(KwKind::Return),
-            Token::Num(3),
+            Token::Num(3, IntKind::Int),
             Token::Punct(";".to_string()),
             Token::Punct("}".to_string()),
         ];
         let expected = Function {
             ty: Ty::Int,
             name: "hello".to_string(),
-            body: vec![Stmt::ExprStmt(Expr::Num(2)), Stmt::ReturnStmt(Expr::Num(3))],
+            is_static: false,
+            params: vec![],
+            body: vec![
+                Stmt::ExprStmt(Expr::Num(2, Ty::Int)),
+                Stmt::ReturnStmt(Some(Expr::Num(3, Ty::Int))),
+            ],
+            stack_size: 0,
+            align: 16,
+            va_args: None,
+        };
+        let decl = Declarator {
+            name: Some("hello".to_string()),
+            ty: Ty::Func(Box::new(FuncTy {
+                ret: Ty::Int,
+                params: None,
+                is_variadic: false,
+            })),
+            param_names: vec![],
         };
-        let (func, rest) = parse_func(&tokens).unwrap();
+        let (func, rest) = parse_func(decl, false, &tokens, &mut Scope::new()).unwrap();
         assert_eq!(expected, func);
         assert_eq!(Vec::<Token>::new(), rest);
     }
+
+    #[test]
+    fn parses_function_with_local_variables() {
+        // (int f()) { int x; int *y; }
+        let tokens = vec![
+            Token::Punct("{".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Ident("x".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Kw(KwKind::Int),
+            Token::Punct("*".to_string()),
+            Token::Ident("y".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Punct("}".to_string()),
+        ];
+        let decl = Declarator {
+            name: Some("f".to_string()),
+            ty: Ty::Func(Box::new(FuncTy {
+                ret: Ty::Int,
+                params: None,
+                is_variadic: false,
+            })),
+            param_names: vec![],
+        };
+        let (func, _) = parse_func(decl, false, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(16, func.stack_size);
+    }
+
+    #[test]
+    fn parses_function_with_parameters() {
+        // (int f(int a, int *b)) { return a; }
+        let tokens = vec![
+            Token::Punct("{".to_string()),
+            Token::Kw(KwKind::Return),
+            Token::Ident("a".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Punct("}".to_string()),
+        ];
+        let decl = Declarator {
+            name: Some("f".to_string()),
+            ty: Ty::Func(Box::new(FuncTy {
+                ret: Ty::Int,
+                params: Some(vec![Ty::Int, Ty::Ptr(Box::new(Ty::Int))]),
+                is_variadic: false,
+            })),
+            param_names: vec![Some("a".to_string()), Some("b".to_string())],
+        };
+        let a = Var {
+            name: "a".to_string(),
+            ty: Ty::Int,
+            offset: 4,
+        };
+        let b = Var {
+            name: "b".to_string(),
+            ty: Ty::Ptr(Box::new(Ty::Int)),
+            offset: 16,
+        };
+        let (func, _) = parse_func(decl, false, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(vec![a.clone(), b], func.params);
+        assert_eq!(vec![Stmt::ReturnStmt(Some(Expr::Var(a)))], func.body);
+        assert_eq!(16, func.stack_size);
+    }
+
+    #[test]
+    fn locates_arguments_as_abi_specifies() {
+        let mut tys = vec![Ty::Int; 5];
+        tys.extend([Ty::LongDouble, Ty::Char, Ty::Int]);
+        tys.extend(vec![Ty::Double; 8]);
+        tys.extend([Ty::Float, Ty::LongDouble]);
+        let (locs, size) = arg_locs(&tys);
+        assert_eq!(ArgLoc::Reg(4), locs[4]);
+        assert_eq!(ArgLoc::Stack(0), locs[5]);
+        assert_eq!(ArgLoc::Reg(5), locs[6]);
+        assert_eq!(ArgLoc::Stack(16), locs[7]);
+        assert_eq!(ArgLoc::FpReg(7), locs[15]);
+        assert_eq!(ArgLoc::Stack(24), locs[16]);
+        // A long double is aligned to 16 bytes.
+        assert_eq!(ArgLoc::Stack(32), locs[17]);
+        assert_eq!(48, size);
+    }
+
+    #[test]
+    fn cannot_define_non_function() {
+        // (int x) { }
+        let tokens = vec![Token::Punct("{".to_string()), Token::Punct("}".to_string())];
+        let decl = Declarator {
+            name: Some("x".to_string()),
+            ty: Ty::Int,
+            param_names: vec![],
+        };
+        assert!(parse_func(decl, false, &tokens, &mut Scope::new()).is_err());
+    }
 }
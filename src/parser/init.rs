@@ -0,0 +1,852 @@
+use super::{
+    consume_punct,
+    decl::Reloc,
+    expr::{
+        check_value, convert, eval, eval_addr, eval_float, eval_long_double, parse_assign,
+        parse_string, Binary, Expr, Var,
+    },
+    scope::Scope,
+};
+use crate::lexer::Token;
+use crate::ty::{BitField, Member, StructRef, Ty};
+
+// Initializer of an object, which has the same structure as its type.
+//
+// A structure or union initialized by an expression of its type as a whole,
+// e.g. `struct point p = q;`, is initialized as if it were a scalar.
+#[derive(Debug, PartialEq)]
+pub(super) enum Init {
+    Scalar(Option<Expr>), // None if zero-initialized
+    Array(Vec<Init>),
+    Struct(Vec<Init>),       // one for each member except unnamed bit-fields
+    Union(usize, Box<Init>), // the index of the member initialized, and its initializer
+}
+
+impl Init {
+    // Returns an initializer of the type which initializes nothing yet. A
+    // union initializes its first member unless designated otherwise.
+    fn new(ty: &Ty) -> Self {
+        match ty.unqual() {
+            Ty::Array(elem, len) => Init::Array((0..*len).map(|_| Init::new(elem)).collect()),
+            Ty::IncompleteArray(_) => Init::Array(Vec::new()),
+            Ty::Struct(s) if s.is_union() => match s.members().first() {
+                Some(member) => Init::Union(0, Box::new(Init::new(&member.ty))),
+                None => Init::Struct(Vec::new()),
+            },
+            Ty::Struct(s) => Init::Struct(s.members().iter().map(|m| Init::new(&m.ty)).collect()),
+            _ => Init::Scalar(None),
+        }
+    }
+
+    // Returns the initializer of the i-th member of a structure or union,
+    // which a union switches to from the member initialized before.
+    //
+    // A flexible array member cannot be initialized, since it is outside the
+    // size of the structure.
+    fn member(&mut self, s: &StructRef, i: usize) -> Result<&mut Init, String> {
+        let members = s.members();
+        if i >= members.len() {
+            return match s.is_union() {
+                true => Err("excess elements in union initializer".to_string()),
+                false => Err("excess elements in struct initializer".to_string()),
+            };
+        }
+        if let Ty::IncompleteArray(_) = members[i].ty {
+            return Err("initialization of a flexible array member".to_string());
+        }
+        match self {
+            Init::Struct(inits) => Ok(&mut inits[i]),
+            Init::Union(active, init) => {
+                if *active != i {
+                    *active = i;
+                    **init = Init::new(&members[i].ty);
+                }
+                Ok(init)
+            }
+            _ => unreachable!("member of a non-structure initializer"),
+        }
+    }
+
+    // Returns the initializer of the i-th element of an array, growing the
+    // array if its length is unknown.
+    fn elem(&mut self, ty: &Ty, i: usize) -> Result<&mut Init, String> {
+        let elems = match self {
+            Init::Array(elems) => elems,
+            _ => unreachable!("element of a non-array initializer"),
+        };
+        match ty {
+            Ty::Array(_, len) if i >= *len => {
+                Err("excess elements in array initializer".to_string())
+            }
+            Ty::IncompleteArray(elem) if i >= elems.len() => {
+                elems.resize_with(i + 1, || Init::new(elem));
+                Ok(&mut elems[i])
+            }
+            _ => Ok(&mut elems[i]),
+        }
+    }
+}
+
+// <initializer> ::= string-literal
+//                 | "{" <init-list> "}"
+//                 | <assign>
+// <init-list>   ::= <init-item> ("," <init-item>)* ","?
+// <init-item>   ::= <designation>? <initializer>
+// <designation> ::= <designator>+ "="
+// <designator>  ::= "[" <const-expr> "]"
+//                 | "." ident
+//
+// Parses an initializer of an object of `ty`, and returns the type completed
+// by the initializer, e.g. `int [3]` for `int a[] = {1, 2, 3}`.
+//
+// As in C, braces around the initializer of a nested array, structure or
+// union may be omitted, in which case it takes as many initializers as its
+// elements or members.
+pub(super) fn parse_initializer<'a>(
+    ty: &Ty,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Init, Ty, &'a [Token]), String> {
+    if ty.is_array()
+        && consume_punct(tokens, "{").is_err()
+        && !matches!(tokens.first(), Some(Token::Str(_)))
+    {
+        return Err("expected a brace-enclosed initializer for an array".to_string());
+    }
+    if let Ty::Struct(s) = ty.unqual() {
+        if !s.is_complete() {
+            return Err(format!("initializer for an incomplete type: {:?}", s));
+        }
+        // Braces are only omitted in an enclosing initializer list.
+        if consume_punct(tokens, "{").is_err() {
+            let (expr, rest) = parse_assign(tokens, scope)?;
+            check_value(&expr)?;
+            return Ok((Init::Scalar(Some(convert(expr, ty)?)), ty.clone(), rest));
+        }
+    }
+
+    let mut init = Init::new(ty);
+    let rest = initializer(&mut init, ty, tokens, scope)?;
+    let ty = match (ty, &init) {
+        (Ty::IncompleteArray(elem), Init::Array(elems)) => Ty::Array(elem.clone(), elems.len()),
+        _ => ty.clone(),
+    };
+    Ok((init, ty, rest))
+}
+
+fn initializer<'a>(
+    init: &mut Init,
+    ty: &Ty,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<&'a [Token], String> {
+    if let Ty::Struct(s) = ty.unqual() {
+        return struct_initializer(init, ty, s, tokens, scope);
+    }
+    if !ty.is_array() {
+        return scalar_initializer(init, ty, tokens, scope);
+    }
+    if let Some((s, rest)) = parse_string(tokens) {
+        return string_initializer(init, ty, s, rest);
+    }
+    // The string literal for a character array may be enclosed in braces.
+    if ty.pointee().map(Ty::unqual) == Some(&Ty::Char) {
+        if let Some((s, rest)) = consume_punct(tokens, "{").ok().and_then(parse_string) {
+            if let Some(rest) = consume_end(rest) {
+                return string_initializer(init, ty, s, rest);
+            }
+        }
+    }
+    match consume_punct(tokens, "{") {
+        Ok(rest) => braced_array_initializer(init, ty, rest, scope),
+        Err(_) => array_initializer(init, ty, tokens, 0, scope),
+    }
+}
+
+// A scalar may be initialized with a brace-enclosed expression, e.g.
+// `int x = {3};`.
+fn scalar_initializer<'a>(
+    init: &mut Init,
+    ty: &Ty,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<&'a [Token], String> {
+    match consume_punct(tokens, "{") {
+        Ok(rest) => {
+            let (expr, rest) = parse_assign(rest, scope)?;
+            check_value(&expr)?;
+            *init = Init::Scalar(Some(convert(expr, ty)?));
+            let rest = consume_punct(rest, ",").unwrap_or(rest);
+            consume_punct(rest, "}")
+        }
+        Err(_) => {
+            let (expr, rest) = parse_assign(tokens, scope)?;
+            check_value(&expr)?;
+            *init = Init::Scalar(Some(convert(expr, ty)?));
+            Ok(rest)
+        }
+    }
+}
+
+// A structure or union is initialized with an expression of its type, or by
+// the initializers of its members, which may be enclosed in braces.
+//
+// Whether an expression has its type is only known after it is parsed, so an
+// expression of another type is parsed again as the initializer of the first
+// member.
+fn struct_initializer<'a>(
+    init: &mut Init,
+    ty: &Ty,
+    s: &StructRef,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<&'a [Token], String> {
+    if let Ok(rest) = consume_punct(tokens, "{") {
+        return braced_struct_initializer(init, s, rest, scope);
+    }
+    let (expr, rest) = parse_assign(tokens, scope)?;
+    if let Ty::Struct(_) = expr.ty() {
+        *init = Init::Scalar(Some(convert(expr, ty)?));
+        return Ok(rest);
+    }
+    struct_member_initializer(init, s, tokens, 0, scope)
+}
+
+// Parses the initializers of the members in braces, of which the opening
+// brace has already been consumed.
+//
+// Only one member of a union is initialized, so an initializer without a
+// designator must be the first in the list.
+fn braced_struct_initializer<'a>(
+    init: &mut Init,
+    s: &StructRef,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<&'a [Token], String> {
+    let members = s.members();
+    let mut rest = tokens;
+    let mut i = 0;
+    let mut first = true;
+    loop {
+        if let Some(r) = consume_end(rest) {
+            return Ok(r);
+        }
+        if !first {
+            rest = consume_punct(rest, ",")?;
+        }
+        first = false;
+
+        if consume_punct(rest, ".").is_ok() {
+            (i, rest) = member_designation(init, s, rest, scope)?;
+        } else if consume_punct(rest, "[").is_ok() {
+            return Err("array designator for a non-array type".to_string());
+        } else {
+            if s.is_union() && i > 0 {
+                return Err("excess elements in union initializer".to_string());
+            }
+            rest = initializer(init.member(s, i)?, &members[i].ty, rest, scope)?;
+        }
+        i += 1;
+    }
+}
+
+// Parses the initializers of the members of a nested structure or union
+// without braces, starting from the `start`-th member. A union takes only one
+// for its first member.
+//
+// It stops at a designator, which designates an element or a member of an
+// enclosing object.
+fn struct_member_initializer<'a>(
+    init: &mut Init,
+    s: &StructRef,
+    tokens: &'a [Token],
+    start: usize,
+    scope: &mut Scope,
+) -> Result<&'a [Token], String> {
+    let members = s.members();
+    let len = match s.is_union() {
+        true => members.len().min(1),
+        false => members.len(),
+    };
+    let mut rest = tokens;
+    for (i, m) in members.iter().enumerate().take(len).skip(start) {
+        if consume_end(rest).is_some() {
+            break;
+        }
+        let before = rest;
+        if i > 0 {
+            rest = consume_punct(rest, ",")?;
+        }
+        if consume_punct(rest, "[").is_ok() || consume_punct(rest, ".").is_ok() {
+            return Ok(before);
+        }
+        rest = initializer(init.member(s, i)?, &m.ty, rest, scope)?;
+    }
+    Ok(rest)
+}
+
+// Parses a designation which begins with a field designator, and the
+// initializer of the member designated. Returns the index of the member.
+//
+// A member of an anonymous structure or union member is designated through
+// it, e.g. `.x` of `struct { union { int x; }; }` designates the anonymous
+// union with the same designator.
+fn member_designation<'a>(
+    init: &mut Init,
+    s: &StructRef,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(usize, &'a [Token]), String> {
+    let members = s.members();
+    let name = match tokens.get(1) {
+        Some(Token::Ident(name)) => name,
+        _ => return Err("expected a member name".to_string()),
+    };
+    if let Some(i) = members.iter().position(|m| m.name.as_ref() == Some(name)) {
+        let rest = designation(init.member(s, i)?, &members[i].ty, &tokens[2..], scope)?;
+        return Ok((i, rest));
+    }
+    let i = members
+        .iter()
+        .position(|m| match (&m.name, m.ty.unqual()) {
+            (None, Ty::Struct(s)) => s.member_names().contains(name),
+            _ => false,
+        })
+        .ok_or_else(|| format!("{:?} has no member named {}", s, name))?;
+    let rest = designation(init.member(s, i)?, &members[i].ty, tokens, scope)?;
+    Ok((i, rest))
+}
+
+// A character array is initialized with the characters of a string literal,
+// followed by a null character if the array has room for it.
+fn string_initializer<'a>(
+    init: &mut Init,
+    ty: &Ty,
+    s: Vec<u8>,
+    rest: &'a [Token],
+) -> Result<&'a [Token], String> {
+    let len = match ty {
+        Ty::Array(elem, len) if *elem.unqual() == Ty::Char => (*len).min(s.len()),
+        Ty::IncompleteArray(elem) if *elem.unqual() == Ty::Char => {
+            init.elem(ty, s.len())?;
+            s.len()
+        }
+        _ => return Err("string literal initializer for a non-character array".to_string()),
+    };
+    for (i, c) in s.into_iter().take(len).enumerate() {
+        *init.elem(ty, i)? = Init::Scalar(Some(Expr::Num(c as i64, Ty::Int)));
+    }
+    Ok(rest)
+}
+
+// Parses the initializers of the elements in braces, of which the opening
+// brace has already been consumed.
+fn braced_array_initializer<'a>(
+    init: &mut Init,
+    ty: &Ty,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<&'a [Token], String> {
+    let elem_ty = ty.pointee().unwrap();
+    let mut rest = tokens;
+    let mut i = 0;
+    let mut first = true;
+    loop {
+        if let Some(r) = consume_end(rest) {
+            return Ok(r);
+        }
+        if !first {
+            rest = consume_punct(rest, ",")?;
+        }
+        first = false;
+
+        if let Ok(r) = consume_punct(rest, "[") {
+            (i, rest) = parse_index(r, scope)?;
+            rest = designation(init.elem(ty, i)?, elem_ty, rest, scope)?;
+        } else {
+            check_field_designator(rest)?;
+            rest = initializer(init.elem(ty, i)?, elem_ty, rest, scope)?;
+        }
+        i += 1;
+    }
+}
+
+// Parses the initializers of the elements of a nested array without braces,
+// starting from the `start`-th element.
+//
+// It stops at a designator, which designates an element of an enclosing
+// array.
+fn array_initializer<'a>(
+    init: &mut Init,
+    ty: &Ty,
+    tokens: &'a [Token],
+    start: usize,
+    scope: &mut Scope,
+) -> Result<&'a [Token], String> {
+    let (elem_ty, len) = match ty {
+        Ty::Array(elem, len) => (elem, *len),
+        _ => return Err("expected a brace-enclosed initializer for an array".to_string()),
+    };
+    let mut rest = tokens;
+    for i in start..len {
+        if consume_end(rest).is_some() {
+            break;
+        }
+        let before = rest;
+        if i > 0 {
+            rest = consume_punct(rest, ",")?;
+        }
+        if consume_punct(rest, "[").is_ok() || consume_punct(rest, ".").is_ok() {
+            return Ok(before);
+        }
+        rest = initializer(init.elem(ty, i)?, elem_ty, rest, scope)?;
+    }
+    Ok(rest)
+}
+
+// Parses the rest of a designation after a designator, and the initializer
+// of the element or member designated.
+//
+// A nested designator, e.g. `[2]` of `[1][2] = x` or `.y` of `.p.y = x`,
+// designates an element or a member of the object designated, and the
+// following initializers without designators initialize the elements or
+// members after it.
+fn designation<'a>(
+    init: &mut Init,
+    ty: &Ty,
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<&'a [Token], String> {
+    if let Ok(r) = consume_punct(tokens, "[") {
+        let elem_ty = match ty {
+            Ty::Array(elem, _) => elem,
+            _ => return Err("array designator for a non-array type".to_string()),
+        };
+        let (i, rest) = parse_index(r, scope)?;
+        let rest = designation(init.elem(ty, i)?, elem_ty, rest, scope)?;
+        return array_initializer(init, ty, rest, i + 1, scope);
+    }
+    if consume_punct(tokens, ".").is_ok() {
+        let s = match ty.unqual() {
+            Ty::Struct(s) => s,
+            _ => return Err("field designator for a non-structure type".to_string()),
+        };
+        let (i, rest) = member_designation(init, s, tokens, scope)?;
+        return struct_member_initializer(init, s, rest, i + 1, scope);
+    }
+    let rest = consume_punct(tokens, "=")?;
+    initializer(init, ty, rest, scope)
+}
+
+// Parses the index of an array designator after "[".
+fn parse_index<'a>(tokens: &'a [Token], scope: &mut Scope) -> Result<(usize, &'a [Token]), String> {
+    let (expr, rest) = parse_assign(tokens, scope)?;
+    let i = eval(&expr)?;
+    if i < 0 {
+        return Err("negative array index in initializer".to_string());
+    }
+    Ok((i as usize, consume_punct(rest, "]")?))
+}
+
+// A field designator only designates a member of a structure or union, so it
+// is an error in the initializer list of an array.
+fn check_field_designator(tokens: &[Token]) -> Result<(), String> {
+    match consume_punct(tokens, ".") {
+        Ok(_) => Err("field designator for a non-structure type".to_string()),
+        Err(_) => Ok(()),
+    }
+}
+
+// Consumes the end of an initializer list, which may have a trailing comma.
+fn consume_end(tokens: &[Token]) -> Option<&[Token]> {
+    let rest = consume_punct(tokens, ",").unwrap_or(tokens);
+    consume_punct(rest, "}").ok()
+}
+
+// Returns assignments which initialize a local variable, in order.
+//
+// Every scalar of the variable is assigned, so that elements without
+// initializers are zero-initialized.
+pub(super) fn init_local(var: &Var, init: Init) -> Vec<Expr> {
+    let mut assigns: Vec<Expr> = Vec::new();
+    assign_scalars(var, &var.ty, 0, init, &mut assigns);
+    assigns
+}
+
+// The scalar at `offset` bytes from the start of a variable is given as
+// another variable, which is at the lower address by `offset` in the stack
+// frame.
+//
+// A bit-field is assigned as the member of the structure at the offset, since
+// it shares its bytes with other members.
+fn assign_scalars(var: &Var, ty: &Ty, offset: usize, init: Init, assigns: &mut Vec<Expr>) {
+    let mut assign_member = |member: Member, init: Init| match (member.bit_field, init) {
+        (Some(_), Init::Scalar(expr)) => {
+            let lhs = Expr::Member(
+                Box::new(Expr::Var(Var {
+                    name: var.name.clone(),
+                    ty: ty.clone(),
+                    offset: var.offset - offset,
+                })),
+                member,
+            );
+            let rhs = expr.unwrap_or(Expr::Num(0, Ty::Int));
+            assigns.push(Expr::Assign(Box::new(Binary { lhs, rhs })));
+        }
+        (_, init) => assign_scalars(var, &member.ty, offset + member.offset, init, assigns),
+    };
+    match init {
+        Init::Array(elems) => {
+            let elem_ty = ty.pointee().unwrap();
+            for (i, elem) in elems.into_iter().enumerate() {
+                assign_scalars(var, elem_ty, offset + i * elem_ty.size(), elem, assigns);
+            }
+        }
+        Init::Struct(inits) => {
+            let s = match ty.unqual() {
+                Ty::Struct(s) => s,
+                _ => unreachable!("structure initializer of a non-structure"),
+            };
+            for (member, init) in s.members().into_iter().zip(inits) {
+                assign_member(member, init);
+            }
+        }
+        Init::Union(i, init) => {
+            let s = match ty.unqual() {
+                Ty::Struct(s) => s,
+                _ => unreachable!("union initializer of a non-union"),
+            };
+            assign_member(s.members().swap_remove(i), *init);
+        }
+        Init::Scalar(expr) => {
+            let lhs = Expr::Var(Var {
+                name: var.name.clone(),
+                ty: ty.clone(),
+                offset: var.offset - offset,
+            });
+            let rhs = expr.unwrap_or(Expr::Num(0, Ty::Int));
+            assigns.push(Expr::Assign(Box::new(Binary { lhs, rhs })));
+        }
+    }
+}
+
+// Evaluates the initializer of a variable with static storage duration into
+// its bytes, since every initializer must be a constant.
+//
+// The address of a symbol is not known until linked, so it is left zero in
+// the bytes, and returned as a relocation.
+pub(super) fn eval_init(ty: &Ty, init: &Init) -> Result<(Vec<u8>, Vec<Reloc>), String> {
+    let mut buf = vec![0; ty.size()];
+    let mut relocs: Vec<Reloc> = Vec::new();
+    write_scalars(&mut buf, &mut relocs, ty, 0, init)?;
+    Ok((buf, relocs))
+}
+
+fn write_scalars(
+    buf: &mut [u8],
+    relocs: &mut Vec<Reloc>,
+    ty: &Ty,
+    offset: usize,
+    init: &Init,
+) -> Result<(), String> {
+    let mut write_member = |member: &Member, init: &Init| match (member.bit_field, init) {
+        (Some(bit_field), Init::Scalar(Some(expr))) => {
+            write_bit_field(buf, offset + member.offset, bit_field, eval(expr)?);
+            Ok(())
+        }
+        (Some(_), _) => Ok(()),
+        (None, init) => write_scalars(buf, relocs, &member.ty, offset + member.offset, init),
+    };
+    match init {
+        Init::Array(elems) => {
+            let elem_ty = ty.pointee().unwrap();
+            for (i, elem) in elems.iter().enumerate() {
+                write_scalars(buf, relocs, elem_ty, offset + i * elem_ty.size(), elem)?;
+            }
+        }
+        Init::Struct(inits) => {
+            let s = match ty.unqual() {
+                Ty::Struct(s) => s,
+                _ => unreachable!("structure initializer of a non-structure"),
+            };
+            for (member, init) in s.members().iter().zip(inits) {
+                write_member(member, init)?;
+            }
+        }
+        Init::Union(i, init) => {
+            let s = match ty.unqual() {
+                Ty::Struct(s) => s,
+                _ => unreachable!("union initializer of a non-union"),
+            };
+            write_member(&s.members()[*i], init)?;
+        }
+        Init::Scalar(Some(expr)) => {
+            let size = ty.size();
+            let bytes = match ty.unqual() {
+                Ty::Float => (eval_float(expr)? as f32).to_le_bytes().to_vec(),
+                Ty::Double => eval_float(expr)?.to_le_bytes().to_vec(),
+                Ty::LongDouble => eval_long_double(expr)?.to_le_bytes().to_vec(),
+                Ty::Ptr(_) => match eval_addr(expr)? {
+                    (Some(label), addend) => {
+                        relocs.push(Reloc {
+                            offset,
+                            label,
+                            addend,
+                        });
+                        vec![0; size]
+                    }
+                    (None, addr) => addr.to_le_bytes().to_vec(),
+                },
+                _ => eval(expr)?.to_le_bytes().to_vec(),
+            };
+            // The padding of a long double is left zero.
+            let size = size.min(bytes.len());
+            buf[offset..offset + size].copy_from_slice(&bytes[..size]);
+        }
+        Init::Scalar(None) => (),
+    }
+    Ok(())
+}
+
+// Writes the lower bits of the value to a bit-field in the bytes from
+// `offset`, leaving the other bits as they are.
+fn write_bit_field(buf: &mut [u8], offset: usize, bit_field: BitField, val: i64) {
+    let BitField { bit_offset, width } = bit_field;
+    for i in 0..width {
+        let pos = bit_offset + i;
+        let byte = &mut buf[offset + pos / 8];
+        if (val >> i) & 1 == 1 {
+            *byte |= 1 << (pos % 8);
+        } else {
+            *byte &= !(1 << (pos % 8));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::IntKind;
+    use crate::ty::MemberDecl;
+
+    fn int_array(len: usize) -> Ty {
+        Ty::Array(Box::new(Ty::Int), len)
+    }
+
+    fn punct(p: &str) -> Token {
+        Token::Punct(p.to_string())
+    }
+
+    #[test]
+    fn completes_array_length() {
+        // {1, 2, 3,}
+        let tokens = vec![
+            punct("{"),
+            Token::Num(1, IntKind::Int),
+            punct(","),
+            Token::Num(2, IntKind::Int),
+            punct(","),
+            Token::Num(3, IntKind::Int),
+            punct(","),
+            punct("}"),
+        ];
+        let ty = Ty::IncompleteArray(Box::new(Ty::Int));
+        let (init, ty, rest) = parse_initializer(&ty, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(int_array(3), ty);
+        assert_eq!(Vec::<Token>::new(), rest);
+        assert_eq!(
+            vec![1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0],
+            eval_init(&ty, &init).unwrap().0
+        );
+    }
+
+    #[test]
+    fn parses_nested_initializer_without_braces() {
+        // {1, 2, {3}, [0][1] = 4, 5}
+        let tokens = vec![
+            punct("{"),
+            Token::Num(1, IntKind::Int),
+            punct(","),
+            Token::Num(2, IntKind::Int),
+            punct(","),
+            punct("{"),
+            Token::Num(3, IntKind::Int),
+            punct("}"),
+            punct(","),
+            punct("["),
+            Token::Num(0, IntKind::Int),
+            punct("]"),
+            punct("["),
+            Token::Num(1, IntKind::Int),
+            punct("]"),
+            punct("="),
+            Token::Num(4, IntKind::Int),
+            punct(","),
+            Token::Num(5, IntKind::Int),
+            punct("}"),
+        ];
+        let ty = Ty::Array(Box::new(int_array(2)), 2);
+        let (init, _, _) = parse_initializer(&ty, &tokens, &mut Scope::new()).unwrap();
+        let bytes = eval_init(&ty, &init).unwrap().0;
+        let ints: Vec<u8> = bytes.chunks(4).map(|c| c[0]).collect();
+        assert_eq!(vec![1, 4, 5, 0], ints);
+    }
+
+    #[test]
+    fn parses_designated_initializer() {
+        // {[2] = 7, 8}
+        let tokens = vec![
+            punct("{"),
+            punct("["),
+            Token::Num(2, IntKind::Int),
+            punct("]"),
+            punct("="),
+            Token::Num(7, IntKind::Int),
+            punct(","),
+            Token::Num(8, IntKind::Int),
+            punct("}"),
+        ];
+        let ty = Ty::IncompleteArray(Box::new(Ty::Int));
+        let (init, ty, _) = parse_initializer(&ty, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(int_array(4), ty);
+        let bytes = eval_init(&ty, &init).unwrap().0;
+        let ints: Vec<u8> = bytes.chunks(4).map(|c| c[0]).collect();
+        assert_eq!(vec![0, 0, 7, 8], ints);
+    }
+
+    #[test]
+    fn cannot_initialize_excess_elements() {
+        // {1, 2}
+        let tokens = vec![
+            punct("{"),
+            Token::Num(1, IntKind::Int),
+            punct(","),
+            Token::Num(2, IntKind::Int),
+            punct("}"),
+        ];
+        assert!(parse_initializer(&int_array(1), &tokens, &mut Scope::new()).is_err());
+    }
+
+    #[test]
+    fn cannot_use_field_designator() {
+        // {.x = 1}
+        let tokens = vec![
+            punct("{"),
+            punct("."),
+            Token::Ident("x".to_string()),
+            punct("="),
+            Token::Num(1, IntKind::Int),
+            punct("}"),
+        ];
+        assert!(parse_initializer(&int_array(1), &tokens, &mut Scope::new()).is_err());
+    }
+
+    #[test]
+    fn initializes_bit_fields_with_designators() {
+        // struct { char a; int b : 3; int c : 30; }
+        let s = StructRef::new(None, false);
+        let decl = |name: &str, ty, width| MemberDecl {
+            name: Some(name.to_string()),
+            ty,
+            width,
+            align: 0,
+            is_packed: false,
+        };
+        s.complete(
+            vec![
+                decl("a", Ty::Char, None),
+                decl("b", Ty::Int, Some(3)),
+                decl("c", Ty::Int, Some(30)),
+            ],
+            false,
+            0,
+        );
+        let ty = Ty::Struct(s);
+
+        // {.b = 3, 100}
+        let tokens = vec![
+            punct("{"),
+            punct("."),
+            Token::Ident("b".to_string()),
+            punct("="),
+            Token::Num(3, IntKind::Int),
+            punct(","),
+            Token::Num(100, IntKind::Int),
+            punct("}"),
+        ];
+        let (init, _, _) = parse_initializer(&ty, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(
+            vec![0, 3, 0, 0, 100, 0, 0, 0],
+            eval_init(&ty, &init).unwrap().0
+        );
+
+        // {1, 2, 3, 4}
+        let tokens = vec![
+            punct("{"),
+            Token::Num(1, IntKind::Int),
+            punct(","),
+            Token::Num(2, IntKind::Int),
+            punct(","),
+            Token::Num(3, IntKind::Int),
+            punct(","),
+            Token::Num(4, IntKind::Int),
+            punct("}"),
+        ];
+        assert_eq!(
+            Err("excess elements in struct initializer".to_string()),
+            parse_initializer(&ty, &tokens, &mut Scope::new()).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn initializes_char_array_with_string() {
+        let tokens = vec![Token::Str(b"ab".to_vec())];
+        let ty = Ty::IncompleteArray(Box::new(Ty::Char));
+        let (init, ty, _) = parse_initializer(&ty, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(Ty::Array(Box::new(Ty::Char), 3), ty);
+        assert_eq!(vec![b'a', b'b', 0], eval_init(&ty, &init).unwrap().0);
+
+        let ty = Ty::Array(Box::new(Ty::Char), 2);
+        let (init, _, _) = parse_initializer(&ty, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(vec![b'a', b'b'], eval_init(&ty, &init).unwrap().0);
+    }
+
+    #[test]
+    fn initializes_char_array_with_braced_string() {
+        // {"ab",}
+        let tokens = vec![
+            Token::Punct("{".to_string()),
+            Token::Str(b"ab".to_vec()),
+            Token::Punct(",".to_string()),
+            Token::Punct("}".to_string()),
+        ];
+        let ty = Ty::IncompleteArray(Box::new(Ty::Char));
+        let (init, ty, rest) = parse_initializer(&ty, &tokens, &mut Scope::new()).unwrap();
+        assert_eq!(vec![b'a', b'b', 0], eval_init(&ty, &init).unwrap().0);
+        assert_eq!(Vec::<Token>::new(), rest);
+    }
+
+    #[test]
+    fn initializes_local_array() {
+        // int a[2] = {3};
+        let var = Var {
+            name: "a".to_string(),
+            ty: int_array(2),
+            offset: 8,
+        };
+        let init = Init::Array(vec![
+            Init::Scalar(Some(Expr::Num(3, Ty::Int))),
+            Init::Scalar(None),
+        ]);
+        let elem = |offset, val| {
+            let lhs = Expr::Var(Var {
+                name: "a".to_string(),
+                ty: Ty::Int,
+                offset,
+            });
+            let rhs = Expr::Num(val, Ty::Int);
+            Expr::Assign(Box::new(Binary { lhs, rhs }))
+        };
+        assert_eq!(vec![elem(8, 3), elem(4, 0)], init_local(&var, init));
+    }
+}
@@ -0,0 +1,680 @@
+use super::decl::{Data, Reloc};
+use super::expr::{GVar, VaArgs, Var};
+use crate::ty::{align_to, Quals, Ty};
+use std::collections::HashMap;
+
+// Symbol bound to an ordinary identifier.
+#[derive(Debug, PartialEq)]
+pub enum Symbol {
+    EnumConst(i64), // enumerator constant
+    Typedef(Ty),    // typedef name
+    Var(Var),       // local variable
+    GVar(GVar),     // static local variable
+    Global(Global), // function or variable with linkage
+}
+
+// Function or variable with linkage, which may be declared more than once but
+// defined only once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub ty: Ty,
+    pub is_defined: bool,
+    pub is_static: bool, // true if it has internal linkage
+}
+
+// Block scopes of identifiers, from the file scope (first) to the innermost
+// block scope (last).
+//
+// Ordinary identifiers and tags (e.g. `E` of `enum E`) are in separate name
+// spaces, so they are looked up independently.
+//
+// The file scope starts with __builtin_va_list declared, which is the type of
+// variable arguments of a variadic function, and which <stdarg.h> declares
+// va_list as.
+//
+// This also keeps the return type of the function being parsed, allocates
+// stack slots for its local variables, and collects static local variables of
+// all functions and string literals.
+pub struct Scope {
+    symbols: Vec<HashMap<String, Symbol>>,
+    // every function and variable with linkage declared so far, including
+    // those declared in blocks which have been left, with the composite type
+    // of all the declarations
+    linked: HashMap<String, Global>,
+    tags: Vec<HashMap<String, Ty>>,
+    // whether each scope allocates variable length arrays, which are freed on
+    // leaving it
+    has_vla: Vec<bool>,
+    ret: Option<Ty>, // return type of the function being parsed
+    // name of the function being parsed, and `__func__` once it is used
+    func_name: Option<(String, Option<GVar>)>,
+    // variable arguments of the function being parsed, if it is variadic, and
+    // the name of its last parameter, which va_start is given
+    va_args: Option<(VaArgs, String)>,
+    stack_size: usize,
+    // alignment of the stack frame, which is more than 16 bytes if a local
+    // variable requires it
+    frame_align: usize,
+    statics: Vec<Data>,
+}
+
+impl Scope {
+    pub fn new() -> Self {
+        let mut file_scope = HashMap::new();
+        file_scope.insert(
+            "__builtin_va_list".to_string(),
+            Symbol::Typedef(Ty::va_list()),
+        );
+        Self {
+            symbols: vec![file_scope],
+            linked: HashMap::new(),
+            tags: vec![HashMap::new()],
+            has_vla: vec![false],
+            ret: None,
+            func_name: None,
+            va_args: None,
+            stack_size: 0,
+            frame_align: 16,
+            statics: Vec::new(),
+        }
+    }
+
+    // Enters the outermost block scope of a function returning `ret`.
+    pub fn enter_func(&mut self, ret: Ty) {
+        self.ret = Some(ret);
+        self.stack_size = 0;
+        self.frame_align = 16;
+        self.enter();
+    }
+
+    // Leaves the outermost block scope of a function, and returns the size and
+    // the alignment of its stack frame. The frame is aligned to at least 16
+    // bytes as the ABI requires.
+    pub fn leave_func(&mut self) -> (usize, usize) {
+        self.ret = None;
+        self.func_name = None;
+        self.va_args = None;
+        self.leave();
+        (align_to(self.stack_size, 16), self.frame_align)
+    }
+
+    // Enters a new block scope.
+    pub fn enter(&mut self) {
+        self.symbols.push(HashMap::new());
+        self.tags.push(HashMap::new());
+        self.has_vla.push(false);
+    }
+
+    // Leaves the innermost block scope, forgetting everything declared in it.
+    pub fn leave(&mut self) {
+        assert!(self.symbols.len() > 1, "cannot leave the file scope");
+        self.symbols.pop();
+        self.tags.pop();
+        self.has_vla.pop();
+    }
+
+    // Enters the scope of parameters of a function declarator, where a
+    // parameter can be referred to by the following ones, e.g. `n` in
+    // `int f(int n, int a[][n])`. Returns the stack size to be restored by
+    // `leave_params`.
+    //
+    // Parameters are allocated from the start of a stack frame, in the same
+    // way as when the function is defined, so that references to them are
+    // valid in its body.
+    pub fn enter_params(&mut self) -> usize {
+        self.enter();
+        std::mem::replace(&mut self.stack_size, 0)
+    }
+
+    // Leaves the scope of parameters.
+    pub fn leave_params(&mut self, stack_size: usize) {
+        self.leave();
+        self.stack_size = stack_size;
+    }
+
+    // Records that a variable length array is allocated in the innermost
+    // scope.
+    pub fn mark_vla(&mut self) {
+        *self.has_vla.last_mut().unwrap() = true;
+    }
+
+    // Returns true if a variable length array is allocated in the innermost
+    // scope.
+    pub fn has_vla(&self) -> bool {
+        *self.has_vla.last().unwrap()
+    }
+
+    // Declares an ordinary identifier in the innermost scope.
+    //
+    // A typedef name may be redefined there to denote a compatible type, unless
+    // it is variably modified. The first definition is kept then.
+    pub fn declare(&mut self, name: &str, sym: Symbol) -> Result<(), String> {
+        let innermost = self.symbols.last_mut().unwrap();
+        match (innermost.get(name), &sym) {
+            (Some(Symbol::Typedef(old)), Symbol::Typedef(new))
+                if old.is_compatible(new)
+                    && !old.is_variably_modified()
+                    && !new.is_variably_modified() =>
+            {
+                Ok(())
+            }
+            (Some(Symbol::Typedef(old)), Symbol::Typedef(new)) => Err(format!(
+                "conflicting types for {}: {} and {}",
+                name, old, new
+            )),
+            (Some(_), _) => Err(format!("redeclaration of {}", name)),
+            (None, _) => {
+                innermost.insert(name.to_string(), sym);
+                Ok(())
+            }
+        }
+    }
+
+    // Returns the return type of the function being parsed.
+    pub fn ret_ty(&self) -> &Ty {
+        self.ret.as_ref().expect("return type outside a function")
+    }
+
+    // Sets where the variable arguments of the function being parsed are,
+    // which follow the parameter named `last`.
+    pub fn set_va_args(&mut self, va_args: VaArgs, last: &str) {
+        self.va_args = Some((va_args, last.to_string()));
+    }
+
+    // Returns where the variable arguments of the function being parsed are,
+    // and the name of the last parameter, or None if it is not variadic.
+    pub fn va_args(&self) -> Option<(&VaArgs, &str)> {
+        self.va_args
+            .as_ref()
+            .map(|(va_args, last)| (va_args, last.as_str()))
+    }
+
+    // Sets the name of the function being parsed, which `__func__` has.
+    pub fn set_func_name(&mut self, name: &str) {
+        self.func_name = Some((name.to_string(), None));
+    }
+
+    // Returns `__func__` of the function being parsed, which is declared as if
+    // by `static const char __func__[] = "name";` at the start of its body
+    // when it is first used. Returns None outside a function.
+    pub fn func_name(&mut self) -> Option<GVar> {
+        let (name, var) = self.func_name.as_ref()?;
+        if let Some(var) = var {
+            return Some(var.clone());
+        }
+        let mut init = name.clone().into_bytes();
+        init.push(0);
+        let quals = Quals {
+            is_const: true,
+            ..Quals::default()
+        };
+        let var = GVar {
+            name: format!("__func__.{}", self.statics.len()),
+            ty: Ty::Array(Box::new(Ty::Char.qualify(quals)), init.len()),
+        };
+        self.statics.push(Data {
+            name: var.name.clone(),
+            ty: var.ty.clone(),
+            init: Some(init),
+            relocs: Vec::new(),
+            is_static: true,
+            align: 1,
+        });
+        self.func_name.as_mut().unwrap().1 = Some(var.clone());
+        Some(var)
+    }
+
+    // Returns true if no function is being parsed.
+    pub fn is_file_scope(&self) -> bool {
+        self.symbols.len() == 1
+    }
+
+    // Declares a local variable in the innermost scope, allocating a stack slot
+    // for it.
+    pub fn declare_local(&mut self, name: &str, ty: Ty) -> Result<Var, String> {
+        let var = self.alloc_local(name, ty);
+        self.declare(name, Symbol::Var(var.clone()))?;
+        Ok(var)
+    }
+
+    // Allocates a stack slot for an object of the function, which may be
+    // unnamed, e.g. a compound literal.
+    //
+    // A variable length array is allocated at runtime, and its slot holds the
+    // address.
+    pub fn alloc_local(&mut self, name: &str, ty: Ty) -> Var {
+        let align = match ty {
+            Ty::Vla(..) => 8,
+            _ => ty.align(),
+        };
+        self.alloc_aligned_local(name, ty, align)
+    }
+
+    // Declares a local variable in the innermost scope, whose stack slot is
+    // aligned to `align` rather than the alignment of its type.
+    pub fn declare_aligned_local(
+        &mut self,
+        name: &str,
+        ty: Ty,
+        align: usize,
+    ) -> Result<Var, String> {
+        let var = self.alloc_aligned_local(name, ty, align);
+        self.declare(name, Symbol::Var(var.clone()))?;
+        Ok(var)
+    }
+
+    // Allocates a stack slot aligned to `align`, raising the alignment of the
+    // stack frame to it if it is larger.
+    fn alloc_aligned_local(&mut self, name: &str, ty: Ty, align: usize) -> Var {
+        self.frame_align = self.frame_align.max(align);
+        let size = match ty {
+            Ty::Vla(..) => 8,
+            _ => ty.size(),
+        };
+        let offset = align_to(self.stack_size + size, align);
+        self.stack_size = offset;
+        Var {
+            name: name.to_string(),
+            ty,
+            offset,
+        }
+    }
+
+    // Declares a static local variable in the innermost scope, which is
+    // aligned to `align`.
+    //
+    // It is given a symbol name unique in the translation unit, e.g. `x.0`,
+    // so that variables of the same name in other functions do not clash.
+    pub fn declare_static_local(
+        &mut self,
+        name: &str,
+        ty: Ty,
+        init: Option<Vec<u8>>,
+        relocs: Vec<Reloc>,
+        align: usize,
+    ) -> Result<GVar, String> {
+        let var = GVar {
+            name: format!("{}.{}", name, self.statics.len()),
+            ty,
+        };
+        self.declare(name, Symbol::GVar(var.clone()))?;
+        self.statics.push(Data {
+            name: var.name.clone(),
+            ty: var.ty.clone(),
+            init,
+            relocs,
+            is_static: true,
+            align,
+        });
+        Ok(var)
+    }
+
+    // Declares an anonymous array for a string literal, which is terminated
+    // by a null character.
+    pub fn declare_string(&mut self, s: Vec<u8>) -> GVar {
+        let mut init = s;
+        init.push(0);
+        let var = GVar {
+            name: format!(".str.{}", self.statics.len()),
+            ty: Ty::Array(Box::new(Ty::Char), init.len()),
+        };
+        self.statics.push(Data {
+            name: var.name.clone(),
+            ty: var.ty.clone(),
+            init: Some(init),
+            relocs: Vec::new(),
+            is_static: true,
+            align: 1,
+        });
+        var
+    }
+
+    // Declares an unnamed object with static storage duration for a compound
+    // literal at file scope.
+    pub fn declare_compound_literal(&mut self, ty: Ty, init: Vec<u8>, relocs: Vec<Reloc>) -> GVar {
+        let var = GVar {
+            name: format!(".compoundlit.{}", self.statics.len()),
+            ty,
+        };
+        self.statics.push(Data {
+            name: var.name.clone(),
+            ty: var.ty.clone(),
+            init: Some(init),
+            relocs,
+            is_static: true,
+            align: var.ty.align(),
+        });
+        var
+    }
+
+    // Takes the static local variables and string literals declared so far.
+    pub fn take_statics(&mut self) -> Vec<Data> {
+        std::mem::take(&mut self.statics)
+    }
+
+    // Declares a function or a variable with linkage in the innermost scope,
+    // and returns the declaration, which has the composite type of the type
+    // `ty` and that of a visible previous declaration.
+    //
+    // It has internal linkage if declared `static`. Otherwise, it has the
+    // linkage of a visible previous declaration, or external linkage if there
+    // is none, e.g. when a block scope declaration hides it.
+    //
+    // All the declarations of the same name in the translation unit must have
+    // compatible types and the same linkage, even if they are not visible to
+    // each other.
+    pub fn declare_global(
+        &mut self,
+        name: &str,
+        ty: Ty,
+        is_static: bool,
+        is_definition: bool,
+    ) -> Result<Global, String> {
+        let innermost = self.symbols.last().unwrap();
+        if let Some(sym) = innermost.get(name) {
+            if !matches!(sym, Symbol::Global(_)) {
+                return Err(format!("redeclaration of {}", name));
+            }
+        }
+
+        let visible = match self.find(name) {
+            Some(Symbol::Global(prev)) => Some(prev),
+            _ => None,
+        };
+        let is_static = is_static || visible.is_some_and(|prev| prev.is_static);
+        let ty = match visible {
+            Some(prev) if prev.ty.is_compatible(&ty) => prev.ty.composite(&ty),
+            _ => ty,
+        };
+
+        if let Some(prev) = self.linked.get(name) {
+            if prev.is_defined && is_definition {
+                return Err(format!("redefinition of {}", name));
+            }
+            if !prev.ty.is_compatible(&ty) {
+                return Err(format!("conflicting types for {}", name));
+            }
+            if is_static && !prev.is_static {
+                return Err(format!("static declaration of {} follows non-static", name));
+            }
+            if !is_static && prev.is_static {
+                return Err(format!("non-static declaration of {} follows static", name));
+            }
+        }
+        let linked = match self.linked.remove(name) {
+            Some(prev) => Global {
+                ty: prev.ty.composite(&ty),
+                is_defined: prev.is_defined || is_definition,
+                is_static,
+            },
+            None => Global {
+                ty: ty.clone(),
+                is_defined: is_definition,
+                is_static,
+            },
+        };
+        let global = Global {
+            ty,
+            is_defined: linked.is_defined,
+            is_static,
+        };
+        self.linked.insert(name.to_string(), linked);
+        self.symbols
+            .last_mut()
+            .unwrap()
+            .insert(name.to_string(), Symbol::Global(global.clone()));
+        Ok(global)
+    }
+
+    // Finds an ordinary identifier, searching from the innermost scope.
+    pub fn find(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().rev().find_map(|s| s.get(name))
+    }
+
+    // Declares a tag in the innermost scope.
+    pub fn declare_tag(&mut self, name: &str, ty: Ty) -> Result<(), String> {
+        let innermost = self.tags.last_mut().unwrap();
+        if innermost.contains_key(name) {
+            return Err(format!("redefinition of tag {}", name));
+        }
+        innermost.insert(name.to_string(), ty);
+        Ok(())
+    }
+
+    // Finds a tag declared in the innermost scope.
+    pub fn find_innermost_tag(&self, name: &str) -> Option<&Ty> {
+        self.tags.last().unwrap().get(name)
+    }
+
+    // Finds a tag, searching from the innermost scope.
+    pub fn find_tag(&self, name: &str) -> Option<&Ty> {
+        self.tags.iter().rev().find_map(|s| s.get(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ty::FuncTy;
+
+    #[test]
+    fn finds_symbol_in_outer_scope() {
+        let mut scope = Scope::new();
+        scope.declare("A", Symbol::EnumConst(1)).unwrap();
+        scope.enter();
+        assert_eq!(Some(&Symbol::EnumConst(1)), scope.find("A"));
+    }
+
+    #[test]
+    fn inner_symbol_shadows_outer_one() {
+        let mut scope = Scope::new();
+        scope.declare("A", Symbol::EnumConst(1)).unwrap();
+        scope.enter();
+        scope.declare("A", Symbol::EnumConst(2)).unwrap();
+        assert_eq!(Some(&Symbol::EnumConst(2)), scope.find("A"));
+        scope.leave();
+        assert_eq!(Some(&Symbol::EnumConst(1)), scope.find("A"));
+    }
+
+    #[test]
+    fn cannot_redeclare_symbol_in_same_scope() {
+        let mut scope = Scope::new();
+        scope.declare("A", Symbol::EnumConst(1)).unwrap();
+        assert!(scope.declare("A", Symbol::EnumConst(2)).is_err());
+    }
+
+    #[test]
+    fn redefines_typedef_name_with_compatible_type() {
+        let mut scope = Scope::new();
+        scope.declare("T", Symbol::Typedef(Ty::Int)).unwrap();
+        scope.declare("T", Symbol::Typedef(Ty::Enum)).unwrap();
+        assert_eq!(Some(&Symbol::Typedef(Ty::Int)), scope.find("T"));
+        assert!(scope.declare("T", Symbol::Typedef(Ty::UInt)).is_err());
+        assert!(scope.declare("T", Symbol::EnumConst(1)).is_err());
+    }
+
+    #[test]
+    fn allocates_stack_slots_for_locals() {
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        assert_eq!(4, scope.declare_local("x", Ty::Int).unwrap().offset);
+        scope.enter();
+        assert_eq!(8, scope.declare_local("y", Ty::Int).unwrap().offset);
+        scope.leave();
+        assert_eq!((16, 16), scope.leave_func());
+    }
+
+    #[test]
+    fn raises_frame_alignment_for_over_aligned_locals() {
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        scope.declare_local("x", Ty::Int).unwrap();
+        let y = scope.declare_aligned_local("y", Ty::Int, 64).unwrap();
+        assert_eq!(64, y.offset);
+        assert_eq!((64, 64), scope.leave_func());
+    }
+
+    fn func_ty(params: Option<Vec<Ty>>) -> Ty {
+        Ty::Func(Box::new(FuncTy {
+            ret: Ty::Int,
+            params,
+            is_variadic: false,
+        }))
+    }
+
+    #[test]
+    fn redeclares_function_with_compatible_type() {
+        let unspecified = func_ty(None);
+        let one = func_ty(Some(vec![Ty::Int]));
+        let mut scope = Scope::new();
+        scope
+            .declare_global("f", unspecified.clone(), false, false)
+            .unwrap();
+        scope.declare_global("f", one.clone(), false, true).unwrap();
+        scope
+            .declare_global("f", unspecified, false, false)
+            .unwrap();
+        let expected = Symbol::Global(Global {
+            ty: one,
+            is_defined: true,
+            is_static: false,
+        });
+        assert_eq!(Some(&expected), scope.find("f"));
+    }
+
+    #[test]
+    fn cannot_redeclare_function_with_conflicting_type() {
+        let one = func_ty(Some(vec![Ty::Int]));
+        let two = func_ty(Some(vec![Ty::Int, Ty::Int]));
+        let mut scope = Scope::new();
+        scope.declare_global("f", one, false, false).unwrap();
+        assert!(scope.declare_global("f", two, false, false).is_err());
+    }
+
+    #[test]
+    fn cannot_redeclare_function_without_prototype_with_promoted_params() {
+        let mut scope = Scope::new();
+        scope
+            .declare_global("f", func_ty(None), false, false)
+            .unwrap();
+        for ty in [Ty::Char, Ty::Short, Ty::Float] {
+            let promoted = func_ty(Some(vec![Ty::Int, ty]));
+            assert!(scope.declare_global("f", promoted, false, false).is_err());
+        }
+        let variadic = Ty::Func(Box::new(FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Int]),
+            is_variadic: true,
+        }));
+        assert!(scope.declare_global("f", variadic, false, false).is_err());
+        let unpromoted = func_ty(Some(vec![Ty::Int, Ty::Double]));
+        assert!(scope.declare_global("f", unpromoted, false, false).is_ok());
+    }
+
+    #[test]
+    fn cannot_redefine_function() {
+        let mut scope = Scope::new();
+        scope
+            .declare_global("f", func_ty(None), false, true)
+            .unwrap();
+        assert!(scope
+            .declare_global("f", func_ty(None), false, true)
+            .is_err());
+    }
+
+    #[test]
+    fn cannot_declare_function_with_name_of_other_symbol() {
+        let mut scope = Scope::new();
+        scope.declare("f", Symbol::EnumConst(0)).unwrap();
+        assert!(scope
+            .declare_global("f", func_ty(None), false, false)
+            .is_err());
+        assert_eq!(Some(&Symbol::EnumConst(0)), scope.find("f"));
+    }
+
+    #[test]
+    fn links_block_scope_declaration_to_file_scope() {
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let unknown = Ty::IncompleteArray(Box::new(Ty::Int));
+        scope
+            .declare_global("a", unknown.clone(), false, false)
+            .unwrap();
+        scope.leave_func();
+        // The block scope declaration is not visible, but is checked against.
+        assert!(scope.declare_global("a", Ty::Char, false, false).is_err());
+        assert!(scope
+            .declare_global("a", unknown.clone(), true, false)
+            .is_err());
+        let three = Ty::Array(Box::new(Ty::Int), 3);
+        let a = scope
+            .declare_global("a", three.clone(), false, true)
+            .unwrap();
+        assert_eq!(three, a.ty);
+        // A later declaration takes the composite type.
+        let a = scope.declare_global("a", unknown, false, false).unwrap();
+        assert_eq!(three, a.ty);
+    }
+
+    #[test]
+    fn redeclaration_keeps_internal_linkage() {
+        let mut scope = Scope::new();
+        assert!(
+            scope
+                .declare_global("x", Ty::Int, true, false)
+                .unwrap()
+                .is_static
+        );
+        assert!(
+            scope
+                .declare_global("x", Ty::Int, false, true)
+                .unwrap()
+                .is_static
+        );
+        assert!(scope.declare_global("y", Ty::Int, false, false).is_ok());
+        assert!(scope.declare_global("y", Ty::Int, true, false).is_err());
+    }
+
+    #[test]
+    fn static_locals_have_unique_names() {
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let x = scope
+            .declare_static_local("x", Ty::Int, Some(vec![3, 0, 0, 0]), Vec::new(), 4)
+            .unwrap();
+        scope.enter();
+        let inner = scope
+            .declare_static_local("x", Ty::Int, None, Vec::new(), 4)
+            .unwrap();
+        assert_eq!("x.0", x.name);
+        assert_eq!("x.1", inner.name);
+        assert_eq!(Some(&Symbol::GVar(inner)), scope.find("x"));
+        let statics = scope.take_statics();
+        assert_eq!(2, statics.len());
+        assert_eq!(Some(vec![3, 0, 0, 0]), statics[0].init);
+    }
+
+    #[test]
+    fn declares_func_name_on_first_use() {
+        let mut scope = Scope::new();
+        assert_eq!(None, scope.func_name());
+        scope.enter_func(Ty::Int);
+        scope.set_func_name("main");
+        let var = scope.func_name().unwrap();
+        assert_eq!(Some(var.clone()), scope.func_name());
+        let statics = scope.take_statics();
+        assert_eq!(1, statics.len());
+        assert_eq!(Some(b"main\0".to_vec()), statics[0].init);
+        assert_eq!(5, var.ty.size());
+        scope.leave_func();
+        assert_eq!(None, scope.func_name());
+    }
+
+    #[test]
+    fn tags_are_separate_from_symbols() {
+        let mut scope = Scope::new();
+        scope.declare_tag("E", Ty::Enum).unwrap();
+        assert_eq!(None, scope.find("E"));
+        assert_eq!(Some(&Ty::Enum), scope.find_tag("E"));
+    }
+}
@@ -1,16 +1,21 @@
 use super::{
     consume_punct,
-    expr::{parse_expr, Expr},
+    decl::{is_declspec, parse_declaration},
+    expr::{check_value, convert, parse_expr, Expr},
+    scope::Scope,
 };
 use crate::lexer::{KwKind, Token};
+use crate::ty::Ty;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, PartialEq)]
 pub enum Stmt {
-    ExprStmt(Expr),        // expression statement
-    ReturnStmt(Expr),      // return statement
-    IfStmt(Box<IfStruct>), // if statement
-    CompStmt(Vec<Stmt>),   // compound statement (block)
-    NullStmt,              // null statement
+    ExprStmt(Expr),           // expression statement
+    ReturnStmt(Option<Expr>), // return statement
+    IfStmt(Box<IfStruct>),    // if statement
+    CompStmt(Vec<Stmt>),      // compound statement (block)
+    VlaBlock(Box<VlaBlock>),  // block which allocates variable length arrays
+    NullStmt,                 // null statement
 }
 
 #[derive(Debug, PartialEq)]
@@ -19,24 +24,72 @@ pub struct IfStruct {
     pub then: Stmt, // then
 }
 
-// <stmt> ::= "return" <expr> ";"
+// Block which allocates variable length arrays on the stack, which are freed
+// by restoring the stack pointer on leaving it. As there is no jump into or
+// out of a block but return, which frees the whole stack frame, it is only
+// left at its end.
+#[derive(Debug, PartialEq)]
+pub struct VlaBlock {
+    pub sp: usize, // offset from rbp of the stack pointer saved on entry
+    pub body: Vec<Stmt>,
+}
+
+// <block-item> ::= <declaration>
+//                | <stmt>
+pub(super) fn parse_block_item<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Stmt, &'a [Token]), String> {
+    if is_declspec(tokens, scope) {
+        parse_declaration(tokens, scope)
+    } else {
+        parse_stmt(tokens, scope)
+    }
+}
+
+// <stmt> ::= "return" <expr>? ";"
 //          | "if" "(" <expr> ")" <stmt>
 //          | ";"
-//          | "{" <stmt>* "}"
+//          | "{" <block-item>* "}"
 //          | <expr> ";"
-pub(super) fn parse_stmt(tokens: &[Token]) -> Result<(Stmt, &[Token]), String> {
-    match tokens.get(0) {
+pub(super) fn parse_stmt<'a>(
+    tokens: &'a [Token],
+    scope: &mut Scope,
+) -> Result<(Stmt, &'a [Token]), String> {
+    match tokens.first() {
         // return statement
+        // A function returning void must not return a value, and others must.
         Some(Token::Kw(KwKind::Return)) => {
-            let (expr, rest) = parse_expr(&tokens[1..])?;
-            Ok((Stmt::ReturnStmt(expr), consume_punct(rest, ";")?))
+            let is_void = *scope.ret_ty() == Ty::Void;
+            if let Ok(rest) = consume_punct(&tokens[1..], ";") {
+                if !is_void {
+                    return Err("return with no value in a non-void function".to_string());
+                }
+                return Ok((Stmt::ReturnStmt(None), rest));
+            }
+            if is_void {
+                return Err("return with a value in a void function".to_string());
+            }
+            let (expr, rest) = parse_expr(&tokens[1..], scope)?;
+            check_value(&expr)?;
+            let expr = convert(expr, scope.ret_ty())?;
+            Ok((Stmt::ReturnStmt(Some(expr)), consume_punct(rest, ";")?))
         }
         // if statement
         Some(Token::Kw(KwKind::If)) => {
             let rest = consume_punct(&tokens[1..], "(")?;
-            let (cond, rest) = parse_expr(rest)?;
+            let (cond, rest) = parse_expr(rest, scope)?;
+            check_value(&cond)?;
+            if let Ty::Struct(_) = cond.ty() {
+                return Err("used a struct value where a scalar is required".to_string());
+            }
+            // A floating condition is compared with zero as converted to _Bool.
+            let cond = match cond.ty() {
+                ty if ty.is_flonum() => convert(cond, &Ty::Bool)?,
+                _ => cond,
+            };
             let rest = consume_punct(rest, ")")?;
-            let (then, rest) = parse_stmt(rest)?;
+            let (then, rest) = parse_stmt(rest, scope)?;
             Ok((Stmt::IfStmt(Box::new(IfStruct { cond, then })), rest))
         }
         Some(tok) => {
@@ -46,6 +99,7 @@ pub(super) fn parse_stmt(tokens: &[Token]) -> Result<(Stmt, &[Token]), String> {
                     ";" => return Ok((Stmt::NullStmt, &tokens[1..])),
                     // compound statement
                     "{" => {
+                        scope.enter();
                         let mut stmts: Vec<Stmt> = Vec::new();
                         let mut rest = &tokens[1..];
                         loop {
@@ -56,11 +110,22 @@ pub(super) fn parse_stmt(tokens: &[Token]) -> Result<(Stmt, &[Token]), String> {
                                 }
                                 Err(_) => {
                                     let stmt;
-                                    (stmt, rest) = parse_stmt(rest)?;
+                                    (stmt, rest) = parse_block_item(rest, scope)?;
                                     stmts.push(stmt);
                                 }
                             }
                         }
+                        if scope.has_vla() {
+                            let sp =
+                                scope.alloc_local("(stack pointer)", Ty::Ptr(Box::new(Ty::Void)));
+                            scope.leave();
+                            let block = VlaBlock {
+                                sp: sp.offset,
+                                body: stmts,
+                            };
+                            return Ok((Stmt::VlaBlock(Box::new(block)), rest));
+                        }
+                        scope.leave();
                         return Ok((Stmt::CompStmt(stmts), rest));
                     }
                     _ => (),
@@ -68,7 +133,7 @@ pub(super) fn parse_stmt(tokens: &[Token]) -> Result<(Stmt, &[Token]), String> {
             };
 
             // expression statement
-            let (expr, rest) = parse_expr(tokens)?;
+            let (expr, rest) = parse_expr(tokens, scope)?;
             Ok((Stmt::ExprStmt(expr), consume_punct(rest, ";")?))
         }
         None => Err("expected a stetement, but got no token".to_string()),
@@ -78,12 +143,13 @@ pub(super) fn parse_stmt(tokens: &[Token]) -> Result<(Stmt, &[Token]), String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lexer::IntKind;
 
     #[test]
     fn parse_expression_stmt() {
-        let tokens = vec![Token::Num(42), Token::Punct(";".to_string())];
-        let expected = Stmt::ExprStmt(Expr::Num(42));
-        let (actual, rest) = parse_stmt(&tokens).unwrap();
+        let tokens = vec![Token::Num(42, IntKind::Int), Token::Punct(";".to_string())];
+        let expected = Stmt::ExprStmt(Expr::Num(42, Ty::Int));
+        let (actual, rest) = parse_stmt(&tokens, &mut Scope::new()).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
     }
@@ -92,31 +158,53 @@ mod tests {
     fn parse_return_stmt() {
         let tokens = vec![
             Token::Kw(KwKind::Return),
-            Token::Num(42),
+            Token::Num(42, IntKind::Int),
             Token::Punct(";".to_string()),
         ];
-        let expected = Stmt::ReturnStmt(Expr::Num(42));
-        let (actual, rest) = parse_stmt(&tokens).unwrap();
+        let expected = Stmt::ReturnStmt(Some(Expr::Num(42, Ty::Int)));
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Int);
+        let (actual, rest) = parse_stmt(&tokens, &mut scope).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
     }
 
+    #[test]
+    fn checks_return_value_against_return_type() {
+        let no_value = vec![Token::Kw(KwKind::Return), Token::Punct(";".to_string())];
+        let value = vec![
+            Token::Kw(KwKind::Return),
+            Token::Num(42, IntKind::Int),
+            Token::Punct(";".to_string()),
+        ];
+
+        let mut scope = Scope::new();
+        scope.enter_func(Ty::Void);
+        let (actual, _) = parse_stmt(&no_value, &mut scope).unwrap();
+        assert_eq!(Stmt::ReturnStmt(None), actual);
+        assert!(parse_stmt(&value, &mut scope).is_err());
+        scope.leave_func();
+
+        scope.enter_func(Ty::Int);
+        assert!(parse_stmt(&no_value, &mut scope).is_err());
+    }
+
     #[test]
     fn parse_if_stmt() {
         // if (1) 2;
         let tokens = vec![
             Token::Kw(KwKind::If),
             Token::Punct("(".to_string()),
-            Token::Num(1),
+            Token::Num(1, IntKind::Int),
             Token::Punct(")".to_string()),
-            Token::Num(2),
+            Token::Num(2, IntKind::Int),
             Token::Punct(";".to_string()),
         ];
         let expected = Stmt::IfStmt(Box::new(IfStruct {
-            cond: Expr::Num(1),
-            then: Stmt::ExprStmt(Expr::Num(2)),
+            cond: Expr::Num(1, Ty::Int),
+            then: Stmt::ExprStmt(Expr::Num(2, Ty::Int)),
         }));
-        let (actual, rest) = parse_stmt(&tokens).unwrap();
+        let (actual, rest) = parse_stmt(&tokens, &mut Scope::new()).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
     }
@@ -127,23 +215,23 @@ mod tests {
         let tokens = vec![
             Token::Kw(KwKind::If),
             Token::Punct("(".to_string()),
-            Token::Num(1),
+            Token::Num(1, IntKind::Int),
             Token::Punct(")".to_string()),
             Token::Punct("{".to_string()),
-            Token::Num(2),
+            Token::Num(2, IntKind::Int),
             Token::Punct(";".to_string()),
-            Token::Num(3),
+            Token::Num(3, IntKind::Int),
             Token::Punct(";".to_string()),
             Token::Punct("}".to_string()),
         ];
         let expected = Stmt::IfStmt(Box::new(IfStruct {
-            cond: Expr::Num(1),
+            cond: Expr::Num(1, Ty::Int),
             then: Stmt::CompStmt(vec![
-                Stmt::ExprStmt(Expr::Num(2)),
-                Stmt::ExprStmt(Expr::Num(3)),
+                Stmt::ExprStmt(Expr::Num(2, Ty::Int)),
+                Stmt::ExprStmt(Expr::Num(3, Ty::Int)),
             ]),
         }));
-        let (actual, rest) = parse_stmt(&tokens).unwrap();
+        let (actual, rest) = parse_stmt(&tokens, &mut Scope::new()).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
     }
@@ -153,29 +241,59 @@ mod tests {
         // { 2; 3; 4; }
         let tokens = vec![
             Token::Punct("{".to_string()),
-            Token::Num(2),
+            Token::Num(2, IntKind::Int),
             Token::Punct(";".to_string()),
-            Token::Num(3),
+            Token::Num(3, IntKind::Int),
             Token::Punct(";".to_string()),
-            Token::Num(4),
+            Token::Num(4, IntKind::Int),
             Token::Punct(";".to_string()),
             Token::Punct("}".to_string()),
         ];
         let expected = Stmt::CompStmt(vec![
-            Stmt::ExprStmt(Expr::Num(2)),
-            Stmt::ExprStmt(Expr::Num(3)),
-            Stmt::ExprStmt(Expr::Num(4)),
+            Stmt::ExprStmt(Expr::Num(2, Ty::Int)),
+            Stmt::ExprStmt(Expr::Num(3, Ty::Int)),
+            Stmt::ExprStmt(Expr::Num(4, Ty::Int)),
         ]);
-        let (actual, rest) = parse_stmt(&tokens).unwrap();
+        let (actual, rest) = parse_stmt(&tokens, &mut Scope::new()).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
     }
 
+    #[test]
+    fn parse_enum_declaration_in_block() {
+        // { enum { A = 3 }; A; } A;
+        let tokens = vec![
+            Token::Punct("{".to_string()),
+            Token::Kw(KwKind::Enum),
+            Token::Punct("{".to_string()),
+            Token::Ident("A".to_string()),
+            Token::Punct("=".to_string()),
+            Token::Num(3, IntKind::Int),
+            Token::Punct("}".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Ident("A".to_string()),
+            Token::Punct(";".to_string()),
+            Token::Punct("}".to_string()),
+            Token::Ident("A".to_string()),
+            Token::Punct(";".to_string()),
+        ];
+        let expected = Stmt::CompStmt(vec![
+            Stmt::CompStmt(Vec::new()),
+            Stmt::ExprStmt(Expr::Num(3, Ty::Int)),
+        ]);
+        let mut scope = Scope::new();
+        let (actual, rest) = parse_stmt(&tokens, &mut scope).unwrap();
+        assert_eq!(expected, actual);
+
+        // The enumerator is out of scope after the block.
+        assert!(parse_stmt(rest, &mut scope).is_err());
+    }
+
     #[test]
     fn parse_null_stmt() {
         let tokens = vec![Token::Punct(";".to_string())];
         let expected = Stmt::NullStmt;
-        let (actual, rest) = parse_stmt(&tokens).unwrap();
+        let (actual, rest) = parse_stmt(&tokens, &mut Scope::new()).unwrap();
         assert_eq!(expected, actual);
         assert_eq!(Vec::<Token>::new(), rest);
     }
@@ -0,0 +1,463 @@
+mod builtin;
+mod cond;
+mod macros;
+mod print;
+
+use crate::lexer::{tokenize_pp, PpKind, PpToken};
+use crate::{MacroOpt, Options};
+use cond::{guard_macro, is_skipping, Cond};
+use macros::Macro;
+pub use print::print_tokens;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// maximum depth of nested #include, which stops files including each other
+// endlessly
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+// directory of the headers built into rocc, which is searched after those
+// given by -I and before the system directories
+const BUILTIN_INCLUDE_PATH: &str = "<rocc>/include";
+
+// freestanding headers built into rocc, which do not depend on those of the
+// system
+const BUILTIN_HEADERS: [(&str, &str); 8] = [
+    ("float.h", include_str!("../include/float.h")),
+    ("limits.h", include_str!("../include/limits.h")),
+    ("stdalign.h", include_str!("../include/stdalign.h")),
+    ("stdarg.h", include_str!("../include/stdarg.h")),
+    ("stdbool.h", include_str!("../include/stdbool.h")),
+    ("stddef.h", include_str!("../include/stddef.h")),
+    ("stdint.h", include_str!("../include/stdint.h")),
+    ("stdnoreturn.h", include_str!("../include/stdnoreturn.h")),
+];
+
+// directories searched for headers after the built-in ones
+const SYSTEM_INCLUDE_PATHS: [&str; 3] = [
+    "/usr/local/include",
+    "/usr/include/x86_64-linux-gnu",
+    "/usr/include",
+];
+
+struct Preprocessor<'a> {
+    include_paths: &'a [String],
+    macros: HashMap<String, Rc<Macro>>,
+    // files with `#pragma once`, which are not included again
+    once: HashSet<PathBuf>,
+    // files guarded by the macros from being included again
+    guards: HashMap<PathBuf, String>,
+    // depth of the file being preprocessed, which is 0 for the main file
+    depth: usize,
+    // the number of times __COUNTER__ has been expanded
+    counter: usize,
+}
+
+// Preprocesses the input of the file, and returns the resulting tokens.
+//
+// The predefined macros are defined first, then macros given by -D and -U are
+// defined and undefined in order as if by directives before the input.
+pub fn preprocess(file: &str, input: &str, opts: &Options) -> Result<Vec<PpToken>, String> {
+    let mut pp = Preprocessor {
+        include_paths: &opts.include_paths,
+        macros: HashMap::new(),
+        once: HashSet::new(),
+        guards: HashMap::new(),
+        depth: 0,
+        counter: 0,
+    };
+    pp.define_builtins();
+
+    let mut directives = String::new();
+    for opt in &opts.macros {
+        match opt {
+            MacroOpt::Define(def) => match def.split_once('=') {
+                Some((name, body)) => directives.push_str(&format!("#define {} {}\n", name, body)),
+                None => directives.push_str(&format!("#define {} 1\n", def)),
+            },
+            MacroOpt::Undef(name) => directives.push_str(&format!("#undef {}\n", name)),
+        }
+    }
+    pp.preprocess_file("<command-line>", &directives)?;
+
+    pp.preprocess_file(file, input)
+}
+
+impl Preprocessor<'_> {
+    // <file>      ::= (<directive> | <text-line>)*
+    // <directive> ::= "#" <token>* <newline>
+    //
+    // A directive is a line starting with "#". Macros are expanded in the
+    // text lines between directives, where an invocation may span lines.
+    // Conditional directives must be closed in the file. `#line` and
+    // linemarkers renumber the lines following them.
+    fn preprocess_file(&mut self, file: &str, input: &str) -> Result<Vec<PpToken>, String> {
+        let mut tokens = tokenize_pp(file, input)?;
+        if let Some(guard) = guard_macro(&tokens) {
+            self.guards.insert(canonicalize(Path::new(file)), guard);
+        }
+
+        let mut output: Vec<PpToken> = Vec::new();
+        let mut conds: Vec<Cond> = Vec::new();
+        let mut pos = 0;
+        while pos < tokens.len() {
+            let is_directive = |tok: &PpToken| tok.at_bol && tok.text == "#";
+            let len = tokens[pos..]
+                .iter()
+                .position(is_directive)
+                .unwrap_or(tokens.len() - pos);
+            if !is_skipping(&conds) {
+                output.extend(self.expand(tokens[pos..pos + len].to_vec())?);
+            }
+            pos += len;
+            if pos == tokens.len() {
+                break;
+            }
+
+            let end = match tokens[pos + 1..].iter().position(|tok| tok.at_bol) {
+                Some(n) => pos + 1 + n,
+                None => tokens.len(),
+            };
+            let line = &tokens[pos + 1..end];
+            match line.first() {
+                Some(name)
+                    if (name.text == "line" || name.kind == PpKind::Num)
+                        && !is_skipping(&conds) =>
+                {
+                    let (num, file) = match name.kind {
+                        PpKind::Num => parse_line(name, line)?,
+                        _ => parse_line(name, &self.expand(line[1..].to_vec())?)?,
+                    };
+                    // The line following the directive is renumbered, and
+                    // so are those after it.
+                    let delta = num as isize - (name.line + 1) as isize;
+                    let file: Option<Rc<str>> = file.map(Rc::from);
+                    for tok in &mut tokens[end..] {
+                        tok.line = (tok.line as isize + delta) as usize;
+                        if let Some(file) = &file {
+                            tok.file = file.clone();
+                        }
+                    }
+                }
+                _ => self.directive(line, &mut conds, &mut output)?,
+            }
+            pos = end;
+        }
+        match conds.first() {
+            Some(cond) => Err(cond.tok.error("unterminated conditional directive")),
+            None => Ok(output),
+        }
+    }
+
+    // Runs the directive of the tokens following "#" in a line. A line of only
+    // "#" is a null directive, which does nothing. Only conditional directives
+    // are run in a skipped group, and any other line is ignored.
+    fn directive(
+        &mut self,
+        line: &[PpToken],
+        conds: &mut Vec<Cond>,
+        output: &mut Vec<PpToken>,
+    ) -> Result<(), String> {
+        let name = match line.first() {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        if let "if" | "ifdef" | "ifndef" | "elif" | "else" | "endif" = name.text.as_str() {
+            return self.cond_directive(name, &line[1..], conds);
+        }
+        if is_skipping(conds) {
+            return Ok(());
+        }
+        match name.text.as_str() {
+            "include" => output.extend(self.include(name, &line[1..])?),
+            "define" => self.define(name, &line[1..])?,
+            "undef" => self.undef(name, &line[1..])?,
+            // Pragmas other than `#pragma once` are ignored.
+            "pragma" => {
+                if let [tok] = &line[1..] {
+                    if tok.text == "once" {
+                        self.once.insert(canonicalize(Path::new(&*name.file)));
+                    }
+                }
+            }
+            "error" => {
+                let msg: String = line[1..]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, t)| match i > 0 && t.has_space {
+                        true => format!(" {}", t.text),
+                        false => t.text.clone(),
+                    })
+                    .collect();
+                return Err(name.error(&format!("#error {}", msg)));
+            }
+            _ => {
+                return Err(name.error(&format!("invalid preprocessing directive: #{}", name.text)))
+            }
+        }
+        Ok(())
+    }
+
+    // <include> ::= "include" (<header-name> | <token>*)
+    //
+    // Returns the preprocessed tokens of the header, which are empty if it has
+    // `#pragma once` and has already been included. Tokens other than a header
+    // name are macro-expanded to make one.
+    fn include(&mut self, tok: &PpToken, args: &[PpToken]) -> Result<Vec<PpToken>, String> {
+        let (name, is_quoted) = match args.first() {
+            Some(t) if t.kind == PpKind::Str || t.text == "<" => parse_header_name(tok, args)?,
+            _ => parse_header_name(tok, &self.expand(args.to_vec())?)?,
+        };
+        let path = self
+            .find_header(&tok.file, &name, is_quoted)
+            .ok_or_else(|| tok.error(&format!("file not found: {}", name)))?;
+        let canonical = canonicalize(&path);
+        let is_guarded = self
+            .guards
+            .get(&canonical)
+            .is_some_and(|guard| self.macros.contains_key(guard));
+        if is_guarded || self.once.contains(&canonical) {
+            return Ok(Vec::new());
+        }
+        if self.depth >= MAX_INCLUDE_DEPTH {
+            return Err(tok.error(&format!("#include nested too deeply: {}", name)));
+        }
+
+        let input = match builtin_header(&path) {
+            Some(input) => input.to_string(),
+            None => fs::read_to_string(&path)
+                .map_err(|err| tok.error(&format!("cannot read {}: {}", path.display(), err)))?,
+        };
+        self.depth += 1;
+        let tokens = self.preprocess_file(&path.to_string_lossy(), &input);
+        self.depth -= 1;
+        tokens
+    }
+
+    // Returns the path of the header. `#include "..."` searches the directory
+    // of the current file first, then both forms search the directories given
+    // by -I, the built-in headers and the system directories.
+    fn find_header(&self, cur_file: &str, name: &str, is_quoted: bool) -> Option<PathBuf> {
+        if Path::new(name).is_absolute() {
+            return Some(PathBuf::from(name)).filter(|path| path.is_file());
+        }
+        let cur_dir = match Path::new(cur_file).parent() {
+            Some(dir) if is_quoted => Some(dir.to_path_buf()),
+            _ => None,
+        };
+        cur_dir
+            .into_iter()
+            .chain(self.include_paths.iter().map(PathBuf::from))
+            .chain(std::iter::once(PathBuf::from(BUILTIN_INCLUDE_PATH)))
+            .chain(SYSTEM_INCLUDE_PATHS.iter().map(PathBuf::from))
+            .map(|dir| dir.join(name))
+            .find(|path| builtin_header(path).is_some() || path.is_file())
+    }
+}
+
+// <header-name> ::= <string> | "<" <token>* ">"
+//
+// Returns the name of the header, and true if it is quoted by double quotes.
+// The name between "<" and ">" is spelled by the tokens in it.
+fn parse_header_name(tok: &PpToken, args: &[PpToken]) -> Result<(String, bool), String> {
+    match args {
+        [name] if name.kind == PpKind::Str => {
+            Ok((name.text[1..name.text.len() - 1].to_string(), true))
+        }
+        [open, rest @ ..] if open.text == "<" => {
+            let end = rest
+                .iter()
+                .position(|tok| tok.text == ">")
+                .ok_or_else(|| tok.error("expected '>'"))?;
+            if end + 1 != rest.len() {
+                return Err(tok.error("extra tokens after #include"));
+            }
+            let mut name = String::new();
+            for tok in &rest[..end] {
+                if tok.has_space && !name.is_empty() {
+                    name.push(' ');
+                }
+                name.push_str(&tok.text);
+            }
+            Ok((name, false))
+        }
+        _ => Err(tok.error("expected a header name")),
+    }
+}
+
+// Returns the content of the built-in header at the path, or None if it is not
+// one.
+fn builtin_header(path: &Path) -> Option<&'static str> {
+    let name = path.strip_prefix(BUILTIN_INCLUDE_PATH).ok()?;
+    BUILTIN_HEADERS
+        .iter()
+        .find(|(header, _)| name == Path::new(header))
+        .map(|(_, input)| *input)
+}
+
+// <line> ::= "line" <number> <string>?
+//          | <number> <string> <number>*
+//
+// Returns the line number and the file name given by `#line`, or by a
+// linemarker of the second form, which is printed by -E. The numbers
+// following the file name of a linemarker are flags, which are ignored.
+fn parse_line(tok: &PpToken, args: &[PpToken]) -> Result<(usize, Option<String>), String> {
+    let is_marker = tok.kind == PpKind::Num;
+    let (num, rest) = match args.split_first() {
+        Some((num, rest)) if num.kind == PpKind::Num => (num, rest),
+        _ => return Err(tok.error("expected a line number")),
+    };
+    let num: usize = match num.text.parse() {
+        Ok(n) if num.text.bytes().all(|b| b.is_ascii_digit()) => n,
+        _ => return Err(num.error(&format!("invalid line number: {}", num.text))),
+    };
+    match rest {
+        [] if !is_marker => Ok((num, None)),
+        [file, flags @ ..]
+            if file.kind == PpKind::Str
+                && (flags.is_empty()
+                    || is_marker && flags.iter().all(|t| t.kind == PpKind::Num)) =>
+        {
+            Ok((num, Some(unquote(&file.text))))
+        }
+        _ => Err(tok.error("expected a file name after the line number")),
+    }
+}
+
+// Returns the content of a string literal, where a backslash escapes the
+// character following it.
+fn unquote(s: &str) -> String {
+    let mut content = String::new();
+    let mut chars = s[1..s.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => content.extend(chars.next()),
+            _ => content.push(c),
+        }
+    }
+    content
+}
+
+// Returns the canonical path of the file, which identifies it however it is
+// included, or the path itself if it does not exist, e.g. for stdin.
+fn canonicalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Returns the texts of the preprocessing tokens.
+    fn texts(tokens: &[PpToken]) -> Vec<&str> {
+        tokens.iter().map(|tok| tok.text.as_str()).collect()
+    }
+
+    // Returns the preprocessed input, with the tokens separated by spaces. It
+    // is in tests/include, where the headers for tests are.
+    pub(super) fn pp(input: &str) -> Result<String, String> {
+        let tokens = preprocess("tests/include/main.c", input, &Options::default())?;
+        Ok(texts(&tokens).join(" "))
+    }
+
+    #[test]
+    fn passes_through_text_lines() {
+        let input = "int x;\n#\nint y;";
+        let tokens = preprocess("f.c", input, &Options::default()).unwrap();
+        assert_eq!(vec!["int", "x", ";", "int", "y", ";"], texts(&tokens));
+    }
+
+    #[test]
+    fn ignores_unknown_pragma() {
+        let input = "#pragma pack(1)\nx";
+        let tokens = preprocess("f.c", input, &Options::default()).unwrap();
+        assert_eq!(vec!["x"], texts(&tokens));
+    }
+
+    #[test]
+    fn hash_not_at_start_of_line_is_not_directive() {
+        let input = "a # include";
+        let tokens = preprocess("f.c", input, &Options::default()).unwrap();
+        assert_eq!(vec!["a", "#", "include"], texts(&tokens));
+    }
+
+    #[test]
+    fn reports_invalid_directive() {
+        let input = "\n#foo";
+        assert_eq!(
+            Err("f.c:2: invalid preprocessing directive: #foo".to_string()),
+            preprocess("f.c", input, &Options::default())
+        );
+    }
+
+    #[test]
+    fn reports_missing_header() {
+        let input = "#include \"no-such-header.h\"";
+        assert_eq!(
+            Err("f.c:1: file not found: no-such-header.h".to_string()),
+            preprocess("f.c", input, &Options::default())
+        );
+        let input = "#include no";
+        assert!(preprocess("f.c", input, &Options::default()).is_err());
+    }
+
+    #[test]
+    fn stops_endless_include() {
+        let input = "#include \"cycle.h\"";
+        assert_eq!(
+            Err("tests/include/cycle.h:1: #include nested too deeply: cycle.h".to_string()),
+            preprocess("tests/include/main.c", input, &Options::default())
+        );
+    }
+
+    #[test]
+    fn includes_builtin_headers() {
+        let input = "#include <stdbool.h>\n#include <limits.h>\nbool true INT_MAX";
+        let tokens = preprocess("f.c", input, &Options::default()).unwrap();
+        assert_eq!(vec!["_Bool", "1", "2147483647"], texts(&tokens));
+
+        let tokens = preprocess("f.c", "#include <stddef.h>", &Options::default()).unwrap();
+        assert_eq!("<rocc>/include/stddef.h", &*tokens[0].file);
+    }
+
+    #[test]
+    fn user_headers_take_precedence_over_builtin_ones() {
+        let opts = Options {
+            include_paths: vec!["tests/include/user".to_string()],
+            ..Options::default()
+        };
+        let input = "#include <stddef.h>\n#include <limits.h>\nNULL CHAR_BIT";
+        let tokens = preprocess("f.c", input, &opts).unwrap();
+        assert_eq!(vec!["0", "8"], texts(&tokens));
+    }
+
+    #[test]
+    fn renumbers_lines_by_line_directive() {
+        let input = "a\n#line 10\nb\n#define F \"g.c\"\n#line 20 F\nc\n# 30 \"h.c\" 2\nd";
+        let tokens = preprocess("f.c", input, &Options::default()).unwrap();
+        let locs: Vec<(&str, usize)> = tokens.iter().map(|t| (&*t.file, t.line)).collect();
+        assert_eq!(
+            vec![("f.c", 1), ("f.c", 10), ("g.c", 20), ("h.c", 30)],
+            locs
+        );
+        assert_eq!(
+            Err("f.c:1: expected a file name after the line number".to_string()),
+            preprocess("f.c", "# 1", &Options::default())
+        );
+        assert_eq!(
+            Err("f.c:1: invalid line number: 0x1".to_string()),
+            preprocess("f.c", "#line 0x1", &Options::default())
+        );
+    }
+
+    #[test]
+    fn spells_header_name_in_angle_brackets() {
+        let tokens = tokenize_pp("f.c", "#include <sys/a-b.h>").unwrap();
+        assert_eq!(
+            Ok(("sys/a-b.h".to_string(), false)),
+            parse_header_name(&tokens[1], &tokens[2..])
+        );
+    }
+}
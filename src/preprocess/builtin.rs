@@ -0,0 +1,170 @@
+use super::macros::Macro;
+use super::Preprocessor;
+use crate::lexer::{tokenize_pp, PpKind, PpToken};
+
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// macros defined before preprocessing, which describe the implementation and
+// the target, x86-64 Linux
+const PREDEFINED_MACROS: [(&str, &str); 41] = [
+    ("__STDC__", "1"),
+    ("__STDC_VERSION__", "201112L"),
+    ("__STDC_HOSTED__", "1"),
+    ("__STDC_NO_ATOMICS__", "1"),
+    ("__STDC_NO_COMPLEX__", "1"),
+    ("__STDC_NO_THREADS__", "1"),
+    ("__rocc__", "1"),
+    ("__x86_64__", "1"),
+    ("__x86_64", "1"),
+    ("__amd64__", "1"),
+    ("__amd64", "1"),
+    ("__linux__", "1"),
+    ("__linux", "1"),
+    ("__gnu_linux__", "1"),
+    ("__unix__", "1"),
+    ("__unix", "1"),
+    ("__ELF__", "1"),
+    ("__LP64__", "1"),
+    ("_LP64", "1"),
+    ("__ORDER_LITTLE_ENDIAN__", "1234"),
+    ("__ORDER_BIG_ENDIAN__", "4321"),
+    ("__BYTE_ORDER__", "__ORDER_LITTLE_ENDIAN__"),
+    ("__CHAR_BIT__", "8"),
+    ("__SIZEOF_SHORT__", "2"),
+    ("__SIZEOF_INT__", "4"),
+    ("__SIZEOF_LONG__", "8"),
+    ("__SIZEOF_LONG_LONG__", "8"),
+    ("__SIZEOF_POINTER__", "8"),
+    ("__SIZEOF_FLOAT__", "4"),
+    ("__SIZEOF_DOUBLE__", "8"),
+    ("__SIZEOF_LONG_DOUBLE__", "16"),
+    ("__SIZEOF_SIZE_T__", "8"),
+    ("__SIZEOF_PTRDIFF_T__", "8"),
+    ("__SIZEOF_WCHAR_T__", "4"),
+    ("__SCHAR_MAX__", "127"),
+    ("__SHRT_MAX__", "32767"),
+    ("__INT_MAX__", "2147483647"),
+    ("__LONG_MAX__", "9223372036854775807L"),
+    ("__LONG_LONG_MAX__", "9223372036854775807LL"),
+    ("__SIZE_MAX__", "18446744073709551615UL"),
+    ("__PTRDIFF_MAX__", "9223372036854775807L"),
+];
+
+// macros whose expansion depends on where they appear, or on how many times
+// they have been expanded for __COUNTER__
+pub(super) const DYNAMIC_MACROS: [&str; 3] = ["__FILE__", "__LINE__", "__COUNTER__"];
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+impl Preprocessor<'_> {
+    // Defines the predefined macros, and __DATE__ and __TIME__ of the start of
+    // preprocessing in UTC.
+    pub(super) fn define_builtins(&mut self) {
+        for (name, body) in PREDEFINED_MACROS {
+            self.define_builtin(name, body);
+        }
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let (date, time) = format_date_time(secs);
+        self.define_builtin("__DATE__", &format!("\"{}\"", date));
+        self.define_builtin("__TIME__", &format!("\"{}\"", time));
+    }
+
+    fn define_builtin(&mut self, name: &str, body: &str) {
+        let body = tokenize_pp("<built-in>", body).unwrap();
+        self.macros
+            .insert(name.to_string(), Rc::new(Macro::object(body)));
+    }
+
+    // Returns the expansion of a dynamic macro at `tok`, or None if it is not
+    // one.
+    pub(super) fn expand_dynamic(&mut self, tok: &PpToken) -> Option<PpToken> {
+        let (kind, text) = match tok.text.as_str() {
+            "__FILE__" => (PpKind::Str, quote(&tok.file)),
+            "__LINE__" => (PpKind::Num, tok.line.to_string()),
+            "__COUNTER__" => {
+                self.counter += 1;
+                (PpKind::Num, (self.counter - 1).to_string())
+            }
+            _ => return None,
+        };
+        Some(PpToken {
+            kind,
+            text,
+            ..tok.clone()
+        })
+    }
+}
+
+// Returns a string literal of the str.
+pub(super) fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Returns the date as `Mmm dd yyyy` and the time as `hh:mm:ss` of the seconds
+// since the Unix epoch, in the forms of __DATE__ and __TIME__.
+fn format_date_time(secs: u64) -> (String, String) {
+    let days = (secs / 86400) as i64;
+    let secs = secs % 86400;
+
+    // the civil date of the days since 1970-01-01, in eras of 400 years
+    // starting on March 1
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    let date = format!("{} {:2} {}", MONTHS[month as usize - 1], day, year);
+    let time = format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+    (date, time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preprocess::tests::pp;
+
+    #[test]
+    fn expands_predefined_macros() {
+        let input = "#if __STDC_VERSION__ >= 201112L && __CHAR_BIT__ == 8
+__STDC__ __SIZEOF_INT__ __rocc__
+#endif";
+        assert_eq!(Ok("1 4 1".to_string()), pp(input));
+    }
+
+    #[test]
+    fn expands_dynamic_macros() {
+        let input = "#define LOC __FILE__:__LINE__
+__COUNTER__ __COUNTER__
+LOC
+#if defined(__LINE__) && __COUNTER__ == 2
+__LINE__
+#endif";
+        assert_eq!(
+            Ok("0 1 \"tests/include/main.c\" : 3 5".to_string()),
+            pp(input)
+        );
+    }
+
+    #[test]
+    fn formats_date_and_time() {
+        assert_eq!(
+            ("Jan  1 1970".to_string(), "00:00:00".to_string()),
+            format_date_time(0)
+        );
+        // 2024-02-29 23:59:59
+        assert_eq!(
+            ("Feb 29 2024".to_string(), "23:59:59".to_string()),
+            format_date_time(1709251199)
+        );
+    }
+}
@@ -0,0 +1,566 @@
+use super::builtin::DYNAMIC_MACROS;
+use super::{parse_header_name, Preprocessor};
+use crate::lexer::{char_value, PpKind, PpToken};
+
+// conditional directive whose groups are being preprocessed, e.g. #if
+pub(super) struct Cond {
+    pub(super) tok: PpToken, // the directive name, for the error of a missing #endif
+    state: CondState,
+    in_else: bool, // true after #else
+}
+
+#[derive(PartialEq)]
+enum CondState {
+    Including, // the current group is included
+    Pending,   // no group has been included yet
+    Done,      // a group has been included, or the whole directive is skipped
+}
+
+// value of an expression of #if, which has the type intmax_t, or uintmax_t if
+// `is_unsigned`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Value {
+    val: i64, // the bits of the value, which are unsigned if `is_unsigned`
+    is_unsigned: bool,
+}
+
+impl Value {
+    fn signed(val: i64) -> Value {
+        Value {
+            val,
+            is_unsigned: false,
+        }
+    }
+
+    fn is_true(self) -> bool {
+        self.val != 0
+    }
+}
+
+// binary operators from the lowest precedence, except "?:" and ","
+const BINARY_OPS: [&[&str]; 10] = [
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", ">", "<=", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+// Returns true if the lines are being skipped in a group not included.
+pub(super) fn is_skipping(conds: &[Cond]) -> bool {
+    conds
+        .last()
+        .is_some_and(|cond| cond.state != CondState::Including)
+}
+
+impl Preprocessor<'_> {
+    // Runs the conditional directive `tok` with the tokens following it, even
+    // in a skipped group to track the nesting.
+    //
+    // <if>     ::= "if" <pp-expr>
+    // <ifdef>  ::= ("ifdef" | "ifndef") <ident>
+    // <elif>   ::= "elif" <pp-expr>
+    // <else>   ::= "else"
+    // <endif>  ::= "endif"
+    pub(super) fn cond_directive(
+        &mut self,
+        tok: &PpToken,
+        line: &[PpToken],
+        conds: &mut Vec<Cond>,
+    ) -> Result<(), String> {
+        let directive = tok.text.as_str();
+        match directive {
+            "if" | "ifdef" | "ifndef" => {
+                let state = match is_skipping(conds) {
+                    true => CondState::Done,
+                    false if self.test_cond(tok, line)? => CondState::Including,
+                    false => CondState::Pending,
+                };
+                conds.push(Cond {
+                    tok: tok.clone(),
+                    state,
+                    in_else: false,
+                });
+            }
+            "elif" | "else" => {
+                let cond = match conds.last_mut() {
+                    Some(cond) if !cond.in_else => cond,
+                    Some(_) => return Err(tok.error(&format!("#{} after #else", directive))),
+                    None => return Err(tok.error(&format!("#{} without #if", directive))),
+                };
+                cond.state = match cond.state {
+                    CondState::Including | CondState::Done => CondState::Done,
+                    CondState::Pending if directive == "else" => CondState::Including,
+                    CondState::Pending if self.test_cond(tok, line)? => CondState::Including,
+                    CondState::Pending => CondState::Pending,
+                };
+                if directive == "else" {
+                    if !line.is_empty() {
+                        return Err(tok.error("extra tokens after #else"));
+                    }
+                    cond.in_else = true;
+                }
+            }
+            _ => {
+                if conds.pop().is_none() {
+                    return Err(tok.error("#endif without #if"));
+                }
+                if !line.is_empty() {
+                    return Err(tok.error("extra tokens after #endif"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the condition of #if, #elif, #ifdef or #ifndef, whose name is
+    // `tok`.
+    fn test_cond(&mut self, tok: &PpToken, line: &[PpToken]) -> Result<bool, String> {
+        match (tok.text.as_str(), line) {
+            ("ifdef", [name]) if name.kind == PpKind::Ident => Ok(self.is_defined(&name.text)),
+            ("ifndef", [name]) if name.kind == PpKind::Ident => Ok(!self.is_defined(&name.text)),
+            ("ifdef" | "ifndef", _) => {
+                Err(tok.error(&format!("expected a macro name after #{}", tok.text)))
+            }
+            _ => Ok(self.eval_cond(tok, line)?.is_true()),
+        }
+    }
+
+    // Returns true if the name is defined as a macro, including the dynamic
+    // ones. __has_include is also defined, so that it can be tested by #ifdef.
+    fn is_defined(&self, name: &str) -> bool {
+        self.macros.contains_key(name) || DYNAMIC_MACROS.contains(&name) || name == "__has_include"
+    }
+
+    // <pp-expr> ::= <expr of integer constants>
+    //
+    // Evaluates the expression of #if or #elif. `defined` and __has_include
+    // are evaluated first, then macros are expanded, and the identifiers left
+    // are 0. Values are computed in 64 bits, signed or unsigned as by the
+    // usual arithmetic conversions.
+    fn eval_cond(&mut self, tok: &PpToken, line: &[PpToken]) -> Result<Value, String> {
+        let mut tokens: Vec<PpToken> = Vec::new();
+        let mut rest = line;
+        while let Some(t) = rest.first() {
+            match t.text.as_str() {
+                // "defined" <ident> | "defined" "(" <ident> ")"
+                "defined" => {
+                    let name;
+                    (name, rest) = match &rest[1..] {
+                        [name, r @ ..] if name.kind == PpKind::Ident => (name, r),
+                        [open, name, close, r @ ..]
+                            if open.text == "("
+                                && name.kind == PpKind::Ident
+                                && close.text == ")" =>
+                        {
+                            (name, r)
+                        }
+                        _ => return Err(t.error("expected a macro name after defined")),
+                    };
+                    tokens.push(number(t, self.is_defined(&name.text) as i64));
+                }
+                // "__has_include" "(" <header-name> ")"
+                "__has_include" => {
+                    let close = rest.iter().position(|t| t.text == ")");
+                    let (name, is_quoted) = match (rest.get(1), close) {
+                        (Some(open), Some(close)) if open.text == "(" => {
+                            let (name, is_quoted) = parse_header_name(t, &rest[2..close])?;
+                            rest = &rest[close + 1..];
+                            (name, is_quoted)
+                        }
+                        _ => return Err(t.error("expected a header name in __has_include")),
+                    };
+                    let found = self.find_header(&t.file, &name, is_quoted).is_some();
+                    tokens.push(number(t, found as i64));
+                }
+                _ => {
+                    tokens.push(t.clone());
+                    rest = &rest[1..];
+                }
+            }
+        }
+
+        let tokens: Vec<PpToken> = self
+            .expand(tokens)?
+            .into_iter()
+            .map(|t| match t.kind {
+                PpKind::Ident => number(&t, 0),
+                _ => t,
+            })
+            .collect();
+        if tokens.is_empty() {
+            return Err(tok.error(&format!("#{} with no expression", tok.text)));
+        }
+        let (val, rest) = parse_expr(&tokens, true).map_err(|err| tok.error(&err))?;
+        match rest.first() {
+            Some(t) => Err(t.error(&format!("unexpected token in #{}: {}", tok.text, t.text))),
+            None => Ok(val),
+        }
+    }
+}
+
+// Returns a number token of the value at the token.
+fn number(tok: &PpToken, val: i64) -> PpToken {
+    PpToken {
+        kind: PpKind::Num,
+        text: val.to_string(),
+        ..tok.clone()
+    }
+}
+
+// Returns the name of the macro guarding the whole file from being included
+// again, i.e. X of the form:
+//
+//   #ifndef X
+//   ...
+//   #endif
+pub(super) fn guard_macro(tokens: &[PpToken]) -> Option<String> {
+    let name = match tokens {
+        [hash, ifndef, name, rest @ ..]
+            if hash.text == "#"
+                && ifndef.text == "ifndef"
+                && name.kind == PpKind::Ident
+                && rest.first().is_none_or(|t| t.at_bol) =>
+        {
+            name
+        }
+        _ => return None,
+    };
+
+    // The #endif matching #ifndef must end the file, and no #elif or #else may
+    // be between them.
+    let mut depth = 0;
+    for (i, hash) in tokens.iter().enumerate() {
+        if !(hash.at_bol && hash.text == "#") {
+            continue;
+        }
+        match tokens.get(i + 1).map(|t| t.text.as_str()) {
+            Some("if" | "ifdef" | "ifndef") => depth += 1,
+            Some("elif" | "else") if depth == 1 => return None,
+            Some("endif") if depth == 1 => {
+                let is_last = tokens[i + 2..].iter().all(|t| !t.at_bol);
+                return is_last.then(|| name.text.clone());
+            }
+            Some("endif") => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+// Parses an expression of #if, and returns its value. The value of an operand
+// not evaluated, e.g. the right of `0 && x`, is computed if `eval` is false,
+// but division by zero in it is not an error.
+//
+// <expr> ::= <cond-expr> ("," <cond-expr>)*
+fn parse_expr(tokens: &[PpToken], eval: bool) -> Result<(Value, &[PpToken]), String> {
+    let (mut val, mut rest) = parse_cond_expr(tokens, eval)?;
+    while let Some(r) = consume(rest, ",") {
+        (val, rest) = parse_cond_expr(r, eval)?;
+    }
+    Ok((val, rest))
+}
+
+// <cond-expr> ::= <binary> ("?" <expr> ":" <cond-expr>)?
+//
+// The result is unsigned if either of the second and third operands is.
+fn parse_cond_expr(tokens: &[PpToken], eval: bool) -> Result<(Value, &[PpToken]), String> {
+    let (cond, rest) = parse_binary(tokens, 0, eval)?;
+    let rest = match consume(rest, "?") {
+        Some(rest) => rest,
+        None => return Ok((cond, rest)),
+    };
+    let (then, rest) = parse_expr(rest, eval && cond.is_true())?;
+    let rest = consume(rest, ":").ok_or("expected ':' in #if")?;
+    let (els, rest) = parse_cond_expr(rest, eval && !cond.is_true())?;
+    let val = Value {
+        val: if cond.is_true() { then.val } else { els.val },
+        is_unsigned: then.is_unsigned || els.is_unsigned,
+    };
+    Ok((val, rest))
+}
+
+// <binary> ::= <unary> (<binary-op> <unary>)*
+//
+// Parses the binary operators of BINARY_OPS[level] and higher precedence.
+//
+// The operands of an arithmetic or a relational operator are converted to
+// unsigned if either is unsigned, e.g. `-1 > 0u` is true. A shift has the
+// type of its left operand, and a logical or an equality operator results in
+// a signed value.
+fn parse_binary(
+    tokens: &[PpToken],
+    level: usize,
+    eval: bool,
+) -> Result<(Value, &[PpToken]), String> {
+    if level == BINARY_OPS.len() {
+        return parse_unary(tokens, eval);
+    }
+    let (mut lhs, mut rest) = parse_binary(tokens, level + 1, eval)?;
+    while let Some(op) = rest
+        .first()
+        .filter(|t| BINARY_OPS[level].contains(&t.text.as_str()))
+    {
+        let eval_rhs = match op.text.as_str() {
+            "&&" => eval && lhs.is_true(),
+            "||" => eval && !lhs.is_true(),
+            _ => eval,
+        };
+        let rhs;
+        (rhs, rest) = parse_binary(&rest[1..], level + 1, eval_rhs)?;
+        let is_unsigned = lhs.is_unsigned || rhs.is_unsigned;
+        let (l, r) = (lhs.val, rhs.val);
+        let (ul, ur) = (l as u64, r as u64);
+        let val = match op.text.as_str() {
+            "||" => (lhs.is_true() || rhs.is_true()) as i64,
+            "&&" => (lhs.is_true() && rhs.is_true()) as i64,
+            "|" => l | r,
+            "^" => l ^ r,
+            "&" => l & r,
+            "==" => (l == r) as i64,
+            "!=" => (l != r) as i64,
+            "<" if is_unsigned => (ul < ur) as i64,
+            ">" if is_unsigned => (ul > ur) as i64,
+            "<=" if is_unsigned => (ul <= ur) as i64,
+            ">=" if is_unsigned => (ul >= ur) as i64,
+            "<" => (l < r) as i64,
+            ">" => (l > r) as i64,
+            "<=" => (l <= r) as i64,
+            ">=" => (l >= r) as i64,
+            "<<" => l.wrapping_shl(r as u32),
+            ">>" if lhs.is_unsigned => ul.wrapping_shr(r as u32) as i64,
+            ">>" => l.wrapping_shr(r as u32),
+            "+" => l.wrapping_add(r),
+            "-" => l.wrapping_sub(r),
+            "*" => l.wrapping_mul(r),
+            _ if r == 0 && eval => return Err("division by zero in #if".to_string()),
+            _ if r == 0 => 0,
+            "/" if is_unsigned => (ul / ur) as i64,
+            "%" if is_unsigned => (ul % ur) as i64,
+            "/" => l.wrapping_div(r),
+            _ => l.wrapping_rem(r),
+        };
+        let is_unsigned = match op.text.as_str() {
+            "|" | "^" | "&" | "+" | "-" | "*" | "/" | "%" => is_unsigned,
+            "<<" | ">>" => lhs.is_unsigned,
+            _ => false,
+        };
+        lhs = Value { val, is_unsigned };
+    }
+    Ok((lhs, rest))
+}
+
+// <unary> ::= ("+" | "-" | "~" | "!") <unary>
+//           | "(" <expr> ")"
+//           | <number>
+//           | <character-constant>
+fn parse_unary(tokens: &[PpToken], eval: bool) -> Result<(Value, &[PpToken]), String> {
+    let tok = tokens.first().ok_or("expected an expression in #if")?;
+    match tok.text.as_str() {
+        "+" | "-" | "~" | "!" => {
+            let (operand, rest) = parse_unary(&tokens[1..], eval)?;
+            let val = match tok.text.as_str() {
+                "+" => operand,
+                "-" => Value {
+                    val: operand.val.wrapping_neg(),
+                    ..operand
+                },
+                "~" => Value {
+                    val: !operand.val,
+                    ..operand
+                },
+                _ => Value::signed(!operand.is_true() as i64),
+            };
+            Ok((val, rest))
+        }
+        "(" => {
+            let (val, rest) = parse_expr(&tokens[1..], eval)?;
+            let rest = consume(rest, ")").ok_or("expected ')' in #if")?;
+            Ok((val, rest))
+        }
+        _ if tok.kind == PpKind::Num => Ok((parse_integer(&tok.text)?, &tokens[1..])),
+        _ if tok.kind == PpKind::Char => Ok((Value::signed(char_value(&tok.text)?), &tokens[1..])),
+        _ => Err(format!("unexpected token in #if: {}", tok.text)),
+    }
+}
+
+// Returns the value of an integer constant, which may be hexadecimal, binary
+// as a GNU extension, or octal, and have suffixes of `u` and `l`. It is
+// unsigned with `u`, or if it is too large to be signed.
+fn parse_integer(text: &str) -> Result<Value, String> {
+    let digits = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    let suffix = &text[digits.len()..];
+    let (digits, radix) = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        (hex, 16)
+    } else if let Some(bin) = digits
+        .strip_prefix("0b")
+        .or_else(|| digits.strip_prefix("0B"))
+    {
+        (bin, 2)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        (&digits[1..], 8)
+    } else {
+        (digits, 10)
+    };
+    let n = u64::from_str_radix(digits, radix)
+        .map_err(|_| format!("invalid integer constant in #if: {}", text))?;
+    Ok(Value {
+        val: n as i64,
+        is_unsigned: suffix.contains(['u', 'U']) || n > i64::MAX as u64,
+    })
+}
+
+// Returns the tokens after the punctuator if they start with it.
+fn consume<'a>(tokens: &'a [PpToken], punct: &str) -> Option<&'a [PpToken]> {
+    match tokens.first() {
+        Some(t) if t.text == punct => Some(&tokens[1..]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize_pp;
+    use crate::preprocess::tests::pp;
+
+    #[test]
+    fn includes_first_group_whose_condition_holds() {
+        let input = "#define A 2
+#if A == 1
+one
+#elif A == 2
+two
+#elif A == 2
+three
+#else
+other
+#endif";
+        assert_eq!(Ok("two".to_string()), pp(input));
+    }
+
+    #[test]
+    fn tests_definition_of_macro() {
+        let input = "#define A
+#ifdef A
+a
+#endif
+#ifndef B
+b
+#else
+c
+#endif
+#if defined A && !defined(B)
+d
+#endif";
+        assert_eq!(Ok("a b d".to_string()), pp(input));
+    }
+
+    #[test]
+    fn evaluates_integer_expression() {
+        let input =
+            "#if (1 + 2 * 3 == 7) + (010 == 8) + (0x10 >> 2 == 4) + (-1 < 0) + (1 ? 2 : 0) == 6
+ok
+#endif
+#if 0 && 1 / 0 || 1 ? 1 : 1 % 0
+ok
+#endif
+#if undefined_name
+ng
+#endif";
+        assert_eq!(Ok("ok ok".to_string()), pp(input));
+        assert!(pp("#if 1 / 0\n#endif").is_err());
+        assert!(pp("#if 1 +\n#endif").is_err());
+        assert!(pp("#if\n#endif").is_err());
+    }
+
+    #[test]
+    fn evaluates_unsigned_expression() {
+        let input = "#if -1 > 0u && -1 < 0 && 0xffffffffffffffff > 0 && (0 ? 1u : -1) > 0
+ok
+#endif
+#if -1 / 2u == 0x7fffffffffffffff && (1u << 63 >> 63) == 1 && (-1 >> 63) == -1
+ok
+#endif";
+        assert_eq!(Ok("ok ok".to_string()), pp(input));
+    }
+
+    #[test]
+    fn evaluates_character_constants() {
+        let input = "#if 'a' == 97 && '\\n' == 10 && '\\xff' < 0 && '\\'' == 39
+ok
+#endif";
+        assert_eq!(Ok("ok".to_string()), pp(input));
+        assert!(pp("#if 'ab'\n#endif").is_err());
+    }
+
+    #[test]
+    fn reports_error_directive() {
+        assert_eq!(
+            Err("tests/include/main.c:4: #error not supported".to_string()),
+            pp("#if 0\n#error skipped\n#endif\n#error not  supported")
+        );
+    }
+
+    #[test]
+    fn skips_nested_groups_loosely() {
+        let input = "#if 0
+#if 1
+it's \"unclosed
+#unknown directive
+#else
+#endif
+#elif 1
+x
+#endif";
+        assert_eq!(Ok("x".to_string()), pp(input));
+    }
+
+    #[test]
+    fn tests_existence_of_header() {
+        let input = "#if __has_include(\"twice.h\") && !__has_include(<no-such-header.h>)
+found
+#endif
+#ifdef __has_include
+defined
+#endif";
+        assert_eq!(Ok("found defined".to_string()), pp(input));
+    }
+
+    #[test]
+    fn reports_unbalanced_conditionals() {
+        assert!(pp("#endif").is_err());
+        assert!(pp("#else").is_err());
+        assert!(pp("#if 1\n#else\n#else\n#endif").is_err());
+        assert!(pp("#if 1\n#else\n#elif 1\n#endif").is_err());
+        assert_eq!(
+            Err("tests/include/main.c:2: unterminated conditional directive".to_string()),
+            pp("\n#ifdef A\n#if 1\n#endif")
+        );
+    }
+
+    #[test]
+    fn detects_include_guard() {
+        let guarded = "#ifndef H\n#define H\n#if 1\n#endif\n#endif";
+        let tokens = tokenize_pp("h.h", guarded).unwrap();
+        assert_eq!(Some("H".to_string()), guard_macro(&tokens));
+
+        for input in [
+            "#ifndef H\n#endif\nx",
+            "x\n#ifndef H\n#endif",
+            "#ifndef H\n#else\n#endif",
+            "#ifdef H\n#endif",
+        ] {
+            let tokens = tokenize_pp("h.h", input).unwrap();
+            assert_eq!(None, guard_macro(&tokens), "{}", input);
+        }
+    }
+}
@@ -0,0 +1,614 @@
+use super::Preprocessor;
+use crate::lexer::{tokenize_pp, PpKind, PpToken};
+
+use std::rc::Rc;
+
+#[derive(Debug)]
+pub(super) struct Macro {
+    // names of the parameters of a function-like macro, where the variable
+    // arguments are named `__VA_ARGS__`, or None for an object-like macro
+    params: Option<Vec<String>>,
+    is_variadic: bool,
+    body: Vec<PpToken>,
+}
+
+impl Macro {
+    // Returns an object-like macro of the body.
+    pub(super) fn object(body: Vec<PpToken>) -> Macro {
+        Macro {
+            params: None,
+            is_variadic: false,
+            body,
+        }
+    }
+
+    // Returns true if the macros are defined identically, with the same
+    // parameters and the same replacement tokens separated by whitespace in
+    // the same places. A macro may only be redefined identically.
+    fn is_identical(&self, other: &Macro) -> bool {
+        self.params == other.params
+            && self.is_variadic == other.is_variadic
+            && self.body.len() == other.body.len()
+            && self
+                .body
+                .iter()
+                .zip(&other.body)
+                .enumerate()
+                .all(|(i, (a, b))| a.text == b.text && (i == 0 || a.has_space == b.has_space))
+    }
+}
+
+impl Preprocessor<'_> {
+    // <define> ::= "define" <ident> ("(" <macro-params>? ")")? <token>*
+    //
+    // Defines a macro by the tokens following "define". It is function-like
+    // if "(" follows the name without whitespace.
+    pub(super) fn define(&mut self, tok: &PpToken, line: &[PpToken]) -> Result<(), String> {
+        let name = match line.first() {
+            Some(name) if name.kind == PpKind::Ident => name,
+            _ => return Err(tok.error("macro name must be an identifier")),
+        };
+        let mut rest = &line[1..];
+        let mut params = None;
+        let mut is_variadic = false;
+        if let Some(paren) = rest.first().filter(|t| t.text == "(" && !t.has_space) {
+            let names;
+            (names, is_variadic, rest) = parse_macro_params(paren, &rest[1..])?;
+            params = Some(names);
+        }
+
+        let body = rest.to_vec();
+        if let (Some(first), Some(last)) = (body.first(), body.last()) {
+            if first.text == "##" || last.text == "##" {
+                return Err(name.error("'##' cannot appear at either end of a macro expansion"));
+            }
+        }
+        if !is_variadic {
+            if let Some(t) = body.iter().find(|t| t.text == "__VA_OPT__") {
+                return Err(t.error("__VA_OPT__ can only appear in a variadic macro"));
+            }
+        }
+        if let Some(params) = &params {
+            for (i, tok) in body.iter().enumerate() {
+                let is_operand = body
+                    .get(i + 1)
+                    .is_some_and(|t| params.contains(&t.text) || t.text == "__VA_OPT__");
+                if tok.text == "#" && !is_operand {
+                    return Err(tok.error("'#' is not followed by a macro parameter"));
+                }
+            }
+        }
+        let m = Macro {
+            params,
+            is_variadic,
+            body,
+        };
+        if let Some(prev) = self.macros.get(&name.text) {
+            if !prev.is_identical(&m) {
+                return Err(name.error(&format!("macro redefined: {}", name.text)));
+            }
+        }
+        self.macros.insert(name.text.clone(), Rc::new(m));
+        Ok(())
+    }
+
+    // <undef> ::= "undef" <ident>
+    pub(super) fn undef(&mut self, tok: &PpToken, line: &[PpToken]) -> Result<(), String> {
+        match line {
+            [name] if name.kind == PpKind::Ident => {
+                self.macros.remove(&name.text);
+                Ok(())
+            }
+            _ => Err(tok.error("expected a macro name after #undef")),
+        }
+    }
+
+    // Expands the macros in the tokens, and rescans the results for more
+    // macros to expand.
+    //
+    // A macro is not expanded in the tokens produced by itself, which are
+    // told by their hide sets, so that a recursive macro terminates.
+    pub(super) fn expand(&mut self, tokens: Vec<PpToken>) -> Result<Vec<PpToken>, String> {
+        // the tokens to scan in reverse order, so that the next one is popped
+        let mut input: Vec<PpToken> = tokens.into_iter().rev().collect();
+        let mut output: Vec<PpToken> = Vec::new();
+        while let Some(tok) = input.pop() {
+            if let Some(t) = self.expand_dynamic(&tok) {
+                output.push(t);
+                continue;
+            }
+            let m = match self.macros.get(&tok.text) {
+                Some(m) if tok.kind == PpKind::Ident && !tok.hideset.contains(&tok.text) => {
+                    m.clone()
+                }
+                _ => {
+                    output.push(tok);
+                    continue;
+                }
+            };
+
+            let mut expanded = match &m.params {
+                None => {
+                    let hideset = union(&tok.hideset, &tok.text);
+                    self.subst(&m, &[], &hideset, &tok)?
+                }
+                // A function-like macro is only invoked by its name followed
+                // by "(".
+                Some(_) if input.last().is_none_or(|t| t.text != "(") => {
+                    output.push(tok);
+                    continue;
+                }
+                Some(params) => {
+                    input.pop();
+                    let named = params.len() - m.is_variadic as usize;
+                    let limit = m.is_variadic.then_some(named);
+                    let (mut args, rparen) = read_args(&tok, &mut input, limit)?;
+                    if args.len() == named && m.is_variadic {
+                        args.push(Vec::new());
+                    }
+                    if params.is_empty() && args.len() == 1 && args[0].is_empty() {
+                        args.clear();
+                    }
+                    if args.len() != params.len() {
+                        return Err(
+                            tok.error(&format!("wrong number of arguments to macro {}", tok.text))
+                        );
+                    }
+                    let hideset: Vec<String> = tok
+                        .hideset
+                        .iter()
+                        .filter(|name| rparen.hideset.contains(name))
+                        .cloned()
+                        .collect();
+                    self.subst(&m, &args, &union(&hideset, &tok.text), &tok)?
+                }
+            };
+
+            // The expansion takes the place of the macro name.
+            match expanded.first_mut() {
+                Some(first) => {
+                    first.at_bol = tok.at_bol;
+                    first.has_space = tok.has_space;
+                }
+                None => {
+                    if let Some(next) = input.last_mut() {
+                        next.has_space |= tok.has_space || tok.at_bol;
+                    }
+                }
+            }
+            input.extend(expanded.into_iter().rev());
+        }
+        Ok(output)
+    }
+
+    // Substitutes the arguments for the parameters in the body of the macro,
+    // and returns the tokens of the expansion, which are added `hideset` and
+    // located at the macro name `tok`.
+    fn subst(
+        &mut self,
+        m: &Macro,
+        args: &[Vec<PpToken>],
+        hideset: &[String],
+        tok: &PpToken,
+    ) -> Result<Vec<PpToken>, String> {
+        let mut tokens = self.subst_tokens(m, &m.body, args)?;
+        for t in &mut tokens {
+            t.file = tok.file.clone();
+            t.line = tok.line;
+            t.at_bol = false;
+            for name in hideset {
+                if !t.hideset.contains(name) {
+                    t.hideset.push(name.clone());
+                }
+            }
+        }
+        Ok(tokens)
+    }
+
+    // Substitutes the arguments in `body`, which is the body of the macro or
+    // the content of __VA_OPT__ in it.
+    //
+    // An argument is fully macro-expanded before substitution, unless it is an
+    // operand of "#" or "##". An empty argument, as an operand of "##", is a
+    // placemarker, which results in the other operand.
+    fn subst_tokens(
+        &mut self,
+        m: &Macro,
+        body: &[PpToken],
+        args: &[Vec<PpToken>],
+    ) -> Result<Vec<PpToken>, String> {
+        let params: &[String] = m.params.as_deref().unwrap_or(&[]);
+        let arg_of = |t: &PpToken| params.iter().position(|p| *p == t.text).map(|i| &args[i]);
+        let has_va_args = m.is_variadic && args.last().is_some_and(|arg| !arg.is_empty());
+        // __VA_OPT__ is replaced by its content if the variable arguments
+        // have any tokens after they are expanded.
+        let has_va_opt = match has_va_args && body.iter().any(|t| t.text == "__VA_OPT__") {
+            true => !self.expand(args.last().unwrap().clone())?.is_empty(),
+            false => false,
+        };
+
+        // The body is divided into operands of "##", which is None.
+        let mut items: Vec<Option<Vec<PpToken>>> = Vec::new();
+        let mut i = 0;
+        while i < body.len() {
+            let t = &body[i];
+            let next = body.get(i + 1);
+            if m.params.is_some() && t.text == "#" {
+                let arg = match arg_of(next.unwrap()) {
+                    Some(arg) => {
+                        i += 2;
+                        arg.clone()
+                    }
+                    // #__VA_OPT__(...)
+                    None => {
+                        let (content, end) = va_opt_content(body, i + 1)?;
+                        i = end + 1;
+                        match has_va_opt {
+                            true => self.subst_tokens(m, content, args)?,
+                            false => Vec::new(),
+                        }
+                    }
+                };
+                items.push(Some(vec![stringize(&arg, t)]));
+                continue;
+            }
+            if t.text == "##" {
+                items.push(None);
+                i += 1;
+                continue;
+            }
+            if m.is_variadic && t.text == "__VA_OPT__" {
+                let content;
+                (content, i) = va_opt_content(body, i)?;
+                items.push(Some(match has_va_opt {
+                    true => self.subst_tokens(m, content, args)?,
+                    false => Vec::new(),
+                }));
+                i += 1;
+                continue;
+            }
+            // GNU extension: `, ## __VA_ARGS__` removes the comma if the
+            // variable arguments are empty, and does not paste it otherwise.
+            if m.is_variadic
+                && t.text == ","
+                && next.is_some_and(|t| t.text == "##")
+                && body.get(i + 2).is_some_and(|t| t.text == "__VA_ARGS__")
+            {
+                if has_va_args {
+                    items.push(Some(vec![t.clone()]));
+                    items.push(Some(args.last().unwrap().clone()));
+                }
+                i += 3;
+                continue;
+            }
+            match arg_of(t) {
+                Some(arg) => {
+                    let is_operand =
+                        (i > 0 && body[i - 1].text == "##") || next.is_some_and(|t| t.text == "##");
+                    let mut arg = match is_operand {
+                        true => arg.clone(),
+                        false => self.expand(arg.clone())?,
+                    };
+                    if let Some(first) = arg.first_mut() {
+                        first.has_space = t.has_space;
+                    }
+                    items.push(Some(arg));
+                }
+                None => items.push(Some(vec![t.clone()])),
+            }
+            i += 1;
+        }
+
+        let mut tokens: Vec<PpToken> = Vec::new();
+        let mut items = items.into_iter();
+        while let Some(item) = items.next() {
+            match item {
+                Some(item) => tokens.extend(item),
+                None => {
+                    let rhs = items.next().flatten().unwrap_or_default();
+                    let mut rhs = rhs.into_iter();
+                    let lhs = match tokens.pop() {
+                        Some(lhs) => lhs,
+                        None => {
+                            tokens.extend(rhs);
+                            continue;
+                        }
+                    };
+                    match rhs.next() {
+                        Some(first) => tokens.push(paste(&lhs, &first)?),
+                        None => tokens.push(lhs),
+                    }
+                    tokens.extend(rhs);
+                }
+            }
+        }
+        Ok(tokens)
+    }
+}
+
+// <macro-params> ::= (<ident> ("," <ident>)* ("," "...")? | "...")? ")"
+//
+// Returns the names of the parameters, and whether the macro is variadic.
+fn parse_macro_params<'a>(
+    paren: &PpToken,
+    tokens: &'a [PpToken],
+) -> Result<(Vec<String>, bool, &'a [PpToken]), String> {
+    let mut params: Vec<String> = Vec::new();
+    let mut rest = tokens;
+    loop {
+        match rest {
+            [t, rest @ ..] if t.text == ")" && params.is_empty() => {
+                return Ok((params, false, rest));
+            }
+            [t, end, rest @ ..] if t.text == "..." && end.text == ")" => {
+                params.push("__VA_ARGS__".to_string());
+                return Ok((params, true, rest));
+            }
+            [t, sep, r @ ..] if t.kind == PpKind::Ident && t.text != "__VA_ARGS__" => {
+                if params.contains(&t.text) {
+                    return Err(t.error(&format!("duplicate macro parameter: {}", t.text)));
+                }
+                params.push(t.text.clone());
+                rest = r;
+                match sep.text.as_str() {
+                    ")" => return Ok((params, false, rest)),
+                    "," => {}
+                    _ => return Err(sep.error("expected ',' or ')' in macro parameters")),
+                }
+            }
+            _ => return Err(paren.error("invalid macro parameters")),
+        }
+    }
+}
+
+// Reads the arguments of a macro invocation after "(" from `input`, which is
+// in reverse order, and returns them with the closing ")". The arguments are
+// separated by commas not in parentheses, but only up to `limit` of them if
+// given, after which the rest are in one argument.
+fn read_args(
+    tok: &PpToken,
+    input: &mut Vec<PpToken>,
+    limit: Option<usize>,
+) -> Result<(Vec<Vec<PpToken>>, PpToken), String> {
+    let mut args: Vec<Vec<PpToken>> = vec![Vec::new()];
+    let mut depth = 0;
+    loop {
+        let t = input.pop().ok_or_else(|| {
+            tok.error(&format!(
+                "unterminated argument list invoking macro {}",
+                tok.text
+            ))
+        })?;
+        match t.text.as_str() {
+            ")" if depth == 0 => return Ok((args, t)),
+            "," if depth == 0 && limit.is_none_or(|n| args.len() <= n) => {
+                args.push(Vec::new());
+                continue;
+            }
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            _ => {}
+        }
+        args.last_mut().unwrap().push(t);
+    }
+}
+
+// <va-opt> ::= "__VA_OPT__" "(" <token>* ")"
+//
+// Returns the tokens in the parentheses of __VA_OPT__ at `start` in the body,
+// and the index of the closing ")".
+fn va_opt_content(body: &[PpToken], start: usize) -> Result<(&[PpToken], usize), String> {
+    let tok = &body[start];
+    if body.get(start + 1).is_none_or(|t| t.text != "(") {
+        return Err(tok.error("expected '(' after __VA_OPT__"));
+    }
+    let mut depth = 0;
+    for (i, t) in body.iter().enumerate().skip(start + 1) {
+        match t.text.as_str() {
+            "(" => depth += 1,
+            ")" if depth == 1 => return Ok((&body[start + 2..i], i)),
+            ")" => depth -= 1,
+            _ => {}
+        }
+    }
+    Err(tok.error("unterminated __VA_OPT__"))
+}
+
+// Returns a string literal spelling the tokens, which are separated by a space
+// where whitespace separates them. Double quotes and backslashes in string
+// literals and character constants are escaped.
+fn stringize(tokens: &[PpToken], hash: &PpToken) -> PpToken {
+    let mut s = String::new();
+    for (i, t) in tokens.iter().enumerate() {
+        if i > 0 && (t.has_space || t.at_bol) {
+            s.push(' ');
+        }
+        match t.kind {
+            PpKind::Str | PpKind::Char => {
+                s.push_str(&t.text.replace('\\', "\\\\").replace('"', "\\\""))
+            }
+            _ => s.push_str(&t.text),
+        }
+    }
+    PpToken {
+        kind: PpKind::Str,
+        text: format!("\"{}\"", s),
+        ..hash.clone()
+    }
+}
+
+// Returns the token of `lhs` and `rhs` pasted by "##", which must be one
+// valid preprocessing token.
+fn paste(lhs: &PpToken, rhs: &PpToken) -> Result<PpToken, String> {
+    let text = format!("{}{}", lhs.text, rhs.text);
+    match tokenize_pp(&lhs.file, &text).as_deref() {
+        Ok([tok]) if tok.text == text => Ok(PpToken {
+            kind: tok.kind,
+            text,
+            ..lhs.clone()
+        }),
+        _ => Err(lhs.error(&format!(
+            "pasting \"{}\" and \"{}\" does not give a valid preprocessing token",
+            lhs.text, rhs.text
+        ))),
+    }
+}
+
+// Returns the hide set with the name added.
+fn union(hideset: &[String], name: &str) -> Vec<String> {
+    let mut hideset = hideset.to_vec();
+    if !hideset.iter().any(|n| n == name) {
+        hideset.push(name.to_string());
+    }
+    hideset
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::preprocess::{preprocess, tests::pp};
+    use crate::{MacroOpt, Options};
+
+    #[test]
+    fn expands_object_like_macro() {
+        let input = "#define N 1 + 2\nN * N\n#undef N\nN";
+        assert_eq!(Ok("1 + 2 * 1 + 2 N".to_string()), pp(input));
+    }
+
+    #[test]
+    fn expands_function_like_macro() {
+        let input = "#define add(a, b) a + (b)\n#define f (x)\nadd(f, add(1, (2, 3))) add f";
+        assert_eq!(
+            Ok("( x ) + ( 1 + ( ( 2 , 3 ) ) ) add ( x )".to_string()),
+            pp(input)
+        );
+    }
+
+    #[test]
+    fn expands_macros_of_standard_example() {
+        // the example of C11 6.10.3.5
+        let input = "#define x 3
+#define f(a) f(x * (a))
+#undef x
+#define x 2
+#define g f
+#define z z[0]
+#define h g(~
+#define m(a) a(w)
+#define w 0,1
+#define t(a) a
+#define p() int
+#define q(x) x
+#define r(x,y) x ## y
+#define str(x) # x
+f(y+1) + f(f(z)) % t(t(g)(0) + t)(1);
+g(x+(3,4)-w) | h 5) & m
+(f)^m(m);
+p() i[q()] = { q(1), r(2,3), r(4,), r(,5), r(,) };
+char c[2][6] = { str(hello), str() };";
+        let expected = [
+            "f ( 2 * ( y + 1 ) ) + f ( 2 * ( f ( 2 * ( z [ 0 ] ) ) ) ) % f ( 2 * ( 0 ) ) + t ( 1 ) ;",
+            "f ( 2 * ( 2 + ( 3 , 4 ) - 0 , 1 ) ) | f ( 2 * ( ~ 5 ) ) & f ( 2 * ( 0 , 1 ) ) ^ m ( 0 , 1 ) ;",
+            "int i [ ] = { 1 , 23 , 4 , 5 , } ;",
+            "char c [ 2 ] [ 6 ] = { \"hello\" , \"\" } ;",
+        ];
+        assert_eq!(Ok(expected.join(" ")), pp(input));
+    }
+
+    #[test]
+    fn stringizes_argument() {
+        let input = "#define s(x) #x\ns( a  +\"b\\n\" )";
+        assert_eq!(Ok(r#""a +\"b\\n\"""#.to_string()), pp(input));
+    }
+
+    #[test]
+    fn cannot_paste_into_invalid_token() {
+        let input = "#define cat(a, b) a ## b\ncat(+, -)";
+        assert_eq!(
+            Err(
+                "f.c:2: pasting \"+\" and \"-\" does not give a valid preprocessing token"
+                    .to_string()
+            ),
+            preprocess("f.c", input, &Options::default())
+        );
+    }
+
+    #[test]
+    fn expands_variable_arguments() {
+        let input = "#define f(fmt, ...) g(fmt __VA_OPT__(,) __VA_ARGS__)
+#define e(fmt, ...) g(fmt, ## __VA_ARGS__)
+f(a) f(a, b, (c, d)) e(a) e(a, b)";
+        assert_eq!(
+            Ok("g ( a ) g ( a , b , ( c , d ) ) g ( a ) g ( a , b )".to_string()),
+            pp(input)
+        );
+    }
+
+    #[test]
+    fn tests_variable_arguments_after_expansion_for_va_opt() {
+        let input = "#define EMPTY
+#define f(x, ...) g(x __VA_OPT__(,) __VA_ARGS__)
+f(0, EMPTY) f(0, EMPTY 1)";
+        assert_eq!(Ok("g ( 0 ) g ( 0 , 1 )".to_string()), pp(input));
+    }
+
+    #[test]
+    fn reports_va_opt_in_non_variadic_macro() {
+        let input = "#define F(x) #__VA_OPT__(x)\nF(1)";
+        assert!(preprocess("f.c", input, &Options::default()).is_err());
+        let input = "#define G __VA_OPT__(x)";
+        assert!(preprocess("f.c", input, &Options::default()).is_err());
+    }
+
+    #[test]
+    fn reports_wrong_number_of_arguments() {
+        let input = "#define f(a, b) a\nf(1)";
+        assert!(preprocess("f.c", input, &Options::default()).is_err());
+        let input = "#define f(a) a\nf(1, 2)";
+        assert!(preprocess("f.c", input, &Options::default()).is_err());
+        let input = "#define f(a) a\nf(1";
+        assert!(preprocess("f.c", input, &Options::default()).is_err());
+    }
+
+    #[test]
+    fn redefines_macros_only_identically() {
+        // the examples of C11 6.10.3.5
+        let valid = "#define OBJ_LIKE (1-1)
+#define OBJ_LIKE /* white space */ (1-1) /* other */
+#define FUNC_LIKE(a) ( a )
+#define FUNC_LIKE( a )( /* note the white space */ \\
+a /* other stuff on this line
+*/ )
+OBJ_LIKE FUNC_LIKE(2)";
+        assert_eq!(Ok("( 1 - 1 ) ( 2 )".to_string()), pp(valid));
+        for invalid in [
+            "#define OBJ_LIKE (1-1)\n#define OBJ_LIKE (0)",
+            "#define OBJ_LIKE (1-1)\n#define OBJ_LIKE (1 - 1)",
+            "#define FUNC_LIKE(a) ( a )\n#define FUNC_LIKE(b) ( a )",
+            "#define FUNC_LIKE(a) ( a )\n#define FUNC_LIKE(a, ...) ( a )",
+        ] {
+            assert!(preprocess("f.c", invalid, &Options::default()).is_err());
+        }
+    }
+
+    #[test]
+    fn does_not_expand_recursive_macro_again() {
+        let input = "#define a a b\n#define b a\na b";
+        assert_eq!(Ok("a a a b".to_string()), pp(input));
+    }
+
+    #[test]
+    fn defines_macros_of_options() {
+        let opts = Options {
+            macros: vec![
+                MacroOpt::Define("A".to_string()),
+                MacroOpt::Define("B=2".to_string()),
+                MacroOpt::Define("C=3".to_string()),
+                MacroOpt::Undef("C".to_string()),
+            ],
+            ..Options::default()
+        };
+        let tokens = preprocess("f.c", "A B C", &opts).unwrap();
+        let texts: Vec<&str> = tokens.iter().map(|tok| tok.text.as_str()).collect();
+        assert_eq!(vec!["1", "2", "C"], texts);
+    }
+}
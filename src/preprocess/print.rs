@@ -0,0 +1,117 @@
+use super::builtin::quote;
+use crate::lexer::{PpKind, PpToken, PUNCTS};
+
+use std::rc::Rc;
+
+// maximum number of lines skipped by newlines, beyond which a linemarker is
+// printed instead
+const MAX_BLANK_LINES: usize = 8;
+
+// Returns the text of the preprocessed tokens, which is compiled into the
+// same tokens.
+//
+// Tokens are printed on the lines they are on, and separated as in the input.
+// A linemarker `# <line> "<file>"` is printed when the file changes, when
+// many lines are skipped, e.g. by a skipped group, or when the line goes back,
+// e.g. by `#line`.
+pub fn print_tokens(tokens: &[PpToken]) -> String {
+    let mut output = String::new();
+    let mut file: Option<Rc<str>> = None;
+    let mut line = 0;
+    let mut prev: Option<&PpToken> = None;
+    for tok in tokens {
+        let is_same_file = file.as_ref().is_some_and(|f| *f == tok.file);
+        if !is_same_file || tok.line > line + MAX_BLANK_LINES || tok.line < line {
+            if !output.is_empty() {
+                output.push('\n');
+            }
+            output.push_str(&format!("# {} {}\n", tok.line, quote(&tok.file)));
+            file = Some(tok.file.clone());
+            line = tok.line;
+            prev = None;
+        } else if tok.line > line {
+            output.push_str(&"\n".repeat(tok.line - line));
+            line = tok.line;
+            prev = None;
+        }
+
+        let needs_space = match prev {
+            Some(prev) => tok.has_space || is_pasted(prev, tok),
+            None => tok.has_space,
+        };
+        if needs_space {
+            output.push(' ');
+        }
+        output.push_str(&tok.text);
+        prev = Some(tok);
+    }
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    output
+}
+
+// Returns true if the tokens are read as other tokens without a space between
+// them, e.g. `-` and `-` from macros, which are read as `--`.
+fn is_pasted(prev: &PpToken, tok: &PpToken) -> bool {
+    let (last, first) = match (prev.text.chars().last(), tok.text.chars().next()) {
+        (Some(last), Some(first)) => (last, first),
+        _ => return false,
+    };
+    let is_ident_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.';
+    if is_ident_char(last) && is_ident_char(first) {
+        return true;
+    }
+    // A number takes a sign after its exponent, e.g. `1e` and `+`.
+    if prev.kind == PpKind::Num && matches!(last, 'e' | 'E' | 'p' | 'P') {
+        return matches!(first, '+' | '-');
+    }
+    let joined = format!("{}{}", prev.text, first);
+    prev.kind == PpKind::Punct && PUNCTS.iter().any(|p| p.starts_with(&joined))
+        || joined.ends_with("//")
+        || joined.ends_with("/*")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preprocess::preprocess;
+    use crate::Options;
+
+    fn print(input: &str) -> String {
+        print_tokens(&preprocess("f.c", input, &Options::default()).unwrap())
+    }
+
+    #[test]
+    fn keeps_lines_and_spaces() {
+        let input = "int  x;\n\nint f(int a,\nint b) {return a;}";
+        assert_eq!(
+            "# 1 \"f.c\"\nint x;\n\nint f(int a,\nint b) {return a;}\n",
+            print(input)
+        );
+    }
+
+    #[test]
+    fn prints_linemarkers() {
+        let input = "#include \"tests/include/answer.h\"\nint x;\n#if 0\n\n\n\n\n\n\n\n\n#endif\ny";
+        let output = print(input);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!("# 3 \"tests/include/twice.h\"", lines[0]);
+        assert_eq!(
+            vec!["# 2 \"f.c\"", "int x;", "# 13 \"f.c\"", "y"],
+            lines[lines.len() - 4..]
+        );
+    }
+
+    #[test]
+    fn separates_tokens_pasted_by_macros() {
+        let input = "#define neg -x\n#define id(a) a\n-neg id(1)id(e)+id(.)id(.)id(.)";
+        assert_eq!("# 3 \"f.c\"\n- -x 1 e+. . .\n", print(input));
+    }
+
+    #[test]
+    fn prints_linemarker_when_line_goes_back() {
+        let input = "a\nb\nc\n#line 1\nd\ne";
+        assert_eq!("# 1 \"f.c\"\na\nb\nc\n# 1 \"f.c\"\nd\ne\n", print(input));
+    }
+}
@@ -1,4 +1,1029 @@
-#[derive(Debug, PartialEq)]
+use crate::parser::Expr;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Ty {
+    Void, // void type, which has no values
+    Bool, // _Bool, whose values are 0 and 1
+    Char, // char, which is signed as `signed char`
+    UChar,
+    Short,
+    UShort,
     Int,
+    UInt,
+    Long,
+    ULong,
+    Float,                    // single precision floating type
+    Double,                   // double precision floating type
+    LongDouble,               // x87 extended precision floating type
+    Enum,                     // enumerated type, which is compatible with int
+    Ptr(Box<Ty>),             // pointer to the type
+    Array(Box<Ty>, usize),    // array of the element type with the length
+    IncompleteArray(Box<Ty>), // array of unknown length, e.g. `int a[]`
+    Vla(Box<Ty>, Box<Expr>),  // variable length array of the length expression
+    Func(Box<FuncTy>),        // function type
+    Struct(StructRef),        // structure or union type
+    Qual(Box<Ty>, Quals),     // qualified type, e.g. `const int`
+}
+
+// Type qualifiers.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Quals {
+    pub is_const: bool,
+    pub is_volatile: bool,
+    pub is_restrict: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncTy {
+    pub ret: Ty, // return type
+    // parameter types, or None if they are unspecified, e.g. `int f()`
+    pub params: Option<Vec<Ty>>,
+    // true if the function takes variable arguments after the parameters,
+    // e.g. `int printf(char *fmt, ...)`
+    pub is_variadic: bool,
+}
+
+// Structure or union type, which is shared by all the types referring to it,
+// so that they see it completed after they are derived, e.g. `struct node *`
+// in the members of `struct node`. Each specifier which declares one makes a
+// distinct type.
+#[derive(Clone)]
+pub struct StructRef(Rc<RefCell<StructTy>>);
+
+#[derive(Debug)]
+struct StructTy {
+    tag: Option<String>,
+    is_union: bool,
+    layout: Option<Layout>, // None until the members are declared
+}
+
+#[derive(Debug)]
+struct Layout {
+    members: Vec<Member>,
+    size: usize,
+    align: usize,
+}
+
+// Member of a structure or union, at `offset` bytes from its start.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub name: Option<String>, // None for an anonymous structure or union
+    pub ty: Ty,
+    pub offset: usize,
+    pub bit_field: Option<BitField>,
+}
+
+// Bit-field, which occupies `width` bits from bit `bit_offset` of the byte at
+// the offset of its member, in the order of significance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitField {
+    pub bit_offset: usize,
+    pub width: usize,
+}
+
+// Declaration of a member, from which the layout of a structure or union is
+// computed.
+#[derive(Debug)]
+pub struct MemberDecl {
+    // None for an unnamed bit-field, or an anonymous structure or union
+    pub name: Option<String>,
+    pub ty: Ty,
+    pub width: Option<usize>, // width if it is a bit-field
+    // alignment by _Alignas or the aligned attribute, or 0 if not given
+    pub align: usize,
+    pub is_packed: bool, // true if it has the packed attribute
+}
+
+thread_local! {
+    // Structure type of the element of va_list, which is only declared once,
+    // so that every va_list has the same type.
+    static VA_LIST_TAG: StructRef = {
+        let s = StructRef::new(Some("__va_list_tag".to_string()), false);
+        let decl = |name: &str, ty| MemberDecl {
+            name: Some(name.to_string()),
+            ty,
+            width: None,
+            align: 0,
+            is_packed: false,
+        };
+        let ptr = || Ty::Ptr(Box::new(Ty::Void));
+        s.complete(
+            vec![
+                decl("gp_offset", Ty::UInt),
+                decl("fp_offset", Ty::UInt),
+                decl("overflow_arg_area", ptr()),
+                decl("reg_save_area", ptr()),
+            ],
+            false,
+            0,
+        );
+        s
+    };
+}
+
+impl Ty {
+    // Returns the type of va_list, which the ABI defines as an array of one
+    // structure, `struct __va_list_tag [1]`.
+    pub fn va_list() -> Ty {
+        let tag = VA_LIST_TAG.with(|s| s.clone());
+        Ty::Array(Box::new(Ty::Struct(tag)), 1)
+    }
+
+    // Size in bytes.
+    //
+    // An incomplete type and a function type have no size, so they must not
+    // be asked. Nor must a variably modified type, whose size is only known
+    // at runtime.
+    pub fn size(&self) -> usize {
+        match self {
+            Ty::Void => unreachable!("size of void"),
+            Ty::Bool | Ty::Char | Ty::UChar => 1,
+            Ty::Short | Ty::UShort => 2,
+            Ty::Int | Ty::UInt | Ty::Float | Ty::Enum => 4,
+            Ty::Long | Ty::ULong | Ty::Double | Ty::Ptr(_) => 8,
+            // The 80-bit value is padded to 16 bytes.
+            Ty::LongDouble => 16,
+            Ty::Array(elem, len) => elem.size() * len,
+            Ty::IncompleteArray(_) => unreachable!("size of an incomplete array type"),
+            Ty::Vla(..) => unreachable!("size of a variable length array type"),
+            Ty::Func(_) => unreachable!("size of a function type"),
+            Ty::Struct(s) => s.layout(|l| l.size),
+            Ty::Qual(ty, _) => ty.size(),
+        }
+    }
+
+    // Alignment in bytes.
+    pub fn align(&self) -> usize {
+        match self {
+            Ty::Void => unreachable!("alignment of void"),
+            Ty::Bool | Ty::Char | Ty::UChar => 1,
+            Ty::Short | Ty::UShort => 2,
+            Ty::Int | Ty::UInt | Ty::Float | Ty::Enum => 4,
+            Ty::Long | Ty::ULong | Ty::Double | Ty::Ptr(_) => 8,
+            Ty::LongDouble => 16,
+            Ty::Array(elem, _) | Ty::IncompleteArray(elem) | Ty::Vla(elem, _) => elem.align(),
+            Ty::Func(_) => unreachable!("alignment of a function type"),
+            Ty::Struct(s) => s.layout(|l| l.align),
+            Ty::Qual(ty, _) => ty.align(),
+        }
+    }
+
+    // Returns true if the type is an integer type.
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self.unqual(),
+            Ty::Bool
+                | Ty::Char
+                | Ty::UChar
+                | Ty::Short
+                | Ty::UShort
+                | Ty::Int
+                | Ty::UInt
+                | Ty::Long
+                | Ty::ULong
+                | Ty::Enum
+        )
+    }
+
+    // Returns true if the type is an unsigned integer type other than _Bool,
+    // whose values are zero-extended rather than sign-extended.
+    pub fn is_unsigned(&self) -> bool {
+        matches!(self.unqual(), Ty::UChar | Ty::UShort | Ty::UInt | Ty::ULong)
+    }
+
+    // Returns true if the type is a floating type.
+    pub fn is_flonum(&self) -> bool {
+        matches!(self.unqual(), Ty::Float | Ty::Double | Ty::LongDouble)
+    }
+
+    // Returns true if the type is an arithmetic type, i.e. an integer type or
+    // a floating type.
+    pub fn is_arith(&self) -> bool {
+        self.is_integer() || self.is_flonum()
+    }
+
+    // Returns true if the type is an array type, whether its length is known
+    // or not.
+    pub fn is_array(&self) -> bool {
+        matches!(self, Ty::Array(..) | Ty::IncompleteArray(_) | Ty::Vla(..))
+    }
+
+    // Returns true if the type is incomplete, i.e. void, an array of unknown
+    // length, or a structure or union whose members are not declared yet.
+    pub fn is_incomplete(&self) -> bool {
+        match self.unqual() {
+            Ty::Void | Ty::IncompleteArray(_) => true,
+            Ty::Struct(s) => !s.is_complete(),
+            _ => false,
+        }
+    }
+
+    // Returns true if the type is variably modified, i.e. it is or derives
+    // from a variable length array type, e.g. `int (*)[n]`. Parameters of a
+    // function type do not make it variably modified.
+    pub fn is_variably_modified(&self) -> bool {
+        match self {
+            Ty::Vla(..) => true,
+            Ty::Ptr(ty) | Ty::Array(ty, _) | Ty::IncompleteArray(ty) | Ty::Qual(ty, _) => {
+                ty.is_variably_modified()
+            }
+            _ => false,
+        }
+    }
+
+    // Returns true if the types are compatible, i.e. they can be the types of
+    // declarations of the same entity. Compatible types have the same
+    // qualifiers.
+    pub fn is_compatible(&self, other: &Ty) -> bool {
+        match (self, other) {
+            (Ty::Qual(a, q), Ty::Qual(b, r)) => q == r && a.is_compatible(b),
+            (Ty::Void, Ty::Void) => true,
+            (Ty::Bool, Ty::Bool) => true,
+            (Ty::Char, Ty::Char) => true,
+            (Ty::UChar, Ty::UChar) => true,
+            (Ty::Short, Ty::Short) => true,
+            (Ty::UShort, Ty::UShort) => true,
+            (Ty::UInt, Ty::UInt) => true,
+            (Ty::Long, Ty::Long) => true,
+            (Ty::ULong, Ty::ULong) => true,
+            (Ty::Float, Ty::Float) => true,
+            (Ty::Double, Ty::Double) => true,
+            (Ty::LongDouble, Ty::LongDouble) => true,
+            (Ty::Int | Ty::Enum, Ty::Int | Ty::Enum) => true,
+            (Ty::Ptr(a), Ty::Ptr(b)) => a.is_compatible(b),
+            (Ty::Array(a, n), Ty::Array(b, m)) => n == m && a.is_compatible(b),
+            // An array of unknown length or variable length is compatible with
+            // any length.
+            (
+                Ty::Array(a, _) | Ty::IncompleteArray(a) | Ty::Vla(a, _),
+                Ty::Array(b, _) | Ty::IncompleteArray(b) | Ty::Vla(b, _),
+            ) => a.is_compatible(b),
+            (Ty::Func(a), Ty::Func(b)) => a.is_compatible(b),
+            (Ty::Struct(a), Ty::Struct(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    // Returns true if the default argument promotions do not change the type,
+    // which promote integers of lower rank than int to int, and float to
+    // double.
+    pub fn is_promoted(&self) -> bool {
+        !matches!(
+            self.unqual(),
+            Ty::Bool | Ty::Char | Ty::UChar | Ty::Short | Ty::UShort | Ty::Float
+        )
+    }
+
+    // Returns the composite type of compatible types, which takes the length of
+    // an array and the parameters of a function from either type that has
+    // them.
+    pub fn composite(&self, other: &Ty) -> Ty {
+        match (self, other) {
+            (Ty::Qual(a, q), Ty::Qual(b, _)) => Ty::Qual(Box::new(a.composite(b)), *q),
+            (Ty::Ptr(a), Ty::Ptr(b)) => Ty::Ptr(Box::new(a.composite(b))),
+            (Ty::Array(a, n), Ty::Array(b, _) | Ty::IncompleteArray(b))
+            | (Ty::IncompleteArray(a), Ty::Array(b, n)) => Ty::Array(Box::new(a.composite(b)), *n),
+            (Ty::IncompleteArray(a), Ty::IncompleteArray(b)) => {
+                Ty::IncompleteArray(Box::new(a.composite(b)))
+            }
+            (Ty::Func(a), Ty::Func(b)) => Ty::Func(Box::new(a.composite(b))),
+            _ => self.clone(),
+        }
+    }
+
+    // Returns the type converted to when used as a value, where an array is
+    // converted to a pointer to its first element, and a function is
+    // converted to a pointer to itself.
+    pub fn decay(&self) -> Ty {
+        match self {
+            Ty::Array(elem, _) | Ty::IncompleteArray(elem) | Ty::Vla(elem, _) => {
+                Ty::Ptr(elem.clone())
+            }
+            Ty::Func(_) => Ty::Ptr(Box::new(self.clone())),
+            _ => self.clone(),
+        }
+    }
+
+    // Returns the type pointed to, if the type is a pointer or an array.
+    pub fn pointee(&self) -> Option<&Ty> {
+        match self {
+            Ty::Ptr(ty) | Ty::Array(ty, _) | Ty::IncompleteArray(ty) | Ty::Vla(ty, _) => Some(ty),
+            Ty::Qual(ty, _) => ty.pointee(),
+            _ => None,
+        }
+    }
+
+    // Returns the type qualified with `quals` in addition to its own
+    // qualifiers.
+    //
+    // As in C, qualifiers of an array type apply to its elements. Those of a
+    // function type are ignored.
+    pub fn qualify(self, quals: Quals) -> Ty {
+        if quals == Quals::default() {
+            return self;
+        }
+        match self {
+            Ty::Array(elem, len) => Ty::Array(Box::new(elem.qualify(quals)), len),
+            Ty::IncompleteArray(elem) => Ty::IncompleteArray(Box::new(elem.qualify(quals))),
+            Ty::Vla(elem, len) => Ty::Vla(Box::new(elem.qualify(quals)), len),
+            Ty::Func(_) => self,
+            Ty::Qual(ty, q) => Ty::Qual(ty, q.merge(quals)),
+            _ => Ty::Qual(Box::new(self), quals),
+        }
+    }
+
+    // Returns the type without its qualifiers.
+    pub fn unqual(&self) -> &Ty {
+        match self {
+            Ty::Qual(ty, _) => ty,
+            _ => self,
+        }
+    }
+
+    // Returns the qualifiers of the type.
+    pub fn quals(&self) -> Quals {
+        match self {
+            Ty::Qual(_, quals) => *quals,
+            _ => Quals::default(),
+        }
+    }
+}
+
+impl Quals {
+    // Returns the qualifiers of both.
+    pub fn merge(self, other: Quals) -> Quals {
+        Quals {
+            is_const: self.is_const || other.is_const,
+            is_volatile: self.is_volatile || other.is_volatile,
+            is_restrict: self.is_restrict || other.is_restrict,
+        }
+    }
+
+    // Returns true if it has all the qualifiers of `other`.
+    pub fn contains(&self, other: Quals) -> bool {
+        self.merge(other) == *self
+    }
+}
+
+impl FuncTy {
+    // Returns the composite type of compatible function types.
+    pub fn composite(&self, other: &FuncTy) -> FuncTy {
+        let ret = self.ret.composite(&other.ret);
+        match (&self.params, &other.params) {
+            (Some(a), Some(b)) => FuncTy {
+                ret,
+                params: Some(a.iter().zip(b).map(|(a, b)| a.composite(b)).collect()),
+                is_variadic: self.is_variadic,
+            },
+            (None, _) => FuncTy {
+                ret,
+                ..other.clone()
+            },
+            (_, None) => FuncTy {
+                ret,
+                ..self.clone()
+            },
+        }
+    }
+
+    // Returns true if the function types are compatible. Unspecified
+    // parameters are only compatible with parameters which the default
+    // argument promotions do not change, without variable arguments, since
+    // arguments to a function without a prototype are promoted.
+    pub fn is_compatible(&self, other: &FuncTy) -> bool {
+        if !self.ret.is_compatible(&other.ret) {
+            return false;
+        }
+        match (&self.params, &other.params) {
+            (Some(_), Some(_)) if self.is_variadic != other.is_variadic => false,
+            // Qualifiers of a parameter only apply in the function body.
+            (Some(a), Some(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b)
+                        .all(|(a, b)| a.unqual().is_compatible(b.unqual()))
+            }
+            (Some(params), None) => !self.is_variadic && params.iter().all(Ty::is_promoted),
+            (None, Some(params)) => !other.is_variadic && params.iter().all(Ty::is_promoted),
+            (None, None) => true,
+        }
+    }
+}
+
+impl StructRef {
+    // Returns a structure or union type of the tag, which is incomplete until
+    // its members are declared.
+    pub fn new(tag: Option<String>, is_union: bool) -> Self {
+        StructRef(Rc::new(RefCell::new(StructTy {
+            tag,
+            is_union,
+            layout: None,
+        })))
+    }
+
+    pub fn is_union(&self) -> bool {
+        self.0.borrow().is_union
+    }
+
+    // Returns true if the type has no tag, e.g. `struct { int x; }`.
+    pub fn is_anonymous(&self) -> bool {
+        self.0.borrow().tag.is_none()
+    }
+
+    // Returns true if the members have been declared.
+    pub fn is_complete(&self) -> bool {
+        self.0.borrow().layout.is_some()
+    }
+
+    // Completes the type with the members, which are laid out as the System V
+    // ABI specifies and gcc does, e.g. for `struct { char a; int b : 3; }`,
+    // `b` is in the same int-sized storage unit as `a`, from bit 8.
+    //
+    // A member is aligned to its type, and the structure to its most aligned
+    // member, so that it can be an element of an array. Each member of a
+    // union is at offset 0. A flexible array member, e.g. `int a[]` at the
+    // end, is aligned to its elements but takes no space.
+    //
+    // A bit-field follows the previous one, unless it would cross a boundary
+    // of the storage units of its type, in which case it starts at the next
+    // unit. A zero-width bit-field also starts the next unit. Unnamed
+    // bit-fields do not affect the alignment of the structure.
+    //
+    // A packed member, or every member if `is_packed`, is aligned to a byte,
+    // and a packed bit-field follows the previous one even across a boundary.
+    // An alignment given to a member, or `align` to the structure, raises
+    // their alignments, even if packed.
+    pub fn complete(&self, decls: Vec<MemberDecl>, is_packed: bool, align: usize) {
+        let is_union = self.is_union();
+        let mut members: Vec<Member> = Vec::new();
+        let mut size = 0; // in bits
+        let mut struct_align = 1;
+        for decl in decls {
+            let MemberDecl {
+                name, ty, width, ..
+            } = decl;
+            let is_packed = is_packed || decl.is_packed;
+            let align = match is_packed {
+                true => decl.align.max(1),
+                false => decl.align.max(ty.align()),
+            };
+            let mut bits = if is_union { 0 } else { size };
+            match width {
+                Some(0) => bits = align_to(bits, ty.align() * 8),
+                Some(width) => {
+                    let unit = ty.size() * 8;
+                    if !is_packed && bits / unit != (bits + width - 1) / unit {
+                        bits = align_to(bits, unit);
+                    }
+                    if name.is_some() {
+                        struct_align = struct_align.max(align);
+                        members.push(Member {
+                            name,
+                            ty,
+                            offset: bits / 8,
+                            bit_field: Some(BitField {
+                                bit_offset: bits % 8,
+                                width,
+                            }),
+                        });
+                    }
+                    bits += width;
+                }
+                None => {
+                    bits = align_to(bits, align * 8);
+                    struct_align = struct_align.max(align);
+                    let offset = bits / 8;
+                    if !matches!(ty, Ty::IncompleteArray(_)) {
+                        bits += ty.size() * 8;
+                    }
+                    members.push(Member {
+                        name,
+                        ty,
+                        offset,
+                        bit_field: None,
+                    });
+                }
+            }
+            size = size.max(bits);
+        }
+        let align = struct_align.max(align);
+        self.0.borrow_mut().layout = Some(Layout {
+            members,
+            size: align_to(size.div_ceil(8), align),
+            align,
+        });
+    }
+
+    // Returns the members in order, except unnamed bit-fields.
+    pub fn members(&self) -> Vec<Member> {
+        self.layout(|l| l.members.clone())
+    }
+
+    // Finds a member of the name, which may be a member of an anonymous
+    // structure or union member, e.g. `x` of `struct { union { int x; }; }`.
+    pub fn find_member(&self, name: &str) -> Option<Member> {
+        for member in self.members() {
+            match (&member.name, member.ty.unqual()) {
+                (Some(n), _) if n == name => return Some(member),
+                (None, Ty::Struct(s)) => {
+                    if let Some(m) = s.find_member(name) {
+                        return Some(Member {
+                            ty: m.ty.qualify(member.ty.quals()),
+                            offset: member.offset + m.offset,
+                            ..m
+                        });
+                    }
+                }
+                _ => (),
+            }
+        }
+        None
+    }
+
+    // Returns the names of the members, including those of anonymous
+    // structure or union members.
+    pub fn member_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for member in self.members() {
+            match (member.name, member.ty.unqual()) {
+                (Some(name), _) => names.push(name),
+                (None, Ty::Struct(s)) => names.extend(s.member_names()),
+                _ => (),
+            }
+        }
+        names
+    }
+
+    // Returns true if any member is const-qualified, including elements of
+    // array members and members of structure members, which makes the
+    // structure not modifiable as a whole.
+    pub fn has_const_member(&self) -> bool {
+        self.members().iter().any(|member| {
+            let mut ty = &member.ty;
+            while let Some(elem) = ty.pointee().filter(|_| ty.is_array()) {
+                ty = elem;
+            }
+            ty.quals().is_const || matches!(ty.unqual(), Ty::Struct(s) if s.has_const_member())
+        })
+    }
+
+    fn layout<T>(&self, f: impl FnOnce(&Layout) -> T) -> T {
+        match &self.0.borrow().layout {
+            Some(layout) => f(layout),
+            None => unreachable!("layout of an incomplete type: {:?}", self),
+        }
+    }
+}
+
+// Structure types are the same only if they are declared by the same
+// specifier.
+impl PartialEq for StructRef {
+    fn eq(&self, other: &StructRef) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// Prints the type by its tag, e.g. `struct node`, since the members may refer
+// to the type itself.
+impl fmt::Debug for StructRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = self.0.borrow();
+        let kind = if s.is_union { "union" } else { "struct" };
+        match &s.tag {
+            Some(tag) => write!(f, "{} {}", kind, tag),
+            None => write!(f, "{} <anonymous>", kind),
+        }
+    }
+}
+
+// Rounds up `n` to the nearest multiple of `align`.
+pub fn align_to(n: usize, align: usize) -> usize {
+    n.div_ceil(align) * align
+}
+
+// Prints the type as a C type name, e.g. `int *` or `char (*)[3]`.
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.type_name(String::new()).trim_end())
+    }
+}
+
+impl Ty {
+    // Returns the type name of a declarator `inner` of the type, which is
+    // built from the outermost derivation inwards as the syntax of C does.
+    fn type_name(&self, inner: String) -> String {
+        let tag;
+        let base = match self {
+            Ty::Void => "void",
+            Ty::Bool => "_Bool",
+            Ty::Char => "char",
+            Ty::UChar => "unsigned char",
+            Ty::Short => "short",
+            Ty::UShort => "unsigned short",
+            Ty::Int => "int",
+            Ty::UInt => "unsigned int",
+            Ty::Long => "long",
+            Ty::ULong => "unsigned long",
+            Ty::Float => "float",
+            Ty::Double => "double",
+            Ty::LongDouble => "long double",
+            Ty::Enum => "enum",
+            Ty::Ptr(ty) => return ty.pointer_name("", inner),
+            Ty::Array(elem, len) => return elem.type_name(format!("{}[{}]", inner, len)),
+            Ty::IncompleteArray(elem) => return elem.type_name(format!("{}[]", inner)),
+            Ty::Vla(elem, _) => return elem.type_name(format!("{}[*]", inner)),
+            Ty::Func(func) => {
+                let mut params: Vec<String> = match &func.params {
+                    Some(params) => params.iter().map(|ty| ty.to_string()).collect(),
+                    None => Vec::new(),
+                };
+                if func.is_variadic {
+                    params.push("...".to_string());
+                } else if func.params.as_ref().is_some_and(|p| p.is_empty()) {
+                    params.push("void".to_string());
+                }
+                return func
+                    .ret
+                    .type_name(format!("{}({})", inner, params.join(", ")));
+            }
+            Ty::Struct(s) => {
+                tag = format!("{:?}", s);
+                &tag
+            }
+            Ty::Qual(ty, quals) => match &**ty {
+                Ty::Ptr(pointee) => return pointee.pointer_name(&quals.to_string(), inner),
+                ty => return format!("{} {}", quals, ty.type_name(inner)),
+            },
+        };
+        if inner.is_empty() || inner.starts_with('[') {
+            format!("{}{}", base, inner)
+        } else {
+            format!("{} {}", base, inner)
+        }
+    }
+
+    // Returns the type name of a pointer to the type with the qualifiers,
+    // which is parenthesized if it points to an array or a function.
+    fn pointer_name(&self, quals: &str, inner: String) -> String {
+        let mut ptr = "*".to_string();
+        ptr.push_str(quals);
+        if !quals.is_empty() && !inner.is_empty() {
+            ptr.push(' ');
+        }
+        ptr.push_str(&inner);
+        match self {
+            Ty::Array(..) | Ty::IncompleteArray(_) | Ty::Vla(..) | Ty::Func(_) => {
+                self.type_name(format!("({})", ptr))
+            }
+            _ => self.type_name(ptr),
+        }
+    }
+}
+
+// Prints the qualifiers separated by spaces, e.g. `const volatile`.
+impl fmt::Display for Quals {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = [
+            (self.is_const, "const"),
+            (self.is_volatile, "volatile"),
+            (self.is_restrict, "restrict"),
+        ];
+        let names: Vec<&str> = names.iter().filter(|(b, _)| *b).map(|(_, n)| *n).collect();
+        f.write_str(&names.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_of_array_of_pointers() {
+        let ty = Ty::Array(Box::new(Ty::Ptr(Box::new(Ty::Int))), 4);
+        assert_eq!(32, ty.size());
+        assert_eq!(8, ty.align());
+    }
+
+    #[test]
+    fn size_of_nested_array() {
+        let ty = Ty::Array(Box::new(Ty::Array(Box::new(Ty::Int), 3)), 2);
+        assert_eq!(24, ty.size());
+        assert_eq!(4, ty.align());
+    }
+
+    #[test]
+    fn unspecified_params_are_compatible_with_any_params() {
+        let unspecified = FuncTy {
+            ret: Ty::Int,
+            params: None,
+            is_variadic: false,
+        };
+        let two = FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Int, Ty::Int]),
+            is_variadic: false,
+        };
+        let one = FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Int]),
+            is_variadic: false,
+        };
+        assert!(unspecified.is_compatible(&two));
+        assert!(two.is_compatible(&unspecified));
+        assert!(!one.is_compatible(&two));
+    }
+
+    #[test]
+    fn incomplete_array_is_compatible_with_any_length() {
+        let incomplete = Ty::IncompleteArray(Box::new(Ty::Int));
+        let three = Ty::Array(Box::new(Ty::Int), 3);
+        let chars = Ty::Array(Box::new(Ty::Char), 3);
+        assert!(incomplete.is_compatible(&three));
+        assert!(three.is_compatible(&incomplete));
+        assert!(!incomplete.is_compatible(&chars));
+        assert_eq!(Ty::Ptr(Box::new(Ty::Int)), incomplete.decay());
+    }
+
+    #[test]
+    fn pointers_to_different_types_are_incompatible() {
+        let p = Ty::Ptr(Box::new(Ty::Int));
+        let pp = Ty::Ptr(Box::new(Ty::Ptr(Box::new(Ty::Int))));
+        assert!(p.is_compatible(&p));
+        assert!(!p.is_compatible(&pp));
+    }
+
+    #[test]
+    fn qualifiers_of_array_apply_to_elements() {
+        let c = Quals {
+            is_const: true,
+            ..Quals::default()
+        };
+        let ty = Ty::Array(Box::new(Ty::Int), 2).qualify(c);
+        let expected = Ty::Array(Box::new(Ty::Qual(Box::new(Ty::Int), c)), 2);
+        assert_eq!(expected, ty);
+        assert_eq!(8, ty.size());
+    }
+
+    #[test]
+    fn differently_qualified_types_are_incompatible() {
+        let c = Quals {
+            is_const: true,
+            ..Quals::default()
+        };
+        let v = Quals {
+            is_volatile: true,
+            ..Quals::default()
+        };
+        let ci = Ty::Int.qualify(c);
+        assert!(ci.is_compatible(&Ty::Int.qualify(c)));
+        assert!(!ci.is_compatible(&Ty::Int));
+        assert!(!ci.is_compatible(&ci.clone().qualify(v)));
+        assert!(ci.is_integer());
+    }
+
+    #[test]
+    fn composite_type_takes_array_lengths_and_parameters() {
+        // int (*)[] and int (*)[3]
+        let unknown = Ty::Ptr(Box::new(Ty::IncompleteArray(Box::new(Ty::Int))));
+        let three = Ty::Ptr(Box::new(Ty::Array(Box::new(Ty::Int), 3)));
+        assert_eq!(three, unknown.composite(&three));
+        assert_eq!(three, three.composite(&unknown));
+
+        // int (*f())[] and int (*f(char))[3]
+        let func = |params, ret| {
+            Ty::Func(Box::new(FuncTy {
+                ret,
+                params,
+                is_variadic: false,
+            }))
+        };
+        let f = func(None, unknown);
+        let g = func(Some(vec![Ty::Char]), three.clone());
+        assert_eq!(func(Some(vec![Ty::Char]), three), f.composite(&g));
+    }
+
+    fn decl(name: Option<&str>, ty: Ty, width: Option<usize>) -> MemberDecl {
+        MemberDecl {
+            name: name.map(str::to_string),
+            ty,
+            width,
+            align: 0,
+            is_packed: false,
+        }
+    }
+
+    #[test]
+    fn lays_out_bit_fields_as_gcc_does() {
+        // struct { char a; char : 0; char b; short c : 9; short d : 9; }
+        let s = StructRef::new(None, false);
+        s.complete(
+            vec![
+                decl(Some("a"), Ty::Char, None),
+                decl(None, Ty::Char, Some(0)),
+                decl(Some("b"), Ty::Char, None),
+                decl(Some("c"), Ty::Short, Some(9)),
+                decl(Some("d"), Ty::Short, Some(9)),
+            ],
+            false,
+            0,
+        );
+        let ty = Ty::Struct(s.clone());
+        assert_eq!((6, 2), (ty.size(), ty.align()));
+        let c = s.find_member("c").unwrap();
+        assert_eq!(
+            (
+                2,
+                Some(BitField {
+                    bit_offset: 0,
+                    width: 9
+                })
+            ),
+            (c.offset, c.bit_field)
+        );
+        // `d` would cross the boundary of a short, so it starts the next one.
+        let d = s.find_member("d").unwrap();
+        assert_eq!(
+            (
+                4,
+                Some(BitField {
+                    bit_offset: 0,
+                    width: 9
+                })
+            ),
+            (d.offset, d.bit_field)
+        );
+
+        // struct { char a; int b : 3; int c : 30; }
+        let s = StructRef::new(None, false);
+        s.complete(
+            vec![
+                decl(Some("a"), Ty::Char, None),
+                decl(Some("b"), Ty::Int, Some(3)),
+                decl(Some("c"), Ty::Int, Some(30)),
+            ],
+            false,
+            0,
+        );
+        assert_eq!(
+            (8, 4),
+            (Ty::Struct(s.clone()).size(), Ty::Struct(s).align())
+        );
+
+        // Unnamed bit-fields do not affect the alignment: struct { char a; int : 5; }
+        let s = StructRef::new(None, false);
+        s.complete(
+            vec![
+                decl(Some("a"), Ty::Char, None),
+                decl(None, Ty::Int, Some(5)),
+            ],
+            false,
+            0,
+        );
+        assert_eq!(
+            (2, 1),
+            (Ty::Struct(s.clone()).size(), Ty::Struct(s).align())
+        );
+    }
+
+    #[test]
+    fn lays_out_packed_and_aligned_members_as_gcc_does() {
+        let size_align = |s: &StructRef| {
+            let ty = Ty::Struct(s.clone());
+            (ty.size(), ty.align())
+        };
+
+        // struct { char a; int b __attribute__((packed)); short c; }
+        let s = StructRef::new(None, false);
+        s.complete(
+            vec![
+                decl(Some("a"), Ty::Char, None),
+                MemberDecl {
+                    is_packed: true,
+                    ..decl(Some("b"), Ty::Int, None)
+                },
+                decl(Some("c"), Ty::Short, None),
+            ],
+            false,
+            0,
+        );
+        assert_eq!((8, 2), size_align(&s));
+        assert_eq!(1, s.find_member("b").unwrap().offset);
+        assert_eq!(6, s.find_member("c").unwrap().offset);
+
+        // A packed bit-field may cross a boundary:
+        // struct __attribute__((packed)) { char a; int b : 30; char c; }
+        let s = StructRef::new(None, false);
+        s.complete(
+            vec![
+                decl(Some("a"), Ty::Char, None),
+                decl(Some("b"), Ty::Int, Some(30)),
+                decl(Some("c"), Ty::Char, None),
+            ],
+            true,
+            0,
+        );
+        assert_eq!((6, 1), size_align(&s));
+        let b = s.find_member("b").unwrap();
+        assert_eq!((1, 0), (b.offset, b.bit_field.unwrap().bit_offset));
+        assert_eq!(5, s.find_member("c").unwrap().offset);
+
+        // struct __attribute__((packed)) { char a; _Alignas(4) char b; int c; }
+        let s = StructRef::new(None, false);
+        s.complete(
+            vec![
+                decl(Some("a"), Ty::Char, None),
+                MemberDecl {
+                    align: 4,
+                    ..decl(Some("b"), Ty::Char, None)
+                },
+                decl(Some("c"), Ty::Int, None),
+            ],
+            true,
+            0,
+        );
+        assert_eq!((12, 4), size_align(&s));
+        assert_eq!(5, s.find_member("c").unwrap().offset);
+
+        // struct __attribute__((packed, aligned(4))) { char a; int b; }
+        let s = StructRef::new(None, false);
+        s.complete(
+            vec![
+                decl(Some("a"), Ty::Char, None),
+                decl(Some("b"), Ty::Int, None),
+            ],
+            true,
+            4,
+        );
+        assert_eq!((8, 4), size_align(&s));
+
+        // struct { int n; char c; double d[]; }
+        let s = StructRef::new(None, false);
+        s.complete(
+            vec![
+                decl(Some("n"), Ty::Int, None),
+                decl(Some("c"), Ty::Char, None),
+                decl(Some("d"), Ty::IncompleteArray(Box::new(Ty::Double)), None),
+            ],
+            false,
+            0,
+        );
+        assert_eq!((8, 8), size_align(&s));
+        assert_eq!(8, s.find_member("d").unwrap().offset);
+    }
+
+    #[test]
+    fn finds_members_of_anonymous_members() {
+        // union { int x; struct { char p, q; }; }
+        let inner = StructRef::new(None, false);
+        inner.complete(
+            vec![
+                decl(Some("p"), Ty::Char, None),
+                decl(Some("q"), Ty::Char, None),
+            ],
+            false,
+            0,
+        );
+        let u = StructRef::new(Some("U".to_string()), true);
+        u.complete(
+            vec![
+                decl(Some("x"), Ty::Int, None),
+                decl(None, Ty::Struct(inner), None),
+            ],
+            false,
+            0,
+        );
+        assert_eq!(4, Ty::Struct(u.clone()).size());
+        assert_eq!(1, u.find_member("q").unwrap().offset);
+        assert_eq!(None, u.find_member("r"));
+        assert_eq!(vec!["x", "p", "q"], u.member_names());
+        assert_eq!("union U", Ty::Struct(u).to_string());
+    }
+
+    #[test]
+    fn prints_c_type_names() {
+        let c = Quals {
+            is_const: true,
+            ..Quals::default()
+        };
+        let p = Ty::Ptr(Box::new(Ty::Int));
+        assert_eq!("int *", p.to_string());
+        assert_eq!("double", Ty::Double.to_string());
+        assert_eq!(
+            "const char *",
+            Ty::Ptr(Box::new(Ty::Char.qualify(c))).to_string()
+        );
+        assert_eq!("int *const", p.clone().qualify(c).to_string());
+        assert_eq!("int *[2]", Ty::Array(Box::new(p.clone()), 2).to_string());
+        let arr = Ty::Array(Box::new(Ty::Char), 3);
+        assert_eq!("char (*)[3]", Ty::Ptr(Box::new(arr)).to_string());
+        let func = Ty::Func(Box::new(FuncTy {
+            ret: Ty::Int,
+            params: Some(vec![Ty::Double]),
+            is_variadic: false,
+        }));
+        assert_eq!("int (*)(double)", Ty::Ptr(Box::new(func)).to_string());
+    }
 }
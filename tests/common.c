@@ -0,0 +1,52 @@
+// Helpers for the tests, which are compiled by gcc and linked with the test
+// programs, since rocc cannot subscript arrays.
+
+int ati(int *a, int i) { return a[i]; }
+int atc(char *s, int i) { return s[i]; }
+double atd(double *a, int i) { return a[i]; }
+int fmix(int a, float b, double c, int d) { return a + b * 10 + c * 100 + d; }
+float half(float x) { return x / 2; }
+long double ldfma(long double a, int b, long double c) { return a * b + c; }
+int isldtenth(long double x) { return x == 0.1L; }
+int issame(void *a, void *b) { return a == b; }
+int isaligned(void *p, int n) { return (long)p % n == 0; }
+int memeq(void *a, void *b, int n) { return __builtin_memcmp(a, b, n) == 0; }
+
+// Structure with bit-fields, whose layout and values are compared with gcc.
+struct bits { char a; int b : 3; int c : 30; unsigned d : 5; _Bool e : 1; unsigned short f : 9; short g : 9; };
+struct bits gbits = {2, -3, 100, 9, 1, 5, -7};
+int bitsize(void) { return sizeof(struct bits); }
+void setbits(struct bits *p) { p->a = 1; p->b = -2; p->c = -12345; p->d = 31; p->e = 1; p->f = 300; p->g = -200; }
+int checkbits(struct bits *p) { return p->a == 7 && p->b == 3 && p->c == 536870911 && p->d == 17 && !p->e && p->f == 511 && p->g == -256; }
+
+// Packed structure of a network header, whose layout and values are compared
+// with gcc.
+struct __attribute__((packed)) pkt { unsigned char kind; unsigned short len; int seq; unsigned flags : 3, id : 13; char data[]; };
+struct pkt gpkt = {7, 1500, -5, 6, 4097};
+int pktsize(void) { return sizeof(struct pkt); }
+int checkpkt(struct pkt *p) { return p->kind == 9 && p->len == 65535 && p->seq == 123456 && p->flags == 5 && p->id == 8191 && p->data[0] == 'x'; }
+
+// Functions taking more arguments than registers, the rest of which are
+// passed in memory, and callers of such functions compiled by rocc.
+int check9(int a, int b, int c, int d, int e, int f, int g, int h, int i) { return a == 1 && b == 2 && c == 3 && d == 4 && e == 5 && f == 6 && g == 7 && h == 8 && i == 9; }
+int checkfp(double a, double b, double c, double d, double e, double f, double g, double h, float i, int j, long double k, double l) { return a == 1 && b == 2 && c == 3 && d == 4 && e == 5 && f == 6 && g == 7 && h == 8 && i == 9.5f && j == 10 && k == 11 && l == 12; }
+int callmany(int (*f)(int, int, int, int, int, int, int, double, long double, int, double, double, double, double, double, double, double, double, double)) { return f(1, 2, 3, 4, 5, 6, 7, 0.5, 8, 9, 1, 2, 3, 4, 5, 6, 7, 8, 9.5); }
+int callvar(int (*f)(int, int, int, int, int, int, int, ...)) { return f(1, 2, 3, 4, 5, 6, 7, 8, 9.5, 10.0L, 11); }
+
+// Integer macros, whose values and sizes in the order of INTEGER_MACROS are
+// compared with those of gcc. Returns the position of the first different
+// one from 1, or 0 if all are the same.
+#include <float.h>
+#include <limits.h>
+#include <stdint.h>
+#include "include/limits.def"
+#define VALUE(m) m,
+#define SIZE(m) sizeof(m),
+int checkmacros(long *vals, int *sizes) {
+  long expected_vals[] = {INTEGER_MACROS(VALUE)};
+  int expected_sizes[] = {INTEGER_MACROS(SIZE)};
+  for (int i = 0; i < sizeof(expected_vals) / sizeof(long); i++)
+    if (vals[i] != expected_vals[i] || sizes[i] != expected_sizes[i])
+      return i + 1;
+  return 0;
+}
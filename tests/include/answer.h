@@ -0,0 +1,3 @@
+#include "twice.h"
+
+int answer() { return twice(21); }
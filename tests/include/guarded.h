@@ -0,0 +1,6 @@
+#ifndef GUARDED_H
+#define GUARDED_H
+
+int three() { return 3; }
+
+#endif
@@ -0,0 +1,4 @@
+#pragma once
+#include "once.h"
+
+int one = 1;
@@ -0,0 +1 @@
+#define NULL 0
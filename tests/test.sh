@@ -1,11 +1,14 @@
 #!/bin/bash
 
+# Compiles the source from stdin with the options following the expected
+# exit code.
 assert() {
   input="$1"
   expected="$2"
+  shift 2
 
-  ./target/debug/rocc "$input" > ./tests/tmp.s
-  gcc -o ./tests/tmp ./tests/tmp.s
+  printf '%s\n' "$input" | ./target/debug/rocc "$@" - > ./tests/tmp.s || exit 1
+  gcc -o ./tests/tmp ./tests/tmp.s ./tests/common.o
   ./tests/tmp
   actual="$?"
 
@@ -18,7 +21,42 @@ assert() {
   fi
 }
 
+# Compiles two sources separately, and links them together.
+assert_link() {
+  input1="$1"
+  input2="$2"
+  expected="$3"
+
+  printf '%s\n' "$input1" | ./target/debug/rocc - > ./tests/tmp1.s || exit 1
+  printf '%s\n' "$input2" | ./target/debug/rocc - > ./tests/tmp2.s || exit 1
+  gcc -o ./tests/tmp ./tests/tmp1.s ./tests/tmp2.s ./tests/common.o
+  ./tests/tmp
+  actual="$?"
+
+  if [ "$actual" = "$expected" ]
+  then
+    echo "$input1 + $input2 => $actual"
+  else
+    echo "$input1 + $input2 => expected $expected, but got $actual"
+    exit 1
+  fi
+}
+
+# Checks that the source from stdin fails to compile.
+assert_error() {
+  input="$1"
+
+  if printf '%s\n' "$input" | ./target/debug/rocc - > ./tests/tmp.s 2> /dev/null
+  then
+    echo "$input => expected a compile error"
+    exit 1
+  else
+    echo "$input => compile error"
+  fi
+}
+
 cargo build
+gcc -c -o ./tests/common.o ./tests/common.c
 
 assert "int main() { return 42; }" "42"
 assert "int main() { return 123; }" "123"
@@ -31,6 +69,7 @@ assert "int main() { return 3+2*13-9; }" "20"
 assert "int main() { return 3+2*3*4-7; }" "20"
 assert "int main() { return 6/2; }" "3"
 assert "int main() { return 4/3; }" "1"
+assert "int main() { int x = 0-7; long y = 0-9; enum { E = (0-7)/2 }; return x/2 + y/3 + E + 20; }" "11"
 assert "int main() { return 2*3-6/2+1; }" "4"
 assert "int main() { return 1+2*3; }" "7"
 assert "int main() { return (1+2)*3; }" "9"
@@ -50,4 +89,369 @@ assert "int main() { if (3-3) { return 1; } return 2; }" "2"
 assert "int main() { if (3-1) { return 1; } return 2; }" "1"
 assert "int main() { if (0) return 1; if (1) return 2; return 3; }" "2"
 
+assert "enum E { A, B, C }; int main() { return C; }" "2"
+assert "enum { A = 5, B, C = 2*A }; int main() { return B+C; }" "16"
+assert "enum { A = 3-4, B }; int main() { return B; }" "0"
+assert "int main() { enum { A = 3 }; return A; }" "3"
+assert "enum { A = 1 }; int main() { { enum { A = 2 }; } return A; }" "1"
+assert "enum { A = 1 }; int main() { enum { A = 2 }; return A; }" "2"
+assert "enum E { A = 7 }; enum E ret() { return A; } int main() { return ret(); }" "7"
+assert "int main() { enum E { A, B }; enum E; return B; }" "1"
+
+assert "int main() { int x; x = 3; return x; }" "3"
+assert "int main() { int x = 3, y = x*2; return x+y; }" "9"
+assert "int main() { int x; int y; x = y = 4; return x*y; }" "16"
+assert "int main() { int x = 1; { int x = 2; } return x; }" "1"
+assert "int main() { int x = 1; { int y = 2; x = x+y; } return x; }" "3"
+
+assert "typedef int T; int main() { T x = 5; return x; }" "5"
+assert "typedef int T, U; int main() { T x = 2; U y = 3; return x*y; }" "6"
+assert "typedef int T; T ret() { return 9; } int main() { return ret(); }" "9"
+assert "int typedef T; int main() { T x = 5; return x; }" "5"
+assert "typedef int T; typedef int T; typedef struct S S; typedef struct S S; struct S { T x; }; int main() { typedef char *P; typedef char *P; S s = {4}; P p = 0; return s.x + sizeof(p); }" "12"
+assert_error "typedef int T; typedef long T; int main() { return 0; }"
+assert_error "int main() { int n = 3; typedef int A[n]; typedef int A[n]; return 0; }"
+assert "typedef enum { A, B } T; int main() { T x = B; return x; }" "1"
+assert "int main() { typedef int T; T x = 4; { typedef T U; U y = x; return y; } }" "4"
+assert "typedef int T; int main() { int T = 2; return T*3; }" "6"
+assert "typedef int T; int main() { T T = 2; return T; }" "2"
+assert "typedef int T; int main() { { int T = 2; } T x = 7; return x; }" "7"
+
+assert "int main() { return sizeof(int); }" "4"
+assert "int main() { return sizeof(int *); }" "8"
+assert "int main() { return sizeof(int *[4]); }" "32"
+assert "int main() { return sizeof(int (*)[4]); }" "8"
+assert "int main() { return sizeof(int [2][3]); }" "24"
+assert "int main() { return sizeof(int (*)(int, int)); }" "8"
+assert "int main() { int *arr[4]; return sizeof(arr); }" "32"
+assert "int main() { int (*p)[4]; return sizeof(p) + sizeof(*p); }" "24"
+assert "int main() { int (*fp)(int, int); return sizeof fp; }" "8"
+assert "int main() { int a[2][3]; return sizeof a + sizeof *a; }" "36"
+assert "int main() { int x = 3; int *p = &x; *p = 5; return x; }" "5"
+assert "int main() { int x = 3; int *p = &x; int **pp = &p; return **pp; }" "3"
+assert "int main() { int x; int *p = &x; x = 7; return *p; }" "7"
+assert "int main() { int (*p)[4]; int a[4]; p = &a; return sizeof(**p); }" "4"
+assert "int main() { return (int)7; }" "7"
+assert "int main() { int x = 2; int *p = (int *)&x; return *p; }" "2"
+assert "typedef int *P; int main() { int x = 4; P p = &x; return *p; }" "4"
+assert "typedef int A[3]; int main() { A a; return sizeof(a); }" "12"
+assert "typedef int (*F)(int); int main() { return sizeof(F); }" "8"
+assert "enum { N = sizeof(int [5]) }; int main() { return N; }" "20"
+
+assert "int add(int a, int b) { return a+b; } int main() { return add(3, 4); }" "7"
+assert "int sub(int a, int b) { return a-b; } int main() { return sub(9, 4); }" "5"
+assert "int six(int a, int b, int c, int d, int e, int f) { return a+b+c+d+e+f; } int main() { return six(1, 2, 3, 4, 5, 6); }" "21"
+assert "int fib(int n) { if (n-1+1) { if (n-1) return fib(n-1)+fib(n-2); return 1; } return 0; } int main() { return fib(10); }" "55"
+assert "int deref(int *p) { return *p; } int main() { int x = 8; return deref(&x); }" "8"
+assert "int set(int *p) { *p = 3; return 0; } int main() { int x; set(&x); return x; }" "3"
+assert "int ret() { return 5; } int main() { int (*fp)() = ret; return fp(); }" "5"
+assert "int ret() { return 5; } int main() { int (*fp)() = &ret; return (*fp)(); }" "5"
+assert "int ret() { return 5; } int main() { return (**ret)(); }" "5"
+assert "int add(int a, int b) { return a+b; } int main() { int (*fp)(int, int) = add; return fp(2, 3); }" "5"
+assert "int mul(int a, int b) { return a*b; } int apply(int (*f)(int, int), int a, int b) { return f(a, b); } int main() { return apply(mul, 6, 7); }" "42"
+assert "int mul(int a, int b) { return a*b; } int apply(int f(int, int), int a, int b) { return f(a, b); } int main() { return apply(&mul, 3, 4); }" "12"
+assert "int one() { return 1; } int two() { return 2; } int main() { int (*fs[2])(); *fs = two; return 10*(**fs)() + one(); }" "21"
+assert "typedef int (*F)(int); int twice(int x) { return 2*x; } int main() { F f = twice; return f(4); }" "8"
+assert "int add(int a, int b) { return a+b; } int main() { return 1 + add(2, add(3, 4)); }" "10"
+
+assert "int twice(int x); int main() { return twice(4); } int twice(int x) { return 2*x; }" "8"
+assert "int f(), g(int); int main() { return f() + g(2); } int f() { return 1; } int g(int x) { return x; }" "3"
+assert "int main() { int g(); return g(); } int g() { return 9; }" "9"
+assert "int abs(int); int main() { return abs(3-10); }" "7"
+assert "int f(); int f(int a, int b); int f(int a, int b) { return a-b; } int main() { return f(5, 2); }" "3"
+assert "int *ret() { return 0; } int main() { return sizeof(ret()); }" "8"
+assert "int ret() { return 3; } int (*get())() { return ret; } int main() { return get()(); }" "3"
+
+assert "int x; int main() { x = 3; return x; }" "3"
+assert "int x = 5, y = 2*3; int main() { return x+y; }" "11"
+assert "int x; int x; int x = 4; int main() { return x; }" "4"
+assert "int *p; int a[3]; int main() { return sizeof(p) + sizeof(a); }" "20"
+assert "int x; int *get() { return &x; } int main() { *get() = 7; return x; }" "7"
+assert "static int x = 2; static int f() { return x; } int main() { return f(); }" "2"
+assert "int count() { static int n; n = n+1; return n; } int main() { count(); count(); return count(); }" "3"
+assert "int f() { static int n = 10; n = n+1; return n; } int g() { static int n = 20; return n; } int main() { f(); return f()+g(); }" "32"
+assert "int f(register int a) { auto int b = 2; register int c = 3; return a + b * c; } int main() { return f(1); }" "7"
+assert "int main() { extern int x; return x; } int x = 6;" "6"
+assert "extern int x; int main() { return x; } int x = 8;" "8"
+assert "int a[3]; int a[]; int b[]; extern int b[2]; int c[]; int main() { { extern int a[]; *c = 4; return sizeof a + sizeof b + *c; } }" "24"
+assert "static int s = 5; int main() { extern int s; int f(int); return s + f(2); } int f(int x) { return x; }" "7"
+assert_error "int x; static int x;"
+assert_error "int f(); int f(char c);"
+assert_error "int f(float x); int f();"
+assert_error "int main() { extern int q; return 0; } long q;"
+assert_error "int main() { extern int q; return 0; } static int q;"
+assert_error "static int s; int main() { int s; { extern int s; } return 0; }"
+assert_link "int x = 3; int get() { return x; }" "int get(); extern int x; int main() { return get()+x; }" "6"
+assert_link "static int x = 1; static int f() { return x; } int g() { return f(); }" "static int x = 2; static int f() { return x; } int g(); int main() { return 10*f()+g(); }" "21"
+
+assert "int ati(int *a, int i); int main() { int a[3] = {1, 2, 3}; return ati(a, 0) + ati(a, 2); }" "4"
+assert "int main() { int a[] = {1, 2, 3, 4}; return sizeof(a); }" "16"
+assert "int ati(int *a, int i); int main() { int a[4] = {1}; return ati(a, 0) + ati(a, 3); }" "1"
+assert "int ati(int *a, int i); int main() { int a[2][3] = {{1, 2, 3}, {4, 5, 6}}; return ati((int *)a, 4); }" "5"
+assert "int ati(int *a, int i); int main() { int a[2][2] = {1, 2, 3, 4}; return ati((int *)a, 3); }" "4"
+assert "int ati(int *a, int i); int main() { int a[5] = {1, [3] = 7, 8}; return ati(a, 3) + ati(a, 4) + ati(a, 1); }" "15"
+assert "int ati(int *a, int i); int main() { int a[2][2] = {[1][0] = 5, 6}; return ati((int *)a, 2) + ati((int *)a, 3); }" "11"
+assert "int main() { int a[] = {[4] = 1}; return sizeof(a); }" "20"
+assert "int main() { int x = {3}; return x; }" "3"
+assert "int main() { char s[] = \"abc\"; return sizeof(s); }" "4"
+assert "int atc(char *s, int i); int main() { char s[] = \"abc\"; return atc(s, 1); }" "98"
+assert "int atc(char *s, int i); int main() { char s[8] = \"hi\"; return atc(s, 1) + atc(s, 2) + atc(s, 7); }" "105"
+assert "int atc(char *s, int i); int main() { char s[] = {96 + 1, 0}; return atc(s, 0); }" "97"
+assert "int strlen(char *s); int main() { return strlen(\"hello\" \" world\"); }" "11"
+assert "int atc(char *s, int i); int main() { char *s = \"xyz\"; return atc(s, 2); }" "122"
+assert "int main() { return sizeof(\"a\\tb\\n\"); }" "5"
+assert "int ati(int *a, int i); int a[3] = {1, 2, 3}; int main() { return ati(a, 1); }" "2"
+assert "int ati(int *a, int i); int a[2][2] = {[1] = {5, 6}}; int main() { return ati((int *)a, 0) + ati((int *)a, 3); }" "6"
+assert "int atc(char *s, int i); char g[] = \"ab\"; int main() { return atc(g, 1) + sizeof(g); }" "101"
+assert "int ati(int *a, int i); int f() { static int a[] = {4, 5}; return ati(a, 1) + sizeof(a); } int main() { return f(); }" "13"
+assert "int atc(char *s, int i); char *s = \"abc\"; int main() { static char *t = \"xyz\"; return atc(s, 1) + atc(t, 0); }" "218"
+assert "int x = 3; int *p = &x; int *q = 0; int main() { static int *r = &x; *r = *p + 1; return x + (int)q; }" "4"
+assert "int f(int a) { return a * 2; } int (*fp)(int) = f; int main() { static int (*tbl[2])(int) = {f, &f}; return fp(1) + (*tbl)(2); }" "6"
+assert_link "int f(int a) { return a + 1; }" "int f(int a); static int (*tbl[2])(int) = {f, f}; int main() { return (*tbl)(4); }" "5"
+assert "int *p = 0; int x = 2*3; int main() { return x; }" "6"
+assert "int main() { char c = 300; return c; }" "44"
+assert "int main() { char x = 255; return x + 2; }" "1"
+assert "int f(char c) { return c; } int main() { return f(258); }" "2"
+assert "int main() { return (char)511 + 2; }" "1"
+assert "char c = 65; char f() { return c + 1; } int main() { return f(); }" "66"
+
+assert "int main() { return (int){7}; }" "7"
+assert "int ati(int *a, int i); int main() { return ati((int[]){1, 2, 3}, 2); }" "3"
+assert "int main() { return sizeof((int[]){1, 2, 3}); }" "12"
+assert "int ati(int *a, int i); int main() { int *p = (int[4]){[2] = 5}; return ati(p, 2) + ati(p, 3); }" "5"
+assert "int main() { int *p = &(int){3}; *p = *p + 4; return *p; }" "7"
+assert "int atc(char *s, int i); int main() { return atc((char[]){\"hey\"}, 1); }" "101"
+assert "int ati(int *a, int i); int f(int x) { return ati((int[]){x, x * 2}, 1); } int main() { return f(3) + f(4); }" "14"
+assert "int x = sizeof((int[]){1, 2}); int main() { return x; }" "8"
+assert "int main() { return sizeof (int[]){1, 2, 3} + sizeof (char[]){\"ab\"}; }" "15"
+
+assert "int x; void set(int v) { x = v; } int main() { set(5); return x; }" "5"
+assert "int x; void f(int v) { x = 1; if (v) return; x = 2; } int main() { f(1); return x; }" "1"
+assert "int x; void f(int v) { x = 1; if (v) return; x = 2; } int main() { f(0); return x; }" "2"
+assert "int f(void) { return 3; } int main(void) { return f(); }" "3"
+assert "int main() { int x = 7; void *p = &x; int *q = p; return *q; }" "7"
+assert "int ati(int *a, int i); int g(void *p) { return ati(p, 1); } int main() { int a[2] = {1, 9}; return g(a); }" "9"
+assert "void f(void); int main() { (void)3; return sizeof(void *); }" "8"
+
+assert "int main() { _Bool b = 2; return b; }" "1"
+assert "int main() { _Bool b = 0; return b; }" "0"
+assert "int main() { _Bool b; b = 256; return b + b; }" "2"
+assert "int main() { int x; _Bool b = &x; return b; }" "1"
+assert "int main() { return (_Bool)3 + (_Bool)0 + sizeof(_Bool); }" "2"
+assert "_Bool f(int x) { return x; } int main() { return f(7) + f(0); }" "1"
+assert "int f(_Bool b) { return b; } int main() { return f(256); }" "1"
+assert "int atc(char *s, int i); _Bool g = 5; _Bool h[2] = {0, 9}; int main() { return g + atc((char *)h, 1) + sizeof(h); }" "4"
+assert "int main() { _Bool b = 0; if (b) return 1; b = b - 1; if (b) return 2; return 3; }" "2"
+
+assert "int main() { const int x = 3; return x; }" "3"
+assert "int main() { int const x = 3; const int *p = &x; return *p; }" "3"
+assert "int main() { int x = 1; int *const p = &x; *p = 5; return x; }" "5"
+assert "int main() { int x = 1; const int *p = &x; x = 4; return *p; }" "4"
+assert "int main() { volatile int x = 2; x = x + 1; return x; }" "3"
+assert "int f(const int *p) { return *p; } int main() { int x = 6; return f(&x); }" "6"
+assert "int atc(char *s, int i); int main() { const char s[] = \"ab\"; return atc((char *)s, 1); }" "98"
+assert "int f(int *restrict p) { return *p; } int main() { int x = 8; return f(&x); }" "8"
+assert "typedef int T[2]; const T a = {1, 2}; int main() { return sizeof(a); }" "8"
+assert "const int g = 4; int f(int); int f(const int x) { return x + g; } int main() { return f(1); }" "5"
+
+assert "int main() { return 1.5 + 1.5; }" "3"
+assert "int main() { double d = 2.5; return d * 4; }" "10"
+assert "int main() { float f = 7; return f / 2 * 2; }" "7"
+assert "int main() { return (int)(7 / 2.0 * 2); }" "7"
+assert "int main() { return (char)2.9f + .5e1 + 0x1.8p1; }" "10"
+assert "int main() { return sizeof(float) + sizeof(double) + sizeof 1.0f + sizeof 1.0; }" "24"
+assert "int main() { double d = 0.1; float f = d; double e = f; return (e - d) * 1e9; }" "1"
+assert "int main() { double d = 0.5; if (d) return 1; return 0; }" "1"
+assert "int main() { double d = 0.5 - 0.5; if (d) return 1; return 0; }" "0"
+assert "int main() { _Bool b = 0.25; return b; }" "1"
+assert "double f(double x, int y, float z) { return x * y + z; } int main() { return f(2.5, 4, 0.5f); }" "10"
+assert "float f(float x) { return x; } int main() { float y = f(2.75f); return y * 4; }" "11"
+assert "int fmix(int a, float b, double c, int d); int main() { return fmix(1, 2, 0.5, 3); }" "74"
+assert "float half(float x); int main() { return half(9) * 2; }" "9"
+assert "double atd(double *a, int i); double g[2] = {1.5, 2.0 / 8}; int main() { return atd(g, 0) * atd(g, 1) * 8; }" "3"
+assert "float g = 3; int i = 2.5; int main() { return g * 2 + i; }" "8"
+assert "int snprintf(); int atc(char *s, int i); int main() { char b[8]; snprintf(b, 8, \"%.1f\", 2.5f); return atc(b, 2); }" "53"
+
+assert "int main() { long double x = 2.5L; return x * 2; }" "5"
+assert "int main() { double long x = 7; return x / 2; }" "3"
+assert "int main() { return sizeof(long double) + sizeof 1.0L; }" "32"
+assert "int main() { return ((1.0L + 1e-17L) - 1.0L) * 1e18L; }" "9"
+assert "int main() { return ((1.0 + 1e-17) - 1.0) * 1e18; }" "0"
+assert "int main() { long double x = 0.5L; if (x) return 1; return 0; }" "1"
+assert "int main() { long double x = 0.5L - 0.5; _Bool b = x; return b; }" "0"
+assert "int main() { float f = 1.5L; double d = f; long double x = d; return (char)(x * 4); }" "6"
+assert "long double f(long double x, int y) { return x * y; } int main() { return f(1.25L, 4); }" "5"
+assert "int f(int a, long double x, double d, long double y) { return a + x * 10 + d * 100 + y * 1000; } int main() { return f(1, 0.2L, 0.5, 0.1); }" "153"
+assert "long double ldfma(long double a, int b, long double c); int main() { return ldfma(2.5L, 3, 0.5L); }" "8"
+assert "int isldtenth(long double x); long double g = 0.1L; int main() { return isldtenth(g) + isldtenth(0.1L) + isldtenth(0.1); }" "2"
+assert "long double g[2] = {1.5L, 2}; int main() { long double *p = g; return *p * 2; }" "3"
+assert "int snprintf(); int atc(char *s, int i); int main() { char b[8]; snprintf(b, 8, \"%.1Lf\", 2.5L); return atc(b, 2); }" "53"
+
+assert "int main() { (void)1.5; (void)2.5L; 3.5L; return 4; }" "4"
+
+assert "#include <stdarg.h>
+int sum(int n, ...) { va_list ap; va_start(ap, n); int s = va_arg(ap, int) + va_arg(ap, int) * 2 + va_arg(ap, int) * 3; va_end(ap); return s; } int main() { return sum(3, 1, 2, 3); }" "14"
+assert "#include <stdarg.h>
+double f(int a, double b, ...) { va_list ap; va_start(ap, b); double c = va_arg(ap, double); int d = va_arg(ap, int); va_end(ap); return a + b + c * d; } int main() { return f(1, 0.5, 2.5f, 4); }" "11"
+assert "#include <stdarg.h>
+long double f(int n, ...) { va_list ap; va_start(ap, n); long double x = va_arg(ap, long double); double d = va_arg(ap, double); long double y = va_arg(ap, long double); return x + d * 10 + y * 100; } int main() { return f(1, 0.5L, 2.0, 1.0L); }" "120"
+assert "#include <stdarg.h>
+char *f(int n, ...) { va_list ap; va_start(ap, n); va_arg(ap, int); char *s = va_arg(ap, char *); va_end(ap); return s; } int atc(char *s, int i); int main() { return atc(f(2, 1, \"ab\"), 1); }" "98"
+assert "int snprintf(char *b, int n, char *f, ...); int atc(char *s, int i); int main() { char b[8]; snprintf(b, 8, \"%d%.1f\", 4, 2.5f); return atc(b, 3); }" "53"
+assert "#include <stdarg.h>
+int vsnprintf(); int atc(char *s, int i); int fmt(char *b, char *f, ...) { va_list ap; va_start(ap, f); va_list aq; va_copy(aq, ap); va_end(ap); int n = vsnprintf(b, 8, f, aq); va_end(aq); return n; } int main() { char b[8]; fmt(b, \"%d%.1Lf\", 4, 2.5L); return atc(b, 3); }" "53"
+
+assert "int main() { int n = 3; int a[n]; return sizeof a; }" "12"
+assert "int main() { int n = 2; int m = 3; int a[n][m]; n = 5; m = 1; return sizeof a + sizeof *a; }" "36"
+assert "int main() { int n = 4; return sizeof(char[n][n + 1]) + sizeof(int[2][n]); }" "52"
+assert "int main() { int n = 2; typedef int T[n]; n = 3; T x; return sizeof(T) + sizeof x; }" "16"
+assert "int ati(int *a, int i); int main() { int n = 2; int a[n]; *a = 7; return ati(a, 0); }" "7"
+assert "int issame(void *a, void *b); int main() { int n = 100; int *p; { int a[n]; p = a; } int b[n]; return issame(p, b); }" "1"
+assert "int issame(void *a, void *b); int main() { int n = 100; int *p; int a[n]; p = a; if (n) { int b[n]; p = b; } int c[n]; return issame(p, c); }" "1"
+assert "int f(int rows, int cols, double m[rows][cols]) { return sizeof *m; } int main() { double x[2][3]; return f(2, 3, x); }" "24"
+assert "int snprintf(); int atc(char *s, int i); int main() { int n = 3; char a[n]; char b[n + 5]; snprintf(b, 8, \"%.1f\", 2.5); return atc(b, 2); }" "53"
+
+assert "int isaligned(void *p, int n); int main() { char a; _Alignas(16) char c; char b; _Alignas(double) char d; return isaligned(&c, 16) + isaligned(&d, 8); }" "2"
+assert "int isaligned(void *p, int n); char x; _Alignas(64) char g; char y; char __attribute__((aligned(32))) h; int main() { return isaligned(&g, 64) + isaligned(&h, 32); }" "2"
+assert "int isaligned(void *p, int n); int main() { char a; static _Alignas(128) char s = 1; __attribute__((aligned)) char t; return isaligned(&s, 128) + isaligned(&t, 16) + s; }" "3"
+assert "int isaligned(void *p, int n); int main() { char a; _Alignas(32) int x = 5; char __attribute__((aligned(64))) b[3]; return isaligned(&x, 32) + isaligned(b, 64) + x; }" "7"
+assert "int isaligned(void *p, int n); char c; int g __attribute__((aligned(32))) = 2, h; int main() { char a; int x __attribute__((aligned(16))) = 3; static char s __attribute__((__aligned__(64))); return isaligned(&g, 32) + isaligned(&x, 16) + isaligned(&s, 64) + g + x; }" "8"
+assert "int isaligned(void *p, int n); int f(int a, int b, int c, int d, int e, int f, int g, long double h) { _Alignas(256) char x = g; return isaligned(&x, 256) + x + h; } int main() { return f(1, 2, 3, 4, 5, 6, 7, 8.5L); }" "16"
+assert "#include <stdarg.h>
+int isaligned(void *p, int n); int f(int n, ...) { struct { _Alignas(32) char c; } s; va_list ap; va_start(ap, n); int x = n; x = x + va_arg(ap, int) + va_arg(ap, int) + va_arg(ap, int) + va_arg(ap, int) + va_arg(ap, int) + va_arg(ap, int) + va_arg(ap, int) + va_arg(ap, int); va_end(ap); return isaligned(&s, 32) * 100 + x; } int main() { return f(1, 2, 3, 4, 5, 6, 7, 8, 9); }" "145"
+assert "int main() { _Alignas(0) int x = 3; _Alignas(8) char a[3]; return sizeof x + sizeof a; }" "7"
+assert_error "int main() { _Alignas(1) int x = 3; return x; }"
+assert_error "_Alignas(char) int *p; int main() { return 0; }"
+assert_error "struct S { _Alignas(2) int x; }; int main() { return 0; }"
+
+bits="struct bits { char a; int b : 3; int c : 30; unsigned d : 5; _Bool e : 1; unsigned short f : 9; short g : 9; };"
+assert "$bits int bitsize(void); int main() { return sizeof(struct bits) * 10 + bitsize(); }" "132"
+assert "$bits void setbits(struct bits *p); int main() { struct bits s; setbits(&s); return s.a + s.b * 2 + (s.c + 12345) + s.d + s.e + s.f + s.g; }" "129"
+assert "$bits int checkbits(struct bits *p); int main() { struct bits s; struct bits *p = &s; p->a = 7; p->b = 3; s.c = 536870911; s.d = 49; s.e = 2 - 2; s.f = 0 - 1; s.g = 0 - 256; return checkbits(p) + (s.d = 113) + (s.b = 4); }" "14"
+assert "$bits int memeq(void *a, void *b, int n); extern struct bits gbits; struct bits x = {2, 0 - 3, 100, 9, 1, 5, 0 - 7}; int main() { struct bits y = {2, 0 - 3, 100, 9, 1, 5, 0 - 7}; return memeq(&x, &gbits, sizeof x) + y.g + y.c; }" "94"
+assert "struct A { char a; int : 0; char b; }; struct B { char a; char : 0; char b; short c : 9; short d : 9; }; union U { char a; int b : 20; }; int main() { return sizeof(struct A) * 10 + sizeof(struct B) * 20 + sizeof(union U); }" "174"
+assert "struct P { int x; struct { char c; int y; } in; }; int main() { struct P p = {1, 2, 3}; struct P q; q = p; struct P *r = &q; return r->x + r->in.c * 10 + q.in.y * 100 - p.in.y * 100; }" "21"
+assert "struct S { int n; union { struct { char a, b; }; int w; }; }; int main() { struct S s = {.w = 258, .n = 3}; struct S t = {1, .a = 4, 5}; return s.a + s.b * 10 + s.n * 20 + t.b * 2 + t.a; }" "86"
+assert "union U { int a; char b; }; union U g = {.b = 3}; int main() { union U u = {0 - 1}; return g.a + u.b + sizeof(union U) + (u.a = 256) / 256; }" "7"
+assert "int main() { unsigned x = 0 - 1; unsigned char c = 255; unsigned short s = 65535; short t = s; signed char d = c; return x / 16777216 + c + 1 - 256 + t + d + sizeof(unsigned short); }" "255"
+assert "int main() { long x = 4294967296 * 3; unsigned long y = 0xffffffffffffffff; return sizeof(4294967296) + sizeof 0x80000000 + sizeof 1u + sizeof 2147483647 + sizeof 1L + x / 4294967296 + y / 0x1000000000000000 + (1lu + 4294967295U) / 4294967296; }" "47"
+assert "double g = 0xffffffffffffffff; unsigned long gu = 1e19; long double gx = 0xfffffffffffffffful;
+int main() { unsigned long u = 0xfffffffffffff800; double d = u; float f = 0xffffff0000000000; long double x = u; unsigned long a = d; unsigned long b = f; unsigned long c = x; unsigned long r = (double)9223372036854776833u; long double y = 18446744073709551615u; unsigned long z = y; return d / 1e18 + (u - a) + (0xffffff0000000000 - b) + (u - c) + (r - 9223372036854775808u) / 1024 + x / 1e17 + f / 1e18 + g / 1e18 + gu / 1000000000000000000 + gx / 1e18 + (z - 18446744073709551615u); }" "14"
+
+pkt="struct __attribute__((packed)) pkt { unsigned char kind; unsigned short len; int seq; unsigned flags : 3, id : 13; char data[]; };"
+assert "$pkt int pktsize(void); int main() { return sizeof(struct pkt) * 10 + pktsize(); }" "99"
+assert "$pkt int memeq(void *a, void *b, int n); extern struct pkt gpkt; struct pkt x = {7, 1500, 0 - 5, 6, 4097}; int main() { struct pkt y = {7, 1500, 0 - 5, 6, 4097}; return memeq(&x, &gpkt, sizeof x) + memeq(&y, &gpkt, sizeof y) * 2 + y.id / 1000; }" "7"
+assert "$pkt int checkpkt(struct pkt *p); int main() { char buf[16]; void *v = buf; struct pkt *p = v; p->kind = 9; p->len = 65535; p->seq = 123456; p->flags = 13; p->id = 0 - 1; *p->data = 120; return checkpkt(p) * 100 + p->flags + (p->id - 8100); }" "196"
+assert "struct A { char c; int x __attribute__((aligned(16))); }; struct B { char c; _Alignas(8) short s; } __attribute__((aligned(32))); int isaligned(void *p, int n); struct B gb; int main() { return sizeof(struct A) + sizeof(struct B) * 2 + isaligned(&gb, 32) + isaligned(&gb.s, 8); }" "98"
+assert "struct F { int n; char c; double d[]; }; struct P { char c; struct F f; } __attribute__((packed)); int main() { struct F f; f.n = 3; return sizeof(struct F) * 10 + sizeof(struct P) + f.n; }" "92"
+assert "int check9(int a, int b, int c, int d, int e, int f, int g, int h, int i); int main() { return check9(1, 2, 3, 4, 5, 6, 7, 8, 9) + 2 * check9(1, 2, 3, 4, 5, 6, 7, 8, 9); }" "3"
+assert "int checkfp(double a, double b, double c, double d, double e, double f, double g, double h, float i, int j, long double k, double l); int main() { return 2 + checkfp(1, 2, 3, 4, 5, 6, 7, 8, 9.5, 10, 11, 12); }" "3"
+assert "typedef int F(int, int, int, int, int, int, int, double, long double, int, double, double, double, double, double, double, double, double, double); int callmany(F *f); int many(int a, int b, int c, int d, int e, int f, int g, double h, long double i, int j, double k1, double k2, double k3, double k4, double k5, double k6, double k7, double k8, double k9) { return a + b + c + d + e + f + g * 20 + h * 2 + i + j * 2 + k1 + k2 + k3 + k4 + k5 + k6 + k7 + k8 + k9 * 2; } int main() { return callmany(many) - many(1, 2, 3, 4, 5, 6, 7, 0.5, 8, 9, 1, 2, 3, 4, 5, 6, 7, 8, 9.5) + many(1, 2, 3, 4, 5, 6, 7, 0.5, 8, 9, 1, 2, 3, 4, 5, 6, 7, 8, 9.5); }" "243"
+assert "#include <stdarg.h>
+int callvar(int (*f)(int, int, int, int, int, int, int, ...)); int vsum(int a, int b, int c, int d, int e, int f, int g, ...) { va_list ap; va_start(ap, g); int x = va_arg(ap, int); double y = va_arg(ap, double); long double z = va_arg(ap, long double); int w = va_arg(ap, int); va_end(ap); return g * 10 + x * 2 + y * 2 + z + w; } int main() { return callvar(vsum) + vsum(1, 2, 3, 4, 5, 6, 7, 8, 9.5, (long double)10, 11) - 126; }" "126"
+assert "#include <stdarg.h>
+int vsum(long double a, int b, ...) { va_list ap; va_start(ap, b); double x = va_arg(ap, double); int y = va_arg(ap, int); va_end(ap); return a + b + x * 2 + y; } int main() { return vsum(100, 20, 2.5, 3, 4, 5, 6, 7, 8, 9, 1.5, 2.5, 3.5, 4.5, 5.5, 6.5, 7.5, 8.5); }" "128"
+assert "int sprintf(char *s, char *fmt, ...); int strcmp(char *a, char *b); int main() { char buf[64]; sprintf(buf, \"%d %d %d %d %d %d %.1f\", 1, 2, 3, 4, 5, 6, 7.5); return strcmp(buf, \"1 2 3 4 5 6 7.5\") + 3; }" "3"
+assert "#include \"tests/include/answer.h\"
+int main() { return answer(); }" "42"
+assert "#include <twice.h>
+#include \"answer.h\"
+int main() { return answer() + twice(1); }" "44" -I tests/include
+assert "# include <once.h>
+#include \"tests/include/once.h\"
+#
+int main() { return one; }" "1" -Itests/include
+
+assert "#define ADD(a, b) ((a) + (b))
+#define TWICE(x) ADD(x, x)
+int main() { return TWICE(ADD(1, 2)); }" "6"
+assert "#define ANSWER 40 + TWO
+int main() { return ANSWER - ONE; }" "41" -DTWO=2 -D ONE -DX -UX
+assert "int snprintf(); int atc(char *s, int i);
+#define CALL(f, ...) f(__VA_ARGS__)
+#define STR(x) #x
+int main() { char b[8]; CALL(snprintf, b, 8, STR(a  b)); return atc(b, 2); }" "98"
+assert "#define HEADER \"tests/include/twice.h\"
+#include HEADER
+#define twice(x) twice(twice(x))
+int main() { return twice(3); }" "12"
+
+assert "#include \"tests/include/guarded.h\"
+#include <guarded.h>
+int main() { return three(); }" "3" -Itests/include
+assert "#if defined(TWO) && TWO * 2 == 4
+int main() { return 2; }
+#elif __has_include(<guarded.h>)
+int main() { return 1; }
+#else
+int main() { return 0; }
+#endif" "2" -DTWO=2
+assert "#ifdef TWO
+int main() { return 2; }
+#elif __has_include(<guarded.h>)
+int main() { return 1; }
+#endif" "1" -Itests/include
+
+assert "int main() { return __LINE__ + __COUNTER__ + __COUNTER__; }" "2"
+assert "int atc(const char *s, int i);
+int main() { return atc(__FILE__, 1) + __STDC__ + sizeof(__DATE__) + sizeof __TIME__; }" "137"
+assert "#if __SIZEOF_POINTER__ == 8 && __CHAR_BIT__ == 8 && defined(__rocc__) && defined(__LINE__)
+int atc(const char *s, int i); int foo() { return atc(__func__, 2); } int main() { return foo() + sizeof(__func__); }
+#endif" "116"
+
+assert "#include <stdbool.h>
+#include <stdint.h>
+#include <stddef.h>
+int main() { bool b = 2; int8_t c = 300; int *p = NULL; return b + c + sizeof(int32_t) + sizeof(wchar_t) + (int)p; }" "53"
+assert "#include <float.h>
+#include <limits.h>
+#include <stdint.h>
+#include \"tests/include/limits.def\"
+#define VALUE(m) m,
+#define SIZE(m) sizeof(m),
+long vals[] = {INTEGER_MACROS(VALUE)};
+int sizes[] = {INTEGER_MACROS(SIZE)};
+int checkmacros(long *vals, int *sizes);
+int main() { return checkmacros(vals, sizes); }" "0"
+assert "int main() { return __STDC_VERSION__ - 201112 + sizeof(__STDC_VERSION__); }" "8"
+assert "#include <limits.h>
+#include <float.h>
+#if INT_MIN + INT_MAX == -1 && LLONG_MIN < 0 && FLT_MIN_10_EXP == -37
+int main() { return INT_MAX / 16777216 + CHAR_BIT + DBL_DIG + (int)(FLT_EPSILON * 8388608); }
+#endif" "151"
+assert "#include <stdarg.h>
+#include <stdalign.h>
+int sum(int n, ...) { va_list ap; va_start(ap, n); __gnuc_va_list aq; __va_copy(aq, ap); int s = va_arg(ap, int) + va_arg(aq, int); va_end(ap); va_end(aq); return s; }
+int main() { alignas(16) int x = sum(1, 3); return x + __alignas_is_defined; }" "7"
+assert "int main() { int va_list = 3; __builtin_va_list *p = 0; return va_list + sizeof(*p); }" "27"
+assert "#include <stddef.h>
+#include <stdint.h>
+struct S { char c; long l; int a[3]; };
+int main() { size_t n = sizeof(ptrdiff_t); uint64_t u = 0 - 1; long x = 3000000; return n + sizeof(size_t) + offsetof(struct S, a[2]) + u / 2 / 1000000000 / 1000000000 + x * x / 1000000000 / 1000; }" "58"
+assert "#include <stdnoreturn.h>
+#include <stdalign.h>
+void exit(int code);
+static noreturn void die(int code) { exit(code); }
+int main() { die(alignof(double) + __alignof_is_defined * 10 + _Alignof(short[3])); }" "20"
+
+# The output of -E is compiled into the same program.
+input="#include <stdbool.h>
+#define add(a, b) a+b
+int f(int a,
+      int b) { return a; }
+int main() { bool t = 2; return add(t, f(40,
+  1)) + __LINE__; }"
+assert "$(printf '%s\n' "$input" | ./target/debug/rocc -E -)" "47"
+assert "#line 40
+int main() { return __LINE__; }
+# 7 \"f.c\"
+int f() { return __LINE__; }" "40"
+
+assert "$(printf '// comments and whitespace\r\nint main() {\n\t/* a\n\t * b */ return 1 + \\\n2 // c \\\n + 4\n\t;\n}\n#define X \\\n  3\nint x = X;')" "3"
+
+assert "int my_var1 = 3; int \$x = 4; int caf\\u00e9 = 5;
+int main() { int _start = 1; return my_var1 + \$x + café + _start + __x86_64__; }" "14"
+assert "#include <stdint.h>
+int main() { int32_t x_1 = INT8_MAX; int8_t y2 = 300; return x_1 + y2; }" "171"
+
 echo OK